@@ -40,6 +40,124 @@ pub enum StakingError {
     PoolTokenAccountMissmatch,
     #[error("User Info missmatch")]
     UserInfoMissmatch,
+    #[error("Not enough reward tokens")]
+    InsufficientRewardFunds,
+    #[error("Invalid block range")]
+    InvalidBlockRange,
+    #[error("Project link is too long")]
+    LinkTooLong,
+    #[error("Reward vault can't cover accrued rewards")]
+    PoolInsolvent,
+    #[error("Emission period must be greater than zero")]
+    InvalidEmissionPeriod,
+    #[error("Not enough staked tokens in the pool")]
+    InsufficientStaked,
+    #[error("Too early to compound again")]
+    CooldownActive,
+    #[error("Invalid number of pools")]
+    InvalidPoolCount,
+    #[error("Swap program is not in the allowlist")]
+    RewardSwapProgramNotAllowed,
+    #[error("Swap program missmatch")]
+    RewardSwapProgramMissmatch,
+    #[error("Swap program took more reward tokens than pending")]
+    RewardSwapFailed,
+    #[error("Token-account can't be a pool vault")]
+    InvalidDestination,
+    #[error("Delegate missmatch")]
+    DelegateMissmatch,
+    #[error("Pool is full")]
+    PoolCapacityExceeded,
+    #[error("Mint is not accepted by the pool")]
+    InvalidStakeMint,
+    #[error("No room for another stake mint")]
+    StakeMintsFull,
+    #[error("Pool is busy with another instruction")]
+    PoolBusy,
+    #[error("Pool is not funded yet")]
+    PoolNotFunded,
+    #[error("Deposit exceeds the limit per user")]
+    UserCapExceeded,
+    #[error("Referral code is already registered")]
+    CodeTaken,
+    #[error("Unable to deserialize ReferralCode")]
+    InvalidReferralCode,
+    #[error("Scheduled rewards exceed funded rewards")]
+    OverCommittedRewards,
+    #[error("Receipt mint is not the one of the pool")]
+    InvalidReceiptMint,
+    #[error("Position already voted on the proposal")]
+    AlreadyVoted,
+    #[error("Vote choice is out of range")]
+    InvalidVoteChoice,
+    #[error("Reward vault doesn't cover the reward schedule of the new pool")]
+    RewardScheduleUnfunded,
+    #[error("Account length is not the length of MasterStaking")]
+    InvalidMasterStakingLength,
+    #[error("Account is not PDA master-staking")]
+    MasterStakingMissmatch,
+    #[error("Reward token index is out of range")]
+    InvalidRewardIndex,
+    #[error("Invalid number of positions")]
+    InvalidPositionCount,
+    #[error("Unknown pool tags")]
+    InvalidTags,
+    #[error("Position is frozen")]
+    UserFrozen,
+    #[error("Reward mint can't migrate while the pool has stake or emits rewards")]
+    MigrationNotAllowed,
+    #[error("Not initialized, run CreateMasterAndAuthority and Initialize first")]
+    NotInitialized,
+    #[error("Pool has no boost vault")]
+    NoBoostVault,
+    #[error("Invalid accrual mode")]
+    InvalidAccrualMode,
+    #[error("Pool has stake")]
+    PoolNotEmpty,
+    #[error("Pool can't close while it has stake or emits rewards")]
+    CloseNotAllowed,
+    #[error("Emergency withdraw is disabled for the pool")]
+    EmergencyDisabled,
+    #[error("No bonus is scheduled or it has started")]
+    BonusNotCancellable,
+    #[error("Instruction can't be called by another program")]
+    CpiNotAllowed,
+    #[error("Price oracle is not in the allowlist")]
+    PriceOracleNotAllowed,
+    #[error("Token-account of the position is closed")]
+    DestinationClosed,
+    #[error("Amount exceeds the rewards not owed to stakers")]
+    RewardsStillOwed,
+    #[error("Unexpected account in the account list, see the log for its index")]
+    InvalidAccountList,
+    #[error("Signer is not the upgrade authority of the program")]
+    NotUpgradeAuthority,
+    #[error("StakePool is intact, only an empty or corrupt state is rebuilt")]
+    ReinitNotAllowed,
+    #[error("Pool has ended")]
+    PoolEnded,
+    #[error("Protocol fee exceeds MAX_PROTOCOL_FEE_BPS")]
+    ProtocolFeeTooHigh,
+    #[error("Protocol fee token-account is not of the recipient or the reward mint")]
+    InvalidProtocolFeeAccount,
+    #[error("Account is not the RewardTokenInfo of the reward index")]
+    InvalidRewardTokenInfo,
+    #[error("Position still has stake")]
+    PositionNotEmpty,
+    #[error("Position has rewards left to claim")]
+    PositionHasRewards,
+    #[error("n_reward_tokens must be from 1 to MAX_REWARD_TOKENS")]
+    InvalidRewardTokenCount,
+    #[error("Pool is paused, deposits are rejected")]
+    PoolPaused,
+    #[error("Staked amount of the position would be below the pool minimum")]
+    BelowMinimumStake,
+    #[error("Reward amount doesn't pay a reward_per_block over the pool duration")]
+    InsufficientRewardFunding,
+    #[error("Position changed after the proposal started")]
+    PositionChangedAfterProposal,
+    #[error("Token decimals must be inferior to 21")]
+    InvalidMintDecimals,
 }
 
 impl PrintProgramError for StakingError {
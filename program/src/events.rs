@@ -0,0 +1,236 @@
+use borsh::{
+    BorshSerialize,
+    BorshDeserialize,
+    BorshSchema,
+};
+use solana_program::{
+    log::sol_log_data,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+/// Emits Borsh serialized event as a program data log
+pub fn emit<T: BorshSerialize>(
+    event: &T,
+) -> Result<(), ProgramError> {
+    sol_log_data(&[&event.try_to_vec()?]);
+
+    Ok(())
+}
+
+#[derive(Debug, BorshSchema, BorshSerialize, BorshDeserialize)]
+pub struct TvlSnapshot {
+    pub pool_index: u64,
+    pub slot: u64,
+    pub staked_amount: u64, // balance of PDA token-account for staked tokens
+    pub reward_amount: u64, // balance of PDA token-account for reward tokens
+    pub total_staked: u64,
+    pub tags: u32, // StakePool.tags
+    pub stakers: u64, // StakePool.stakers
+}
+
+/// Everything a front end shows for a pool, independent of the StakePool layout. See LogPoolSummary
+#[derive(Debug, BorshSchema, BorshSerialize, BorshDeserialize)]
+pub struct PoolSummary {
+    pub pool_index: u64,
+    pub slot: u64,
+    pub pool_name: [u8; 32],
+    pub project_link: [u8; 128],
+    pub theme_id: u8,
+    pub tags: u32,
+    pub start_block: u64,
+    pub end_block: u64,
+    pub remaining_blocks: u64, // till end_block
+    pub bonus_active: bool,
+    pub bonus_multiplier: u8,
+    pub reward_per_day: u128, // base units, without the bonus
+    pub apr_bps: u64, // without the bonus
+    pub staked_amount: u64, // balance of PDA token-account for staked tokens
+    pub total_staked: u64,
+    pub stakers: u64, // StakePool.stakers
+}
+
+/// PoolSummary of several pools at the same slot, see LogPoolSummaries
+#[derive(Debug, BorshSchema, BorshSerialize, BorshDeserialize)]
+pub struct PoolSummaries {
+    pub summaries: Vec<PoolSummary>,
+}
+
+#[derive(Debug, BorshSchema, BorshSerialize, BorshDeserialize)]
+pub struct UserPosition {
+    pub token_account: Pubkey,
+    pub amount: u64,
+    pub pending: u64, // at UserPositions.slot
+}
+
+/// Page of positions of a pool, see LogUserPositions
+#[derive(Debug, BorshSchema, BorshSerialize, BorshDeserialize)]
+pub struct UserPositions {
+    pub pool_index: u64,
+    pub slot: u64,
+    pub positions: Vec<UserPosition>,
+}
+
+/// Estimate of a position's reward till end_block, see LogUserProjectedRewards
+#[derive(Debug, BorshSchema, BorshSerialize, BorshDeserialize)]
+pub struct UserProjectedRewards {
+    pub pool_index: u64,
+    pub token_account: Pubkey,
+    pub slot: u64,
+    pub shares: u64,
+    pub staked_supply: u64,
+    pub remaining_blocks: u64, // till end_block
+    pub projected: u64, // with the bonus, if the staked supply stays the same. Pending rewards are not included
+}
+
+/// Deposit made with a referral code. Referral rewards are settled off-chain
+#[derive(Debug, BorshSchema, BorshSerialize, BorshDeserialize)]
+pub struct ReferralDeposit {
+    pub pool_index: u64,
+    pub code: [u8; 16],
+    pub referrer: Pubkey,
+    pub token_account: Pubkey,
+    pub amount: u64,
+}
+
+/// Emitted by MigrateRewardMint
+#[derive(Debug, BorshSchema, BorshSerialize, BorshDeserialize)]
+pub struct RewardMintMigrated {
+    pub pool_index: u64,
+    pub old_mint: Pubkey,
+    pub new_mint: Pubkey,
+    pub swept_amount: u64, // leftovers of the old vaults sent to the owner
+}
+
+/// Emitted by Deposit, Withdraw, Harvest and EmergencyWithdraw, the activity of a position for wallets
+#[derive(Debug, BorshSchema, BorshSerialize, BorshDeserialize)]
+pub struct PositionUpdatedEvent {
+    pub pool_index: u64,
+    pub token_account: Pubkey,
+    pub new_amount: u64, // staked after the instruction
+    pub reward_paid: u64, // reward tokens sent, without the protocol fee
+    pub slot: u64,
+}
+
+/// Emitted by ClaimBoost
+#[derive(Debug, BorshSchema, BorshSerialize, BorshDeserialize)]
+pub struct BoostClaimed {
+    pub pool_index: u64,
+    pub token_account: Pubkey,
+    pub amount: u64,
+    pub unclaimed: u64, // left for a later claim when the boost vault runs dry
+}
+
+/// Emitted by ClaimRewardToken
+#[derive(Debug, BorshSchema, BorshSerialize, BorshDeserialize)]
+pub struct RewardTokenClaimed {
+    pub pool_index: u64,
+    pub reward_index: u8,
+    pub token_account: Pubkey,
+    pub amount: u64,
+    pub unclaimed: u64, // left for a later claim when the vault runs dry
+}
+
+/// Emitted by ClosePool
+#[derive(Debug, BorshSchema, BorshSerialize, BorshDeserialize)]
+pub struct PoolClosed {
+    pub pool_index: u64,
+    pub swept_amount: u64, // leftovers of the vaults sent to the owner
+    pub reclaimed_lamports: u64, // rent of the vaults, PDA wallet stake pool and StakePool
+}
+
+/// Result of SetBonusTime with the same arguments at slot, see LogBonusPreview
+#[derive(Debug, BorshSchema, BorshSerialize, BorshDeserialize)]
+pub struct BonusPreview {
+    pub pool_index: u64,
+    pub slot: u64,
+    pub bonus_multiplier: u8,
+    pub bonus_start_block: u64,
+    pub bonus_end_block: u64, // capped by end_block
+    pub end_block: u64, // shortened by the bonus. Current end_block when not feasible
+    pub feasible: bool,
+}
+
+/// Emitted by ReclaimRewards
+#[derive(Debug, BorshSchema, BorshSerialize, BorshDeserialize)]
+pub struct RewardsReclaimed {
+    pub pool_index: u64,
+    pub amount: u64,
+    pub total_owed: u64, // left in the reward vault for the stakers
+}
+
+/// Emitted by ReinitPoolState
+#[derive(Debug, BorshSchema, BorshSerialize, BorshDeserialize)]
+pub struct PoolStateReinitialized {
+    pub pool_index: u64,
+    pub upgrade_authority: Pubkey,
+    pub total_staked: u64,
+    pub total_owed: u64,
+    pub recreated: bool, // StakePool account was gone, not only its data
+}
+
+/// Emitted by DonateRewards. The donor is only in the transaction
+#[derive(Debug, BorshSchema, BorshSerialize, BorshDeserialize)]
+pub struct DonationEvent {
+    pub pool_index: u64,
+    pub amount: u64,
+    pub blocks_added: u64,
+    pub end_block: u64, // after the donation
+}
+
+/// Emitted by SetProtocolFee
+#[derive(Debug, BorshSchema, BorshSerialize, BorshDeserialize)]
+pub struct ProtocolFeeChanged {
+    pub old_bps: u16,
+    pub new_bps: u16,
+    pub recipient: Pubkey,
+}
+
+/// Emitted by SetUserFrozen
+#[derive(Debug, BorshSchema, BorshSerialize, BorshDeserialize)]
+pub struct UserFrozenChanged {
+    pub pool_index: u64,
+    pub token_account: Pubkey,
+    pub frozen: bool,
+}
+
+#[derive(Debug, BorshSchema, BorshSerialize, BorshDeserialize)]
+pub enum ConfigField {
+    ProjectInfo, // values are theme_id. pool_name and project_link are read from StakePool
+    BonusMultiplier,
+    BonusStartBlock,
+    BonusEndBlock,
+    EndBlock,
+    MaxTotalStaked,
+    WarmupSlots,
+    Tags,
+    NRewardTokens,
+    AccrualMode,
+    EmergencyWithdrawEnabled, // values are 0 or 1. Disabling takes effect at StakePool.emergency_withdraw_disable_slot
+    MinRewardDuration,
+    MaxClaimPerTx,
+    Paused, // values are 0 or 1
+}
+
+/// Emitted by owner only instructions changing the pool
+#[derive(Debug, BorshSchema, BorshSerialize, BorshDeserialize)]
+pub struct ConfigChangedEvent {
+    pub pool_index: u64,
+    pub field: ConfigField,
+    pub old_value: u64,
+    pub new_value: u64,
+}
+
+pub fn emit_config_changed(
+    pool_index: u64,
+    field: ConfigField,
+    old_value: u64,
+    new_value: u64,
+) -> Result<(), ProgramError> {
+    emit(&ConfigChangedEvent {
+        pool_index,
+        field,
+        old_value,
+        new_value,
+    })
+}
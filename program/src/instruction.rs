@@ -3,6 +3,32 @@ use borsh::{
     BorshDeserialize,
     BorshSchema,
 };
+use solana_program::{
+    instruction::{
+        AccountMeta,
+        Instruction,
+    },
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    system_program,
+    sysvar,
+};
+use crate::{
+    pda::{
+        master_staking_address,
+        token_account_authority_address,
+        stake_pool_address,
+        wallet_pool_address,
+        staked_token_account_address,
+        reward_token_account_address,
+        receipt_mint_address,
+        boost_token_account_address,
+        reward_token_info_address,
+        extra_reward_token_account_address,
+    },
+    state::MAX_REWARD_TOKENS,
+    id as this_program_id,
+};
 
 #[derive(BorshSchema, BorshSerialize, BorshDeserialize)]
 pub enum StakingInstruction {
@@ -19,18 +45,20 @@ pub enum StakingInstruction {
     /// 6. '[]' rent
     /// 7. '[]' system-program 
     /// 8. '[]' token-program
-    /// 9. '[writable]' token-account with tokens for reward. Tokens will be relocated to the pool token-account
+    /// 9. '[writable]' token-account with tokens for reward. Tokens will be relocated to the pool token-account. Not used without prefund
     /// 10. '[writable]' PDA authority for the token-account 
     /// 11. '[writable]' PDA token-account for the staked tokens
     /// 12. '[writable]' PDA token-account for the reward tokens
+    /// 13. '[writable]' PDA mint of receipt tokens. Optional, see MintReceipt
+    /// 14. '[writable]' PDA token-account of the boost vault. Optional, see ClaimBoost. Requires 13
+    ///
+    /// Last 4 * (n_reward_tokens - 1). for each reward token after the first, by reward index:
+    ///     '[]' its mint
+    ///     '[writable]' token-account with its reward tokens. Not used without prefund
+    ///     '[writable]' PDA RewardTokenInfo, see pda::reward_token_info_address
+    ///     '[writable]' PDA token-account of its vault, see pda::extra_reward_token_account_address
     Initialize {
-        n_reward_tokens: u8, // Number of reward tokens
-        reward_amount: u64,
-        start_block: u64,
-        end_block: u64,
-        pool_name: [u8; 32],
-        project_link: [u8; 128],
-        theme_id: u8,
+        params: InitializeParams, // borsh encodes it as the fields inline
     },
     /// Deposit staked tokens and collect reward tokens (if any)
     ///
@@ -49,6 +77,11 @@ pub enum StakingInstruction {
     /// 10. '[]' clock
     /// 11. '[]' system-program
     /// 12. '[]' token-program
+    /// 13. '[writable]' token-account for reward tokens. Only if token mint is not the mint of the pool, see AddStakeMint
+    ///
+    /// 13 or 14. '[]' PDA ReferralCode. Optional, see RegisterReferralCode
+    /// Then '[]' price oracle of lib::PRICE_ORACLES. Optional, updates UserInfo.avg_entry_price
+    /// Last two. optional protocol fee accounts, see SetProtocolFee
     Deposit {
         amount: u64,
     },
@@ -65,8 +98,19 @@ pub enum StakingInstruction {
     /// 6. '[writable]' PDA for state UserInfo. Should be created prior to this instruction
     /// 7. '[]' clock
     /// 8. '[]' token-program
+    /// 9. '[writable]' token-account for reward tokens. Only if token-account is not of the mint of the pool,
+    ///    see AddStakeMint. The following accounts are shifted by one
+    /// 9. '[]' swap program. Only if StakePool.reward_swap_program is set
+    /// 10. '[writable]' token-account for swapped reward tokens. Only if StakePool.reward_swap_program is set
+    ///
+    /// 11.. accounts required by the swap program. Only if StakePool.reward_swap_program is set
+    ///
+    /// With close_on_empty PDA wallet stake pool '[writable]' goes right after 8 (or 9 with AddStakeMint)
+    /// and the swap accounts are shifted by one
+    /// Last two. optional protocol fee accounts, see SetProtocolFee
     Withdraw {
         amount: u64,
+        close_on_empty: bool, // close UserInfo if the position ends empty. Rent goes back to PDA wallet stake pool
     },
     /// Withdraw staked tokens without caring about rewards 
     ///
@@ -75,10 +119,13 @@ pub enum StakingInstruction {
     /// 0. '[signer]' owner of the token-account
     /// 1. '[writable]' token-account for staked tokens
     /// 2. '[]' PDA authority for the token-account. Should be created prior to this instruction
-    /// 3. '[wirtable]' PDA token-account for staked tokens. Should be created prior to this instruction
-    /// 4. '[writable]' PDA for state UserInfo. Should be created prior to this instruction 
-    /// 5. '[]' PDA for state StakePool. Should be created prior to this instruction
+    /// 3. '[wirtable]' PDA token-account for staked tokens of the mint of the token-account. Should be created prior to this instruction
+    /// 4. '[writable]' PDA for state UserInfo. Should be created prior to this instruction
+    /// 5. '[writable]' PDA for state StakePool. Should be created prior to this instruction
     /// 6. '[]' token-program
+    /// 7. '[]' clock
+    /// 8. '[writable]' PDA wallet stake pool. Optional, closes UserInfo if nothing is left to claim.
+    ///    Rent goes back to PDA wallet stake pool, which paid for it
     EmergencyWithdraw,
     /// Update project info
     ///
@@ -92,7 +139,7 @@ pub enum StakingInstruction {
         project_link: [u8; 128],
         theme_id: u8,
     },
-    /// Set bonus time
+    /// Set bonus time. With a boost vault the extra blocks of the bonus are paid by ClaimBoost
     ///
     /// Accounts expected:
     ///
@@ -101,12 +148,13 @@ pub enum StakingInstruction {
     /// 2. '[writable]' PDA for state StakePool. Should be created prior to this instruction
     /// 3. '[]' PDA token-account for staked tokens. Should be created prior to this instruction
     /// 4. '[]' clock
+    /// 5. '[]' PDA token-account for reward tokens. Should be created prior to this instruction
     SetBonusTime {
         bonus_multiplier: u8,
         bonus_start_block: u64,
         bonus_end_block: u64,
     },
-    /// Change time of end pool
+    /// Change time of end pool. Transfers the reward for the added blocks, so the schedule stays within StakePool.total_funded
     ///
     /// Accounts expected:
     ///
@@ -117,6 +165,10 @@ pub enum StakingInstruction {
     /// 4. '[]' token-program
     /// 5. '[writable]' token-account with reward
     /// 6. '[writable]' PDA token-account for reward
+    ///
+    /// 7.. for each reward token after the first, by reward index:
+    ///     '[writable]' token-account of the owner with its reward tokens
+    ///     '[writable]' PDA token-account of its vault
     UpdateEndBlock {
         end_block: u64,
     },
@@ -131,4 +183,1128 @@ pub enum StakingInstruction {
     /// 4. '[]' rent
     /// 5. '[]' system-program
     CreateMasterAndAuthority,
+    /// Log amount of reward tokens emitted between two blocks. Read only
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. '[]' PDA for state StakePool. Should be created prior to this instruction
+    LogEmittedBetween {
+        from: u64,
+        to: u64,
+    },
+    /// Set project link longer than 128 bytes. StakePool keeps the first 128 bytes as a fallback
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. '[writable, signer]' Pool owner
+    /// 1. '[]' mint of the reward token
+    /// 2. '[writable]' PDA for state StakePool. Should be created prior to this instruction
+    /// 3. '[writable]' PDA for long project link
+    /// 4. '[]' rent
+    /// 5. '[]' system-program
+    SetLongLink {
+        uri: Vec<u8>,
+    },
+    /// Move a staked position to another token-account. Amount and reward debt are kept,
+    /// so pending rewards move together with the position
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. '[writable, signer]' owner of the current token-account. Pays for the new UserInfo and gets rent of the old one
+    /// 1. '[]' current token-account of the position
+    /// 2. '[]' new token-account of the position
+    /// 3. '[]' PDA for state StakePool. Should be created prior to this instruction
+    /// 4. '[writable]' PDA for state UserInfo of the current token-account. Should be created prior to this instruction
+    /// 5. '[writable]' PDA for state UserInfo of the new token-account
+    /// 6. '[]' rent
+    /// 7. '[]' system-program
+    /// 8. '[]' clock
+    TransferPosition,
+    /// Stake pending reward tokens. Staked and reward tokens share the mint of the pool
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. '[signer]' owner of the token-account
+    /// 1. '[]' token-account of the position
+    /// 2. '[writable]' PDA for state StakePool. Should be created prior to this instruction
+    /// 3. '[]' PDA authority for the token-account. Should be created prior to this instruction
+    /// 4. '[writable]' PDA token-account for staked tokens. Should be created prior to this instruction
+    /// 5. '[writable]' PDA token-account for reward tokens. Should be created prior to this instruction
+    /// 6. '[writable]' PDA for state UserInfo. Should be created prior to this instruction
+    /// 7. '[]' clock
+    /// 8. '[]' token-program
+    ///
+    /// Last two. optional protocol fee accounts, see SetProtocolFee
+    Compound,
+    /// Emit TvlSnapshot event with the current balances of the pool. Read only
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. '[]' PDA for state StakePool. Should be created prior to this instruction
+    /// 1. '[]' PDA token-account for staked tokens. Should be created prior to this instruction
+    /// 2. '[]' PDA token-account for reward tokens. Should be created prior to this instruction
+    /// 3. '[]' clock
+    LogTvlSnapshot,
+    /// Change time of end for several pools of the same owner. Up to MAX_POOLS_PER_UPDATE pools.
+    /// end_blocks[i] is applied to the i-th group of accounts. A group has 4 accounts and 2 more for each
+    /// reward token of the pool after the first
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. '[signer]' owner of the token-accounts with reward. Pool owner
+    /// 1. '[]' clock
+    /// 2. '[]' token-program
+    ///
+    /// then for every pool:
+    ///     '[writable]' PDA for state StakePool. Should be created prior to this instruction
+    ///     '[]' mint of the reward token
+    ///     '[writable]' token-account with reward
+    ///     '[writable]' PDA token-account for reward
+    ///     for each reward token after the first, by reward index:
+    ///         '[writable]' token-account of the owner with its reward tokens
+    ///         '[writable]' PDA token-account of its vault
+    UpdateEndBlockMany {
+        end_blocks: Vec<u64>,
+    },
+    /// Set or unset the program converting rewards on Withdraw. Only programs from REWARD_SWAP_PROGRAMS are accepted
+    ///
+    /// When set, Withdraw pays pending rewards with a CPI into the swap program instead of a direct transfer.
+    /// Instruction data of the CPI is Borsh serialized RewardSwap. Accounts passed to the swap program:
+    ///
+    /// 0. '[signer]' PDA authority for the token-account
+    /// 1. '[writable]' PDA token-account for reward tokens. Source of the swap, at most amount_in is taken
+    /// 2. '[writable]' token-account of the user receiving the swapped tokens
+    /// 3. '[]' token-program
+    ///
+    /// 4.. accounts of the swap itself (pools, vaults, oracles), in the order given to Withdraw
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. '[signer]' Pool owner
+    /// 1. '[]' mint of the reward token
+    /// 2. '[writable]' PDA for state StakePool. Should be created prior to this instruction
+    SetRewardSwapProgram {
+        reward_swap_program: Option<Pubkey>,
+    },
+    /// Set or unset the delegate allowed to submit WithdrawDelegated for the position
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. '[signer]' owner of the token-account
+    /// 1. '[]' token-account of the position
+    /// 2. '[]' PDA for state StakePool. Should be created prior to this instruction
+    /// 3. '[writable]' PDA for state UserInfo. Should be created prior to this instruction
+    SetDelegate {
+        delegate: Option<Pubkey>,
+    },
+    /// Withdraw on behalf of the owner. Staked and reward tokens go only to the token-account of the position,
+    /// so the delegate (e.g. a relayer paying fees) can't redirect them
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. '[signer]' delegate set by SetDelegate
+    /// 1. '[writable]' token-account of the position
+    ///
+    /// 2-8. same as accounts 2-8 of Withdraw
+    /// 9.. same as accounts 9.. of Withdraw
+    WithdrawDelegated {
+        amount: u64,
+    },
+    /// Set the cap of staked tokens in the pool. Deposits over the cap are rejected. 0 means no cap
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. '[signer]' Pool owner
+    /// 1. '[]' mint of the reward token
+    /// 2. '[writable]' PDA for state StakePool. Should be created prior to this instruction
+    SetMaxTotalStaked {
+        max_total_staked: u64,
+    },
+    /// Log max_total_staked, staked tokens and the headroom left. Read only
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. '[]' PDA for state StakePool. Should be created prior to this instruction
+    /// 1. '[]' PDA token-account for staked tokens. Should be created prior to this instruction
+    LogPoolCapacity,
+    /// Accept one more stake mint in the pool (basket staking). Rewards are still paid in the mint of the pool
+    ///
+    /// Stakers share rewards by shares, not by tokens: amount * weight / STAKE_WEIGHT_PRECISION.
+    /// The mint of the pool has weight STAKE_WEIGHT_PRECISION. Weight also has to level out different decimals
+    /// of the mints. Weights can't be changed after the mint is added, so shares of stakers stay the same.
+    /// Stake mint positions can't Compound
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. '[writable, signer]' Pool owner. Pays for the new PDA token-account
+    /// 1. '[]' mint of the reward token
+    /// 2. '[writable]' PDA for state StakePool. Should be created prior to this instruction
+    /// 3. '[]' stake mint to add
+    /// 4. '[]' PDA authority for the token-account
+    /// 5. '[writable]' PDA token-account for staked tokens of the stake mint
+    /// 6. '[]' rent
+    /// 7. '[]' system-program
+    /// 8. '[]' token-program
+    AddStakeMint {
+        weight: u16, // in basis points of STAKE_WEIGHT_PRECISION
+    },
+    /// Transfer reward tokens to the pool. The pool accepts deposits once the vault covers
+    /// the rest of the schedule and StakePool.total_funded covers the whole schedule. Used for pools initialized without prefund
+    /// and for refunding an emptied vault. The pool is updated first, so blocks while the vault was empty are not paid
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. '[signer]' Pool owner
+    /// 1. '[]' mint of the reward token
+    /// 2. '[writable]' PDA for state StakePool. Should be created prior to this instruction
+    /// 3. '[writable]' token-account with reward
+    /// 4. '[writable]' PDA token-account for reward tokens. Should be created prior to this instruction
+    /// 5. '[]' token-program
+    /// 6. '[]' PDA token-account for staked tokens. Should be created prior to this instruction
+    /// 7. '[]' clock
+    FundRewards {
+        amount: u64,
+    },
+    /// Log bitmask of pool invariants, see HEALTH_* in state. Read only
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. '[]' PDA for state StakePool. Should be created prior to this instruction
+    /// 1. '[]' PDA token-account for staked tokens. Should be created prior to this instruction
+    /// 2. '[]' PDA token-account for reward tokens. Should be created prior to this instruction
+    LogPoolHealth,
+    /// Register a referral code pointing to the referrer token-account. Deposit accepts the code PDA
+    /// as the last account and emits ReferralDeposit
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. '[writable, signer]' owner of the referrer token-account. Pays for PDA ReferralCode
+    /// 1. '[]' referrer token-account
+    /// 2. '[writable]' PDA ReferralCode
+    /// 3. '[]' rent
+    /// 4. '[]' system-program
+    RegisterReferralCode {
+        code: [u8; 16],
+    },
+    /// EmergencyWithdraw with the bump of PDA UserInfo, so the address is checked with a single hash.
+    /// Accounts are the same as EmergencyWithdraw
+    EmergencyWithdrawWithBump {
+        user_info_bump: u8, // bump returned by pda::user_info_address
+    },
+    /// Move `amount` of the position into receipt tokens. Pending rewards are paid as in Withdraw.
+    /// Staked tokens stay in the pool, one receipt token stands for one staked token removed from
+    /// UserInfo.amount. Tokens behind receipts earn no rewards. Not available for pools with AddStakeMint
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. '[signer]' owner of the token-account
+    /// 1. '[writable]' token-account for staked tokens. Reward tokens are transferred to it
+    /// 2. '[writable]' PDA mint of receipt tokens. Created by Initialize
+    /// 3. '[writable]' token-account for receipt tokens
+    /// 4. '[writable]' PDA for state StakePool
+    /// 5. '[]' PDA authority for the token-account
+    /// 6. '[writable]' PDA token-account for staked tokens
+    /// 7. '[writable]' PDA token-account for reward tokens
+    /// 8. '[writable]' PDA for state UserInfo
+    /// 9. '[]' clock
+    /// 10. '[]' token-program
+    ///
+    /// 11.. accounts of the reward swap, see Withdraw
+    /// Last two. optional protocol fee accounts, see SetProtocolFee
+    MintReceipt {
+        amount: u64,
+    },
+    /// Burn receipt tokens and receive the staked tokens behind them. Any holder of receipts can redeem
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. '[signer]' owner of the token-account with receipt tokens
+    /// 1. '[writable]' token-account with receipt tokens
+    /// 2. '[writable]' token-account for staked tokens
+    /// 3. '[writable]' PDA for state StakePool
+    /// 4. '[]' PDA authority for the token-account
+    /// 5. '[writable]' PDA token-account for staked tokens
+    /// 6. '[writable]' PDA mint of receipt tokens
+    /// 7. '[]' token-program
+    BurnReceipt {
+        amount: u64,
+    },
+    /// Log the slot at which the reward vault runs out at the current rate, bonus included.
+    /// end_block if the vault outlasts the schedule. Read only
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. '[]' PDA for state StakePool. Should be created prior to this instruction
+    /// 1. '[]' PDA token-account for reward tokens. Should be created prior to this instruction
+    LogDepletionSlot,
+    /// Vote on a proposal of the pool with the weight of UserInfo.amount. One vote per position.
+    /// PDA ProposalTally ([StakePool, "PROPOSAL", proposal_id]) holds the weight per choice and is
+    /// created by the first vote. PDA VoteReceipt ([ProposalTally, UserInfo, "VOTE"]) marks the position as voted.
+    /// The first vote starts the proposal. Only positions unchanged since then vote, so the weight is the amount
+    /// at the start. Tokens withdrawn or moved by TransferPosition after a vote can't vote again
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. '[writable, signer]' owner of the token-account. Pays for the PDAs
+    /// 1. '[]' token-account for staked tokens
+    /// 2. '[]' PDA for state StakePool
+    /// 3. '[]' PDA for state UserInfo
+    /// 4. '[writable]' PDA ProposalTally
+    /// 5. '[writable]' PDA VoteReceipt
+    /// 6. '[]' rent
+    /// 7. '[]' system-program
+    /// 8. '[]' clock
+    CastVote {
+        proposal_id: u64,
+        choice: u8, // less than state::MAX_VOTE_CHOICES
+    },
+    /// Set the warmup of new deposits. Pending rewards of a position are paid as
+    /// pending * min(slot - UserInfo.deposit_slot, warmup_slots) / warmup_slots,
+    /// the rest stays in the reward vault. Every Deposit restarts the warmup. 0 disables it
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. '[signer]' Pool owner
+    /// 1. '[]' mint of the reward token
+    /// 2. '[writable]' PDA for state StakePool. Should be created prior to this instruction
+    SetWarmupSlots {
+        warmup_slots: u64,
+    },
+    /// Log UserInfo, accrued_token_per_share and the pending reward of the position at the current slot. Read only
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. '[]' PDA for state StakePool
+    /// 1. '[]' PDA for state UserInfo
+    /// 2. '[]' token-account for staked tokens
+    /// 3. '[]' PDA token-account for staked tokens
+    /// 4. '[]' PDA token-account for reward tokens
+    /// 5. '[]' clock
+    LogUserDebug,
+    /// Emit UserPositions event with amount and pending reward of up to MAX_POSITIONS_PER_LOG positions. Read only
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. '[]' PDA for state StakePool
+    /// 1. '[]' PDA token-account for staked tokens
+    /// 2. '[]' PDA token-account for reward tokens
+    /// 3. '[]' clock
+    ///
+    /// 4 + 2 * i. '[]' PDA for state UserInfo of the pool
+    /// 5 + 2 * i. '[]' token-account for staked tokens of the UserInfo
+    LogUserPositions,
+    /// Set the discovery tags of the pool, a combination of state::TAG_* bits
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. '[signer]' Pool owner
+    /// 1. '[]' mint of the reward token
+    /// 2. '[writable]' PDA for state StakePool. Should be created prior to this instruction
+    SetTags {
+        tags: u32,
+    },
+    /// Freeze or unfreeze a position pending a compliance review. A frozen position can't
+    /// Withdraw, WithdrawDelegated, MintReceipt or TransferPosition, nor Deposit, which pays out the
+    /// pending rewards. EmergencyWithdraw is blocked as well, so the principal is frozen together
+    /// with the rewards. Compound keeps working, its rewards stay in the position
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. '[signer]' Pool owner
+    /// 1. '[]' mint of the reward token
+    /// 2. '[]' PDA for state StakePool
+    /// 3. '[]' token-account of the position
+    /// 4. '[writable]' PDA for state UserInfo
+    /// 5. '[]' instructions sysvar, see utils::validate_not_cpi
+    SetUserFrozen {
+        frozen: bool,
+    },
+    /// Emit PoolSummary event with the project info and the derived fields (APR, remaining blocks,
+    /// bonus status). Front ends read it instead of decoding StakePool. Read only
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. '[]' PDA for state StakePool. Should be created prior to this instruction
+    /// 1. '[]' PDA token-account for staked tokens. Should be created prior to this instruction
+    /// 2. '[]' clock
+    LogPoolSummary,
+    /// Move an ended pool to a new reward mint. The staked token is the reward token, so the pool
+    /// starts over with new vaults of `new mint` and a new schedule of `reward_amount` over
+    /// start_block..end_block. Deposits wait for FundRewards, like Initialize without prefund.
+    ///
+    /// Sequencing:
+    /// 1. the pool ends (slot >= end_block) and every position withdraws, paying the old rewards.
+    ///    Pools with receipts or other stake mints can't migrate
+    /// 2. MigrateRewardMint sends the leftovers of the old vaults to the owner and closes them
+    /// 3. FundRewards with `new mint`. The new vaults are at pda::stake_mint_token_account_address
+    ///    and pda::migrated_reward_token_account_address of `new mint`
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. '[writable, signer]' Pool owner. Pays for the new vaults and gets the rent of the old ones
+    /// 1. '[]' current mint of the pool
+    /// 2. '[writable]' PDA for state StakePool
+    /// 3. '[]' new mint
+    /// 4. '[]' PDA authority for the token-account
+    /// 5. '[writable]' PDA token-account for staked tokens. Closed
+    /// 6. '[writable]' PDA token-account for reward tokens. Closed
+    /// 7. '[writable]' token-account of the current mint receiving the leftovers of 5 and 6
+    /// 8. '[writable]' new PDA token-account for staked tokens
+    /// 9. '[writable]' new PDA token-account for reward tokens
+    /// 10. '[]' rent
+    /// 11. '[]' clock
+    /// 12. '[]' system-program
+    /// 13. '[]' token-program
+    /// 14. '[]' instructions sysvar, see utils::validate_not_cpi
+    MigrateRewardMint {
+        reward_amount: u64,
+        start_block: u64,
+        end_block: u64,
+    },
+    /// Retire the last reward token of the pool, its rate is zeroed and n_reward_tokens decremented.
+    /// `index` must be n_reward_tokens - 1, the primary reward token (index 0) can't be retired.
+    /// The pool is updated first, so blocks till the retirement stay claimable
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. '[signer]' Pool owner
+    /// 1. '[]' mint of the reward token
+    /// 2. '[writable]' PDA for state StakePool. Should be created prior to this instruction
+    /// 3. '[]' PDA token-account for staked tokens. Should be created prior to this instruction
+    /// 4. '[]' PDA token-account for reward tokens. Should be created prior to this instruction
+    /// 5. '[]' clock
+    RetireRewardToken {
+        index: u8,
+    },
+    /// Fund the boost vault paying the extra blocks of the bonus (bonus_multiplier - 1 per block).
+    /// Only pools initialized with a boost vault
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. '[signer]' Pool owner
+    /// 1. '[]' mint of the reward token
+    /// 2. '[]' PDA for state StakePool
+    /// 3. '[writable]' token-account of the owner with reward tokens
+    /// 4. '[writable]' PDA token-account of the boost vault
+    /// 5. '[]' token-program
+    FundBoostVault {
+        amount: u64,
+    },
+    /// Pay the boost of the position from the boost vault. Boosts are settled on every change of the
+    /// position and paid only by this instruction. Pays what the boost vault holds, the rest stays unclaimed
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. '[signer]' owner of the token-account
+    /// 1. '[]' token-account of the position
+    /// 2. '[writable]' PDA for state StakePool
+    /// 3. '[]' PDA authority for the token-account
+    /// 4. '[]' PDA token-account for staked tokens
+    /// 5. '[]' PDA token-account for reward tokens
+    /// 6. '[writable]' PDA token-account of the boost vault
+    /// 7. '[writable]' PDA for state UserInfo
+    /// 8. '[writable]' token-account of the owner for reward tokens
+    /// 9. '[]' clock
+    /// 10. '[]' token-program
+    ClaimBoost,
+    /// Set how shares of positions are computed. Only while the pool has no stake
+    ///
+    /// ACCRUAL_MODE_CLASSIC: shares are the staked amount (weighted by the stake mint)
+    /// ACCRUAL_MODE_TIME_WEIGHTED: shares are multiplied by the loyalty weight of the continuous stake,
+    /// 1 + 0.1 per LOYALTY_STEP_SLOTS (about a week) since UserInfo.stake_start_slot, capped at 2.
+    /// Withdraw restarts the stake, Deposit and Compound keep it. The weight is applied on the next
+    /// Deposit, Withdraw or Compound of the position, Withdraw of 0 only refreshes it
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. '[signer]' Pool owner
+    /// 1. '[]' mint of the reward token
+    /// 2. '[writable]' PDA for state StakePool. Should be created prior to this instruction
+    /// 3. '[]' instructions sysvar, see utils::validate_not_cpi
+    SetAccrualMode {
+        accrual_mode: u8, // state::ACCRUAL_MODE_*
+    },
+    /// Close an ended pool without stake. Leftovers of the vaults go to the owner token-account,
+    /// rent of the vaults, PDA wallet stake pool and StakePool goes to the owner. StakePool is zeroed.
+    /// Pools with receipts, other stake mints or a boost vault can't close
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. '[writable, signer]' Pool owner
+    /// 1. '[]' mint of the reward token
+    /// 2. '[writable]' PDA for state StakePool
+    /// 3. '[]' PDA authority for the token-account
+    /// 4. '[writable]' PDA token-account for staked tokens
+    /// 5. '[writable]' PDA token-account for reward tokens
+    /// 6. '[writable]' token-account of the owner receiving the leftovers of 4 and 5
+    /// 7. '[writable]' PDA wallet stake pool
+    /// 8. '[]' clock
+    /// 9. '[]' system-program
+    /// 10. '[]' token-program
+    /// 11. '[]' instructions sysvar, see utils::validate_not_cpi
+    ClosePool,
+    /// Log BonusPreview: end_block and bonus_end_block SetBonusTime would set with the same
+    /// arguments, and whether it would succeed. Nothing is written
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. '[]' PDA for state StakePool
+    /// 1. '[]' PDA token-account for staked tokens
+    /// 2. '[]' clock
+    /// 3. '[]' PDA token-account for reward tokens
+    LogBonusPreview {
+        bonus_multiplier: u8,
+        bonus_start_block: u64,
+        bonus_end_block: u64,
+    },
+    /// Grow PDA UserInfo written with an earlier layout to USER_INFO_LEN. Existing fields are kept,
+    /// reward_debt of u64 layouts is multiplied by the precision factor, new fields are zero.
+    /// Anyone can call it, the payer tops up the rent. Does nothing for an up to date UserInfo
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. '[writable, signer]' payer
+    /// 1. '[writable]' PDA for state StakePool. A position of the first layout is added to its stakers
+    /// 2. '[writable]' PDA UserInfo
+    /// 3. '[]' rent
+    /// 4. '[]' system-program
+    MigrateUserInfo,
+    /// Enable or disable EmergencyWithdraw, for pools enforcing a lockup. Enabling is immediate,
+    /// disabling takes effect after state::EMERGENCY_WITHDRAW_TIMELOCK_SLOTS
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. '[signer]' Pool owner
+    /// 1. '[]' mint of the reward token
+    /// 2. '[writable]' PDA for state StakePool. Should be created prior to this instruction
+    /// 3. '[]' clock
+    /// 4. '[]' instructions sysvar, see utils::validate_not_cpi
+    SetEmergencyWithdrawEnabled {
+        enabled: bool,
+    },
+    /// Cancel a bonus set by SetBonusTime before it starts. end_block gets back the blocks
+    /// the bonus took from the end of the pool
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. '[signer]' Pool owner
+    /// 1. '[]' mint of the reward token
+    /// 2. '[writable]' PDA for state StakePool. Should be created prior to this instruction
+    /// 3. '[]' clock
+    /// 4. '[]' instructions sysvar, see utils::validate_not_cpi
+    CancelBonus,
+    /// Set the slots a position stays after its last Deposit before any reward is paid. Rewards
+    /// settled earlier by Withdraw, Deposit or Compound are forfeited and stay in the reward vault,
+    /// the principal can be withdrawn anytime. 0 disables it
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. '[signer]' Pool owner
+    /// 1. '[]' mint of the reward token
+    /// 2. '[writable]' PDA for state StakePool. Should be created prior to this instruction
+    SetMinRewardDuration {
+        min_reward_duration: u64,
+    },
+    /// Emit PoolSummaries event with PoolSummary of up to MAX_POOLS_PER_LOG pools. Read only
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. '[]' clock
+    ///
+    /// 1 + 2 * i. '[]' PDA for state StakePool
+    /// 2 + 2 * i. '[]' PDA token-account for staked tokens of the StakePool
+    LogPoolSummaries,
+    /// Emit UserProjectedRewards event: reward of the position from now till end_block with the bonus,
+    /// if the staked supply stays the same. Read only
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. '[]' PDA for state StakePool
+    /// 1. '[]' PDA for state UserInfo
+    /// 2. '[]' token-account for staked tokens of the UserInfo
+    /// 3. '[]' PDA token-account for staked tokens
+    /// 4. '[]' clock
+    LogUserProjectedRewards,
+    /// Send rewards nobody is owed from the reward vault to the owner: the vault less the rewards
+    /// accrued and not paid yet and the rewards scheduled till end_block
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. '[signer]' Pool owner
+    /// 1. '[]' mint of the reward token
+    /// 2. '[writable]' PDA for state StakePool
+    /// 3. '[]' PDA authority for the token-account
+    /// 4. '[]' PDA token-account for staked tokens
+    /// 5. '[writable]' PDA token-account for reward tokens
+    /// 6. '[writable]' token-account of the owner receiving the rewards
+    /// 7. '[]' clock
+    /// 8. '[]' token-program
+    ReclaimRewards {
+        amount: u64,
+    },
+    /// Deposit into the position of the owner's associated token-account, created if it doesn't exist.
+    /// `amount` is moved from the source token-account to the associated one first
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. '[writable, signer]' owner of the token-accounts. Pays for the associated token-account
+    /// 1. '[writable]' associated token-account of the owner for the mint
+    /// 2. '[]' token mint for staked token
+    /// 3. '[writable]' source token-account with tokens for deposit. May be 1
+    /// 4. '[]' associated-token-account program
+    ///
+    /// 5.. accounts of Deposit from 3 on
+    DepositWithAta {
+        amount: u64,
+    },
+    /// Set the max reward paid by one Deposit, Withdraw or Compound. The rest of a larger payout is
+    /// carried in UserInfo.pending_owed and paid first by the following ones. 0 disables it
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. '[signer]' Pool owner
+    /// 1. '[]' mint of the reward token
+    /// 2. '[writable]' PDA for state StakePool. Should be created prior to this instruction
+    SetMaxClaimPerTx {
+        max_claim_per_tx: u64,
+    },
+    /// Disaster recovery. Rebuilds a lost or corrupt StakePool of existing vaults from the given values,
+    /// without creating vaults or moving tokens. Intact StakePools are rejected.
+    /// Accounting values come from the last known state, e.g. the events of the pool. Bonus, stake mints,
+    /// receipts and the other settings start as after Initialize
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. '[writable, signer]' upgrade authority of this program. Pays for StakePool if the account is gone
+    /// 1. '[]' program data account of this program
+    /// 2. '[]' PDA master-staking
+    /// 3. '[writable]' PDA for state StakePool of `pool_index`
+    /// 4. '[]' token mint of the pool
+    /// 5. '[]' PDA authority for the token-account
+    /// 6. '[]' PDA token-account for staked tokens
+    /// 7. '[]' PDA token-account for reward tokens
+    /// 8. '[]' PDA wallet stake pool
+    /// 9. '[]' rent
+    /// 10. '[]' system-program
+    /// 11. '[]' PDA token-account of the boost vault. Optional, only if the pool had one
+    ReinitPoolState {
+        params: ReinitPoolStateParams, // borsh encodes it as the fields inline
+    },
+    /// Donate reward tokens to a running pool. Anyone can donate. The donation extends end_block by
+    /// the blocks it pays at the current reward_per_block, the rate stays the same. The dust of the
+    /// division deepens the reward vault. Pools with several reward tokens keep their end_block, the donation
+    /// only deepens the vault. Ended pools don't accept donations, nothing would pay them out
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. '[signer]' donor
+    /// 1. '[writable]' token-account of the donor with reward tokens
+    /// 2. '[writable]' PDA for state StakePool. Should be created prior to this instruction
+    /// 3. '[writable]' PDA token-account for reward tokens. Should be created prior to this instruction
+    /// 4. '[]' clock
+    /// 5. '[]' token-program
+    DonateRewards {
+        amount: u64,
+    },
+    /// Collect pending rewards, the staked amount stays the same. Replaces a Deposit of 0 for collecting.
+    /// Reward tokens after the first are settled and paid by ClaimRewardToken
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. '[signer]' owner of the token-account
+    /// 1. '[writable]' token-account for staked tokens. Receives the rewards if it is of the mint of the pool
+    /// 2. '[writable]' PDA for state StakePool. Should be created prior to this instruction
+    /// 3. '[]' PDA authority for the token-account. Should be created prior to this instruction
+    /// 4. '[]' PDA token-account for staked tokens. Should be created prior to this instruction
+    /// 5. '[writable]' PDA token-account for reward tokens. Should be created prior to this instruction
+    /// 6. '[writable]' PDA for state UserInfo. Should be created prior to this instruction
+    /// 7. '[]' clock
+    /// 8. '[]' token-program
+    /// 9. '[writable]' token-account for reward tokens. Only if token-account is not of the mint of the pool,
+    ///    see AddStakeMint
+    ///
+    /// Last two. optional protocol fee accounts, see SetProtocolFee
+    Harvest,
+    /// Set the share of every reward payout sent to the protocol. Signed by the upgrade authority of this program.
+    /// Instructions paying rewards (Deposit, Withdraw, WithdrawDelegated, MintReceipt, Compound, Harvest) may end
+    /// with '[]' PDA master-staking and '[writable]' token-account of `protocol_fee_recipient` for reward tokens.
+    /// The fee is taken from payouts of instructions passing them, so clients from before the fee don't change.
+    /// The token-account isn't read while the fee is 0
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. '[writable, signer]' upgrade authority of this program. Pays for resizing a legacy MasterStaking
+    /// 1. '[]' program data account of this program
+    /// 2. '[writable]' PDA master-staking
+    /// 3. '[]' rent
+    /// 4. '[]' system-program
+    SetProtocolFee {
+        protocol_fee_bps: u16, // at most state::MAX_PROTOCOL_FEE_BPS
+        protocol_fee_recipient: Pubkey,
+    },
+    /// Pay a reward token after the first to the position. These rewards are settled on every change of the
+    /// position and paid only by this instruction. Pays what the vault holds, the rest stays unclaimed
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. '[signer]' owner of the token-account
+    /// 1. '[]' token-account of the position
+    /// 2. '[writable]' PDA for state StakePool
+    /// 3. '[]' PDA authority for the token-account
+    /// 4. '[]' PDA token-account for staked tokens
+    /// 5. '[]' PDA token-account for reward tokens
+    /// 6. '[writable]' PDA for state UserInfo
+    /// 7. '[]' PDA RewardTokenInfo of reward_index
+    /// 8. '[writable]' PDA token-account of its vault
+    /// 9. '[writable]' token-account of the owner for the reward token
+    /// 10. '[]' clock
+    /// 11. '[]' token-program
+    ClaimRewardToken {
+        reward_index: u8, // from 1, index 0 is paid with the other rewards
+    },
+    /// Close an empty position. The staked amount and every unclaimed reward must be 0, pending rewards
+    /// of index 0 are paid by the Withdraw that emptied it. Rent goes back to PDA wallet stake pool,
+    /// which paid for UserInfo in Deposit
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. '[signer]' owner of the token-account
+    /// 1. '[]' token-account of the position
+    /// 2. '[]' PDA for state StakePool
+    /// 3. '[writable]' PDA for state UserInfo
+    /// 4. '[writable]' PDA wallet stake pool
+    CloseUserInfo,
+    /// Halt or resume deposits, e.g. during an incident. Withdraw, Harvest and EmergencyWithdraw
+    /// stay allowed while paused, so stakers are never locked in
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. '[signer]' Pool owner
+    /// 1. '[]' mint of the reward token
+    /// 2. '[writable]' PDA for state StakePool. Should be created prior to this instruction
+    SetPaused {
+        paused: bool,
+    },
+    /// Resize a StakePool of state::LEGACY_STAKE_POOL_LEN to the current layout. Other instructions reject the
+    /// pool till then. total_staked is the staked vault and total_owed the reward vault less the rewards left to
+    /// emit. Anyone can call it, run MigrateUserInfo for the positions after it
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. '[writable, signer]' payer of the rent for the larger account
+    /// 1. '[writable]' PDA for state StakePool
+    /// 2. '[]' PDA token-account for staked tokens
+    /// 3. '[]' PDA token-account for reward tokens
+    /// 4. '[]' rent
+    /// 5. '[]' system-program
+    MigrateStakePool,
+}
+
+impl StakingInstruction {
+    /// Readable summary of the instruction, e.g. "Deposit 1000". For logs and tools decoding transactions
+    pub fn describe(&self) -> String {
+        match self {
+            StakingInstruction::Initialize { params } => format!(
+                "Initialize pool '{}' reward {} over blocks {}..{}",
+                bytes_to_string(&params.pool_name),
+                params.reward_amount,
+                params.start_block,
+                params.end_block,
+            ),
+            StakingInstruction::Deposit { amount } => format!("Deposit {}", amount),
+            StakingInstruction::Withdraw { amount, close_on_empty } => format!(
+                "Withdraw {}{}",
+                amount,
+                if *close_on_empty { " and close empty position" } else { "" },
+            ),
+            StakingInstruction::EmergencyWithdraw => "Emergency withdraw".to_string(),
+            StakingInstruction::UpdateProjectInfo {
+                pool_name,
+                theme_id,
+                ..
+            } => format!(
+                "Update project info: pool '{}' theme {}",
+                bytes_to_string(pool_name),
+                theme_id,
+            ),
+            StakingInstruction::SetBonusTime {
+                bonus_multiplier,
+                bonus_start_block,
+                bonus_end_block,
+            } => format!(
+                "Set bonus x{} over blocks {}..{}",
+                bonus_multiplier,
+                bonus_start_block,
+                bonus_end_block,
+            ),
+            StakingInstruction::UpdateEndBlock { end_block } => format!("Update end block to {}", end_block),
+            StakingInstruction::CreateMasterAndAuthority => "Create master and authority".to_string(),
+            StakingInstruction::LogEmittedBetween { from, to } => format!("Log emitted between blocks {}..{}", from, to),
+            StakingInstruction::SetLongLink { uri } => format!("Set long link '{}'", bytes_to_string(uri)),
+            StakingInstruction::TransferPosition => "Transfer position".to_string(),
+            StakingInstruction::Compound => "Compound".to_string(),
+            StakingInstruction::LogTvlSnapshot => "Log TVL snapshot".to_string(),
+            StakingInstruction::UpdateEndBlockMany { end_blocks } => format!("Update end blocks to {:?}", end_blocks),
+            StakingInstruction::SetRewardSwapProgram { reward_swap_program } => match reward_swap_program {
+                Some(key) => format!("Set reward swap program {}", key),
+                None => "Unset reward swap program".to_string(),
+            },
+            StakingInstruction::SetDelegate { delegate } => match delegate {
+                Some(key) => format!("Set delegate {}", key),
+                None => "Unset delegate".to_string(),
+            },
+            StakingInstruction::WithdrawDelegated { amount } => format!("Withdraw delegated {}", amount),
+            StakingInstruction::SetMaxTotalStaked { max_total_staked } => format!("Set max total staked {}", max_total_staked),
+            StakingInstruction::LogPoolCapacity => "Log pool capacity".to_string(),
+            StakingInstruction::AddStakeMint { weight } => format!("Add stake mint with weight {}", weight),
+            StakingInstruction::FundRewards { amount } => format!("Fund rewards {}", amount),
+            StakingInstruction::LogPoolHealth => "Log pool health".to_string(),
+            StakingInstruction::RegisterReferralCode { code } => format!("Register referral code '{}'", bytes_to_string(code)),
+            StakingInstruction::EmergencyWithdrawWithBump { user_info_bump } => format!("Emergency withdraw with bump {}", user_info_bump),
+            StakingInstruction::MintReceipt { amount } => format!("Mint receipt {}", amount),
+            StakingInstruction::BurnReceipt { amount } => format!("Burn receipt {}", amount),
+            StakingInstruction::LogDepletionSlot => "Log depletion slot".to_string(),
+            StakingInstruction::CastVote { proposal_id, choice } => format!("Cast vote {} on proposal {}", choice, proposal_id),
+            StakingInstruction::SetWarmupSlots { warmup_slots } => format!("Set warmup slots {}", warmup_slots),
+            StakingInstruction::LogUserDebug => "Log user debug".to_string(),
+            StakingInstruction::LogUserPositions => "Log user positions".to_string(),
+            StakingInstruction::SetTags { tags } => format!("Set tags {:#b}", tags),
+            StakingInstruction::SetUserFrozen { frozen } => format!("Set user frozen {}", frozen),
+            StakingInstruction::LogPoolSummary => "Log pool summary".to_string(),
+            StakingInstruction::MigrateRewardMint {
+                reward_amount,
+                start_block,
+                end_block,
+            } => format!(
+                "Migrate reward mint with reward {} over blocks {}..{}",
+                reward_amount,
+                start_block,
+                end_block,
+            ),
+            StakingInstruction::RetireRewardToken { index } => format!("Retire reward token {}", index),
+            StakingInstruction::FundBoostVault { amount } => format!("Fund boost vault {}", amount),
+            StakingInstruction::ClaimBoost => "Claim boost".to_string(),
+            StakingInstruction::SetAccrualMode { accrual_mode } => format!("Set accrual mode {}", accrual_mode),
+            StakingInstruction::ClosePool => "Close pool".to_string(),
+            StakingInstruction::LogBonusPreview { bonus_multiplier, bonus_start_block, bonus_end_block } => format!(
+                "Log bonus preview x{} from block {} to block {}",
+                bonus_multiplier, bonus_start_block, bonus_end_block,
+            ),
+            StakingInstruction::MigrateUserInfo => "Migrate user info".to_string(),
+            StakingInstruction::SetEmergencyWithdrawEnabled { enabled } => format!(
+                "{} emergency withdraw",
+                if *enabled { "Enable" } else { "Disable" },
+            ),
+            StakingInstruction::CancelBonus => "Cancel bonus".to_string(),
+            StakingInstruction::SetMinRewardDuration { min_reward_duration } => format!("Set min reward duration {}", min_reward_duration),
+            StakingInstruction::LogPoolSummaries => "Log pool summaries".to_string(),
+            StakingInstruction::LogUserProjectedRewards => "Log user projected rewards".to_string(),
+            StakingInstruction::ReclaimRewards { amount } => format!("Reclaim rewards {}", amount),
+            StakingInstruction::DepositWithAta { amount } => format!("Deposit {} with ATA", amount),
+            StakingInstruction::SetMaxClaimPerTx { max_claim_per_tx } => format!("Set max claim per tx {}", max_claim_per_tx),
+            StakingInstruction::ReinitPoolState { params } => format!("Reinit state of pool {}", params.pool_index),
+            StakingInstruction::DonateRewards { amount } => format!("Donate rewards {}", amount),
+            StakingInstruction::Harvest => "Harvest".to_string(),
+            StakingInstruction::SetProtocolFee { protocol_fee_bps, protocol_fee_recipient } => format!(
+                "Set protocol fee {} bps to {}",
+                protocol_fee_bps, protocol_fee_recipient,
+            ),
+            StakingInstruction::ClaimRewardToken { reward_index } => format!("Claim reward token {}", reward_index),
+            StakingInstruction::CloseUserInfo => "Close user info".to_string(),
+            StakingInstruction::SetPaused { paused } => format!(
+                "{} deposits",
+                if *paused { "Pause" } else { "Resume" },
+            ),
+            StakingInstruction::MigrateStakePool => "Migrate stake pool".to_string(),
+        }
+    }
+}
+
+/// Zero padded bytes (pool_name, project_link) as text
+fn bytes_to_string(bytes: &[u8]) -> String {
+    let len = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+
+    String::from_utf8_lossy(&bytes[..len]).into_owned()
+}
+
+/// Settings of the pool created by Initialize
+#[derive(BorshSchema, BorshSerialize, BorshDeserialize)]
+pub struct InitializeParams {
+    pub n_reward_tokens: u8, // Number of reward tokens, StakePool.mint included
+    pub reward_amount: u64,
+    pub start_block: u64,
+    pub end_block: u64,
+    pub emission_period: u64, // Number of blocks per reward_per_block. 1 means reward is paid every block
+    pub min_compound_interval: u64, // Number of blocks between two Compound of the same user
+    pub round_up_rewards: u8, // 1 rounds pending rewards up, so the reward vault ends empty. 0 rounds down
+    pub prefund: bool, // false creates the pool without the reward transfer. Deposits wait for FundRewards
+    pub max_stake_per_user: u64, // Max staked amount of one UserInfo. 0 means no limit
+    pub min_stake_amount: u64, // Min staked amount of one UserInfo holding stake, in base units. 0 means no minimum
+    pub pool_name: [u8; 32],
+    pub project_link: [u8; 128],
+    pub theme_id: u8,
+    pub tags: u32, // state::TAG_* bits
+    pub extra_reward_amounts: [u64; MAX_REWARD_TOKENS - 1], // reward of the tokens after the first over the same blocks
+}
+
+/// Values of the StakePool rebuilt by ReinitPoolState
+#[derive(BorshSchema, BorshSerialize, BorshDeserialize)]
+pub struct ReinitPoolStateParams {
+    pub pool_index: u64,
+    pub owner: Pubkey,
+    pub start_block: u64,
+    pub end_block: u64,
+    pub reward_per_block: u64,
+    pub emission_period: u64,
+    pub min_compound_interval: u64,
+    pub round_up_rewards: u8,
+    pub max_stake_per_user: u64,
+    pub accrued_token_per_share: u128,
+    pub last_reward_block: u64,
+    pub total_staked: u64,
+    pub total_shares: u64,
+    pub total_owed: u64,
+    pub pool_name: [u8; 32],
+    pub project_link: [u8; 128],
+    pub theme_id: u8,
+    pub tags: u32,
+    pub stakers: u64,
+}
+
+/// Instruction data of the CPI into StakePool.reward_swap_program
+#[derive(BorshSchema, BorshSerialize, BorshDeserialize)]
+pub struct RewardSwap {
+    pub amount_in: u64, // amount of reward tokens to convert
+}
+
+/// Build Initialize for pool_index fetched with pda::next_pool_index.
+/// All PDAs are derived from the same pool_index, so they match the pool the program creates.
+/// `extra_reward_tokens` are (mint, token-account with its reward tokens) of the reward tokens after the first,
+/// one for each of params.n_reward_tokens - 1
+pub fn initialize_for_next_index(
+    pool_index: u64,
+    owner: &Pubkey,
+    mint: &Pubkey,
+    reward_token_account: &Pubkey,
+    extra_reward_tokens: &[(Pubkey, Pubkey)],
+    params: InitializeParams,
+) -> Result<Instruction, ProgramError> {
+    if params.n_reward_tokens as usize != 1 + extra_reward_tokens.len() {
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let data = StakingInstruction::Initialize { params }.try_to_vec()?;
+
+    let mut accounts = vec![
+        AccountMeta::new(*owner, true), // 0
+        AccountMeta::new(master_staking_address()?, false), // 1
+        AccountMeta::new(stake_pool_address(pool_index).0, false), // 2
+        AccountMeta::new(wallet_pool_address(pool_index).0, false), // 3
+        AccountMeta::new_readonly(this_program_id(), false), // 4
+        AccountMeta::new_readonly(*mint, false), // 5
+        AccountMeta::new_readonly(sysvar::rent::id(), false), // 6
+        AccountMeta::new_readonly(system_program::id(), false), // 7
+        AccountMeta::new_readonly(spl_token::id(), false), // 8
+        AccountMeta::new(*reward_token_account, false), // 9
+        AccountMeta::new(token_account_authority_address()?, false), // 10
+        AccountMeta::new(staked_token_account_address(pool_index).0, false), // 11
+        AccountMeta::new(reward_token_account_address(pool_index).0, false), // 12
+        AccountMeta::new(receipt_mint_address(pool_index).0, false), // 13
+        AccountMeta::new(boost_token_account_address(pool_index, mint).0, false), // 14
+    ];
+    for (i, (reward_mint, reward_token_account)) in extra_reward_tokens.iter().enumerate() {
+        let reward_index = 1 + i as u8;
+        accounts.push(AccountMeta::new_readonly(*reward_mint, false));
+        accounts.push(AccountMeta::new(*reward_token_account, false));
+        accounts.push(AccountMeta::new(reward_token_info_address(pool_index, reward_index).0, false));
+        accounts.push(AccountMeta::new(extra_reward_token_account_address(pool_index, reward_index).0, false));
+    }
+
+    Ok(Instruction {
+        program_id: this_program_id(),
+        accounts,
+        data,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn name(text: &str) -> [u8; 32] {
+        let mut bytes = [0; 32];
+        bytes[..text.len()].copy_from_slice(text.as_bytes());
+        bytes
+    }
+
+    fn assert_describes(cases: Vec<(StakingInstruction, &str)>) {
+        for (instruction, summary) in cases {
+            assert_eq!(instruction.describe(), summary);
+        }
+    }
+
+    #[test]
+    fn test_describe_pool_setup() {
+        assert_describes(vec![
+            (
+                StakingInstruction::Initialize {
+                    params: InitializeParams {
+                        n_reward_tokens: 1,
+                        reward_amount: 5000,
+                        start_block: 100,
+                        end_block: 200,
+                        emission_period: 1,
+                        min_compound_interval: 0,
+                        round_up_rewards: 0,
+                        prefund: true,
+                        max_stake_per_user: 0,
+                        min_stake_amount: 0,
+                        pool_name: name("MyPool"),
+                        project_link: [0; 128],
+                        theme_id: 0,
+                        tags: 0,
+                        extra_reward_amounts: [0; MAX_REWARD_TOKENS - 1],
+                    },
+                },
+                "Initialize pool 'MyPool' reward 5000 over blocks 100..200",
+            ),
+            (StakingInstruction::CreateMasterAndAuthority, "Create master and authority"),
+            (
+                StakingInstruction::UpdateProjectInfo {
+                    pool_name: name("Renamed"),
+                    project_link: [0; 128],
+                    theme_id: 3,
+                },
+                "Update project info: pool 'Renamed' theme 3",
+            ),
+            (StakingInstruction::SetLongLink { uri: b"https://example.com".to_vec() }, "Set long link 'https://example.com'"),
+            (StakingInstruction::SetTags { tags: 0b101 }, "Set tags 0b101"),
+            (StakingInstruction::AddStakeMint { weight: 20000 }, "Add stake mint with weight 20000"),
+            (StakingInstruction::SetMaxTotalStaked { max_total_staked: 3000 }, "Set max total staked 3000"),
+            (StakingInstruction::SetWarmupSlots { warmup_slots: 10 }, "Set warmup slots 10"),
+            (StakingInstruction::SetAccrualMode { accrual_mode: 1 }, "Set accrual mode 1"),
+            (StakingInstruction::SetMinRewardDuration { min_reward_duration: 50 }, "Set min reward duration 50"),
+            (StakingInstruction::SetMaxClaimPerTx { max_claim_per_tx: 700 }, "Set max claim per tx 700"),
+            (StakingInstruction::SetPaused { paused: true }, "Pause deposits"),
+            (StakingInstruction::SetPaused { paused: false }, "Resume deposits"),
+            (StakingInstruction::SetEmergencyWithdrawEnabled { enabled: true }, "Enable emergency withdraw"),
+            (StakingInstruction::SetEmergencyWithdrawEnabled { enabled: false }, "Disable emergency withdraw"),
+            (StakingInstruction::ClosePool, "Close pool"),
+            (StakingInstruction::MigrateStakePool, "Migrate stake pool"),
+            (StakingInstruction::MigrateUserInfo, "Migrate user info"),
+        ]);
+    }
+
+    #[test]
+    fn test_describe_pool_admin() {
+        let key = Pubkey::new_from_array([1; 32]);
+        let params = ReinitPoolStateParams {
+            pool_index: 4,
+            owner: key,
+            start_block: 100,
+            end_block: 200,
+            reward_per_block: 10,
+            emission_period: 1,
+            min_compound_interval: 0,
+            round_up_rewards: 0,
+            max_stake_per_user: 0,
+            accrued_token_per_share: 0,
+            last_reward_block: 100,
+            total_staked: 0,
+            total_shares: 0,
+            total_owed: 0,
+            pool_name: [0; 32],
+            project_link: [0; 128],
+            theme_id: 0,
+            tags: 0,
+            stakers: 0,
+        };
+        assert_describes(vec![
+            (
+                StakingInstruction::SetBonusTime {
+                    bonus_multiplier: 3,
+                    bonus_start_block: 200,
+                    bonus_end_block: 300,
+                },
+                "Set bonus x3 over blocks 200..300",
+            ),
+            (StakingInstruction::CancelBonus, "Cancel bonus"),
+            (StakingInstruction::UpdateEndBlock { end_block: 1100 }, "Update end block to 1100"),
+            (StakingInstruction::UpdateEndBlockMany { end_blocks: vec![1100, 1200] }, "Update end blocks to [1100, 1200]"),
+            (StakingInstruction::SetRewardSwapProgram { reward_swap_program: Some(key) }, &format!("Set reward swap program {}", key)),
+            (StakingInstruction::SetRewardSwapProgram { reward_swap_program: None }, "Unset reward swap program"),
+            (StakingInstruction::FundRewards { amount: 1000 }, "Fund rewards 1000"),
+            (StakingInstruction::DonateRewards { amount: 20 }, "Donate rewards 20"),
+            (StakingInstruction::ReclaimRewards { amount: 30 }, "Reclaim rewards 30"),
+            (StakingInstruction::FundBoostVault { amount: 40 }, "Fund boost vault 40"),
+            (
+                StakingInstruction::MigrateRewardMint {
+                    reward_amount: 5000,
+                    start_block: 300,
+                    end_block: 400,
+                },
+                "Migrate reward mint with reward 5000 over blocks 300..400",
+            ),
+            (StakingInstruction::RetireRewardToken { index: 2 }, "Retire reward token 2"),
+            (StakingInstruction::ReinitPoolState { params }, "Reinit state of pool 4"),
+            (
+                StakingInstruction::SetProtocolFee {
+                    protocol_fee_bps: 50,
+                    protocol_fee_recipient: key,
+                },
+                &format!("Set protocol fee 50 bps to {}", key),
+            ),
+            (StakingInstruction::SetUserFrozen { frozen: true }, "Set user frozen true"),
+        ]);
+    }
+
+    #[test]
+    fn test_describe_positions() {
+        let key = Pubkey::new_from_array([2; 32]);
+        assert_describes(vec![
+            (StakingInstruction::Deposit { amount: 1000 }, "Deposit 1000"),
+            (StakingInstruction::DepositWithAta { amount: 1000 }, "Deposit 1000 with ATA"),
+            (StakingInstruction::Withdraw { amount: 500, close_on_empty: false }, "Withdraw 500"),
+            (StakingInstruction::Withdraw { amount: 500, close_on_empty: true }, "Withdraw 500 and close empty position"),
+            (StakingInstruction::WithdrawDelegated { amount: 500 }, "Withdraw delegated 500"),
+            (StakingInstruction::EmergencyWithdraw, "Emergency withdraw"),
+            (StakingInstruction::EmergencyWithdrawWithBump { user_info_bump: 254 }, "Emergency withdraw with bump 254"),
+            (StakingInstruction::Harvest, "Harvest"),
+            (StakingInstruction::Compound, "Compound"),
+            (StakingInstruction::ClaimRewardToken { reward_index: 1 }, "Claim reward token 1"),
+            (StakingInstruction::ClaimBoost, "Claim boost"),
+            (StakingInstruction::TransferPosition, "Transfer position"),
+            (StakingInstruction::SetDelegate { delegate: Some(key) }, &format!("Set delegate {}", key)),
+            (StakingInstruction::SetDelegate { delegate: None }, "Unset delegate"),
+            (StakingInstruction::MintReceipt { amount: 70 }, "Mint receipt 70"),
+            (StakingInstruction::BurnReceipt { amount: 70 }, "Burn receipt 70"),
+            (StakingInstruction::RegisterReferralCode { code: *b"WELCOME\0\0\0\0\0\0\0\0\0" }, "Register referral code 'WELCOME'"),
+            (StakingInstruction::CastVote { proposal_id: 9, choice: 1 }, "Cast vote 1 on proposal 9"),
+            (StakingInstruction::CloseUserInfo, "Close user info"),
+        ]);
+    }
+
+    #[test]
+    fn test_describe_logs() {
+        assert_describes(vec![
+            (StakingInstruction::LogEmittedBetween { from: 150, to: 350 }, "Log emitted between blocks 150..350"),
+            (StakingInstruction::LogTvlSnapshot, "Log TVL snapshot"),
+            (StakingInstruction::LogPoolCapacity, "Log pool capacity"),
+            (StakingInstruction::LogPoolHealth, "Log pool health"),
+            (StakingInstruction::LogDepletionSlot, "Log depletion slot"),
+            (StakingInstruction::LogUserDebug, "Log user debug"),
+            (StakingInstruction::LogUserPositions, "Log user positions"),
+            (StakingInstruction::LogPoolSummary, "Log pool summary"),
+            (StakingInstruction::LogPoolSummaries, "Log pool summaries"),
+            (StakingInstruction::LogUserProjectedRewards, "Log user projected rewards"),
+            (
+                StakingInstruction::LogBonusPreview {
+                    bonus_multiplier: 2,
+                    bonus_start_block: 200,
+                    bonus_end_block: 300,
+                },
+                "Log bonus preview x2 from block 200 to block 300",
+            ),
+        ]);
+    }
 }
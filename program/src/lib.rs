@@ -1,8 +1,13 @@
 pub mod processor;
+// BorshSchema builds a struct per variant whose fields are never read
+#[allow(dead_code)]
 pub mod instruction;
 pub mod state;
 pub mod error;
 pub mod utils;
+pub mod events;
+pub mod pda;
+pub mod math;
 
 #[cfg(not(feature = "no-entrypoint"))]
 pub mod entrypoint;
@@ -16,5 +21,29 @@ pub const BUMP_SEED_MASTER_STAKING: u8 = 254;
 pub const ADD_SEED_STATE_POOL: &str = "STATE_POOL";
 pub const ADD_SEED_WALLET_POOL: &str = "WALLET_POOL"; // PDA with SOL for creating PDA UserInfo
 pub const ADD_SEED_STAKED: &str = "STAKED"; // PDA t-a with staked tokens. Reward tokens are kept in other PDA t-a
+pub const ADD_SEED_LINK: &str = "LINK"; // PDA with project link longer than StakePool.project_link
+pub const ADD_SEED_REFERRAL: &str = "REF"; // PDA ReferralCode, seeds are ["REF", code]
+pub const ADD_SEED_RECEIPT: &str = "RECEIPT"; // PDA mint of receipt tokens. Authority is PDA authority for token-accounts
+pub const ADD_SEED_PROPOSAL: &str = "PROPOSAL"; // PDA ProposalTally, seeds are [StakePool, "PROPOSAL", proposal_id]
+pub const ADD_SEED_VOTE: &str = "VOTE"; // PDA VoteReceipt, seeds are [ProposalTally, UserInfo, "VOTE"]
+pub const ADD_SEED_BOOST: &str = "BOOST"; // PDA t-a paying the bonus, seeds are [pool_index, mint, "BOOST"]
+pub const ADD_SEED_REWARD: &str = "REWARD"; // PDA RewardTokenInfo, seeds are [pool_index, reward_index, "REWARD"]
+pub const ADD_SEED_REWARD_VAULT: &str = "REWARD_VAULT"; // PDA t-a of a reward token after the first, seeds are [pool_index, reward_index, "REWARD_VAULT"]
+
+// Programs allowed as StakePool.reward_swap_program. See StakingInstruction::SetRewardSwapProgram for the CPI contract.
+// Empty, so rewards are paid directly. A deployment lists the audited swap programs here and redeploys,
+// SetRewardSwapProgram rejects any other program
+#[cfg(not(feature = "test-bpf"))]
+pub const REWARD_SWAP_PROGRAMS: &[&str] = &[];
+// Mock swap program of the integration tests, see tests/reward_swap.rs
+#[cfg(feature = "test-bpf")]
+pub const REWARD_SWAP_PROGRAMS: &[&str] = &["SwapMock11111111111111111111111111111111111"];
+
+// Program of associated token-accounts, see DepositWithAta
+pub const ASSOCIATED_TOKEN_PROGRAM: &str = "ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL";
+
+// Accounts accepted as the price oracle of Deposit. Data starts with the price of one staked token
+// in quote units as u64 LE, see UserInfo.avg_entry_price
+pub const PRICE_ORACLES: &[&str] = &[];
 
 solana_program::declare_id!("EyJ4ZNzAK8HJJrRbTTE6x769RA2h95zj826194DxyEbw");
\ No newline at end of file
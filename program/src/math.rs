@@ -0,0 +1,156 @@
+//! Conversions for display. Pure functions without accounts, usable off-chain by the SDK
+use std::convert::TryFrom;
+use crate::error::StakingError;
+
+/// Slots in a day at the target slot time of 400ms
+pub const SLOTS_PER_DAY: u64 = 216_000;
+
+/// Decimals of the fixed-point UI amounts returned by this module, floats are avoided on-chain
+pub const UI_AMOUNT_DECIMALS: u8 = 6;
+
+/// Bounds of a min_stake_amount that Initialize accepts without a warning, as UI amounts.
+/// Outside them the decimals of the mint were likely forgotten
+pub const MIN_STAKE_WARN_LOW_UI: u128 = 1_000; // 0.001 token
+pub const MIN_STAKE_WARN_HIGH_UI: u128 = 1_000_000_000_000; // 1_000_000 tokens
+
+/// Daily emission in base units. reward_per_block is paid once per emission_period blocks
+pub fn reward_per_day(
+    reward_per_block: u64,
+    emission_period: u64,
+) -> Result<u128, StakingError> {
+    let reward_per_day = (reward_per_block as u128)
+        .checked_mul(SLOTS_PER_DAY as u128)
+        .ok_or(StakingError::Overflow)?
+        .checked_div(emission_period as u128)
+        .ok_or(StakingError::Overflow)?;
+
+    Ok(reward_per_day)
+}
+
+/// APR in basis points when the staked and the reward token are the same, 0 without stake
+pub fn apr_bps(
+    reward_per_day: u128,
+    staked_amount: u64,
+) -> Result<u64, StakingError> {
+    if staked_amount == 0 {
+        return Ok(0);
+    }
+
+    let apr_bps = reward_per_day
+        .checked_mul(365 * 10_000)
+        .ok_or(StakingError::Overflow)?
+        .checked_div(staked_amount as u128)
+        .ok_or(StakingError::Overflow)?;
+
+    Ok(u64::try_from(apr_bps).unwrap_or(u64::MAX))
+}
+
+/// Average price of `amount` bought at `average_price` and `added` bought at `price`
+pub fn weighted_average_price(
+    average_price: u64,
+    amount: u64,
+    price: u64,
+    added: u64,
+) -> Result<u64, StakingError> {
+    let total = (amount as u128)
+        .checked_add(added as u128)
+        .ok_or(StakingError::Overflow)?;
+    if total == 0 {
+        return Ok(price);
+    }
+
+    let average_price = (average_price as u128)
+        .checked_mul(amount as u128)
+        .ok_or(StakingError::Overflow)?
+        .checked_add(
+            (price as u128)
+            .checked_mul(added as u128)
+            .ok_or(StakingError::Overflow)?)
+        .ok_or(StakingError::Overflow)?
+        .checked_div(total)
+        .ok_or(StakingError::Overflow)?;
+
+    u64::try_from(average_price).map_err(StakingError::from)
+}
+
+/// Base units of a mint with `decimals` as a fixed-point amount with UI_AMOUNT_DECIMALS.
+/// 1_500_000_000 with 9 decimals is 1_500_000, i.e. 1.5 tokens
+pub fn base_to_ui_fixed(
+    amount: u128,
+    decimals: u8,
+) -> Result<u128, StakingError> {
+    let ui_factor = 10_u128
+        .checked_pow(UI_AMOUNT_DECIMALS as u32)
+        .ok_or(StakingError::Overflow)?;
+    let mint_factor = 10_u128
+        .checked_pow(decimals as u32)
+        .ok_or(StakingError::Overflow)?;
+
+    let ui_amount = amount
+        .checked_mul(ui_factor)
+        .ok_or(StakingError::Overflow)?
+        .checked_div(mint_factor)
+        .ok_or(StakingError::Overflow)?;
+
+    Ok(ui_amount)
+}
+
+/// UI amount of a mint with `decimals` in base units, for configuring amounts stored on chain.
+/// 1.5 with 9 decimals is 1_500_000_000. Rounds to the nearest unit, negative and NaN give 0
+/// and amounts above u64::MAX saturate
+pub fn ui_amount_to_base(
+    ui: f64,
+    decimals: u8,
+) -> u64 {
+    (ui * 10_f64.powi(decimals as i32)).round() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reward_per_day_ui(reward_per_block: u64, emission_period: u64, decimals: u8) -> u128 {
+        base_to_ui_fixed(reward_per_day(reward_per_block, emission_period).unwrap(), decimals).unwrap()
+    }
+
+    #[test]
+    fn test_reward_per_day_ui_of_decimals_and_rates() {
+        // 1_000 tokens of a mint without decimals
+        assert_eq!(reward_per_day_ui(1_000, 1, 0), 216_000_000 * 1_000_000);
+        // 0.005 token per block with 9 decimals is 1_080 tokens
+        assert_eq!(reward_per_day_ui(5_000_000, 1, 9), 1_080_000_000);
+        // 0.001 token per block with 18 decimals is 216 tokens
+        assert_eq!(reward_per_day_ui(1_000_000_000_000_000, 1, 18), 216_000_000);
+        // 1 base unit every 10 blocks with 6 decimals is 0.0216 token
+        assert_eq!(reward_per_day_ui(1, 10, 6), 21_600);
+        // below the precision of the UI amount
+        assert_eq!(reward_per_day_ui(1, 1, 12), 0);
+    }
+
+    #[test]
+    fn test_weighted_average_price_of_two_deposits() {
+        // 1_000 at 100, then 3_000 at 200
+        let average_price = weighted_average_price(0, 0, 100, 1_000).unwrap();
+        assert_eq!(average_price, 100);
+        assert_eq!(weighted_average_price(average_price, 1_000, 200, 3_000).unwrap(), 175);
+
+        // rounded down
+        assert_eq!(weighted_average_price(100, 2, 101, 1).unwrap(), 100);
+        // whole supplies of a u64 mint
+        assert_eq!(weighted_average_price(1_000_000_000_000, u64::MAX, 1_000_000_000_000, u64::MAX).unwrap(), 1_000_000_000_000);
+    }
+
+    #[test]
+    fn test_ui_amount_to_base_of_decimals() {
+        assert_eq!(ui_amount_to_base(1.5, 9), 1_500_000_000);
+        assert_eq!(ui_amount_to_base(0.01, 6), 10_000);
+        assert_eq!(ui_amount_to_base(250.0, 0), 250);
+        // rounded to the nearest unit
+        assert_eq!(ui_amount_to_base(0.0000015, 6), 2);
+        assert_eq!(ui_amount_to_base(0.4, 0), 0);
+
+        assert_eq!(ui_amount_to_base(-1.0, 6), 0);
+        assert_eq!(ui_amount_to_base(f64::NAN, 6), 0);
+        assert_eq!(ui_amount_to_base(1e12, 18), u64::MAX);
+    }
+}
@@ -0,0 +1,278 @@
+pub mod seeds;
+
+use solana_program::{
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+use crate::{
+    state::MasterStaking,
+    id as this_program_id,
+    ADD_SEED_STAKED,
+    ADD_SEED_REFERRAL,
+    ADD_SEED_RECEIPT,
+    ADD_SEED_PROPOSAL,
+    ADD_SEED_VOTE,
+    ADD_SEED_BOOST,
+    ADD_SEED_REWARD,
+    ADD_SEED_REWARD_VAULT,
+    ASSOCIATED_TOKEN_PROGRAM,
+};
+use std::str::FromStr;
+
+/// Address and bump of PDA UserInfo. The only place the UserInfo seeds are defined
+pub fn user_info_address(
+    stake_pool_key: &Pubkey,
+    token_account_key: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[stake_pool_key.as_ref(), token_account_key.as_ref()],
+        &this_program_id(),
+    )
+}
+
+/// Address of PDA UserInfo for a known bump. A single hash instead of the search of user_info_address
+pub fn user_info_address_with_bump(
+    stake_pool_key: &Pubkey,
+    token_account_key: &Pubkey,
+    bump_seed: u8,
+) -> Result<Pubkey, ProgramError> {
+    Ok(Pubkey::create_program_address(
+        &[stake_pool_key.as_ref(), token_account_key.as_ref(), &[bump_seed]],
+        &this_program_id(),
+    )?)
+}
+
+/// Index the next Initialize will use. master_data is the data of PDA master-staking
+pub fn next_pool_index(
+    master_data: &[u8],
+) -> Result<u64, ProgramError> {
+    let master_staking = MasterStaking::from_slice(master_data)?;
+
+    Ok(master_staking.pool_counter)
+}
+
+pub fn master_staking_address() -> Result<Pubkey, ProgramError> {
+    Ok(Pubkey::create_program_address(
+        &seeds::master_staking(),
+        &this_program_id(),
+    )?)
+}
+
+pub fn token_account_authority_address() -> Result<Pubkey, ProgramError> {
+    Ok(Pubkey::create_program_address(
+        &seeds::token_account_authority(),
+        &this_program_id(),
+    )?)
+}
+
+pub fn stake_pool_address(
+    pool_index: u64,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &seeds::stake_pool(&pool_index.to_le_bytes()),
+        &this_program_id(),
+    )
+}
+
+pub fn wallet_pool_address(
+    pool_index: u64,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &seeds::wallet_pool(&pool_index.to_le_bytes()),
+        &this_program_id(),
+    )
+}
+
+pub fn staked_token_account_address(
+    pool_index: u64,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &seeds::staked_token_account(&pool_index.to_le_bytes()),
+        &this_program_id(),
+    )
+}
+
+pub fn reward_token_account_address(
+    pool_index: u64,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &seeds::reward_token_account(&pool_index.to_le_bytes()),
+        &this_program_id(),
+    )
+}
+
+/// Reward vault of a pool after MigrateRewardMint to `reward_mint`
+pub fn migrated_reward_token_account_address(
+    pool_index: u64,
+    reward_mint: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[&pool_index.to_le_bytes(), reward_mint.as_ref()],
+        &this_program_id(),
+    )
+}
+
+/// Boost vault paying the extra blocks of the bonus, see ClaimBoost
+pub fn boost_token_account_address(
+    pool_index: u64,
+    mint: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[&pool_index.to_le_bytes(), mint.as_ref(), ADD_SEED_BOOST.as_bytes()],
+        &this_program_id(),
+    )
+}
+
+/// RewardTokenInfo of the reward token `reward_index`, 0 is StakePool.mint and has none
+pub fn reward_token_info_address(
+    pool_index: u64,
+    reward_index: u8,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[&pool_index.to_le_bytes(), &[reward_index], ADD_SEED_REWARD.as_bytes()],
+        &this_program_id(),
+    )
+}
+
+/// Vault paying the reward token `reward_index`, see ClaimRewardToken
+pub fn extra_reward_token_account_address(
+    pool_index: u64,
+    reward_index: u8,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[&pool_index.to_le_bytes(), &[reward_index], ADD_SEED_REWARD_VAULT.as_bytes()],
+        &this_program_id(),
+    )
+}
+
+/// Vault for a stake mint other than StakePool.mint
+pub fn stake_mint_token_account_address(
+    pool_index: u64,
+    stake_mint: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[&pool_index.to_le_bytes(), stake_mint.as_ref(), ADD_SEED_STAKED.as_bytes()],
+        &this_program_id(),
+    )
+}
+
+pub fn associated_token_program_id() -> Result<Pubkey, ProgramError> {
+    Pubkey::from_str(ASSOCIATED_TOKEN_PROGRAM).map_err(|_| ProgramError::InvalidArgument)
+}
+
+/// Associated token-account of `owner` for `mint`, derived as the associated token-account program does
+pub fn associated_token_account_address(
+    owner: &Pubkey,
+    mint: &Pubkey,
+) -> Result<Pubkey, ProgramError> {
+    Ok(Pubkey::find_program_address(
+        &[owner.as_ref(), spl_token::id().as_ref(), mint.as_ref()],
+        &associated_token_program_id()?,
+    ).0)
+}
+
+pub fn referral_code_address(
+    code: &[u8; 16],
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[ADD_SEED_REFERRAL.as_bytes(), code],
+        &this_program_id(),
+    )
+}
+
+/// Mint of receipt tokens of the pool, see MintReceipt
+pub fn receipt_mint_address(
+    pool_index: u64,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[&pool_index.to_le_bytes(), ADD_SEED_RECEIPT.as_bytes()],
+        &this_program_id(),
+    )
+}
+
+/// Tally of votes of the pool stakers for `proposal_id`, see CastVote
+pub fn proposal_tally_address(
+    stake_pool_key: &Pubkey,
+    proposal_id: u64,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[stake_pool_key.as_ref(), ADD_SEED_PROPOSAL.as_bytes(), &proposal_id.to_le_bytes()],
+        &this_program_id(),
+    )
+}
+
+/// Exists once the position of `user_info_key` has voted on the proposal
+pub fn vote_receipt_address(
+    proposal_tally_key: &Pubkey,
+    user_info_key: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[proposal_tally_key.as_ref(), user_info_key.as_ref(), ADD_SEED_VOTE.as_bytes()],
+        &this_program_id(),
+    )
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use borsh::BorshSerialize;
+    use crate::{
+        instruction::{
+            initialize_for_next_index,
+            InitializeParams,
+        },
+        state::{
+            MASTER_STAKING_LEN,
+            MAX_REWARD_TOKENS,
+        },
+    };
+
+    #[test]
+    fn test_next_pool_index_of_master_buffer() {
+        let master_data = MasterStaking {
+            pool_counter: 7,
+            protocol_fee_bps: 50,
+            protocol_fee_recipient: Pubkey::new_from_array([1; 32]),
+        }
+        .try_to_vec()
+        .unwrap();
+        assert_eq!(master_data.len(), MASTER_STAKING_LEN);
+        assert_eq!(next_pool_index(&master_data), Ok(7));
+
+        // Master staking created before the protocol fee holds only the counter
+        assert_eq!(next_pool_index(&7_u64.to_le_bytes()), Ok(7));
+        assert!(next_pool_index(&master_data[..MASTER_STAKING_LEN - 1]).is_err());
+
+        let owner = Pubkey::new_from_array([3; 32]);
+        let instruction = initialize_for_next_index(
+            next_pool_index(&master_data).unwrap(),
+            &owner,
+            &Pubkey::new_from_array([4; 32]),
+            &Pubkey::new_from_array([5; 32]),
+            &[],
+            InitializeParams {
+                n_reward_tokens: 1,
+                reward_amount: 1_000,
+                start_block: 100,
+                end_block: 200,
+                emission_period: 1,
+                min_compound_interval: 0,
+                round_up_rewards: 0,
+                prefund: true,
+                max_stake_per_user: 0,
+                min_stake_amount: 0,
+                pool_name: [0; 32],
+                project_link: [0; 128],
+                theme_id: 0,
+                tags: 0,
+                extra_reward_amounts: [0; MAX_REWARD_TOKENS - 1],
+            },
+        )
+        .unwrap();
+        assert_eq!(instruction.accounts[2].pubkey, stake_pool_address(7).0);
+        assert_eq!(instruction.accounts[3].pubkey, wallet_pool_address(7).0);
+        assert_eq!(instruction.accounts[11].pubkey, staked_token_account_address(7).0);
+        assert_eq!(instruction.accounts[12].pubkey, reward_token_account_address(7).0);
+    }
+}
@@ -0,0 +1,127 @@
+//! Seeds of the PDAs of a pool. Creation, signing and validation all take the seeds from here
+use crate::{
+    ADD_SEED_TOKEN_ACCOUNT_AUTHORITY,
+    BUMP_SEED_TOKEN_ACCOUNT_AUTHORITY,
+    ADD_SEED_MASTER_STAKING,
+    BUMP_SEED_MASTER_STAKING,
+    ADD_SEED_STATE_POOL,
+    ADD_SEED_WALLET_POOL,
+    ADD_SEED_STAKED,
+};
+
+/// Signer seeds of PDA authority for token-accounts, the bump included
+pub fn token_account_authority() -> [&'static [u8]; 2] {
+    [ADD_SEED_TOKEN_ACCOUNT_AUTHORITY.as_bytes(), &[BUMP_SEED_TOKEN_ACCOUNT_AUTHORITY]]
+}
+
+/// Signer seeds of PDA master-staking, the bump included
+pub fn master_staking() -> [&'static [u8]; 2] {
+    [ADD_SEED_MASTER_STAKING.as_bytes(), &[BUMP_SEED_MASTER_STAKING]]
+}
+
+/// `pool_index` is pool_index.to_le_bytes()
+pub fn stake_pool(
+    pool_index: &[u8; 8],
+) -> [&[u8]; 2] {
+    [pool_index, ADD_SEED_STATE_POOL.as_bytes()]
+}
+
+pub fn wallet_pool(
+    pool_index: &[u8; 8],
+) -> [&[u8]; 2] {
+    [pool_index, ADD_SEED_WALLET_POOL.as_bytes()]
+}
+
+pub fn staked_token_account(
+    pool_index: &[u8; 8],
+) -> [&[u8]; 2] {
+    [pool_index, ADD_SEED_STAKED.as_bytes()]
+}
+
+pub fn reward_token_account(
+    pool_index: &[u8; 8],
+) -> [&[u8]; 1] {
+    [pool_index]
+}
+
+/// Signer seeds of a PDA found with `seeds`
+pub fn with_bump<'a>(
+    seeds: &[&'a [u8]],
+    bump_seed: &'a [u8; 1],
+) -> Vec<&'a [u8]> {
+    let mut signer_seeds = seeds.to_vec();
+    signer_seeds.push(bump_seed);
+
+    signer_seeds
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_program::pubkey::Pubkey;
+    use crate::{
+        id as this_program_id,
+        pda::{
+            master_staking_address,
+            token_account_authority_address,
+            stake_pool_address,
+            wallet_pool_address,
+            staked_token_account_address,
+            reward_token_account_address,
+        },
+    };
+
+    #[test]
+    fn test_seeds_with_fixed_bump_match_inline_derivation() {
+        assert_eq!(token_account_authority(), [ADD_SEED_TOKEN_ACCOUNT_AUTHORITY.as_bytes(), &[BUMP_SEED_TOKEN_ACCOUNT_AUTHORITY]]);
+        assert_eq!(
+            Pubkey::find_program_address(&[ADD_SEED_TOKEN_ACCOUNT_AUTHORITY.as_bytes()], &this_program_id()),
+            (token_account_authority_address().unwrap(), BUMP_SEED_TOKEN_ACCOUNT_AUTHORITY),
+        );
+        assert_eq!(master_staking(), [ADD_SEED_MASTER_STAKING.as_bytes(), &[BUMP_SEED_MASTER_STAKING]]);
+        assert_eq!(
+            Pubkey::find_program_address(&[ADD_SEED_MASTER_STAKING.as_bytes()], &this_program_id()),
+            (master_staking_address().unwrap(), BUMP_SEED_MASTER_STAKING),
+        );
+    }
+
+    #[test]
+    fn test_pool_seeds_match_inline_derivation() {
+        for pool_index in [0, 1, 7, u64::MAX] {
+            let pool_index_seed = pool_index.to_le_bytes();
+            for (inline_seeds, seeds, (address, bump_seed)) in [
+                (
+                    vec![&pool_index_seed[..], ADD_SEED_STATE_POOL.as_bytes()],
+                    stake_pool(&pool_index_seed).to_vec(),
+                    stake_pool_address(pool_index),
+                ),
+                (
+                    vec![&pool_index_seed[..], ADD_SEED_WALLET_POOL.as_bytes()],
+                    wallet_pool(&pool_index_seed).to_vec(),
+                    wallet_pool_address(pool_index),
+                ),
+                (
+                    vec![&pool_index_seed[..], ADD_SEED_STAKED.as_bytes()],
+                    staked_token_account(&pool_index_seed).to_vec(),
+                    staked_token_account_address(pool_index),
+                ),
+                (
+                    vec![&pool_index_seed[..]],
+                    reward_token_account(&pool_index_seed).to_vec(),
+                    reward_token_account_address(pool_index),
+                ),
+            ] {
+                assert_eq!(seeds, inline_seeds);
+                assert_eq!(Pubkey::find_program_address(&inline_seeds, &this_program_id()), (address, bump_seed));
+
+                // signer seeds of invoke_signed
+                let bump_seed = [bump_seed];
+                assert_eq!(
+                    Pubkey::create_program_address(&with_bump(&seeds, &bump_seed), &this_program_id()),
+                    Ok(address),
+                );
+            }
+        }
+    }
+}
+
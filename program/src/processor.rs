@@ -9,8 +9,13 @@ use solana_program::{
     },
     program_error::{
         ProgramError,
+        PrintProgramError,
     },
     program_option::COption,
+    instruction::{
+        AccountMeta,
+        Instruction,
+    },
     entrypoint::ProgramResult, 
     program_pack::Pack, 
     pubkey::Pubkey, 
@@ -25,6 +30,7 @@ use spl_token::{
     state::Mint as TokenMint,
     error::TokenError,
 };
+use arrayref::array_ref;
 use borsh::{
     BorshDeserialize,
     BorshSerialize,
@@ -34,28 +40,174 @@ use crate::{
         MasterStaking,
         StakePool,
         UserInfo,
+        LongLink,
+        ReferralCode,
+        ProposalTally,
+        VoteReceipt,
+        RewardTokenInfo,
         MASTER_STAKING_LEN,
+        LEGACY_MASTER_STAKING_LEN,
+        LEGACY_STAKE_POOL_LEN,
+        MAX_PROTOCOL_FEE_BPS,
         USER_INFO_LEN,
+        LEGACY_USER_INFO_LEN,
+        LONG_LINK_LEN,
+        REFERRAL_CODE_LEN,
+        PROPOSAL_TALLY_LEN,
+        VOTE_RECEIPT_LEN,
+        REWARD_TOKEN_INFO_LEN,
+        MAX_VOTE_CHOICES,
+        MAX_LONG_LINK_LEN,
+        MAX_STAKE_MINTS,
+        MAX_REWARD_TOKENS,
+        KNOWN_TAGS,
+        ACCRUAL_MODE_CLASSIC,
+        ACCRUAL_MODE_TIME_WEIGHTED,
+        EMERGENCY_WITHDRAW_TIMELOCK_SLOTS,
+        MIN_POOL_DURATION_BLOCKS,
+        HEALTH_STAKED_COVERED,
+        HEALTH_REWARD_COVERED,
+        HEALTH_BONUS_CONSISTENT,
+        HEALTH_LAST_REWARD_BLOCK,
+        HEALTH_SOLVENT,
+    },
+    math::{
+        reward_per_day,
+        apr_bps,
+        weighted_average_price,
+        base_to_ui_fixed,
+        MIN_STAKE_WARN_LOW_UI,
+        MIN_STAKE_WARN_HIGH_UI,
+        UI_AMOUNT_DECIMALS,
     },
     utils::{
         validate_pool_token_account,
         validate_stake_pool,
         validate_user_state,
+        validate_user_state_with_bump,
+        validate_user_token_account,
+        validate_staked_token_account,
+        validate_reward_swap_program,
+        validate_rent_sysvar,
+        validate_clock_sysvar,
+        validate_not_cpi,
+        validate_upgrade_authority,
+        split_protocol_fee_accounts,
+        split_reward_token_accounts,
+        validate_deposit_accounts,
+        validate_withdraw_accounts,
+        validate_mint_receipt_accounts,
+        unpack_destination_token_account,
+        is_price_oracle,
+        get_oracle_price,
+        validate_initialized_pool,
+        is_reward_vault,
         get_pending,
         get_reward_debt,
+        get_unpaid,
+    },
+    pda::{
+        seeds,
+        associated_token_program_id,
+        associated_token_account_address,
+        user_info_address,
+        master_staking_address,
+        token_account_authority_address,
+        stake_pool_address,
+        wallet_pool_address,
+        stake_mint_token_account_address,
+        staked_token_account_address,
+        reward_token_account_address,
+        migrated_reward_token_account_address,
+        boost_token_account_address,
+        reward_token_info_address,
+        extra_reward_token_account_address,
+        referral_code_address,
+        receipt_mint_address,
+        proposal_tally_address,
+        vote_receipt_address,
+    },
+    events::{
+        emit,
+        TvlSnapshot,
+        PoolSummary,
+        PoolSummaries,
+        UserProjectedRewards,
+        RewardsReclaimed,
+        ReferralDeposit,
+        UserPosition,
+        UserPositions,
+        UserFrozenChanged,
+        RewardMintMigrated,
+        BoostClaimed,
+        PositionUpdatedEvent,
+        RewardTokenClaimed,
+        PoolClosed,
+        PoolStateReinitialized,
+        ProtocolFeeChanged,
+        DonationEvent,
+        BonusPreview,
+        emit_config_changed,
+        ConfigField,
+    },
+    error::StakingError,
+    instruction::{
+        StakingInstruction,
+        InitializeParams,
+        ReinitPoolStateParams,
+        RewardSwap,
     },
-    error::StakingError, 
-    instruction::StakingInstruction,
     id as this_program_id,
-    ADD_SEED_TOKEN_ACCOUNT_AUTHORITY,
-    BUMP_SEED_TOKEN_ACCOUNT_AUTHORITY,
-    ADD_SEED_MASTER_STAKING,
-    BUMP_SEED_MASTER_STAKING,
-    ADD_SEED_STATE_POOL,
-    ADD_SEED_WALLET_POOL,
     ADD_SEED_STAKED,
+    ADD_SEED_LINK,
+    ADD_SEED_REFERRAL,
+    ADD_SEED_RECEIPT,
+    ADD_SEED_PROPOSAL,
+    ADD_SEED_VOTE,
+    ADD_SEED_BOOST,
+    ADD_SEED_REWARD,
+    ADD_SEED_REWARD_VAULT,
 };
 
+/// Upper bound for pools in a single UpdateEndBlockMany, keeps the instruction within compute budget
+pub const MAX_POOLS_PER_UPDATE: usize = 8;
+
+/// Upper bound for positions in a single LogUserPositions, keeps the instruction within compute budget
+pub const MAX_POSITIONS_PER_LOG: usize = 16;
+
+/// Upper bound for pools in a single LogPoolSummaries, keeps the instruction within compute budget
+pub const MAX_POOLS_PER_LOG: usize = 8;
+
+/// Accounts of Initialize creating the RewardTokenInfo and vault of a reward token after the first
+struct RewardTokenAccounts<'b, 'a> {
+    owner_account_info: &'b AccountInfo<'a>,
+    pda_stake_pool_info: &'b AccountInfo<'a>,
+    reward_mint_info: &'b AccountInfo<'a>,
+    reward_token_account_info: &'b AccountInfo<'a>,
+    pda_reward_token_info: &'b AccountInfo<'a>,
+    pda_reward_vault_info: &'b AccountInfo<'a>,
+    pda_pool_token_account_authority_info: &'b AccountInfo<'a>,
+    rent_info: &'b AccountInfo<'a>,
+    system_program_info: &'b AccountInfo<'a>,
+    token_program_info: &'b AccountInfo<'a>,
+}
+
+/// Position a withdrawal takes from, with the delegate signing instead of the owner in WithdrawDelegated
+struct WithdrawPosition<'b, 'a> {
+    owner_key: &'b Pubkey,
+    delegate_key: Option<&'b Pubkey>,
+    token_account_info: &'b AccountInfo<'a>,
+}
+
+/// Pool accounts of UpdateEndBlock, one group per pool in UpdateEndBlockMany
+struct EndBlockAccounts<'b, 'a> {
+    mint_info: &'b AccountInfo<'a>,
+    pda_stake_pool_info: &'b AccountInfo<'a>,
+    reward_token_account_info: &'b AccountInfo<'a>,
+    pda_pool_token_account_reward_info: &'b AccountInfo<'a>,
+    extra_reward_infos: &'b [AccountInfo<'a>],
+}
+
 pub struct Processor;
 impl Processor {
     pub fn process(
@@ -63,28 +215,24 @@ impl Processor {
         accounts: &[AccountInfo],
         instruction_data: &[u8],
     ) -> ProgramResult{
-        let instruction = StakingInstruction::try_from_slice(instruction_data)?; 
+        if instruction_data.is_empty() {
+            StakingError::InvalidInstruction.print::<StakingError>();
+            return Err(StakingError::InvalidInstruction.into());
+        }
+        let instruction = StakingInstruction::try_from_slice(instruction_data)
+            .map_err(|_| {
+                StakingError::InvalidInstruction.print::<StakingError>();
+                StakingError::InvalidInstruction
+            })?;
 
         match instruction {
-            StakingInstruction::Initialize {  
-                n_reward_tokens,
-                reward_amount, 
-                start_block,
-                end_block,
-                pool_name, 
-                project_link,
-                theme_id,
+            StakingInstruction::Initialize {
+                params,
             } => {
                 msg!("Instruction: Initialize stake pool");
                 Self::process_initialize(
                     accounts,
-                    n_reward_tokens,
-                    reward_amount,
-                    start_block,
-                    end_block,
-                    pool_name,
-                    project_link,
-                    theme_id,
+                    params,
                 )
             },
             StakingInstruction::Deposit {
@@ -98,11 +246,13 @@ impl Processor {
             },
             StakingInstruction::Withdraw {
                 amount,
+                close_on_empty,
             } => {
                 msg!("Instruction: Withdraw");
                 Self::process_withdraw(
                     accounts,
                     amount,
+                    close_on_empty,
                 )
             },
             StakingInstruction::EmergencyWithdraw 
@@ -110,6 +260,7 @@ impl Processor {
                 msg!("Instruction: Emergency Withdraw");
                 Self::process_emergency_withdraw(
                     accounts,
+                    None,
                 )
             },
             StakingInstruction::UpdateProjectInfo {
@@ -155,19 +306,457 @@ impl Processor {
                     accounts,
                 )
             },
+            StakingInstruction::LogEmittedBetween{
+                from,
+                to,
+            } => {
+                msg!("Instruction: Log emitted between");
+                Self::process_log_emitted_between(
+                    accounts,
+                    from,
+                    to,
+                )
+            },
+            StakingInstruction::SetLongLink{
+                uri,
+            } => {
+                msg!("Instruction: Set long link");
+                Self::process_set_long_link(
+                    accounts,
+                    uri,
+                )
+            },
+            StakingInstruction::TransferPosition
+            => {
+                msg!("Instruction: Transfer position");
+                Self::process_transfer_position(
+                    accounts,
+                )
+            },
+            StakingInstruction::Compound
+            => {
+                msg!("Instruction: Compound");
+                Self::process_compound(
+                    accounts,
+                )
+            },
+            StakingInstruction::LogTvlSnapshot
+            => {
+                msg!("Instruction: Log TVL snapshot");
+                Self::process_log_tvl_snapshot(
+                    accounts,
+                )
+            },
+            StakingInstruction::UpdateEndBlockMany{
+                end_blocks,
+            } => {
+                msg!("Instruction: Update End Block Many");
+                Self::process_update_end_block_many(
+                    accounts,
+                    end_blocks,
+                )
+            },
+            StakingInstruction::SetRewardSwapProgram{
+                reward_swap_program,
+            } => {
+                msg!("Instruction: Set reward swap program");
+                Self::process_set_reward_swap_program(
+                    accounts,
+                    reward_swap_program,
+                )
+            },
+            StakingInstruction::SetDelegate{
+                delegate,
+            } => {
+                msg!("Instruction: Set delegate");
+                Self::process_set_delegate(
+                    accounts,
+                    delegate,
+                )
+            },
+            StakingInstruction::WithdrawDelegated{
+                amount,
+            } => {
+                msg!("Instruction: Withdraw delegated");
+                Self::process_withdraw_delegated(
+                    accounts,
+                    amount,
+                )
+            },
+            StakingInstruction::SetMaxTotalStaked{
+                max_total_staked,
+            } => {
+                msg!("Instruction: Set max total staked");
+                Self::process_set_max_total_staked(
+                    accounts,
+                    max_total_staked,
+                )
+            },
+            StakingInstruction::LogPoolCapacity
+            => {
+                msg!("Instruction: Log pool capacity");
+                Self::process_log_pool_capacity(
+                    accounts,
+                )
+            },
+            StakingInstruction::AddStakeMint{
+                weight,
+            } => {
+                msg!("Instruction: Add stake mint");
+                Self::process_add_stake_mint(
+                    accounts,
+                    weight,
+                )
+            },
+            StakingInstruction::FundRewards{
+                amount,
+            } => {
+                msg!("Instruction: Fund rewards");
+                Self::process_fund_rewards(
+                    accounts,
+                    amount,
+                )
+            },
+            StakingInstruction::LogPoolHealth
+            => {
+                msg!("Instruction: Log pool health");
+                Self::process_log_pool_health(
+                    accounts,
+                )
+            },
+            StakingInstruction::RegisterReferralCode{
+                code,
+            } => {
+                msg!("Instruction: Register referral code");
+                Self::process_register_referral_code(
+                    accounts,
+                    code,
+                )
+            },
+            StakingInstruction::EmergencyWithdrawWithBump{
+                user_info_bump,
+            } => {
+                msg!("Instruction: Emergency Withdraw with bump");
+                Self::process_emergency_withdraw(
+                    accounts,
+                    Some(user_info_bump),
+                )
+            },
+            StakingInstruction::MintReceipt{
+                amount,
+            } => {
+                msg!("Instruction: Mint receipt");
+                Self::process_mint_receipt(
+                    accounts,
+                    amount,
+                )
+            },
+            StakingInstruction::BurnReceipt{
+                amount,
+            } => {
+                msg!("Instruction: Burn receipt");
+                Self::process_burn_receipt(
+                    accounts,
+                    amount,
+                )
+            },
+            StakingInstruction::LogDepletionSlot
+            => {
+                msg!("Instruction: Log depletion slot");
+                Self::process_log_depletion_slot(
+                    accounts,
+                )
+            },
+            StakingInstruction::CastVote{
+                proposal_id,
+                choice,
+            } => {
+                msg!("Instruction: Cast vote");
+                Self::process_cast_vote(
+                    accounts,
+                    proposal_id,
+                    choice,
+                )
+            },
+            StakingInstruction::SetWarmupSlots{
+                warmup_slots,
+            } => {
+                msg!("Instruction: Set warmup slots");
+                Self::process_set_warmup_slots(
+                    accounts,
+                    warmup_slots,
+                )
+            },
+            StakingInstruction::LogUserDebug
+            => {
+                msg!("Instruction: Log user debug");
+                Self::process_log_user_debug(
+                    accounts,
+                )
+            },
+            StakingInstruction::LogUserPositions
+            => {
+                msg!("Instruction: Log user positions");
+                Self::process_log_user_positions(
+                    accounts,
+                )
+            },
+            StakingInstruction::SetTags{
+                tags,
+            } => {
+                msg!("Instruction: Set tags");
+                Self::process_set_tags(
+                    accounts,
+                    tags,
+                )
+            },
+            StakingInstruction::SetUserFrozen{
+                frozen,
+            } => {
+                msg!("Instruction: Set user frozen");
+                Self::process_set_user_frozen(
+                    accounts,
+                    frozen,
+                )
+            },
+            StakingInstruction::LogPoolSummary
+            => {
+                msg!("Instruction: Log pool summary");
+                Self::process_log_pool_summary(
+                    accounts,
+                )
+            },
+            StakingInstruction::MigrateRewardMint{
+                reward_amount,
+                start_block,
+                end_block,
+            } => {
+                msg!("Instruction: Migrate reward mint");
+                Self::process_migrate_reward_mint(
+                    accounts,
+                    reward_amount,
+                    start_block,
+                    end_block,
+                )
+            },
+            StakingInstruction::RetireRewardToken{
+                index,
+            } => {
+                msg!("Instruction: Retire reward token");
+                Self::process_retire_reward_token(
+                    accounts,
+                    index,
+                )
+            },
+            StakingInstruction::FundBoostVault{
+                amount,
+            } => {
+                msg!("Instruction: Fund boost vault");
+                Self::process_fund_boost_vault(
+                    accounts,
+                    amount,
+                )
+            },
+            StakingInstruction::ClaimBoost
+            => {
+                msg!("Instruction: Claim boost");
+                Self::process_claim_boost(
+                    accounts,
+                )
+            },
+            StakingInstruction::SetAccrualMode{
+                accrual_mode,
+            } => {
+                msg!("Instruction: Set accrual mode");
+                Self::process_set_accrual_mode(
+                    accounts,
+                    accrual_mode,
+                )
+            },
+            StakingInstruction::ClosePool
+            => {
+                msg!("Instruction: Close pool");
+                Self::process_close_pool(
+                    accounts,
+                )
+            },
+            StakingInstruction::LogBonusPreview{
+                bonus_multiplier,
+                bonus_start_block,
+                bonus_end_block,
+            } => {
+                msg!("Instruction: Log bonus preview");
+                Self::process_log_bonus_preview(
+                    accounts,
+                    bonus_multiplier,
+                    bonus_start_block,
+                    bonus_end_block,
+                )
+            },
+            StakingInstruction::MigrateUserInfo
+            => {
+                msg!("Instruction: Migrate user info");
+                Self::process_migrate_user_info(
+                    accounts,
+                )
+            },
+            StakingInstruction::SetEmergencyWithdrawEnabled{
+                enabled,
+            } => {
+                msg!("Instruction: Set emergency withdraw enabled");
+                Self::process_set_emergency_withdraw_enabled(
+                    accounts,
+                    enabled,
+                )
+            },
+            StakingInstruction::CancelBonus
+            => {
+                msg!("Instruction: Cancel bonus");
+                Self::process_cancel_bonus(
+                    accounts,
+                )
+            },
+            StakingInstruction::SetMinRewardDuration{
+                min_reward_duration,
+            } => {
+                msg!("Instruction: Set min reward duration");
+                Self::process_set_min_reward_duration(
+                    accounts,
+                    min_reward_duration,
+                )
+            },
+            StakingInstruction::LogPoolSummaries
+            => {
+                msg!("Instruction: Log pool summaries");
+                Self::process_log_pool_summaries(
+                    accounts,
+                )
+            },
+            StakingInstruction::LogUserProjectedRewards
+            => {
+                msg!("Instruction: Log user projected rewards");
+                Self::process_log_user_projected_rewards(
+                    accounts,
+                )
+            },
+            StakingInstruction::ReclaimRewards{
+                amount,
+            } => {
+                msg!("Instruction: Reclaim rewards");
+                Self::process_reclaim_rewards(
+                    accounts,
+                    amount,
+                )
+            },
+            StakingInstruction::DepositWithAta{
+                amount,
+            } => {
+                msg!("Instruction: Deposit with ATA");
+                Self::process_deposit_with_ata(
+                    accounts,
+                    amount,
+                )
+            },
+            StakingInstruction::SetMaxClaimPerTx{
+                max_claim_per_tx,
+            } => {
+                msg!("Instruction: Set max claim per tx");
+                Self::process_set_max_claim_per_tx(
+                    accounts,
+                    max_claim_per_tx,
+                )
+            },
+            StakingInstruction::ReinitPoolState{
+                params,
+            } => {
+                msg!("Instruction: Reinit pool state");
+                Self::process_reinit_pool_state(
+                    accounts,
+                    params,
+                )
+            },
+            StakingInstruction::DonateRewards{
+                amount,
+            } => {
+                msg!("Instruction: Donate rewards");
+                Self::process_donate_rewards(
+                    accounts,
+                    amount,
+                )
+            },
+            StakingInstruction::Harvest => {
+                msg!("Instruction: Harvest");
+                Self::process_harvest(accounts)
+            },
+            StakingInstruction::SetProtocolFee{
+                protocol_fee_bps,
+                protocol_fee_recipient,
+            } => {
+                msg!("Instruction: Set protocol fee");
+                Self::process_set_protocol_fee(
+                    accounts,
+                    protocol_fee_bps,
+                    protocol_fee_recipient,
+                )
+            },
+            StakingInstruction::ClaimRewardToken {
+                reward_index,
+            } => {
+                msg!("Instruction: Claim reward token");
+                Self::process_claim_reward_token(
+                    accounts,
+                    reward_index,
+                )
+            },
+            StakingInstruction::CloseUserInfo
+            => {
+                msg!("Instruction: Close user info");
+                Self::process_close_user_info(
+                    accounts,
+                )
+            },
+            StakingInstruction::SetPaused {
+                paused,
+            } => {
+                msg!("Instruction: Set paused");
+                Self::process_set_paused(
+                    accounts,
+                    paused,
+                )
+            },
+            StakingInstruction::MigrateStakePool
+            => {
+                msg!("Instruction: Migrate stake pool");
+                Self::process_migrate_stake_pool(
+                    accounts,
+                )
+            },
         }
     }
 
     fn process_initialize(
         accounts: &[AccountInfo],
-        n_reward_tokens: u8,
-        reward_amount: u64,
-        start_block: u64,
-        end_block: u64,
-        pool_name: [u8; 32],
-        project_link: [u8; 128],
-        theme_id: u8,
+        params: InitializeParams,
     ) -> ProgramResult {
+        let InitializeParams {
+            n_reward_tokens,
+            reward_amount,
+            start_block,
+            end_block,
+            emission_period,
+            min_compound_interval,
+            round_up_rewards,
+            prefund,
+            max_stake_per_user,
+            min_stake_amount,
+            pool_name,
+            project_link,
+            theme_id,
+            tags,
+            extra_reward_amounts,
+        } = params;
+
+        let (accounts, reward_token_infos) = split_reward_token_accounts(accounts, n_reward_tokens)?;
         let account_info_iter = &mut accounts.iter();
 
         let owner_account_info = next_account_info(account_info_iter)?; // 0
@@ -188,6 +777,7 @@ impl Processor {
         let mint = TokenMint::unpack_unchecked(&mint_info.data.borrow())?;
 
         let rent_info = next_account_info(account_info_iter)?; // 6
+        validate_rent_sysvar(rent_info)?;
         let rent = &Rent::from_account_info(rent_info)?; 
 
         let system_program_info = next_account_info(account_info_iter)?; // 7
@@ -200,21 +790,132 @@ impl Processor {
         // TODO: Add validate for token-account
         let pda_pool_token_account_reward_info = next_account_info(account_info_iter)?; // 12
 
-        let mut master_staking = MasterStaking::from_account_info(&pda_master_staking_info)?;
+        // pool_counter is the source of pool indexes, only the canonical PDA is trusted
+        if master_staking_address()? != *pda_master_staking_info.key {
+            StakingError::MasterStakingMissmatch.print::<StakingError>();
+            return Err(StakingError::MasterStakingMissmatch.into());
+        }
+        let mut master_staking = MasterStaking::from_account_info(pda_master_staking_info)?;
         let pool_index = master_staking.pool_counter;
 
+        // Everything is validated before the first account is created. A failed transaction reverts
+        // created accounts anyway, this keeps the failing paths cheap and the order of checks obvious
+        if end_block < start_block.saturating_add(MIN_POOL_DURATION_BLOCKS) {
+            StakingError::InvalidBlockRange.print::<StakingError>();
+            return Err(StakingError::InvalidBlockRange.into());
+        }
+        let duration = end_block
+            .checked_sub(start_block)
+            .ok_or(StakingError::Overflow)?;
+
+        if emission_period == 0 {
+            StakingError::InvalidEmissionPeriod.print::<StakingError>();
+            return Err(StakingError::InvalidEmissionPeriod.into());
+        }
+
+        // reward_per_block is paid once per emission_period blocks
+        let reward_per_block = reward_amount
+            .checked_mul(emission_period)
+            .ok_or(StakingError::Overflow)?
+            .checked_div(duration)
+            .ok_or(StakingError::Overflow)?;
+        // Also rejects a reward_amount of 0. The remainder of the division stays in the reward vault
+        if reward_per_block == 0 {
+            StakingError::InsufficientRewardFunding.print::<StakingError>();
+            return Err(StakingError::InsufficientRewardFunding.into());
+        }
+
+        let required_reward = reward_per_block
+            .checked_mul(duration)
+            .ok_or(StakingError::Overflow)?
+            .checked_div(emission_period)
+            .ok_or(StakingError::Overflow)?;
+        // Holds by the rounding of reward_per_block, checked so the schedule never advertises more than reward_amount
+        if required_reward > reward_amount {
+            StakingError::InsufficientRewardFunding.print::<StakingError>();
+            return Err(StakingError::InsufficientRewardFunding.into());
+        }
+
+        // Stored in base units, a UI amount far off usually means the decimals of the mint were forgotten
+        if min_stake_amount != 0 {
+            let min_stake_ui = base_to_ui_fixed(min_stake_amount as u128, mint.decimals)?;
+            if !(MIN_STAKE_WARN_LOW_UI..=MIN_STAKE_WARN_HIGH_UI).contains(&min_stake_ui) {
+                msg!(
+                    "Warning: min_stake_amount {} is {} tokens at {} decimals",
+                    min_stake_amount,
+                    min_stake_ui as f64 / 10_f64.powi(UI_AMOUNT_DECIMALS as i32),
+                    mint.decimals,
+                );
+            }
+        }
+
+        if prefund {
+            let token_account = TokenAccount::unpack(
+                &token_account_info.data.borrow(),
+            )?;
+            if token_account.amount < reward_amount {
+                StakingError::InsufficientRewardFunds.print::<StakingError>();
+                return Err(StakingError::InsufficientRewardFunds.into());
+            }
+        }
+
+        // Reward tokens after the first are emitted over the same blocks, from their own vaults
+        let mut reward_per_block_by_token = [0; MAX_REWARD_TOKENS];
+        reward_per_block_by_token[0] = reward_per_block;
+        let mut required_reward_by_token = [0; MAX_REWARD_TOKENS];
+        for (i, reward_token_accounts) in reward_token_infos.chunks(4).enumerate() {
+            let reward_index = i + 1;
+            let extra_reward_amount = extra_reward_amounts[i];
+
+            reward_per_block_by_token[reward_index] = extra_reward_amount
+                .checked_mul(emission_period)
+                .ok_or(StakingError::Overflow)?
+                .checked_div(duration)
+                .ok_or(StakingError::Overflow)?;
+            required_reward_by_token[reward_index] = reward_per_block_by_token[reward_index]
+                .checked_mul(duration)
+                .ok_or(StakingError::Overflow)?
+                .checked_div(emission_period)
+                .ok_or(StakingError::Overflow)?;
+
+            if prefund {
+                let token_account = TokenAccount::unpack(
+                    &reward_token_accounts[1].data.borrow(),
+                )?;
+                if token_account.amount < extra_reward_amount {
+                    StakingError::InsufficientRewardFunds.print::<StakingError>();
+                    return Err(StakingError::InsufficientRewardFunds.into());
+                }
+            }
+        }
+
+        if tags & !KNOWN_TAGS != 0 {
+            StakingError::InvalidTags.print::<StakingError>();
+            return Err(StakingError::InvalidTags.into());
+        }
+
+        if mint.decimals >= 21 {
+            StakingError::InvalidMintDecimals.print::<StakingError>();
+            return Err(StakingError::InvalidMintDecimals.into());
+        }
+
+        let precision_factor_rank = 21_u8
+            .checked_sub(mint.decimals as u8)
+            .ok_or(StakingError::Overflow)?;
+
         let minimum_balance_token_acc = rent.minimum_balance(TokenAccount::LEN);
 
+        let pool_index_seed = pool_index.to_le_bytes();
+
         let (_pda_token_account_staked_pubkey, bump_seed_pda_token_account_staked) = Pubkey::find_program_address(
-            &[&pool_index.to_le_bytes(), ADD_SEED_STAKED.as_bytes()],
-            &this_program_info.key,
+            &seeds::staked_token_account(&pool_index_seed),
+            this_program_info.key,
+        );
+        let bump_seed_pda_token_account_staked = [bump_seed_pda_token_account_staked];
+        let sign_seeds_pda_token_account_staked = seeds::with_bump(
+            &seeds::staked_token_account(&pool_index_seed),
+            &bump_seed_pda_token_account_staked,
         );
-        let sign_seeds_pda_token_account_staked: &[&[_]] = 
-            &[
-            &pool_index.to_le_bytes(),
-            ADD_SEED_STAKED.as_bytes(),
-            &[bump_seed_pda_token_account_staked],
-            ];
 
         invoke_signed(
             &system_instruction::create_account(
@@ -246,14 +947,14 @@ impl Processor {
         )?;
 
         let (_pda_token_account_pubkey, bump_seed_pda_token_account) = Pubkey::find_program_address(
-            &[&pool_index.to_le_bytes()],
-            &this_program_info.key
+            &seeds::reward_token_account(&pool_index_seed),
+            this_program_info.key
+        );
+        let bump_seed_pda_token_account = [bump_seed_pda_token_account];
+        let sign_seeds_pda_token_account = seeds::with_bump(
+            &seeds::reward_token_account(&pool_index_seed),
+            &bump_seed_pda_token_account,
         );
-        let sign_seeds_pda_token_account: &[&[_]] = 
-            &[
-            &pool_index.to_le_bytes(),
-            &[bump_seed_pda_token_account],
-            ];
 
         invoke_signed(
             &system_instruction::create_account(
@@ -284,35 +985,167 @@ impl Processor {
             &[&sign_seeds_pda_token_account],
         )?;
 
-        invoke(
-            &spl_token::instruction::transfer(
-                &spl_token::id(),
-                token_account_info.key,
-                pda_pool_token_account_reward_info.key,
-                owner_account_info.key,
-                &[owner_account_info.key],
-                reward_amount,
-            )?,
-            &[
-            token_account_info.clone(), 
-            pda_pool_token_account_reward_info.clone(), 
-            owner_account_info.clone(),
-            token_program_info.clone(),
-            ],
+        // Optional, pools created without it have no receipts
+        if let Ok(pda_receipt_mint_info) = next_account_info(account_info_iter) { // 13
+            let (pda_receipt_mint_pubkey, bump_seed_receipt_mint) = receipt_mint_address(pool_index);
+            if pda_receipt_mint_pubkey != *pda_receipt_mint_info.key {
+                StakingError::InvalidReceiptMint.print::<StakingError>();
+                return Err(StakingError::InvalidReceiptMint.into());
+            }
+            let sign_seeds_pda_receipt_mint: &[&[_]] = 
+                &[
+                &pool_index.to_le_bytes(),
+                ADD_SEED_RECEIPT.as_bytes(),
+                &[bump_seed_receipt_mint],
+                ];
+
+            invoke_signed(
+                &system_instruction::create_account(
+                    owner_account_info.key,
+                    pda_receipt_mint_info.key,
+                    rent.minimum_balance(TokenMint::LEN),
+                    TokenMint::LEN as u64,
+                    &spl_token::id(),
+                ),
+                &[owner_account_info.clone(), pda_receipt_mint_info.clone(), system_program_info.clone()],
+                &[sign_seeds_pda_receipt_mint],
+            )?;
+
+            invoke(
+                &spl_token::instruction::initialize_mint(
+                    &spl_token::id(),
+                    pda_receipt_mint_info.key,
+                    pda_pool_token_account_authority_info.key,
+                    None,
+                    mint.decimals,
+                )?,
+                &[
+                pda_receipt_mint_info.clone(),
+                rent_info.clone(),
+                token_program_info.clone(),
+                ],
+            )?;
+        }
+
+        // Optional, pools created without it pay the bonus from the reward vault
+        let has_boost_vault = if let Ok(pda_boost_token_account_info) = next_account_info(account_info_iter) { // 14
+            let (pda_boost_token_account_pubkey, bump_seed_boost_token_account) = boost_token_account_address(
+                pool_index,
+                mint_info.key,
+            );
+            if pda_boost_token_account_pubkey != *pda_boost_token_account_info.key {
+                StakingError::PoolTokenAccountMissmatch.print::<StakingError>();
+                return Err(StakingError::PoolTokenAccountMissmatch.into());
+            }
+            let sign_seeds_pda_boost_token_account: &[&[_]] =
+                &[
+                &pool_index.to_le_bytes(),
+                mint_info.key.as_ref(),
+                ADD_SEED_BOOST.as_bytes(),
+                &[bump_seed_boost_token_account],
+                ];
+
+            invoke_signed(
+                &system_instruction::create_account(
+                    owner_account_info.key,
+                    pda_boost_token_account_info.key,
+                    minimum_balance_token_acc,
+                    TokenAccount::LEN as u64,
+                    &spl_token::id(),
+                ),
+                &[owner_account_info.clone(), pda_boost_token_account_info.clone(), system_program_info.clone()],
+                &[sign_seeds_pda_boost_token_account],
+            )?;
+
+            invoke_signed(
+                &spl_token::instruction::initialize_account(
+                    &spl_token::id(),
+                    pda_boost_token_account_info.key,
+                    mint_info.key,
+                    pda_pool_token_account_authority_info.key,
+                )?,
+                &[
+                pda_boost_token_account_info.clone(),
+                mint_info.clone(),
+                pda_pool_token_account_authority_info.clone(),
+                rent_info.clone(),
+                token_program_info.clone(),
+                ],
+                &[sign_seeds_pda_boost_token_account],
+            )?;
+
+            true
+        } else {
+            false
+        };
+
+        for (i, reward_token_accounts) in reward_token_infos.chunks(4).enumerate() {
+            let reward_index = i + 1;
+
+            Self::create_reward_token(
+                RewardTokenAccounts {
+                    owner_account_info,
+                    pda_stake_pool_info,
+                    reward_mint_info: &reward_token_accounts[0],
+                    reward_token_account_info: &reward_token_accounts[1],
+                    pda_reward_token_info: &reward_token_accounts[2],
+                    pda_reward_vault_info: &reward_token_accounts[3],
+                    pda_pool_token_account_authority_info,
+                    rent_info,
+                    system_program_info,
+                    token_program_info,
+                },
+                pool_index,
+                reward_index as u8,
+                if prefund { extra_reward_amounts[i] } else { 0 },
+                if prefund { required_reward_by_token[reward_index] } else { 0 },
+            )?;
+        }
+
+        if prefund {
+            invoke(
+                &spl_token::instruction::transfer(
+                    &spl_token::id(),
+                    token_account_info.key,
+                    pda_pool_token_account_reward_info.key,
+                    owner_account_info.key,
+                    &[owner_account_info.key],
+                    reward_amount,
+                )?,
+                &[
+                token_account_info.clone(),
+                pda_pool_token_account_reward_info.clone(),
+                owner_account_info.clone(),
+                token_program_info.clone(),
+                ],
+            )?;
+        }
+
+        // A mint with a transfer fee delivers less than reward_amount to the vault
+        let pda_pool_token_account_reward = TokenAccount::unpack(
+            &pda_pool_token_account_reward_info.data.borrow(),
         )?;
+        if prefund && pda_pool_token_account_reward.amount < required_reward {
+            msg!(
+                "Reward vault received {} but schedule requires {}",
+                pda_pool_token_account_reward.amount,
+                required_reward,
+            );
+            StakingError::RewardScheduleUnfunded.print::<StakingError>();
+            return Err(StakingError::RewardScheduleUnfunded.into());
+        }
 
         let min_balance_wallet_pool = rent.minimum_balance(USER_INFO_LEN) * 5; 
 
         let (_pda_wallet_for_create_user_pubkey, bump_seed_wallet_for_create_user) = Pubkey::find_program_address(
-            &[&pool_index.to_le_bytes(), ADD_SEED_WALLET_POOL.as_bytes()],
-            &this_program_info.key,
+            &seeds::wallet_pool(&pool_index_seed),
+            this_program_info.key,
+        );
+        let bump_seed_wallet_for_create_user = [bump_seed_wallet_for_create_user];
+        let sign_seeds_pda_wallet_pool = seeds::with_bump(
+            &seeds::wallet_pool(&pool_index_seed),
+            &bump_seed_wallet_for_create_user,
         );
-        let sign_seeds_pda_wallet_pool: &[&[_]] = 
-            &[
-            &pool_index.to_le_bytes(),
-            ADD_SEED_WALLET_POOL.as_bytes(),
-            &[bump_seed_wallet_for_create_user],
-            ];
 
         invoke_signed(
             &system_instruction::create_account(
@@ -329,15 +1162,14 @@ impl Processor {
         let min_balance_stake_pool = rent.minimum_balance(StakePool::LEN);
 
         let (_pda_state_pool_pubkey, bump_seed_state_pool) = Pubkey::find_program_address(
-            &[&pool_index.to_le_bytes(), ADD_SEED_STATE_POOL.as_bytes()],
-            &this_program_info.key,
+            &seeds::stake_pool(&pool_index_seed),
+            this_program_info.key,
+        );
+        let bump_seed_state_pool = [bump_seed_state_pool];
+        let sign_seeds_pda_state_pool = seeds::with_bump(
+            &seeds::stake_pool(&pool_index_seed),
+            &bump_seed_state_pool,
         );
-        let sign_seeds_pda_state_pool: &[&[_]] = 
-            &[
-            &pool_index.to_le_bytes(),
-            ADD_SEED_STATE_POOL.as_bytes(),
-            &[bump_seed_state_pool]
-            ];
 
         invoke_signed( 
             &system_instruction::create_account(
@@ -351,19 +1183,6 @@ impl Processor {
             &[&sign_seeds_pda_state_pool],
         )?;
 
-        assert!(mint.decimals < 21, "Token decimals must be inferior to 21");
-
-        let precision_factor_rank = 21_u8
-            .checked_sub(mint.decimals as u8)
-            .ok_or(StakingError::Overflow)?;
-
-        let reward_per_block = reward_amount
-            .checked_div(
-                end_block
-                .checked_sub(start_block)
-                .ok_or(StakingError::Overflow)?)
-            .ok_or(StakingError::Overflow)?;
-
         let stake_pool = StakePool {
             n_reward_tokens,
             pool_index,
@@ -377,33 +1196,211 @@ impl Processor {
             last_reward_block: 0,
             start_block,
             end_block,
-            reward_amount: 0, // TODO: delete this
-            reward_per_block,
+            total_reward: reward_amount,
+            reward_per_block: reward_per_block_by_token,
             accrued_token_per_share: 0,
             pool_name,
             project_link,
             theme_id,
+            total_owed: 0,
+            is_solvent: 1,
+            emission_period,
+            min_compound_interval,
+            total_staked: 0,
+            reward_swap_program: COption::None,
+            round_up_rewards,
+            max_total_staked: 0,
+            stake_mints: [Pubkey::default(); MAX_STAKE_MINTS],
+            stake_mint_weights: [0; MAX_STAKE_MINTS],
+            total_shares: 0,
+            in_progress: 0,
+            is_funded: prefund as u8,
+            max_stake_per_user,
+            total_funded: if prefund { reward_amount } else { 0 },
+            receipt_supply: 0,
+            warmup_slots: 0,
+            pending_accrual: 0,
+            tags,
+            has_boost_vault: has_boost_vault as u8,
+            accrued_boost_per_share: 0,
+            pending_boost_accrual: 0,
+            boost_owed: 0,
+            accrual_mode: ACCRUAL_MODE_CLASSIC,
+            emergency_withdraw_enabled: 1,
+            emergency_withdraw_disable_slot: 0,
+            pre_bonus_end_block: 0,
+            min_reward_duration: 0,
+            max_claim_per_tx: 0,
+            deposit_count: 0,
+            withdraw_count: 0,
+            lifetime_deposited: 0,
+            lifetime_withdrawn: 0,
+            extra_accrued_token_per_share: [0; MAX_REWARD_TOKENS],
+            extra_pending_accrual: [0; MAX_REWARD_TOKENS],
+            extra_owed: [0; MAX_REWARD_TOKENS],
+            stakers: 0,
+            is_paused: 0,
+            min_stake_amount,
         };
 
         StakePool::pack(stake_pool, &mut pda_stake_pool_info.data.borrow_mut())
             .expect("Failed to serialize StakePool");
 
-        // debug 
-        let stake_pool_unpacked = StakePool::unpack(&pda_stake_pool_info.data.borrow())
-            .expect("Failed to serialize StakePool");
-        println!("Stake Pool check {:#?}", stake_pool_unpacked);
-        //
-
         master_staking.increase_counter()?;
-        master_staking.serialize(&mut &mut pda_master_staking_info.data.borrow_mut()[..])?;
+        master_staking.serialize_into(&mut pda_master_staking_info.data.borrow_mut())?;
         
         Ok(())
     }
 
+    /// RewardTokenInfo and vault of the reward token `reward_index` of Initialize. `reward_amount` is
+    /// moved to the vault, which must receive at least `required_reward`
+    fn create_reward_token(
+        accounts: RewardTokenAccounts,
+        pool_index: u64,
+        reward_index: u8,
+        reward_amount: u64,
+        required_reward: u64,
+    ) -> ProgramResult {
+        let RewardTokenAccounts {
+            owner_account_info,
+            pda_stake_pool_info,
+            reward_mint_info,
+            reward_token_account_info,
+            pda_reward_token_info,
+            pda_reward_vault_info,
+            pda_pool_token_account_authority_info,
+            rent_info,
+            system_program_info,
+            token_program_info,
+        } = accounts;
+        let rent = &Rent::from_account_info(rent_info)?;
+
+        let (pda_reward_token_info_pubkey, bump_seed_reward_token_info) = reward_token_info_address(
+            pool_index,
+            reward_index,
+        );
+        if pda_reward_token_info_pubkey != *pda_reward_token_info.key {
+            StakingError::InvalidRewardTokenInfo.print::<StakingError>();
+            return Err(StakingError::InvalidRewardTokenInfo.into());
+        }
+        let (pda_reward_vault_pubkey, bump_seed_reward_vault) = extra_reward_token_account_address(
+            pool_index,
+            reward_index,
+        );
+        if pda_reward_vault_pubkey != *pda_reward_vault_info.key {
+            StakingError::PoolTokenAccountMissmatch.print::<StakingError>();
+            return Err(StakingError::PoolTokenAccountMissmatch.into());
+        }
+
+        let sign_seeds_pda_reward_token_info: &[&[_]] =
+            &[
+            &pool_index.to_le_bytes(),
+            &[reward_index],
+            ADD_SEED_REWARD.as_bytes(),
+            &[bump_seed_reward_token_info],
+            ];
+
+        invoke_signed(
+            &system_instruction::create_account(
+                owner_account_info.key,
+                pda_reward_token_info.key,
+                rent.minimum_balance(REWARD_TOKEN_INFO_LEN),
+                REWARD_TOKEN_INFO_LEN as u64,
+                &this_program_id(),
+            ),
+            &[owner_account_info.clone(), pda_reward_token_info.clone(), system_program_info.clone()],
+            &[sign_seeds_pda_reward_token_info],
+        )?;
+
+        let reward_token_info = RewardTokenInfo {
+            stake_pool: *pda_stake_pool_info.key,
+            reward_index,
+            mint: *reward_mint_info.key,
+            vault: *pda_reward_vault_info.key,
+        };
+        reward_token_info.serialize(&mut &mut pda_reward_token_info.data.borrow_mut()[..])?;
+
+        let sign_seeds_pda_reward_vault: &[&[_]] =
+            &[
+            &pool_index.to_le_bytes(),
+            &[reward_index],
+            ADD_SEED_REWARD_VAULT.as_bytes(),
+            &[bump_seed_reward_vault],
+            ];
+
+        invoke_signed(
+            &system_instruction::create_account(
+                owner_account_info.key,
+                pda_reward_vault_info.key,
+                rent.minimum_balance(TokenAccount::LEN),
+                TokenAccount::LEN as u64,
+                &spl_token::id(),
+            ),
+            &[owner_account_info.clone(), pda_reward_vault_info.clone(), system_program_info.clone()],
+            &[sign_seeds_pda_reward_vault],
+        )?;
+
+        invoke_signed(
+            &spl_token::instruction::initialize_account(
+                &spl_token::id(),
+                pda_reward_vault_info.key,
+                reward_mint_info.key,
+                pda_pool_token_account_authority_info.key,
+            )?,
+            &[
+            pda_reward_vault_info.clone(),
+            reward_mint_info.clone(),
+            pda_pool_token_account_authority_info.clone(),
+            rent_info.clone(),
+            token_program_info.clone(),
+            ],
+            &[sign_seeds_pda_reward_vault],
+        )?;
+
+        if reward_amount > 0 {
+            invoke(
+                &spl_token::instruction::transfer(
+                    &spl_token::id(),
+                    reward_token_account_info.key,
+                    pda_reward_vault_info.key,
+                    owner_account_info.key,
+                    &[owner_account_info.key],
+                    reward_amount,
+                )?,
+                &[
+                reward_token_account_info.clone(),
+                pda_reward_vault_info.clone(),
+                owner_account_info.clone(),
+                token_program_info.clone(),
+                ],
+            )?;
+        }
+
+        // Same as the reward vault, a transfer fee can leave the vault short of the schedule
+        let pda_reward_vault = TokenAccount::unpack(
+            &pda_reward_vault_info.data.borrow(),
+        )?;
+        if pda_reward_vault.amount < required_reward {
+            msg!(
+                "Vault of reward token {} received {} but schedule requires {}",
+                reward_index,
+                pda_reward_vault.amount,
+                required_reward,
+            );
+            StakingError::RewardScheduleUnfunded.print::<StakingError>();
+            return Err(StakingError::RewardScheduleUnfunded.into());
+        }
+
+        Ok(())
+    }
+
     pub fn process_deposit(
         accounts: &[AccountInfo],
         amount: u64,
     ) -> ProgramResult {
+        let (accounts, protocol_fee_infos) = split_protocol_fee_accounts(accounts)?;
+        validate_deposit_accounts(accounts)?;
+
         let account_info_iter = &mut accounts.iter();
         
         let owner_token_account_info = next_account_info(account_info_iter)?; // 0
@@ -411,11 +1408,25 @@ impl Processor {
         let mint_info = next_account_info(account_info_iter)?; // 2
         
         let pda_stake_pool_info = next_account_info(account_info_iter)?; // 3
+        let pda_pool_token_account_authority_info = next_account_info(account_info_iter)?; // 4
+
         let mut stake_pool = StakePool::unpack(&pda_stake_pool_info.data.borrow_mut())
             .expect("Failed to deserialie StakePool");
         let pool_index = stake_pool.pool_index;
 
-        let pda_pool_token_account_authority_info = next_account_info(account_info_iter)?; // 4
+        if !stake_pool.is_funded() {
+            StakingError::PoolNotFunded.print::<StakingError>();
+            return Err(StakingError::PoolNotFunded.into());
+        }
+        if stake_pool.is_paused() {
+            StakingError::PoolPaused.print::<StakingError>();
+            return Err(StakingError::PoolPaused.into());
+        }
+
+        // Persisted before any CPI, so a callback into this program sees the pool busy
+        stake_pool.lock()?;
+        StakePool::pack(stake_pool, &mut pda_stake_pool_info.data.borrow_mut())?;
+
         let pda_pool_token_account_staked_info = next_account_info(account_info_iter)?; // 5
         let pda_pool_token_account_reward_info = next_account_info(account_info_iter)?; // 6
         let pda_wallet_for_create_user_info = next_account_info(account_info_iter)?; // 7
@@ -429,34 +1440,86 @@ impl Processor {
             return Err(ProgramError::IllegalOwner);
         }
 
+        let pda_pool_token_account_reward = TokenAccount::unpack(
+            &pda_pool_token_account_reward_info.data.borrow(),
+        )?;
+
         let rent_info = next_account_info(account_info_iter)?; // 9
+        validate_rent_sysvar(rent_info)?;
         let rent = &Rent::from_account_info(rent_info)?;
 
         let clock_program_info = next_account_info(account_info_iter)?; // 10
+        validate_clock_sysvar(clock_program_info)?;
         let clock = &Clock::from_account_info(clock_program_info)?;
 
         let system_program_info = next_account_info(account_info_iter)?; // 11
         let token_program_info = next_account_info(account_info_iter)?; // 12
-        
+
+        validate_user_token_account(
+            token_account_info,
+            &[pda_pool_token_account_staked_info, pda_pool_token_account_reward_info],
+        )?;
+
+        let token_account = TokenAccount::unpack(
+            &token_account_info.data.borrow(),
+        )?;
+        if token_account.mint != *mint_info.key {
+            StakingError::InvalidStakeMint.print::<StakingError>();
+            return Err(StakingError::InvalidStakeMint.into());
+        }
+        validate_staked_token_account(
+            &stake_pool,
+            pda_pool_token_account_staked_info,
+            mint_info.key,
+        )?;
+
+        let reward_destination_info = if *mint_info.key == stake_pool.mint {
+            token_account_info
+        } else {
+            next_account_info(account_info_iter)? // 13
+        };
+        let reward_destination = TokenAccount::unpack(
+            &reward_destination_info.data.borrow(),
+        )?;
+        if reward_destination.owner != *owner_token_account_info.key {
+            return Err(TokenError::OwnerMismatch.into());
+        }
+
+        // Optional accounts, a price oracle is recognized by its address
+        let mut referral_code = None;
+        let mut entry_price = None;
+        for optional_info in account_info_iter { // 13 or 14 and the next one
+            if is_price_oracle(optional_info.key) {
+                entry_price = Some(get_oracle_price(optional_info)?);
+                continue;
+            }
+
+            let code = ReferralCode::from_account_info(optional_info)?;
+            if referral_code_address(&code.code).0 != *optional_info.key {
+                return Err(ProgramError::InvalidSeeds);
+            }
+            referral_code = Some(code);
+        }
+
         if pda_user_state_info.data_is_empty() {
             msg!("Creating account for UserInfo");
 
+            let pool_index_seed = pool_index.to_le_bytes();
             let (_pda_wallet_pubkey, bump_seed_wallet) = Pubkey::find_program_address(
-                &[&pool_index.to_le_bytes(), ADD_SEED_WALLET_POOL.as_bytes()],
+                &seeds::wallet_pool(&pool_index_seed),
                 &this_program_id(), 
             );
+            let bump_seed_wallet = [bump_seed_wallet];
     
-            let (_pda_user_state_pubkey, bump_seed_user_state) = Pubkey::find_program_address(
-                &[pda_stake_pool_info.key.as_ref(), token_account_info.key.as_ref()],
-                &this_program_id(),
+            let (_pda_user_state_pubkey, bump_seed_user_state) = user_info_address(
+                pda_stake_pool_info.key,
+                token_account_info.key,
             );
             
-            let signers_seeds_pda_wallet: &[&[_]] = 
-                &[
-                &pool_index.to_le_bytes(),
-                ADD_SEED_WALLET_POOL.as_bytes(),
-                &[bump_seed_wallet],
-                ];
+            let signers_seeds_pda_wallet = seeds::with_bump(
+                &seeds::wallet_pool(&pool_index_seed),
+                &bump_seed_wallet,
+            );
             
             let signers_seeds_pda_user_state: &[&[_]] = 
                 &[
@@ -476,106 +1539,220 @@ impl Processor {
                     &this_program_id(),
                 ),
                 &[pda_wallet_for_create_user_info.clone(), pda_user_state_info.clone(), system_program_info.clone()],
-                &[&signers_seeds_pda_wallet, &signers_seeds_pda_user_state],
+                &[&signers_seeds_pda_wallet, signers_seeds_pda_user_state],
             )?;
     
             let user_data = UserInfo {
-                token_account_id: *token_account_info.key, 
+                token_account_id: *token_account_info.key,
                 amount: 0,
                 reward_debt: 0,
+                last_compound_slot: 0,
+                delegate: Pubkey::default(),
+                deposit_slot: 0,
+                frozen: 0,
+                boost_debt: 0,
+                boost_unclaimed: 0,
+                stake_start_slot: 0,
+                shares: 0,
+                avg_entry_price: 0,
+                pending_owed: 0,
+                extra_reward_debt: [0; MAX_REWARD_TOKENS],
+                extra_unclaimed: [0; MAX_REWARD_TOKENS],
+                amount_change_slot: 0,
             };
     
             user_data.serialize(&mut &mut pda_user_state_info.data.borrow_mut()[..])?;
         } 
 
-        invoke(
-            &spl_token::instruction::transfer(
-                &spl_token::id(),
-                token_account_info.key,
-                pda_pool_token_account_staked_info.key,
-                owner_token_account_info.key,
-                &[owner_token_account_info.key],
-                amount,
-            )?, 
-            &[
-            token_account_info.clone(),
-            pda_pool_token_account_staked_info.clone(),
-            owner_token_account_info.clone(),
-            token_program_info.clone()
-            ],
-        )?;
-
-        // TODO: make transfer instruction after update_pool
-        // TODO: stakers++
-        // TODO: add loop
         stake_pool.update_pool(
             &pda_pool_token_account_staked,
+            &pda_pool_token_account_reward,
             clock
         )
-        .expect("Unable to update pool");  
+        .expect("Unable to update pool");
+
+        if !stake_pool.is_solvent() {
+            StakingError::PoolInsolvent.print::<StakingError>();
+            return Err(StakingError::PoolInsolvent.into());
+        }
+
+        let staked_after = pda_pool_token_account_staked.amount
+            .checked_add(amount)
+            .ok_or(StakingError::Overflow)?;
+        if stake_pool.max_total_staked != 0 && staked_after > stake_pool.max_total_staked {
+            StakingError::PoolCapacityExceeded.print::<StakingError>();
+            return Err(StakingError::PoolCapacityExceeded.into());
+        }
+
+        let mut user_data = UserInfo::from_account_info(pda_user_state_info)?; 
+        // Deposit pays out pending rewards, which a frozen position can't collect
+        user_data.check_not_frozen()?;
 
-        let mut user_data = UserInfo::from_account_info(&pda_user_state_info)?; 
-        
         let current_amount = user_data.amount;
+        let current_shares = stake_pool.get_position_shares(mint_info.key, &user_data)?;
+        if current_amount == 0 {
+            user_data.stake_start_slot = clock.slot;
+        }
+
+        if let Some(price) = entry_price {
+            // Tokens deposited without a price are left out of the average
+            let priced_amount = if user_data.avg_entry_price == 0 { 0 } else { current_amount };
+            user_data.avg_entry_price = weighted_average_price(
+                user_data.avg_entry_price,
+                priced_amount,
+                price,
+                amount,
+            )?;
+        }
 
         user_data.amount = user_data
             .amount
             .checked_add(amount)
             .ok_or(StakingError::Overflow)?;
- 
-        if current_amount > 0 {
+        if amount > 0 {
+            user_data.amount_change_slot = clock.slot;
+        }
+
+        if stake_pool.max_stake_per_user != 0 && user_data.amount > stake_pool.max_stake_per_user {
+            StakingError::UserCapExceeded.print::<StakingError>();
+            return Err(StakingError::UserCapExceeded.into());
+        }
+
+        // A zero deposit only harvests and is never rejected
+        if amount > 0 && user_data.amount < stake_pool.min_stake_amount {
+            StakingError::BelowMinimumStake.print::<StakingError>();
+            return Err(StakingError::BelowMinimumStake.into());
+        }
+
+        stake_pool.total_staked = stake_pool
+            .total_staked
+            .checked_add(amount)
+            .ok_or(StakingError::Overflow)?;
+        stake_pool.record_deposit(amount);
+        stake_pool.update_stakers(current_amount, user_data.amount)?;
+
+        let shares = stake_pool.get_weighted_shares(
+            mint_info.key,
+            user_data.amount,
+            user_data.stake_start_slot,
+            clock.slot,
+        )?;
+        stake_pool.update_total_shares(current_shares, shares)?;
+        user_data.shares = shares;
+
+        let mut reward_paid = 0;
+        let mut unpaid = 0;
+        if current_amount > 0 || user_data.pending_owed > 0 {
             let pending = get_pending(
-                current_amount,
+                current_shares,
+                stake_pool.accrued_token_per_share,
+                stake_pool.precision_factor_rank,
+                user_data.reward_debt,
+                stake_pool.rounds_up_rewards(),
+            )?;
+            let pending = stake_pool.cap_rounded_up(pending, pda_pool_token_account_reward.amount);
+            let earned = stake_pool.get_warmed_up(pending, user_data.deposit_slot, clock.slot)?;
+            unpaid = get_unpaid(
+                current_shares,
                 stake_pool.accrued_token_per_share,
                 stake_pool.precision_factor_rank,
                 user_data.reward_debt,
-            )
-            .expect("Unable to get pending value");
+                pending,
+            )?;
+            let (claimed, settled) = user_data.take_claim(&stake_pool, pending, earned)?;
 
-            // TODO: Check reward_amount > pending
-            if pending > 0 {
-                let sign_seeds_pda_pool_token_account_authority: &[&[_]] = 
-                    &[
-                    ADD_SEED_TOKEN_ACCOUNT_AUTHORITY.as_bytes(),
-                    &[BUMP_SEED_TOKEN_ACCOUNT_AUTHORITY],
-                    ];
+            if pending.max(claimed) > pda_pool_token_account_reward.amount {
+                StakingError::InsufficientRewardFunds.print::<StakingError>();
+                return Err(StakingError::InsufficientRewardFunds.into());
+            }
+            if settled > 0 {
+                let claimed = Self::pay_protocol_fee(
+                    protocol_fee_infos,
+                    &stake_pool,
+                    pda_pool_token_account_reward_info,
+                    pda_pool_token_account_authority_info,
+                    token_program_info,
+                    claimed,
+                )?;
+                reward_paid = claimed;
+
+                let sign_seeds_pda_pool_token_account_authority = seeds::token_account_authority();
 
                 invoke_signed(
                     &spl_token::instruction::transfer(
                         &spl_token::id(),
                         pda_pool_token_account_reward_info.key,
-                        token_account_info.key,
+                        reward_destination_info.key,
                         pda_pool_token_account_authority_info.key,
                         &[pda_pool_token_account_authority_info.key],
-                        pending,
+                        claimed,
                     )?, 
                     &[
                     pda_pool_token_account_reward_info.clone(),
-                    token_account_info.clone(),
+                    reward_destination_info.clone(),
                     pda_pool_token_account_authority_info.clone(), 
                     token_program_info.clone(),
                     ],
                     &[&sign_seeds_pda_pool_token_account_authority]
                 )?;
+
+                // The part lost to the warmup stays in the reward vault
+                stake_pool.decrease_owed(settled)?;
             }
         }
+        user_data.settle_boost(&stake_pool, current_shares, shares)?;
+        user_data.settle_extra_rewards(&stake_pool, current_shares, shares)?;
+        // New tokens restart the warmup of the position
+        user_data.deposit_slot = clock.slot;
         user_data.set_reward_debt(
             get_reward_debt(
-                user_data.amount,
+                shares,
                 stake_pool.accrued_token_per_share,
-                stake_pool.precision_factor_rank,
+                unpaid,
             )?
         );
 
+        // Pull the stake in last, once update_pool has run and the pending reward is paid
+        invoke(
+            &spl_token::instruction::transfer(
+                &spl_token::id(),
+                token_account_info.key,
+                pda_pool_token_account_staked_info.key,
+                owner_token_account_info.key,
+                &[owner_token_account_info.key],
+                amount,
+            )?, 
+            &[
+            token_account_info.clone(),
+            pda_pool_token_account_staked_info.clone(),
+            owner_token_account_info.clone(),
+            token_program_info.clone()
+            ],
+        )?;
+
+        emit(&PositionUpdatedEvent {
+            pool_index: stake_pool.pool_index,
+            token_account: *token_account_info.key,
+            new_amount: user_data.amount,
+            reward_paid,
+            slot: clock.slot,
+        })?;
+
         user_data.serialize(&mut &mut pda_user_state_info.data.borrow_mut()[..])?; 
 
-        msg!("stake_pool after deposit is {:#?}", stake_pool);
-        StakePool::pack(stake_pool, &mut pda_stake_pool_info.data.borrow_mut())?;
+        if let Some(referral_code) = referral_code {
+            emit(&ReferralDeposit {
+                pool_index,
+                code: referral_code.code,
+                referrer: referral_code.referrer,
+                token_account: *token_account_info.key,
+                amount,
+            })?;
+        }
+
+        stake_pool.unlock();
 
-        // debug
-        let unpacked_user_data = UserInfo::try_from_slice(&pda_user_state_info.data.borrow())?; 
-        msg!("unpacked_user_data is {:#?}", unpacked_user_data);
-        // 
+        StakePool::pack(stake_pool, &mut pda_stake_pool_info.data.borrow_mut())?;
         
         Ok(())
     }
@@ -583,14 +1760,21 @@ impl Processor {
     pub fn process_withdraw(
         accounts: &[AccountInfo],
         amount: u64,
+        close_on_empty: bool,
     ) -> ProgramResult {
+        let (accounts, protocol_fee_infos) = split_protocol_fee_accounts(accounts)?;
+        validate_withdraw_accounts(accounts)?;
+
         let account_info_iter = &mut accounts.iter();
 
         let owner_info = next_account_info(account_info_iter)?; // 0
         let token_account_info = next_account_info(account_info_iter)?; // 1
+        let token_account = unpack_destination_token_account(token_account_info)?;
 
-        let token_account = TokenAccount::unpack(
-            &token_account_info.data.borrow(),
+        // Vaults are owned by the PDA authority, so without this a vault would fail as an owner mismatch
+        validate_user_token_account(
+            token_account_info,
+            &[],
         )?;
 
         if token_account.owner != *owner_info.key {
@@ -600,6 +1784,67 @@ impl Processor {
             return Err(ProgramError::MissingRequiredSignature);
         }
 
+        Self::withdraw(
+            WithdrawPosition {
+                owner_key: &token_account.owner,
+                delegate_key: None,
+                token_account_info,
+            },
+            None,
+            protocol_fee_infos,
+            account_info_iter,
+            amount,
+            close_on_empty,
+        )
+    }
+
+    pub fn process_withdraw_delegated(
+        accounts: &[AccountInfo],
+        amount: u64,
+    ) -> ProgramResult {
+        let (accounts, protocol_fee_infos) = split_protocol_fee_accounts(accounts)?;
+        validate_withdraw_accounts(accounts)?;
+
+        let account_info_iter = &mut accounts.iter();
+
+        let delegate_info = next_account_info(account_info_iter)?; // 0
+        if !delegate_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        // Position's own token-account. Staked and reward tokens never go anywhere else
+        let token_account_info = next_account_info(account_info_iter)?; // 1
+        let token_account = unpack_destination_token_account(token_account_info)?;
+
+        Self::withdraw(
+            WithdrawPosition {
+                owner_key: &token_account.owner,
+                delegate_key: Some(delegate_info.key),
+                token_account_info,
+            },
+            None,
+            protocol_fee_infos,
+            account_info_iter,
+            amount,
+            false,
+        )
+    }
+
+    /// With `receipt_infos` (receipt mint, receipt token-account) the withdrawn amount stays in
+    /// the staked vault and is minted as receipt tokens, see MintReceipt
+    fn withdraw<'a, 'b>(
+        position: WithdrawPosition<'_, 'a>,
+        receipt_infos: Option<(&AccountInfo<'a>, &AccountInfo<'a>)>,
+        protocol_fee_infos: &[AccountInfo<'a>],
+        account_info_iter: &mut std::slice::Iter<'b, AccountInfo<'a>>,
+        amount: u64,
+        close_on_empty: bool,
+    ) -> ProgramResult {
+        let WithdrawPosition {
+            owner_key,
+            delegate_key,
+            token_account_info,
+        } = position;
         let pda_stake_pool_info = next_account_info(account_info_iter)?; // 2
         let pda_pool_token_account_authority_info = next_account_info(account_info_iter)?; // 3
         let pda_pool_token_account_staked_info = next_account_info(account_info_iter)?; // 4
@@ -607,81 +1852,235 @@ impl Processor {
         let pda_user_state_info = next_account_info(account_info_iter)?; // 6
 
         let clock_program_info = next_account_info(account_info_iter)?; // 7
+        validate_clock_sysvar(clock_program_info)?;
         let clock = &Clock::from_account_info(clock_program_info)?;
 
         let token_program_info = next_account_info(account_info_iter)?; // 8
 
-        let pda_pool_token_account_staked = TokenAccount::unpack( 
+        validate_user_token_account(
+            token_account_info,
+            &[pda_pool_token_account_staked_info, pda_pool_token_account_reward_info],
+        )?;
+
+        let token_account = unpack_destination_token_account(token_account_info)?;
+
+        let pda_pool_token_account_staked = TokenAccount::unpack(
             &pda_pool_token_account_staked_info.data.borrow(),
         )?;
+        let pda_pool_token_account_reward = TokenAccount::unpack(
+            &pda_pool_token_account_reward_info.data.borrow(),
+        )?;
 
-        let sign_seeds_pda_pool_token_account_authority: &[&[_]] = 
-            &[
-            ADD_SEED_TOKEN_ACCOUNT_AUTHORITY.as_bytes(),
-            &[BUMP_SEED_TOKEN_ACCOUNT_AUTHORITY],
-            ];
+        let sign_seeds_pda_pool_token_account_authority = seeds::token_account_authority();
 
         let mut stake_pool = StakePool::unpack(&pda_stake_pool_info.data.borrow_mut())
             .expect("Failed to deserialie StakePool");
 
-        let mut user_data = UserInfo::from_account_info(&pda_user_state_info)?;
+        // Persisted before any CPI, so a callback into this program sees the pool busy
+        stake_pool.lock()?;
+        StakePool::pack(stake_pool, &mut pda_stake_pool_info.data.borrow_mut())?;
+
+        validate_staked_token_account(
+            &stake_pool,
+            pda_pool_token_account_staked_info,
+            &token_account.mint,
+        )?;
+
+        if let Some((pda_receipt_mint_info, _)) = receipt_infos {
+            // Receipts are redeemed for the tokens of StakePool.mint only
+            if stake_pool.has_stake_mints() {
+                StakingError::InvalidStakeMint.print::<StakingError>();
+                return Err(StakingError::InvalidStakeMint.into());
+            }
+            if receipt_mint_address(stake_pool.pool_index).0 != *pda_receipt_mint_info.key {
+                StakingError::InvalidReceiptMint.print::<StakingError>();
+                return Err(StakingError::InvalidReceiptMint.into());
+            }
+        }
+
+        let reward_destination_info = if token_account.mint == stake_pool.mint {
+            token_account_info
+        } else {
+            next_account_info(account_info_iter)? // 9
+        };
+        let reward_destination = TokenAccount::unpack(
+            &reward_destination_info.data.borrow(),
+        )?;
+        if reward_destination.owner != *owner_key {
+            return Err(TokenError::OwnerMismatch.into());
+        }
+
+        // Read before the accounts of the reward swap
+        let pda_wallet_pool_info = if close_on_empty {
+            let pda_wallet_pool_info = next_account_info(account_info_iter)?; // 9 or 10
+            if wallet_pool_address(stake_pool.pool_index).0 != *pda_wallet_pool_info.key {
+                return Err(ProgramError::InvalidSeeds);
+            }
+            Some(pda_wallet_pool_info)
+        } else {
+            None
+        };
+
+        let mut user_data = UserInfo::from_account_info(pda_user_state_info)?;
+
+        if let Some(delegate_key) = delegate_key {
+            validate_user_state(
+                pda_user_state_info,
+                pda_stake_pool_info,
+                token_account_info,
+            )?;
+            user_data.check_delegate(delegate_key)?;
+        }
+        user_data.check_not_frozen()?;
         
         assert!(user_data.amount >= amount, "Amount to withdraw too high");
 
+        if pda_pool_token_account_staked.amount < amount {
+            msg!(
+                "Staked vault holds {} but {} requested",
+                pda_pool_token_account_staked.amount,
+                amount,
+            );
+            StakingError::InsufficientStaked.print::<StakingError>();
+            return Err(StakingError::InsufficientStaked.into());
+        }
+
         stake_pool.update_pool(
             &pda_pool_token_account_staked,
-            &clock,
+            &pda_pool_token_account_reward,
+            clock,
         )?;
 
-        let current_amount = user_data.amount;
+        let current_shares = stake_pool.get_position_shares(&token_account.mint, &user_data)?;
 
         if amount > 0 {
+            let current_amount = user_data.amount;
             user_data.amount = user_data
                 .amount
                 .checked_sub(amount)
                 .ok_or(StakingError::Overflow)?;
+            // Stake isn't continuous anymore
+            user_data.stake_start_slot = clock.slot;
+            user_data.amount_change_slot = clock.slot;
 
-            invoke_signed(
-                &spl_token::instruction::transfer(
-                    &spl_token::id(),
-                    pda_pool_token_account_staked_info.key,
-                    token_account_info.key,
-                    pda_pool_token_account_authority_info.key,
-                    &[pda_pool_token_account_authority_info.key],
-                    amount,
-                )?,
-                &[
-                pda_pool_token_account_staked_info.clone(),
-                token_account_info.clone(),
-                pda_pool_token_account_authority_info.clone(),
-                token_program_info.clone(),
-                ],
-                &[&sign_seeds_pda_pool_token_account_authority]
-            )?;
+            stake_pool.total_staked = stake_pool
+                .total_staked
+                .checked_sub(amount)
+                .ok_or(StakingError::Overflow)?;
+            stake_pool.record_withdraw(amount);
+            stake_pool.update_stakers(current_amount, user_data.amount)?;
+
+            if let Some((pda_receipt_mint_info, receipt_token_account_info)) = receipt_infos {
+                stake_pool.receipt_supply = stake_pool
+                    .receipt_supply
+                    .checked_add(amount)
+                    .ok_or(StakingError::Overflow)?;
+
+                invoke_signed(
+                    &spl_token::instruction::mint_to(
+                        &spl_token::id(),
+                        pda_receipt_mint_info.key,
+                        receipt_token_account_info.key,
+                        pda_pool_token_account_authority_info.key,
+                        &[pda_pool_token_account_authority_info.key],
+                        amount,
+                    )?,
+                    &[
+                    pda_receipt_mint_info.clone(),
+                    receipt_token_account_info.clone(),
+                    pda_pool_token_account_authority_info.clone(),
+                    token_program_info.clone(),
+                    ],
+                    &[&sign_seeds_pda_pool_token_account_authority]
+                )?;
+            } else {
+                invoke_signed(
+                    &spl_token::instruction::transfer(
+                        &spl_token::id(),
+                        pda_pool_token_account_staked_info.key,
+                        token_account_info.key,
+                        pda_pool_token_account_authority_info.key,
+                        &[pda_pool_token_account_authority_info.key],
+                        amount,
+                    )?,
+                    &[
+                    pda_pool_token_account_staked_info.clone(),
+                    token_account_info.clone(),
+                    pda_pool_token_account_authority_info.clone(),
+                    token_program_info.clone(),
+                    ],
+                    &[&sign_seeds_pda_pool_token_account_authority]
+                )?;
+            }
         }
 
+        let shares = stake_pool.get_weighted_shares(
+            &token_account.mint,
+            user_data.amount,
+            user_data.stake_start_slot,
+            clock.slot,
+        )?;
+        stake_pool.update_total_shares(current_shares, shares)?;
+        user_data.shares = shares;
+
         let pending = get_pending(
-            current_amount,
+            current_shares,
             stake_pool.accrued_token_per_share,
             stake_pool.precision_factor_rank,
             user_data.reward_debt,
-        )
-        .expect("Unable to get pending value");
-        
-        // TODO: add loop for reward tokens
-            if pending > 0 {
+            stake_pool.rounds_up_rewards(),
+        )?;
+        let pending = stake_pool.cap_rounded_up(pending, pda_pool_token_account_reward.amount);
+        let earned = stake_pool.get_warmed_up(pending, user_data.deposit_slot, clock.slot)?;
+        let unpaid = get_unpaid(
+            current_shares,
+            stake_pool.accrued_token_per_share,
+            stake_pool.precision_factor_rank,
+            user_data.reward_debt,
+            pending,
+        )?;
+        let mut reward_paid = 0;
+        let (claimed, settled) = user_data.take_claim(&stake_pool, pending, earned)?;
+
+        if pending.max(claimed) > pda_pool_token_account_reward.amount {
+            StakingError::InsufficientRewardFunds.print::<StakingError>();
+            return Err(StakingError::InsufficientRewardFunds.into());
+        }
+
+        if settled > 0 {
+            let claimed = Self::pay_protocol_fee(
+                protocol_fee_infos,
+                &stake_pool,
+                pda_pool_token_account_reward_info,
+                pda_pool_token_account_authority_info,
+                token_program_info,
+                claimed,
+            )?;
+            reward_paid = claimed;
+
+            if let COption::Some(reward_swap_program) = stake_pool.reward_swap_program {
+                Self::swap_reward(
+                    &reward_swap_program,
+                    owner_key,
+                    pda_pool_token_account_authority_info,
+                    pda_pool_token_account_reward_info,
+                    token_program_info,
+                    account_info_iter,
+                    claimed,
+                )?;
+            } else {
                 invoke_signed(
                     &spl_token::instruction::transfer(
                         &spl_token::id(),
                         pda_pool_token_account_reward_info.key,
-                        token_account_info.key,
+                        reward_destination_info.key,
                         pda_pool_token_account_authority_info.key,
                         &[pda_pool_token_account_authority_info.key],
-                        pending,
+                        claimed,
                     )?, 
                     &[
                     pda_pool_token_account_reward_info.clone(),
-                    token_account_info.clone(),
+                    reward_destination_info.clone(),
                     pda_pool_token_account_authority_info.clone(), 
                     token_program_info.clone(),
                     ],
@@ -689,32 +2088,71 @@ impl Processor {
                 )?;
             }
 
-            user_data.set_reward_debt(
-                get_reward_debt(
-                    user_data.amount,
-                    stake_pool.accrued_token_per_share,
-                    stake_pool.precision_factor_rank,
-                )?
-            );
+            // The part lost to the warmup stays in the reward vault
+            stake_pool.decrease_owed(settled)?;
+        }
+
+        user_data.settle_boost(&stake_pool, current_shares, shares)?;
+        user_data.settle_extra_rewards(&stake_pool, current_shares, shares)?;
+        user_data.set_reward_debt(
+            get_reward_debt(
+                shares,
+                stake_pool.accrued_token_per_share,
+                unpaid,
+            )?
+        );
+
+        emit(&PositionUpdatedEvent {
+            pool_index: stake_pool.pool_index,
+            token_account: *token_account_info.key,
+            new_amount: user_data.amount,
+            reward_paid,
+            slot: clock.slot,
+        })?;
 
         user_data.serialize(&mut &mut pda_user_state_info.data.borrow_mut()[..])?;
 
-        msg!("stake_pool after deposit is {:#?}", stake_pool);
+        stake_pool.unlock();
+
         StakePool::pack(stake_pool, &mut pda_stake_pool_info.data.borrow_mut())?;
 
-        // debug
-        let unpacked_user_data = UserInfo::try_from_slice(&pda_user_state_info.data.borrow())?; 
-        msg!("unpacked_user_data is {:#?}", unpacked_user_data);
-        // 
+        // Pending is paid above, so an empty position has nothing left unless the payout was capped.
+        // Boost and other reward tokens are paid only by ClaimBoost and ClaimRewardToken
+        if let Some(pda_wallet_pool_info) = pda_wallet_pool_info {
+            if user_data.is_closable() {
+                Self::close_user_info(
+                    pda_user_state_info,
+                    pda_wallet_pool_info,
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Zero UserInfo and move its lamports to `destination_info`. The runtime removes the account after the transaction.
+    /// Rent goes back to PDA wallet pool, which paid for UserInfo in Deposit.
+    /// Every byte is zeroed, so a Deposit recreating the PDA in the same transaction reads an empty position
+    fn close_user_info<'a>(
+        pda_user_state_info: &AccountInfo<'a>,
+        destination_info: &AccountInfo<'a>,
+    ) -> ProgramResult {
+        pda_user_state_info.data.borrow_mut().fill(0);
+
+        let lamports = pda_user_state_info.lamports();
+        **destination_info.lamports.borrow_mut() = destination_info
+            .lamports()
+            .checked_add(lamports)
+            .ok_or(StakingError::Overflow)?;
+        **pda_user_state_info.lamports.borrow_mut() = 0;
 
-        // TODO: Need to delete UserInfo, but it can't possible.
-        // TODO: stakers--; 
-        
         Ok(())
     }
 
+    /// `user_info_bump` skips the search of the UserInfo address, see EmergencyWithdrawWithBump
     pub fn process_emergency_withdraw(
-        accounts: &[AccountInfo]
+        accounts: &[AccountInfo],
+        user_info_bump: Option<u8>,
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
 
@@ -724,9 +2162,7 @@ impl Processor {
         }
 
         let token_account_info = next_account_info(account_info_iter)?; // 1
-        let token_account = TokenAccount::unpack(
-            &token_account_info.data.borrow(),
-        )?;
+        let token_account = unpack_destination_token_account(token_account_info)?;
         if token_account.owner != *owner_info.key {
             return Err(TokenError::OwnerMismatch.into());
         }
@@ -737,31 +2173,76 @@ impl Processor {
         let pda_stake_pool_info = next_account_info(account_info_iter)?; // 5
         let token_program_info = next_account_info(account_info_iter)?; // 6
 
+        let clock_info = next_account_info(account_info_iter)?; // 7
+        validate_clock_sysvar(clock_info)?;
+        let clock = &Clock::from_account_info(clock_info)?;
+
+        // Optional, without it UserInfo stays after the position is emptied
+        let pda_wallet_pool_info = next_account_info(account_info_iter).ok(); // 8
+
         validate_pool_token_account(
-            &pda_pool_token_account_staked_info,
-        )?;
-        validate_user_state(
-            &pda_user_state_info,
-            &pda_stake_pool_info,
-            &token_account_info,
+            pda_pool_token_account_staked_info,
         )?;
+        match user_info_bump {
+            Some(bump_seed) => validate_user_state_with_bump(
+                pda_user_state_info,
+                pda_stake_pool_info,
+                token_account_info,
+                bump_seed,
+            )?,
+            None => validate_user_state(
+                pda_user_state_info,
+                pda_stake_pool_info,
+                token_account_info,
+            )?,
+        }
+
+        let mut stake_pool = StakePool::unpack(&pda_stake_pool_info.data.borrow())?;
+        if !stake_pool.is_emergency_withdraw_enabled(clock.slot) {
+            StakingError::EmergencyDisabled.print::<StakingError>();
+            return Err(StakingError::EmergencyDisabled.into());
+        }
 
-        let mut user_data = UserInfo::from_account_info(&pda_user_state_info)?;
+        let mut user_data = UserInfo::from_account_info(pda_user_state_info)?;
+        // Principal is frozen too, otherwise EmergencyWithdraw is a way around the freeze
+        user_data.check_not_frozen()?;
+
+        validate_staked_token_account(
+            &stake_pool,
+            pda_pool_token_account_staked_info,
+            &token_account.mint,
+        )?;
 
         let amount_to_transfer = user_data.amount;
+        let current_shares = stake_pool.get_position_shares(&token_account.mint, &user_data)?;
 
-        // TODO: Stakers--;
         if amount_to_transfer > 0 {
             user_data.amount = user_data
                 .amount
                 .checked_sub(amount_to_transfer)
                 .ok_or(StakingError::Overflow)?;
+            user_data.amount_change_slot = clock.slot;
 
-            let sign_seeds_pda_pool_token_account_authority: &[&[_]] = 
-                &[
-                ADD_SEED_TOKEN_ACCOUNT_AUTHORITY.as_bytes(),
-                &[BUMP_SEED_TOKEN_ACCOUNT_AUTHORITY],
-                ];
+            stake_pool.total_staked = stake_pool
+                .total_staked
+                .checked_sub(amount_to_transfer)
+                .ok_or(StakingError::Overflow)?;
+            stake_pool.record_withdraw(amount_to_transfer);
+            stake_pool.update_stakers(amount_to_transfer, 0)?;
+
+            stake_pool.update_total_shares(current_shares, 0)?;
+            user_data.shares = 0;
+
+            // Pending rewards are forfeited, the part carried over a capped payout included
+            user_data.reward_debt = 0;
+            stake_pool.decrease_owed(user_data.pending_owed)?;
+            user_data.pending_owed = 0;
+            // Pending boost is forfeited like pending rewards, boost_unclaimed stays claimable
+            user_data.boost_debt = 0;
+            // Same for the other reward tokens, extra_unclaimed stays claimable
+            user_data.extra_reward_debt = [0; MAX_REWARD_TOKENS];
+
+            let sign_seeds_pda_pool_token_account_authority = seeds::token_account_authority();
 
             invoke_signed(
                 &spl_token::instruction::transfer(
@@ -782,11 +2263,31 @@ impl Processor {
             )?;
         }
 
-        //debug
-        msg!("user_data after emergency-withdraw is {:#?}", user_data);
-        //
+        emit(&PositionUpdatedEvent {
+            pool_index: stake_pool.pool_index,
+            token_account: *token_account_info.key,
+            new_amount: user_data.amount,
+            reward_paid: 0,
+            slot: clock.slot,
+        })?;
+
         user_data.serialize(&mut &mut pda_user_state_info.data.borrow_mut()[..])?;
 
+        // Unclaimed boost or reward tokens keep UserInfo open for their claims
+        if let Some(pda_wallet_pool_info) = pda_wallet_pool_info {
+            if wallet_pool_address(stake_pool.pool_index).0 != *pda_wallet_pool_info.key {
+                return Err(ProgramError::InvalidSeeds);
+            }
+            if user_data.is_closable() {
+                Self::close_user_info(
+                    pda_user_state_info,
+                    pda_wallet_pool_info,
+                )?;
+            }
+        }
+
+        StakePool::pack(stake_pool, &mut pda_stake_pool_info.data.borrow_mut())?;
+
         Ok(())
     }
 
@@ -814,13 +2315,21 @@ impl Processor {
             mint_info.key,
         )?;
 
+        let old_theme_id = stake_pool.theme_id;
+
         stake_pool.update_project_info(
             pool_name,
             project_link,
             theme_id,
         );
 
-        msg!("stake_pool after update_project_info is {:#?}", stake_pool);
+        emit_config_changed(
+            stake_pool.pool_index,
+            ConfigField::ProjectInfo,
+            old_theme_id as u64,
+            theme_id as u64,
+        )?;
+
         StakePool::pack(stake_pool, &mut pda_stake_pool_info.data.borrow_mut())?;        
 
         Ok(())
@@ -856,31 +2365,96 @@ impl Processor {
         )?;
 
         validate_pool_token_account(
-            &pda_pool_token_account_staked_info,
+            pda_pool_token_account_staked_info,
         )?;
         
         let clock_info = next_account_info(account_info_iter)?; // 4
+        validate_clock_sysvar(clock_info)?;
         let clock = &Clock::from_account_info(clock_info)?;
 
-        assert!(bonus_start_block < bonus_end_block);
-        assert!(bonus_start_block >= stake_pool.start_block, 
-            "Cant set early than start time");
+        let pda_pool_token_account_reward_info = next_account_info(account_info_iter)?; // 5
+        let pda_pool_token_account_reward = TokenAccount::unpack(
+            &pda_pool_token_account_reward_info.data.borrow(),
+        )?;
+
+        validate_pool_token_account(
+            pda_pool_token_account_reward_info,
+        )?;
 
         stake_pool.update_pool(
             &pda_pool_token_account_staked,
-            &clock,
+            &pda_pool_token_account_reward,
+            clock,
         )?;
 
-        assert!(stake_pool.bonus_end_block == COption::None, 
-            "Can't start another Bonus time");
+        let old_bonus_multiplier = stake_pool.bonus_multiplier.unwrap_or(1) as u64;
+        let old_bonus_start_block = stake_pool.bonus_start_block.unwrap_or(0);
+        let old_bonus_end_block = stake_pool.bonus_end_block.unwrap_or(0);
+        let old_end_block = stake_pool.end_block;
+
+        Self::apply_bonus_time(
+            &mut stake_pool,
+            pda_pool_token_account_reward.amount,
+            clock.slot,
+            bonus_multiplier,
+            bonus_start_block,
+            bonus_end_block,
+        )?;
 
-        let end_block = stake_pool.end_block
-            .checked_sub(
-                (bonus_end_block - bonus_start_block) * (bonus_multiplier as u64 - 1))
+        emit_config_changed(stake_pool.pool_index, ConfigField::BonusMultiplier, old_bonus_multiplier, bonus_multiplier as u64)?;
+        emit_config_changed(stake_pool.pool_index, ConfigField::BonusStartBlock, old_bonus_start_block, bonus_start_block)?;
+        emit_config_changed(stake_pool.pool_index, ConfigField::BonusEndBlock, old_bonus_end_block, stake_pool.bonus_end_block.unwrap_or(0))?;
+        emit_config_changed(stake_pool.pool_index, ConfigField::EndBlock, old_end_block, stake_pool.end_block)?;
+
+        StakePool::pack(stake_pool, &mut pda_stake_pool_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    /// Sets the bonus on an updated pool, shortening end_block by the extra blocks paid by the bonus.
+    /// Shared by SetBonusTime and LogBonusPreview
+    fn apply_bonus_time(
+        stake_pool: &mut StakePool,
+        reward_vault_amount: u64,
+        slot: u64,
+        bonus_multiplier: u8,
+        bonus_start_block: u64,
+        bonus_end_block: u64,
+    ) -> ProgramResult {
+        if bonus_start_block >= bonus_end_block || bonus_start_block < stake_pool.start_block {
+            msg!("Bonus must start at start_block or later and before its end");
+            StakingError::InvalidBlockRange.print::<StakingError>();
+            return Err(StakingError::InvalidBlockRange.into());
+        }
+
+        if stake_pool.bonus_end_block != COption::None {
+            msg!("Can't start another Bonus time");
+            StakingError::InvalidBlockRange.print::<StakingError>();
+            return Err(StakingError::InvalidBlockRange.into());
+        }
+
+        // Extra blocks paid by the bonus are taken from the end of the pool, unless the boost vault pays them
+        let bonus_extra_blocks = if stake_pool.has_boost_vault() {
+            0
+        } else {
+            bonus_end_block
+                .checked_sub(bonus_start_block)
+                .ok_or(StakingError::Overflow)?
+                .checked_mul(
+                    (bonus_multiplier as u64)
+                    .checked_sub(1)
+                    .ok_or(StakingError::Overflow)?)
+                .ok_or(StakingError::Overflow)?
+        };
+        let end_block = stake_pool.end_block
+            .checked_sub(bonus_extra_blocks)
             .ok_or(StakingError::Overflow)?;
 
-        assert!(end_block > clock.slot && end_block > stake_pool.start_block, 
-            "Not enough rewards for Bonus");
+        if end_block <= slot || end_block <= stake_pool.start_block {
+            msg!("Not enough rewards for Bonus");
+            StakingError::InsufficientRewardFunds.print::<StakingError>();
+            return Err(StakingError::InsufficientRewardFunds.into());
+        }
 
         if end_block < bonus_end_block {
             stake_pool.set_bonus_end_block(end_block);
@@ -890,10 +2464,24 @@ impl Processor {
         }
         stake_pool.set_bonus_multiplier(bonus_multiplier);
         stake_pool.set_bonus_start_block(bonus_start_block);
+        stake_pool.pre_bonus_end_block = stake_pool.end_block;
         stake_pool.set_end_block(end_block);
+        stake_pool.check_funding()?;
 
-        msg!("stake_pool after set_bonus_time is {:#?}", stake_pool);
-        StakePool::pack(stake_pool, &mut pda_stake_pool_info.data.borrow_mut())?;
+        // Rewards accrued but not paid yet are in the vault too
+        let required = stake_pool
+            .get_remaining_reward()?
+            .checked_add(stake_pool.total_owed)
+            .ok_or(StakingError::Overflow)?;
+        if reward_vault_amount < required {
+            msg!(
+                "Reward vault holds {} but bonus requires {}",
+                reward_vault_amount,
+                required,
+            );
+            StakingError::InsufficientRewardFunds.print::<StakingError>();
+            return Err(StakingError::InsufficientRewardFunds.into());
+        }
 
         Ok(())
     }
@@ -907,17 +2495,112 @@ impl Processor {
         let pool_owner_info = next_account_info(account_info_iter)?; // 0
         let mint_info = next_account_info(account_info_iter)?; // 1
         let pda_stake_pool_info = next_account_info(account_info_iter)?; // 2
-        
+
         let clock_info = next_account_info(account_info_iter)?; // 3
+        validate_clock_sysvar(clock_info)?;
         let clock = &Clock::from_account_info(clock_info)?;
 
         let token_program_info = next_account_info(account_info_iter)?; // 4
 
         let reward_token_account_info = next_account_info(account_info_iter)?; // 5
         let pda_pool_token_account_reward_info = next_account_info(account_info_iter)?; // 6
-        
+
+        Self::update_end_block(
+            pool_owner_info,
+            EndBlockAccounts {
+                mint_info,
+                pda_stake_pool_info,
+                reward_token_account_info,
+                pda_pool_token_account_reward_info,
+                extra_reward_infos: account_info_iter.as_slice(), // 7..
+            },
+            clock,
+            token_program_info,
+            end_block,
+        )
+    }
+
+    pub fn process_update_end_block_many(
+        accounts: &[AccountInfo],
+        end_blocks: Vec<u64>,
+    ) -> ProgramResult {
+        if end_blocks.is_empty() || end_blocks.len() > MAX_POOLS_PER_UPDATE {
+            StakingError::InvalidPoolCount.print::<StakingError>();
+            return Err(StakingError::InvalidPoolCount.into());
+        }
+        if accounts.len() < 3 + 4 * end_blocks.len() {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        }
+
+        let account_info_iter = &mut accounts.iter();
+
+        let pool_owner_info = next_account_info(account_info_iter)?; // 0
+
+        let clock_info = next_account_info(account_info_iter)?; // 1
+        validate_clock_sysvar(clock_info)?;
+        let clock = &Clock::from_account_info(clock_info)?;
+
+        let token_program_info = next_account_info(account_info_iter)?; // 2
+
+        for end_block in end_blocks {
+            let pda_stake_pool_info = next_account_info(account_info_iter)?; // group + 0
+            let mint_info = next_account_info(account_info_iter)?; // group + 1
+            let reward_token_account_info = next_account_info(account_info_iter)?; // group + 2
+            let pda_pool_token_account_reward_info = next_account_info(account_info_iter)?; // group + 3
+
+            // Followed by 2 accounts for each reward token after the first, as in UpdateEndBlock
+            let n_reward_tokens = StakePool::unpack(&pda_stake_pool_info.data.borrow())?.n_reward_tokens;
+            let n_extra_accounts = 2 * (n_reward_tokens as usize).saturating_sub(1);
+            let rest = account_info_iter.as_slice();
+            if rest.len() < n_extra_accounts {
+                return Err(ProgramError::NotEnoughAccountKeys);
+            }
+            let (extra_reward_infos, rest) = rest.split_at(n_extra_accounts);
+            *account_info_iter = rest.iter();
+
+            Self::update_end_block(
+                pool_owner_info,
+                EndBlockAccounts {
+                    mint_info,
+                    pda_stake_pool_info,
+                    reward_token_account_info,
+                    pda_pool_token_account_reward_info,
+                    extra_reward_infos,
+                },
+                clock,
+                token_program_info,
+                end_block,
+            )?;
+        }
+
+        if account_info_iter.next().is_some() {
+            msg!("More accounts than the pools of end_blocks take");
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        Ok(())
+    }
+
+    fn update_end_block<'a>(
+        pool_owner_info: &AccountInfo<'a>,
+        pool: EndBlockAccounts<'_, 'a>,
+        clock: &Clock,
+        token_program_info: &AccountInfo<'a>,
+        end_block: u64,
+    ) -> ProgramResult {
+        let EndBlockAccounts {
+            mint_info,
+            pda_stake_pool_info,
+            reward_token_account_info,
+            pda_pool_token_account_reward_info,
+            extra_reward_infos,
+        } = pool;
+        if !pool_owner_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
         validate_pool_token_account(
-            &pda_pool_token_account_reward_info,
+            pda_pool_token_account_reward_info,
         )?;
 
         let mut stake_pool = StakePool::unpack(&pda_stake_pool_info.data.borrow_mut())
@@ -929,38 +2612,136 @@ impl Processor {
             mint_info.key,
         )?;
 
+        // The authority of every pool vault is the same PDA, the funding goes to the vault of this pool
+        if !is_reward_vault(&stake_pool, pda_pool_token_account_reward_info.key) {
+            StakingError::PoolTokenAccountMissmatch.print::<StakingError>();
+            return Err(StakingError::PoolTokenAccountMissmatch.into());
+        }
+
         let current_block = clock.slot;
 
-        assert!(stake_pool.end_block > current_block, "Pool already finished");
-        assert!(end_block > stake_pool.end_block, "Cannot shorten");
+        if stake_pool.end_block <= current_block {
+            StakingError::PoolEnded.print::<StakingError>();
+            return Err(StakingError::PoolEnded.into());
+        }
+        // Shortening would take rewards already promised to stakers
+        if end_block <= stake_pool.end_block {
+            StakingError::InvalidBlockRange.print::<StakingError>();
+            return Err(StakingError::InvalidBlockRange.into());
+        }
+
+        let old_end_block = stake_pool.end_block;
 
-        let blocks_added = end_block - stake_pool.end_block;
+        // Reward tokens after the first are funded for the added blocks as well,
+        // their vaults would run dry before the new end_block otherwise
+        let n_extra_reward_tokens = (stake_pool.n_reward_tokens as usize).saturating_sub(1);
+        if extra_reward_infos.len() != 2 * n_extra_reward_tokens {
+            msg!("Expected 2 accounts for each of {} reward tokens after the first", n_extra_reward_tokens);
+            return Err(ProgramError::NotEnoughAccountKeys);
+        }
+
+        // All amounts are computed and checked before any transfer,
+        // so the instruction never leaves the pool partially funded
+        // Difference of scheduled rewards rather than get_reward(blocks_added),
+        // so rounding by emission_period never leaves the schedule over total_funded
+        let scheduled_before = stake_pool.get_scheduled_reward()?;
+        let mut extra_scheduled_before = [0; MAX_REWARD_TOKENS];
+        for (reward_index, scheduled) in extra_scheduled_before.iter_mut().enumerate().take(n_extra_reward_tokens + 1).skip(1) {
+            *scheduled = stake_pool.get_scheduled_token_reward(reward_index)?;
+        }
+        stake_pool.set_end_block(end_block);
+        // CancelBonus keeps the added blocks
+        if stake_pool.pre_bonus_end_block != 0 {
+            stake_pool.pre_bonus_end_block = stake_pool.pre_bonus_end_block
+                .checked_add(end_block - old_end_block)
+                .ok_or(StakingError::Overflow)?;
+        }
+        let to_transfer = stake_pool
+            .get_scheduled_reward()?
+            .checked_sub(scheduled_before)
+            .ok_or(StakingError::Overflow)?;
+
+        stake_pool.add_funded(to_transfer)?;
+        stake_pool.check_funding()?;
+
+        let reward_token_account = TokenAccount::unpack(
+            &reward_token_account_info.data.borrow(),
+        )?;
+        if reward_token_account.amount < to_transfer {
+            StakingError::InsufficientRewardFunds.print::<StakingError>();
+            return Err(StakingError::InsufficientRewardFunds.into());
+        }
+
+        let mut extra_to_transfer = [0; MAX_REWARD_TOKENS];
+        for (i, extra_reward_accounts) in extra_reward_infos.chunks(2).enumerate() {
+            let reward_index = i + 1;
+            if extra_reward_token_account_address(stake_pool.pool_index, reward_index as u8).0 != *extra_reward_accounts[1].key {
+                StakingError::PoolTokenAccountMissmatch.print::<StakingError>();
+                return Err(StakingError::PoolTokenAccountMissmatch.into());
+            }
+
+            extra_to_transfer[reward_index] = stake_pool
+                .get_scheduled_token_reward(reward_index)?
+                .checked_sub(extra_scheduled_before[reward_index])
+                .ok_or(StakingError::Overflow)?;
+
+            let token_account = TokenAccount::unpack(
+                &extra_reward_accounts[0].data.borrow(),
+            )?;
+            if token_account.amount < extra_to_transfer[reward_index] {
+                StakingError::InsufficientRewardFunds.print::<StakingError>();
+                return Err(StakingError::InsufficientRewardFunds.into());
+            }
+        }
+
+        invoke(
+            &spl_token::instruction::transfer(
+                &spl_token::id(),
+                reward_token_account_info.key,
+                pda_pool_token_account_reward_info.key,
+                pool_owner_info.key,
+                &[pool_owner_info.key],
+                to_transfer,
+            )?,
+            &[
+            reward_token_account_info.clone(),
+            pda_pool_token_account_reward_info.clone(),
+            pool_owner_info.clone(),
+            token_program_info.clone(),
+            ],
+        )?;
 
-        // TODO: add loop for reward tokens
-            let to_transfer = blocks_added * stake_pool.reward_per_block;
+        for (i, extra_reward_accounts) in extra_reward_infos.chunks(2).enumerate() {
+            let amount = extra_to_transfer[i + 1];
+            if amount == 0 {
+                continue;
+            }
 
             invoke(
                 &spl_token::instruction::transfer(
                     &spl_token::id(),
-                    reward_token_account_info.key,
-                    pda_pool_token_account_reward_info.key,
+                    extra_reward_accounts[0].key,
+                    extra_reward_accounts[1].key,
                     pool_owner_info.key,
                     &[pool_owner_info.key],
-                    to_transfer,
+                    amount,
                 )?,
                 &[
-                reward_token_account_info.clone(),
-                pda_pool_token_account_reward_info.clone(),
+                extra_reward_accounts[0].clone(),
+                extra_reward_accounts[1].clone(),
                 pool_owner_info.clone(),
                 token_program_info.clone(),
                 ],
             )?;
+        }
 
-        stake_pool.set_end_block(end_block);
+        emit_config_changed(
+            stake_pool.pool_index,
+            ConfigField::EndBlock,
+            old_end_block,
+            end_block,
+        )?;
 
-        //debug
-        msg!("StakePool after instruction is \n{:#?}", stake_pool); 
-        //
         StakePool::pack(stake_pool, &mut pda_stake_pool_info.data.borrow_mut())?;
 
         Ok(())
@@ -982,15 +2763,12 @@ impl Processor {
         }
 
         let rent_info = next_account_info(account_info_iter)?; // 4
+        validate_rent_sysvar(rent_info)?;
         let rent = &Rent::from_account_info(rent_info)?;
 
         let system_program_info = next_account_info(account_info_iter)?; // 5
 
-        let sign_seeds_pda_token_account_authority: &[&[_]] = 
-            &[
-            ADD_SEED_TOKEN_ACCOUNT_AUTHORITY.as_bytes(),
-            &[BUMP_SEED_TOKEN_ACCOUNT_AUTHORITY],
-            ];
+        let sign_seeds_pda_token_account_authority = seeds::token_account_authority();
 
         let instruction_create_token_account_authority = system_instruction::create_account(
             payer_info.key,
@@ -1008,11 +2786,7 @@ impl Processor {
 
         let minimum_balance_master = rent.minimum_balance(MASTER_STAKING_LEN);
 
-        let sign_seeds_pda_master_staking: &[&[_]] = 
-            &[
-            ADD_SEED_MASTER_STAKING.as_bytes(),
-            &[BUMP_SEED_MASTER_STAKING],
-            ];
+        let sign_seeds_pda_master_staking = seeds::master_staking();
         
         let instruction_create_master_staking = system_instruction::create_account(
             payer_info.key,
@@ -1030,10 +2804,3898 @@ impl Processor {
 
         let master_staking = MasterStaking {
             pool_counter: 0,
+            protocol_fee_bps: 0,
+            protocol_fee_recipient: Pubkey::default(),
         };
 
-        master_staking.serialize(&mut *pda_master_staking_info.data.borrow_mut())?;
- 
+        master_staking.serialize(&mut &mut pda_master_staking_info.data.borrow_mut()[..])?;
+
+        Ok(())
+    }
+
+    pub fn process_log_emitted_between(
+        accounts: &[AccountInfo],
+        from: u64,
+        to: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let pda_stake_pool_info = next_account_info(account_info_iter)?; // 0
+        let stake_pool = StakePool::unpack(&pda_stake_pool_info.data.borrow())?;
+
+        if from > to {
+            StakingError::InvalidBlockRange.print::<StakingError>();
+            return Err(StakingError::InvalidBlockRange.into());
+        }
+
+        let emitted = stake_pool.get_reward(
+            stake_pool.get_multiplier(from, to),
+        )?;
+
+        msg!("Emitted between {} and {}: {}", from, to, emitted);
+
+        Ok(())
+    }
+
+    pub fn process_set_long_link(
+        accounts: &[AccountInfo],
+        uri: Vec<u8>,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let pool_owner_info = next_account_info(account_info_iter)?; // 0
+        if !pool_owner_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let mint_info = next_account_info(account_info_iter)?; // 1
+        let pda_stake_pool_info = next_account_info(account_info_iter)?; // 2
+        let mut stake_pool = StakePool::unpack(&pda_stake_pool_info.data.borrow())?;
+
+        validate_stake_pool(
+            &stake_pool,
+            pool_owner_info.key,
+            mint_info.key,
+        )?;
+
+        let pda_long_link_info = next_account_info(account_info_iter)?; // 3
+
+        let rent_info = next_account_info(account_info_iter)?; // 4
+        validate_rent_sysvar(rent_info)?;
+        let rent = &Rent::from_account_info(rent_info)?;
+
+        let system_program_info = next_account_info(account_info_iter)?; // 5
+
+        if uri.len() > MAX_LONG_LINK_LEN {
+            StakingError::LinkTooLong.print::<StakingError>();
+            return Err(StakingError::LinkTooLong.into());
+        }
+
+        let pool_index = stake_pool.pool_index;
+
+        let (pda_long_link_pubkey, bump_seed_long_link) = Pubkey::find_program_address(
+            &[&pool_index.to_le_bytes(), ADD_SEED_LINK.as_bytes()],
+            &this_program_id(),
+        );
+        if pda_long_link_pubkey != *pda_long_link_info.key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+
+        // Account is created with the max size at once, so any later link fits into it
+        if pda_long_link_info.data_is_empty() {
+            let sign_seeds_pda_long_link: &[&[_]] =
+                &[
+                &pool_index.to_le_bytes(),
+                ADD_SEED_LINK.as_bytes(),
+                &[bump_seed_long_link],
+                ];
+
+            invoke_signed(
+                &system_instruction::create_account(
+                    pool_owner_info.key,
+                    pda_long_link_info.key,
+                    rent.minimum_balance(LONG_LINK_LEN),
+                    LONG_LINK_LEN as u64,
+                    &this_program_id(),
+                ),
+                &[pool_owner_info.clone(), pda_long_link_info.clone(), system_program_info.clone()],
+                &[sign_seeds_pda_long_link],
+            )?;
+        }
+
+        let mut project_link = [0u8; 128];
+        let fallback_len = uri.len().min(project_link.len());
+        project_link[..fallback_len].copy_from_slice(&uri[..fallback_len]);
+
+        let long_link = LongLink {
+            uri,
+        };
+
+        let mut long_link_data = pda_long_link_info.data.borrow_mut();
+        long_link_data.fill(0);
+        long_link.serialize(&mut &mut long_link_data[..])?;
+
+        stake_pool.set_project_link(project_link);
+
+        StakePool::pack(stake_pool, &mut pda_stake_pool_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    pub fn process_transfer_position(
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let owner_info = next_account_info(account_info_iter)?; // 0
+        if !owner_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let token_account_info = next_account_info(account_info_iter)?; // 1
+        let token_account = TokenAccount::unpack(
+            &token_account_info.data.borrow(),
+        )?;
+        if token_account.owner != *owner_info.key {
+            return Err(TokenError::OwnerMismatch.into());
+        }
+
+        let new_token_account_info = next_account_info(account_info_iter)?; // 2
+        let new_token_account = TokenAccount::unpack(
+            &new_token_account_info.data.borrow(),
+        )?;
+        // Every pool vault is owned by the PDA authority, so none of them can take the position
+        validate_user_token_account(
+            new_token_account_info,
+            &[],
+        )?;
+
+        let pda_stake_pool_info = next_account_info(account_info_iter)?; // 3
+        if *pda_stake_pool_info.owner != this_program_id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        StakePool::unpack(&pda_stake_pool_info.data.borrow())?;
+
+        // shares of the position depend on the mint
+        if new_token_account.mint != token_account.mint {
+            return Err(TokenError::MintMismatch.into());
+        }
+
+        let pda_user_state_info = next_account_info(account_info_iter)?; // 4
+        let pda_new_user_state_info = next_account_info(account_info_iter)?; // 5
+
+        let rent_info = next_account_info(account_info_iter)?; // 6
+        validate_rent_sysvar(rent_info)?;
+        let rent = &Rent::from_account_info(rent_info)?;
+
+        let system_program_info = next_account_info(account_info_iter)?; // 7
+
+        let clock_info = next_account_info(account_info_iter)?; // 8
+        validate_clock_sysvar(clock_info)?;
+        let clock = &Clock::from_account_info(clock_info)?;
+
+        validate_user_state(
+            pda_user_state_info,
+            pda_stake_pool_info,
+            token_account_info,
+        )?;
+
+        let (pda_new_user_state_pubkey, bump_seed_new_user_state) = user_info_address(
+            pda_stake_pool_info.key,
+            new_token_account_info.key,
+        );
+        if pda_new_user_state_pubkey != *pda_new_user_state_info.key {
+            StakingError::UserInfoMissmatch.print::<StakingError>();
+            return Err(StakingError::UserInfoMissmatch.into());
+        }
+        if !pda_new_user_state_info.data_is_empty() {
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+
+        let user_data = UserInfo::from_account_info(pda_user_state_info)?;
+        user_data.check_not_frozen()?;
+
+        let signers_seeds_pda_new_user_state: &[&[_]] =
+            &[
+            pda_stake_pool_info.key.as_ref(),
+            new_token_account_info.key.as_ref(),
+            &[bump_seed_new_user_state],
+            ];
+
+        invoke_signed(
+            &system_instruction::create_account(
+                owner_info.key,
+                pda_new_user_state_info.key,
+                rent.minimum_balance(USER_INFO_LEN),
+                USER_INFO_LEN as u64,
+                &this_program_id(),
+            ),
+            &[owner_info.clone(), pda_new_user_state_info.clone(), system_program_info.clone()],
+            &[signers_seeds_pda_new_user_state],
+        )?;
+
+        let new_user_data = UserInfo {
+            token_account_id: *new_token_account_info.key,
+            amount: user_data.amount,
+            reward_debt: user_data.reward_debt,
+            last_compound_slot: user_data.last_compound_slot,
+            delegate: Pubkey::default(), // delegate is set by the owner of the position
+            deposit_slot: user_data.deposit_slot,
+            frozen: 0,
+            boost_debt: user_data.boost_debt,
+            boost_unclaimed: user_data.boost_unclaimed,
+            stake_start_slot: user_data.stake_start_slot,
+            shares: user_data.shares,
+            avg_entry_price: user_data.avg_entry_price,
+            pending_owed: user_data.pending_owed,
+            extra_reward_debt: user_data.extra_reward_debt,
+            extra_unclaimed: user_data.extra_unclaimed,
+            // A new token-account can't vote on proposals started before, its tokens may have voted
+            amount_change_slot: clock.slot,
+        };
+
+        new_user_data.serialize(&mut &mut pda_new_user_state_info.data.borrow_mut()[..])?;
+
+        // Old UserInfo is closed, its rent goes back to the owner
+        pda_user_state_info.data.borrow_mut().fill(0);
+
+        let user_state_lamports = pda_user_state_info.lamports();
+        **pda_user_state_info.lamports.borrow_mut() = 0;
+        **owner_info.lamports.borrow_mut() = owner_info
+            .lamports()
+            .checked_add(user_state_lamports)
+            .ok_or(StakingError::Overflow)?;
+
+
+        Ok(())
+    }
+
+    pub fn process_compound(
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let (accounts, protocol_fee_infos) = split_protocol_fee_accounts(accounts)?;
+        let account_info_iter = &mut accounts.iter();
+
+        let owner_info = next_account_info(account_info_iter)?; // 0
+        if !owner_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let token_account_info = next_account_info(account_info_iter)?; // 1
+        let token_account = TokenAccount::unpack(
+            &token_account_info.data.borrow(),
+        )?;
+        if token_account.owner != *owner_info.key {
+            return Err(TokenError::OwnerMismatch.into());
+        }
+
+        let pda_stake_pool_info = next_account_info(account_info_iter)?; // 2
+        let pda_pool_token_account_authority_info = next_account_info(account_info_iter)?; // 3
+        let pda_pool_token_account_staked_info = next_account_info(account_info_iter)?; // 4
+        let pda_pool_token_account_reward_info = next_account_info(account_info_iter)?; // 5
+        let pda_user_state_info = next_account_info(account_info_iter)?; // 6
+
+        let clock_info = next_account_info(account_info_iter)?; // 7
+        validate_clock_sysvar(clock_info)?;
+        let clock = &Clock::from_account_info(clock_info)?;
+
+        let token_program_info = next_account_info(account_info_iter)?; // 8
+
+        validate_pool_token_account(
+            pda_pool_token_account_staked_info,
+        )?;
+        validate_pool_token_account(
+            pda_pool_token_account_reward_info,
+        )?;
+        validate_user_state(
+            pda_user_state_info,
+            pda_stake_pool_info,
+            token_account_info,
+        )?;
+
+        let pda_pool_token_account_staked = TokenAccount::unpack(
+            &pda_pool_token_account_staked_info.data.borrow(),
+        )?;
+        let pda_pool_token_account_reward = TokenAccount::unpack(
+            &pda_pool_token_account_reward_info.data.borrow(),
+        )?;
+
+        let mut stake_pool = StakePool::unpack(&pda_stake_pool_info.data.borrow())?;
+        let mut user_data = UserInfo::from_account_info(pda_user_state_info)?;
+
+        // Persisted before any CPI, so a callback into this program sees the pool busy
+        stake_pool.lock()?;
+        StakePool::pack(stake_pool, &mut pda_stake_pool_info.data.borrow_mut())?;
+
+        // reward tokens can be staked only as StakePool.mint
+        if token_account.mint != stake_pool.mint {
+            StakingError::InvalidStakeMint.print::<StakingError>();
+            return Err(StakingError::InvalidStakeMint.into());
+        }
+
+        user_data.check_compound_interval(
+            stake_pool.min_compound_interval,
+            clock.slot,
+        )?;
+
+        stake_pool.update_pool(
+            &pda_pool_token_account_staked,
+            &pda_pool_token_account_reward,
+            clock,
+        )?;
+
+        let current_shares = stake_pool.get_position_shares(&token_account.mint, &user_data)?;
+        let pending = get_pending(
+            current_shares,
+            stake_pool.accrued_token_per_share,
+            stake_pool.precision_factor_rank,
+            user_data.reward_debt,
+            stake_pool.rounds_up_rewards(),
+        )?;
+        let pending = stake_pool.cap_rounded_up(pending, pda_pool_token_account_reward.amount);
+        let earned = stake_pool.get_warmed_up(pending, user_data.deposit_slot, clock.slot)?;
+        let unpaid = get_unpaid(
+            current_shares,
+            stake_pool.accrued_token_per_share,
+            stake_pool.precision_factor_rank,
+            user_data.reward_debt,
+            pending,
+        )?;
+        let (claimed, settled) = user_data.take_claim(&stake_pool, pending, earned)?;
+
+        if pending.max(claimed) > pda_pool_token_account_reward.amount {
+            StakingError::InsufficientRewardFunds.print::<StakingError>();
+            return Err(StakingError::InsufficientRewardFunds.into());
+        }
+
+        if settled > 0 {
+            let claimed = Self::pay_protocol_fee(
+                protocol_fee_infos,
+                &stake_pool,
+                pda_pool_token_account_reward_info,
+                pda_pool_token_account_authority_info,
+                token_program_info,
+                claimed,
+            )?;
+
+            let sign_seeds_pda_pool_token_account_authority = seeds::token_account_authority();
+
+            invoke_signed(
+                &spl_token::instruction::transfer(
+                    &spl_token::id(),
+                    pda_pool_token_account_reward_info.key,
+                    pda_pool_token_account_staked_info.key,
+                    pda_pool_token_account_authority_info.key,
+                    &[pda_pool_token_account_authority_info.key],
+                    claimed,
+                )?,
+                &[
+                pda_pool_token_account_reward_info.clone(),
+                pda_pool_token_account_staked_info.clone(),
+                pda_pool_token_account_authority_info.clone(),
+                token_program_info.clone(),
+                ],
+                &[&sign_seeds_pda_pool_token_account_authority]
+            )?;
+
+            // The part lost to the warmup stays in the reward vault
+            stake_pool.decrease_owed(settled)?;
+
+            let amount_before = user_data.amount;
+            user_data.amount = user_data
+                .amount
+                .checked_add(claimed)
+                .ok_or(StakingError::Overflow)?;
+            user_data.amount_change_slot = clock.slot;
+            stake_pool.update_stakers(amount_before, user_data.amount)?;
+
+            stake_pool.total_staked = stake_pool
+                .total_staked
+                .checked_add(claimed)
+                .ok_or(StakingError::Overflow)?;
+        }
+
+        let shares = stake_pool.get_weighted_shares(
+            &token_account.mint,
+            user_data.amount,
+            user_data.stake_start_slot,
+            clock.slot,
+        )?;
+        stake_pool.update_total_shares(current_shares, shares)?;
+        user_data.shares = shares;
+
+        user_data.settle_boost(&stake_pool, current_shares, shares)?;
+        user_data.settle_extra_rewards(&stake_pool, current_shares, shares)?;
+        user_data.set_reward_debt(
+            get_reward_debt(
+                shares,
+                stake_pool.accrued_token_per_share,
+                unpaid,
+            )?
+        );
+        user_data.last_compound_slot = clock.slot;
+
+        user_data.serialize(&mut &mut pda_user_state_info.data.borrow_mut()[..])?;
+
+        stake_pool.unlock();
+
+        StakePool::pack(stake_pool, &mut pda_stake_pool_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    pub fn process_log_tvl_snapshot(
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let pda_stake_pool_info = next_account_info(account_info_iter)?; // 0
+        let stake_pool = StakePool::unpack(&pda_stake_pool_info.data.borrow())?;
+
+        let pda_pool_token_account_staked_info = next_account_info(account_info_iter)?; // 1
+        let pda_pool_token_account_reward_info = next_account_info(account_info_iter)?; // 2
+
+        let clock_info = next_account_info(account_info_iter)?; // 3
+        validate_clock_sysvar(clock_info)?;
+        let clock = &Clock::from_account_info(clock_info)?;
+
+        validate_pool_token_account(
+            pda_pool_token_account_staked_info,
+        )?;
+        validate_pool_token_account(
+            pda_pool_token_account_reward_info,
+        )?;
+
+        let pda_pool_token_account_staked = TokenAccount::unpack(
+            &pda_pool_token_account_staked_info.data.borrow(),
+        )?;
+        let pda_pool_token_account_reward = TokenAccount::unpack(
+            &pda_pool_token_account_reward_info.data.borrow(),
+        )?;
+
+        emit(&TvlSnapshot {
+            pool_index: stake_pool.pool_index,
+            slot: clock.slot,
+            staked_amount: pda_pool_token_account_staked.amount,
+            reward_amount: pda_pool_token_account_reward.amount,
+            total_staked: stake_pool.total_staked,
+            tags: stake_pool.tags,
+            stakers: stake_pool.stakers,
+        })?;
+
+        Ok(())
+    }
+
+    /// Sends MasterStaking.protocol_fee_bps of `payout` from the reward vault to the protocol fee
+    /// token-account. Returns the part of `payout` left for the user
+    fn pay_protocol_fee<'a>(
+        protocol_fee_infos: &[AccountInfo<'a>],
+        stake_pool: &StakePool,
+        pda_pool_token_account_reward_info: &AccountInfo<'a>,
+        pda_pool_token_account_authority_info: &AccountInfo<'a>,
+        token_program_info: &AccountInfo<'a>,
+        payout: u64,
+    ) -> Result<u64, ProgramError> {
+        // Clients from before the protocol fee don't pass its accounts
+        if protocol_fee_infos.is_empty() {
+            return Ok(payout);
+        }
+        let pda_master_staking_info = &protocol_fee_infos[0];
+        let protocol_fee_token_account_info = &protocol_fee_infos[1];
+
+        let master_staking = MasterStaking::from_account_info(pda_master_staking_info)?;
+        let fee = master_staking.get_protocol_fee(payout)?;
+        if fee == 0 {
+            return Ok(payout);
+        }
+
+        let protocol_fee_token_account = TokenAccount::unpack(
+            &protocol_fee_token_account_info.data.borrow(),
+        )?;
+        if protocol_fee_token_account.owner != master_staking.protocol_fee_recipient
+            || protocol_fee_token_account.mint != stake_pool.mint
+        {
+            StakingError::InvalidProtocolFeeAccount.print::<StakingError>();
+            return Err(StakingError::InvalidProtocolFeeAccount.into());
+        }
+
+        let sign_seeds_pda_pool_token_account_authority = seeds::token_account_authority();
+
+        invoke_signed(
+            &spl_token::instruction::transfer(
+                &spl_token::id(),
+                pda_pool_token_account_reward_info.key,
+                protocol_fee_token_account_info.key,
+                pda_pool_token_account_authority_info.key,
+                &[pda_pool_token_account_authority_info.key],
+                fee,
+            )?,
+            &[
+            pda_pool_token_account_reward_info.clone(),
+            protocol_fee_token_account_info.clone(),
+            pda_pool_token_account_authority_info.clone(),
+            token_program_info.clone(),
+            ],
+            &[&sign_seeds_pda_pool_token_account_authority]
+        )?;
+
+        Ok(payout - fee)
+    }
+
+    /// Pays pending rewards through StakePool.reward_swap_program.
+    /// Accounts are taken from the rest of Withdraw accounts, see StakingInstruction::SetRewardSwapProgram
+    fn swap_reward<'a, 'b>(
+        reward_swap_program: &Pubkey,
+        owner_key: &Pubkey,
+        pda_pool_token_account_authority_info: &AccountInfo<'a>,
+        pda_pool_token_account_reward_info: &AccountInfo<'a>,
+        token_program_info: &AccountInfo<'a>,
+        account_info_iter: &mut std::slice::Iter<'b, AccountInfo<'a>>,
+        amount_in: u64,
+    ) -> ProgramResult {
+        validate_reward_swap_program(reward_swap_program)?;
+
+        let swap_program_info = next_account_info(account_info_iter)?; // 9
+        if swap_program_info.key != reward_swap_program {
+            StakingError::RewardSwapProgramMissmatch.print::<StakingError>();
+            return Err(StakingError::RewardSwapProgramMissmatch.into());
+        }
+
+        let destination_info = next_account_info(account_info_iter)?; // 10
+        let destination = TokenAccount::unpack(
+            &destination_info.data.borrow(),
+        )?;
+        if destination.owner != *owner_key {
+            return Err(TokenError::OwnerMismatch.into());
+        }
+
+        let swap_accounts_infos: Vec<AccountInfo<'a>> = account_info_iter.cloned().collect(); // 11..
+
+        let mut accounts_metas = vec![
+            AccountMeta::new_readonly(*pda_pool_token_account_authority_info.key, true),
+            AccountMeta::new(*pda_pool_token_account_reward_info.key, false),
+            AccountMeta::new(*destination_info.key, false),
+            AccountMeta::new_readonly(*token_program_info.key, false),
+        ];
+        for account_info in swap_accounts_infos.iter() {
+            accounts_metas.push(AccountMeta {
+                pubkey: *account_info.key,
+                is_signer: account_info.is_signer,
+                is_writable: account_info.is_writable,
+            });
+        }
+
+        let mut cpi_accounts_infos = vec![
+            pda_pool_token_account_authority_info.clone(),
+            pda_pool_token_account_reward_info.clone(),
+            destination_info.clone(),
+            token_program_info.clone(),
+            swap_program_info.clone(),
+        ];
+        cpi_accounts_infos.extend(swap_accounts_infos);
+
+        let reward_before = TokenAccount::unpack(
+            &pda_pool_token_account_reward_info.data.borrow(),
+        )?.amount;
+
+        let sign_seeds_pda_pool_token_account_authority = seeds::token_account_authority();
+
+        invoke_signed(
+            &Instruction {
+                program_id: *reward_swap_program,
+                accounts: accounts_metas,
+                data: RewardSwap { amount_in }.try_to_vec()?,
+            },
+            &cpi_accounts_infos,
+            &[&sign_seeds_pda_pool_token_account_authority]
+        )?;
+
+        let reward_after = TokenAccount::unpack(
+            &pda_pool_token_account_reward_info.data.borrow(),
+        )?.amount;
+        let taken = reward_before
+            .checked_sub(reward_after)
+            .ok_or(StakingError::RewardSwapFailed)?;
+        if taken > amount_in {
+            StakingError::RewardSwapFailed.print::<StakingError>();
+            return Err(StakingError::RewardSwapFailed.into());
+        }
+
+        Ok(())
+    }
+
+    pub fn process_set_reward_swap_program(
+        accounts: &[AccountInfo],
+        reward_swap_program: Option<Pubkey>,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let pool_owner_info = next_account_info(account_info_iter)?; // 0
+        if !pool_owner_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let mint_info = next_account_info(account_info_iter)?; // 1
+        let pda_stake_pool_info = next_account_info(account_info_iter)?; // 2
+        let mut stake_pool = StakePool::unpack(&pda_stake_pool_info.data.borrow_mut())
+            .expect("Failed to deserialie StakePool");
+
+        validate_stake_pool(
+            &stake_pool,
+            pool_owner_info.key,
+            mint_info.key,
+        )?;
+
+        let reward_swap_program = match reward_swap_program {
+            Some(key) => {
+                validate_reward_swap_program(&key)?;
+                COption::Some(key)
+            },
+            None => COption::None,
+        };
+
+        stake_pool.set_reward_swap_program(reward_swap_program);
+
+        StakePool::pack(stake_pool, &mut pda_stake_pool_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    pub fn process_set_delegate(
+        accounts: &[AccountInfo],
+        delegate: Option<Pubkey>,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let owner_info = next_account_info(account_info_iter)?; // 0
+        if !owner_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let token_account_info = next_account_info(account_info_iter)?; // 1
+        let token_account = TokenAccount::unpack(
+            &token_account_info.data.borrow(),
+        )?;
+        if token_account.owner != *owner_info.key {
+            return Err(TokenError::OwnerMismatch.into());
+        }
+
+        let pda_stake_pool_info = next_account_info(account_info_iter)?; // 2
+        let pda_user_state_info = next_account_info(account_info_iter)?; // 3
+
+        validate_user_state(
+            pda_user_state_info,
+            pda_stake_pool_info,
+            token_account_info,
+        )?;
+
+        let mut user_data = UserInfo::from_account_info(pda_user_state_info)?;
+
+        user_data.set_delegate(delegate.unwrap_or_default());
+
+        user_data.serialize(&mut &mut pda_user_state_info.data.borrow_mut()[..])?;
+
+        Ok(())
+    }
+
+    pub fn process_set_max_total_staked(
+        accounts: &[AccountInfo],
+        max_total_staked: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let pool_owner_info = next_account_info(account_info_iter)?; // 0
+        if !pool_owner_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let mint_info = next_account_info(account_info_iter)?; // 1
+        let pda_stake_pool_info = next_account_info(account_info_iter)?; // 2
+        let mut stake_pool = StakePool::unpack(&pda_stake_pool_info.data.borrow_mut())
+            .expect("Failed to deserialie StakePool");
+
+        validate_stake_pool(
+            &stake_pool,
+            pool_owner_info.key,
+            mint_info.key,
+        )?;
+
+        emit_config_changed(
+            stake_pool.pool_index,
+            ConfigField::MaxTotalStaked,
+            stake_pool.max_total_staked,
+            max_total_staked,
+        )?;
+
+        stake_pool.set_max_total_staked(max_total_staked);
+
+        StakePool::pack(stake_pool, &mut pda_stake_pool_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    pub fn process_log_pool_capacity(
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let pda_stake_pool_info = next_account_info(account_info_iter)?; // 0
+        let stake_pool = StakePool::unpack(&pda_stake_pool_info.data.borrow())?;
+
+        let pda_pool_token_account_staked_info = next_account_info(account_info_iter)?; // 1
+        validate_pool_token_account(
+            pda_pool_token_account_staked_info,
+        )?;
+        let pda_pool_token_account_staked = TokenAccount::unpack(
+            &pda_pool_token_account_staked_info.data.borrow(),
+        )?;
+
+        if stake_pool.max_total_staked == 0 {
+            msg!("Capacity: unlimited, staked: {}", pda_pool_token_account_staked.amount);
+            return Ok(());
+        }
+
+        // over cap is possible if tokens were sent to the vault directly
+        let headroom = stake_pool.max_total_staked
+            .saturating_sub(pda_pool_token_account_staked.amount);
+
+        msg!(
+            "Capacity: {}, staked: {}, headroom: {}",
+            stake_pool.max_total_staked,
+            pda_pool_token_account_staked.amount,
+            headroom,
+        );
+
+        Ok(())
+    }
+
+    pub fn process_add_stake_mint(
+        accounts: &[AccountInfo],
+        weight: u16,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let pool_owner_info = next_account_info(account_info_iter)?; // 0
+        if !pool_owner_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let mint_info = next_account_info(account_info_iter)?; // 1
+        let pda_stake_pool_info = next_account_info(account_info_iter)?; // 2
+        let stake_mint_info = next_account_info(account_info_iter)?; // 3
+        let pda_pool_token_account_authority_info = next_account_info(account_info_iter)?; // 4
+        let pda_stake_mint_token_account_info = next_account_info(account_info_iter)?; // 5
+
+        let rent_info = next_account_info(account_info_iter)?; // 6
+        validate_rent_sysvar(rent_info)?;
+        let rent = &Rent::from_account_info(rent_info)?;
+
+        let system_program_info = next_account_info(account_info_iter)?; // 7
+        let token_program_info = next_account_info(account_info_iter)?; // 8
+
+        let mut stake_pool = StakePool::unpack(&pda_stake_pool_info.data.borrow_mut())
+            .expect("Failed to deserialie StakePool");
+
+        validate_stake_pool(
+            &stake_pool,
+            pool_owner_info.key,
+            mint_info.key,
+        )?;
+
+        stake_pool.add_stake_mint(stake_mint_info.key, weight)?;
+
+        let (pda_stake_mint_token_account_pubkey, bump_seed_stake_mint_token_account) = stake_mint_token_account_address(
+            stake_pool.pool_index,
+            stake_mint_info.key,
+        );
+        if pda_stake_mint_token_account_pubkey != *pda_stake_mint_token_account_info.key {
+            StakingError::PoolTokenAccountMissmatch.print::<StakingError>();
+            return Err(StakingError::PoolTokenAccountMissmatch.into());
+        }
+
+        let sign_seeds_pda_stake_mint_token_account: &[&[_]] =
+            &[
+            &stake_pool.pool_index.to_le_bytes(),
+            stake_mint_info.key.as_ref(),
+            ADD_SEED_STAKED.as_bytes(),
+            &[bump_seed_stake_mint_token_account],
+            ];
+
+        invoke_signed(
+            &system_instruction::create_account(
+                pool_owner_info.key,
+                pda_stake_mint_token_account_info.key,
+                rent.minimum_balance(TokenAccount::LEN),
+                TokenAccount::LEN as u64,
+                &spl_token::id(),
+            ),
+            &[pool_owner_info.clone(), pda_stake_mint_token_account_info.clone(), system_program_info.clone()],
+            &[sign_seeds_pda_stake_mint_token_account],
+        )?;
+
+        invoke_signed(
+            &spl_token::instruction::initialize_account(
+                &spl_token::id(),
+                pda_stake_mint_token_account_info.key,
+                stake_mint_info.key,
+                pda_pool_token_account_authority_info.key,
+            )?,
+            &[
+            pda_stake_mint_token_account_info.clone(),
+            stake_mint_info.clone(),
+            pda_pool_token_account_authority_info.clone(),
+            rent_info.clone(),
+            token_program_info.clone(),
+            ],
+            &[sign_seeds_pda_stake_mint_token_account],
+        )?;
+
+        validate_pool_token_account(
+            pda_stake_mint_token_account_info,
+        )?;
+
+        StakePool::pack(stake_pool, &mut pda_stake_pool_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    pub fn process_fund_rewards(
+        accounts: &[AccountInfo],
+        amount: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let pool_owner_info = next_account_info(account_info_iter)?; // 0
+        if !pool_owner_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let mint_info = next_account_info(account_info_iter)?; // 1
+        let pda_stake_pool_info = next_account_info(account_info_iter)?; // 2
+        let reward_token_account_info = next_account_info(account_info_iter)?; // 3
+        let pda_pool_token_account_reward_info = next_account_info(account_info_iter)?; // 4
+        let token_program_info = next_account_info(account_info_iter)?; // 5
+        let pda_pool_token_account_staked_info = next_account_info(account_info_iter)?; // 6
+
+        let clock_info = next_account_info(account_info_iter)?; // 7
+        validate_clock_sysvar(clock_info)?;
+        let clock = &Clock::from_account_info(clock_info)?;
+
+        let mut stake_pool = StakePool::unpack(&pda_stake_pool_info.data.borrow_mut())
+            .expect("Failed to deserialie StakePool");
+
+        validate_stake_pool(
+            &stake_pool,
+            pool_owner_info.key,
+            mint_info.key,
+        )?;
+        validate_pool_token_account(
+            pda_pool_token_account_reward_info,
+        )?;
+        validate_staked_token_account(
+            &stake_pool,
+            pda_pool_token_account_staked_info,
+            &stake_pool.mint,
+        )?;
+
+        // Blocks till now are accrued with the vault before the transfer, an empty vault skips them
+        stake_pool.update_pool(
+            &TokenAccount::unpack(&pda_pool_token_account_staked_info.data.borrow())?,
+            &TokenAccount::unpack(&pda_pool_token_account_reward_info.data.borrow())?,
+            clock,
+        )?;
+
+        invoke(
+            &spl_token::instruction::transfer(
+                &spl_token::id(),
+                reward_token_account_info.key,
+                pda_pool_token_account_reward_info.key,
+                pool_owner_info.key,
+                &[pool_owner_info.key],
+                amount,
+            )?,
+            &[
+            reward_token_account_info.clone(),
+            pda_pool_token_account_reward_info.clone(),
+            pool_owner_info.clone(),
+            token_program_info.clone(),
+            ],
+        )?;
+
+        stake_pool.add_funded(amount)?;
+
+        let pda_pool_token_account_reward = TokenAccount::unpack(
+            &pda_pool_token_account_reward_info.data.borrow(),
+        )?;
+        let required = stake_pool
+            .get_remaining_reward()?
+            .checked_add(stake_pool.total_owed)
+            .ok_or(StakingError::Overflow)?;
+
+        if pda_pool_token_account_reward.amount >= required
+            && stake_pool.total_funded >= stake_pool.get_scheduled_reward()?
+        {
+            stake_pool.set_funded();
+        }
+        msg!(
+            "Reward vault holds {}, schedule requires {}",
+            pda_pool_token_account_reward.amount,
+            required,
+        );
+
+        StakePool::pack(stake_pool, &mut pda_stake_pool_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    pub fn process_log_pool_health(
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let pda_stake_pool_info = next_account_info(account_info_iter)?; // 0
+        let stake_pool = StakePool::unpack(&pda_stake_pool_info.data.borrow())?;
+
+        let pda_pool_token_account_staked_info = next_account_info(account_info_iter)?; // 1
+        let pda_pool_token_account_reward_info = next_account_info(account_info_iter)?; // 2
+
+        validate_pool_token_account(
+            pda_pool_token_account_staked_info,
+        )?;
+        validate_pool_token_account(
+            pda_pool_token_account_reward_info,
+        )?;
+
+        let pda_pool_token_account_staked = TokenAccount::unpack(
+            &pda_pool_token_account_staked_info.data.borrow(),
+        )?;
+        let pda_pool_token_account_reward = TokenAccount::unpack(
+            &pda_pool_token_account_reward_info.data.borrow(),
+        )?;
+
+        let health = stake_pool.get_health(
+            &pda_pool_token_account_staked,
+            &pda_pool_token_account_reward,
+        )?;
+
+        msg!("Pool health: {:#07b}", health);
+        msg!("staked covered: {}", health & HEALTH_STAKED_COVERED != 0);
+        msg!("reward covered: {}", health & HEALTH_REWARD_COVERED != 0);
+        msg!("bonus consistent: {}", health & HEALTH_BONUS_CONSISTENT != 0);
+        msg!("last reward block within pool: {}", health & HEALTH_LAST_REWARD_BLOCK != 0);
+        msg!("solvent: {}", health & HEALTH_SOLVENT != 0);
+
+        Ok(())
+    }
+
+    pub fn process_register_referral_code(
+        accounts: &[AccountInfo],
+        code: [u8; 16],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let owner_info = next_account_info(account_info_iter)?; // 0
+        if !owner_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let referrer_token_account_info = next_account_info(account_info_iter)?; // 1
+        let referrer_token_account = TokenAccount::unpack(
+            &referrer_token_account_info.data.borrow(),
+        )?;
+        if referrer_token_account.owner != *owner_info.key {
+            return Err(TokenError::OwnerMismatch.into());
+        }
+
+        let pda_referral_code_info = next_account_info(account_info_iter)?; // 2
+
+        let rent_info = next_account_info(account_info_iter)?; // 3
+        validate_rent_sysvar(rent_info)?;
+        let rent = &Rent::from_account_info(rent_info)?;
+
+        let system_program_info = next_account_info(account_info_iter)?; // 4
+
+        let (pda_referral_code_pubkey, bump_seed_referral_code) = referral_code_address(&code);
+        if pda_referral_code_pubkey != *pda_referral_code_info.key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        if !pda_referral_code_info.data_is_empty() {
+            StakingError::CodeTaken.print::<StakingError>();
+            return Err(StakingError::CodeTaken.into());
+        }
+
+        let sign_seeds_pda_referral_code: &[&[_]] =
+            &[
+            ADD_SEED_REFERRAL.as_bytes(),
+            &code,
+            &[bump_seed_referral_code],
+            ];
+
+        invoke_signed(
+            &system_instruction::create_account(
+                owner_info.key,
+                pda_referral_code_info.key,
+                rent.minimum_balance(REFERRAL_CODE_LEN),
+                REFERRAL_CODE_LEN as u64,
+                &this_program_id(),
+            ),
+            &[owner_info.clone(), pda_referral_code_info.clone(), system_program_info.clone()],
+            &[sign_seeds_pda_referral_code],
+        )?;
+
+        let referral_code = ReferralCode {
+            code,
+            referrer: *referrer_token_account_info.key,
+        };
+        referral_code.serialize(&mut &mut pda_referral_code_info.data.borrow_mut()[..])?;
+
+        Ok(())
+    }
+
+    pub fn process_mint_receipt(
+        accounts: &[AccountInfo],
+        amount: u64,
+    ) -> ProgramResult {
+        let (accounts, protocol_fee_infos) = split_protocol_fee_accounts(accounts)?;
+        validate_mint_receipt_accounts(accounts)?;
+
+        let account_info_iter = &mut accounts.iter();
+
+        let owner_info = next_account_info(account_info_iter)?; // 0
+        let token_account_info = next_account_info(account_info_iter)?; // 1
+        let pda_receipt_mint_info = next_account_info(account_info_iter)?; // 2
+        let receipt_token_account_info = next_account_info(account_info_iter)?; // 3
+
+        let token_account = TokenAccount::unpack(
+            &token_account_info.data.borrow(),
+        )?;
+
+        if token_account.owner != *owner_info.key {
+            return Err(TokenError::OwnerMismatch.into());
+        }
+        if !owner_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        Self::withdraw(
+            WithdrawPosition {
+                owner_key: &token_account.owner,
+                delegate_key: None,
+                token_account_info,
+            },
+            Some((pda_receipt_mint_info, receipt_token_account_info)),
+            protocol_fee_infos,
+            account_info_iter,
+            amount,
+            false,
+        )
+    }
+
+    pub fn process_burn_receipt(
+        accounts: &[AccountInfo],
+        amount: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let holder_info = next_account_info(account_info_iter)?; // 0
+        if !holder_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let receipt_token_account_info = next_account_info(account_info_iter)?; // 1
+        let token_account_info = next_account_info(account_info_iter)?; // 2
+        let pda_stake_pool_info = next_account_info(account_info_iter)?; // 3
+        let pda_pool_token_account_authority_info = next_account_info(account_info_iter)?; // 4
+        let pda_pool_token_account_staked_info = next_account_info(account_info_iter)?; // 5
+        let pda_receipt_mint_info = next_account_info(account_info_iter)?; // 6
+        let token_program_info = next_account_info(account_info_iter)?; // 7
+
+        if *pda_stake_pool_info.owner != this_program_id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let mut stake_pool = StakePool::unpack(&pda_stake_pool_info.data.borrow())?;
+
+        validate_staked_token_account(
+            &stake_pool,
+            pda_pool_token_account_staked_info,
+            &stake_pool.mint,
+        )?;
+        validate_user_token_account(
+            token_account_info,
+            &[pda_pool_token_account_staked_info],
+        )?;
+        let token_account = TokenAccount::unpack(
+            &token_account_info.data.borrow(),
+        )?;
+        if token_account.mint != stake_pool.mint {
+            return Err(TokenError::MintMismatch.into());
+        }
+        if receipt_mint_address(stake_pool.pool_index).0 != *pda_receipt_mint_info.key {
+            StakingError::InvalidReceiptMint.print::<StakingError>();
+            return Err(StakingError::InvalidReceiptMint.into());
+        }
+
+        stake_pool.receipt_supply = stake_pool
+            .receipt_supply
+            .checked_sub(amount)
+            .ok_or(StakingError::Overflow)?;
+
+        invoke(
+            &spl_token::instruction::burn(
+                &spl_token::id(),
+                receipt_token_account_info.key,
+                pda_receipt_mint_info.key,
+                holder_info.key,
+                &[holder_info.key],
+                amount,
+            )?,
+            &[
+            receipt_token_account_info.clone(),
+            pda_receipt_mint_info.clone(),
+            holder_info.clone(),
+            token_program_info.clone(),
+            ],
+        )?;
+
+        let sign_seeds_pda_pool_token_account_authority = seeds::token_account_authority();
+
+        invoke_signed(
+            &spl_token::instruction::transfer(
+                &spl_token::id(),
+                pda_pool_token_account_staked_info.key,
+                token_account_info.key,
+                pda_pool_token_account_authority_info.key,
+                &[pda_pool_token_account_authority_info.key],
+                amount,
+            )?,
+            &[
+            pda_pool_token_account_staked_info.clone(),
+            token_account_info.clone(),
+            pda_pool_token_account_authority_info.clone(),
+            token_program_info.clone(),
+            ],
+            &[&sign_seeds_pda_pool_token_account_authority]
+        )?;
+
+        StakePool::pack(stake_pool, &mut pda_stake_pool_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    pub fn process_log_depletion_slot(
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let pda_stake_pool_info = next_account_info(account_info_iter)?; // 0
+        let stake_pool = StakePool::unpack(&pda_stake_pool_info.data.borrow())?;
+
+        let pda_pool_token_account_reward_info = next_account_info(account_info_iter)?; // 1
+        validate_pool_token_account(
+            pda_pool_token_account_reward_info,
+        )?;
+        let pda_pool_token_account_reward = TokenAccount::unpack(
+            &pda_pool_token_account_reward_info.data.borrow(),
+        )?;
+
+        // Rewards accrued but not paid yet are not available for the rest of the schedule
+        let available = pda_pool_token_account_reward.amount
+            .saturating_sub(stake_pool.total_owed);
+
+        let depletion_slot = stake_pool.get_depletion_slot(available)?;
+
+        msg!(
+            "Reward per block: {} (per {} blocks), available: {}",
+            stake_pool.effective_reward_per_block(stake_pool.last_reward_block)?,
+            stake_pool.emission_period,
+            available,
+        );
+        msg!("Depletion slot: {}, end block: {}", depletion_slot, stake_pool.end_block);
+
+        Ok(())
+    }
+
+    pub fn process_cast_vote(
+        accounts: &[AccountInfo],
+        proposal_id: u64,
+        choice: u8,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let owner_info = next_account_info(account_info_iter)?; // 0
+        if !owner_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let token_account_info = next_account_info(account_info_iter)?; // 1
+        let token_account = TokenAccount::unpack(
+            &token_account_info.data.borrow(),
+        )?;
+        if token_account.owner != *owner_info.key {
+            return Err(TokenError::OwnerMismatch.into());
+        }
+
+        let pda_stake_pool_info = next_account_info(account_info_iter)?; // 2
+        StakePool::unpack(&pda_stake_pool_info.data.borrow())?;
+
+        let pda_user_state_info = next_account_info(account_info_iter)?; // 3
+        validate_user_state(
+            pda_user_state_info,
+            pda_stake_pool_info,
+            token_account_info,
+        )?;
+        let user_data = UserInfo::from_account_info(pda_user_state_info)?;
+
+        let pda_proposal_tally_info = next_account_info(account_info_iter)?; // 4
+        let pda_vote_receipt_info = next_account_info(account_info_iter)?; // 5
+
+        let rent_info = next_account_info(account_info_iter)?; // 6
+        validate_rent_sysvar(rent_info)?;
+        let rent = &Rent::from_account_info(rent_info)?;
+
+        let system_program_info = next_account_info(account_info_iter)?; // 7
+
+        let clock_info = next_account_info(account_info_iter)?; // 8
+        validate_clock_sysvar(clock_info)?;
+        let clock = &Clock::from_account_info(clock_info)?;
+
+        if choice as usize >= MAX_VOTE_CHOICES {
+            StakingError::InvalidVoteChoice.print::<StakingError>();
+            return Err(StakingError::InvalidVoteChoice.into());
+        }
+
+        let (pda_proposal_tally_pubkey, bump_seed_proposal_tally) = proposal_tally_address(
+            pda_stake_pool_info.key,
+            proposal_id,
+        );
+        if pda_proposal_tally_pubkey != *pda_proposal_tally_info.key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+
+        let (pda_vote_receipt_pubkey, bump_seed_vote_receipt) = vote_receipt_address(
+            pda_proposal_tally_info.key,
+            pda_user_state_info.key,
+        );
+        if pda_vote_receipt_pubkey != *pda_vote_receipt_info.key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        if !pda_vote_receipt_info.data_is_empty() {
+            StakingError::AlreadyVoted.print::<StakingError>();
+            return Err(StakingError::AlreadyVoted.into());
+        }
+
+        // The first vote on a proposal creates its tally
+        let mut proposal_tally = if pda_proposal_tally_info.data_is_empty() {
+            let sign_seeds_pda_proposal_tally: &[&[_]] =
+                &[
+                pda_stake_pool_info.key.as_ref(),
+                ADD_SEED_PROPOSAL.as_bytes(),
+                &proposal_id.to_le_bytes(),
+                &[bump_seed_proposal_tally],
+                ];
+
+            invoke_signed(
+                &system_instruction::create_account(
+                    owner_info.key,
+                    pda_proposal_tally_info.key,
+                    rent.minimum_balance(PROPOSAL_TALLY_LEN),
+                    PROPOSAL_TALLY_LEN as u64,
+                    &this_program_id(),
+                ),
+                &[owner_info.clone(), pda_proposal_tally_info.clone(), system_program_info.clone()],
+                &[sign_seeds_pda_proposal_tally],
+            )?;
+
+            ProposalTally {
+                stake_pool: *pda_stake_pool_info.key,
+                proposal_id,
+                start_slot: clock.slot,
+                weights: [0; MAX_VOTE_CHOICES],
+            }
+        } else {
+            ProposalTally::try_from_slice(&pda_proposal_tally_info.data.borrow())?
+        };
+
+        // The weight is the amount when the proposal started. A position changed since, or moved
+        // to its token-account since, may hold tokens that voted already
+        if user_data.amount_change_slot >= proposal_tally.start_slot {
+            StakingError::PositionChangedAfterProposal.print::<StakingError>();
+            return Err(StakingError::PositionChangedAfterProposal.into());
+        }
+
+        let sign_seeds_pda_vote_receipt: &[&[_]] =
+            &[
+            pda_proposal_tally_info.key.as_ref(),
+            pda_user_state_info.key.as_ref(),
+            ADD_SEED_VOTE.as_bytes(),
+            &[bump_seed_vote_receipt],
+            ];
+
+        invoke_signed(
+            &system_instruction::create_account(
+                owner_info.key,
+                pda_vote_receipt_info.key,
+                rent.minimum_balance(VOTE_RECEIPT_LEN),
+                VOTE_RECEIPT_LEN as u64,
+                &this_program_id(),
+            ),
+            &[owner_info.clone(), pda_vote_receipt_info.clone(), system_program_info.clone()],
+            &[sign_seeds_pda_vote_receipt],
+        )?;
+
+        let weight = user_data.amount;
+        proposal_tally.add_vote(choice, weight)?;
+
+        let vote_receipt = VoteReceipt {
+            choice,
+            weight,
+        };
+
+        vote_receipt.serialize(&mut &mut pda_vote_receipt_info.data.borrow_mut()[..])?;
+        proposal_tally.serialize(&mut &mut pda_proposal_tally_info.data.borrow_mut()[..])?;
+
+
+        Ok(())
+    }
+
+    pub fn process_set_warmup_slots(
+        accounts: &[AccountInfo],
+        warmup_slots: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let pool_owner_info = next_account_info(account_info_iter)?; // 0
+        if !pool_owner_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let mint_info = next_account_info(account_info_iter)?; // 1
+        let pda_stake_pool_info = next_account_info(account_info_iter)?; // 2
+        let mut stake_pool = StakePool::unpack(&pda_stake_pool_info.data.borrow_mut())
+            .expect("Failed to deserialie StakePool");
+
+        validate_stake_pool(
+            &stake_pool,
+            pool_owner_info.key,
+            mint_info.key,
+        )?;
+
+        emit_config_changed(
+            stake_pool.pool_index,
+            ConfigField::WarmupSlots,
+            stake_pool.warmup_slots,
+            warmup_slots,
+        )?;
+
+        stake_pool.warmup_slots = warmup_slots;
+
+        StakePool::pack(stake_pool, &mut pda_stake_pool_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    pub fn process_log_user_debug(
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let pda_stake_pool_info = next_account_info(account_info_iter)?; // 0
+        let pda_user_state_info = next_account_info(account_info_iter)?; // 1
+        let token_account_info = next_account_info(account_info_iter)?; // 2
+        let pda_pool_token_account_staked_info = next_account_info(account_info_iter)?; // 3
+        let pda_pool_token_account_reward_info = next_account_info(account_info_iter)?; // 4
+
+        let clock_info = next_account_info(account_info_iter)?; // 5
+        validate_clock_sysvar(clock_info)?;
+        let clock = &Clock::from_account_info(clock_info)?;
+
+        validate_user_state(
+            pda_user_state_info,
+            pda_stake_pool_info,
+            token_account_info,
+        )?;
+        validate_pool_token_account(
+            pda_pool_token_account_staked_info,
+        )?;
+        validate_pool_token_account(
+            pda_pool_token_account_reward_info,
+        )?;
+
+        let mut stake_pool = StakePool::unpack(&pda_stake_pool_info.data.borrow())?;
+        let user_data = UserInfo::from_account_info(pda_user_state_info)?;
+        let token_account = TokenAccount::unpack(
+            &token_account_info.data.borrow(),
+        )?;
+        let pda_pool_token_account_staked = TokenAccount::unpack(
+            &pda_pool_token_account_staked_info.data.borrow(),
+        )?;
+        let pda_pool_token_account_reward = TokenAccount::unpack(
+            &pda_pool_token_account_reward_info.data.borrow(),
+        )?;
+
+        msg!(
+            "amount: {}, reward_debt: {}, shares: {}, pending_owed: {}, deposit_slot: {}",
+            user_data.amount,
+            user_data.reward_debt,
+            user_data.shares,
+            user_data.pending_owed,
+            user_data.deposit_slot,
+        );
+        msg!("stored accrued_token_per_share: {}", stake_pool.accrued_token_per_share);
+
+        // Pool is updated in memory only, so pending is what a Withdraw in this slot pays
+        stake_pool.update_pool(
+            &pda_pool_token_account_staked,
+            &pda_pool_token_account_reward,
+            clock,
+        )?;
+
+        let shares = stake_pool.get_position_shares(&token_account.mint, &user_data)?;
+        let pending = get_pending(
+            shares,
+            stake_pool.accrued_token_per_share,
+            stake_pool.precision_factor_rank,
+            user_data.reward_debt,
+            stake_pool.rounds_up_rewards(),
+        )?;
+        let earned = stake_pool.get_warmed_up(pending, user_data.deposit_slot, clock.slot)?;
+
+        msg!(
+            "slot: {}, accrued_token_per_share: {}, shares: {}, pending: {}, earned after warmup: {}",
+            clock.slot,
+            stake_pool.accrued_token_per_share,
+            shares,
+            pending,
+            earned,
+        );
+
+        Ok(())
+    }
+
+    pub fn process_log_user_positions(
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let n_positions = accounts.len().saturating_sub(4) / 2;
+        if n_positions == 0 || n_positions > MAX_POSITIONS_PER_LOG || accounts.len() != 4 + 2 * n_positions {
+            StakingError::InvalidPositionCount.print::<StakingError>();
+            return Err(StakingError::InvalidPositionCount.into());
+        }
+
+        let account_info_iter = &mut accounts.iter();
+
+        let pda_stake_pool_info = next_account_info(account_info_iter)?; // 0
+        let pda_pool_token_account_staked_info = next_account_info(account_info_iter)?; // 1
+        let pda_pool_token_account_reward_info = next_account_info(account_info_iter)?; // 2
+
+        let clock_info = next_account_info(account_info_iter)?; // 3
+        validate_clock_sysvar(clock_info)?;
+        let clock = &Clock::from_account_info(clock_info)?;
+
+        validate_pool_token_account(
+            pda_pool_token_account_staked_info,
+        )?;
+        validate_pool_token_account(
+            pda_pool_token_account_reward_info,
+        )?;
+
+        let mut stake_pool = StakePool::unpack(&pda_stake_pool_info.data.borrow())?;
+        let pda_pool_token_account_staked = TokenAccount::unpack(
+            &pda_pool_token_account_staked_info.data.borrow(),
+        )?;
+        let pda_pool_token_account_reward = TokenAccount::unpack(
+            &pda_pool_token_account_reward_info.data.borrow(),
+        )?;
+
+        // In memory only, pending is what a Withdraw in this slot pays
+        stake_pool.update_pool(
+            &pda_pool_token_account_staked,
+            &pda_pool_token_account_reward,
+            clock,
+        )?;
+
+        let mut positions = Vec::with_capacity(n_positions);
+        for _ in 0..n_positions {
+            let pda_user_state_info = next_account_info(account_info_iter)?; // 4 + 2 * i
+            let token_account_info = next_account_info(account_info_iter)?; // 5 + 2 * i
+
+            validate_user_state(
+                pda_user_state_info,
+                pda_stake_pool_info,
+                token_account_info,
+            )?;
+            let user_data = UserInfo::from_account_info(pda_user_state_info)?;
+            let token_account = TokenAccount::unpack(
+                &token_account_info.data.borrow(),
+            )?;
+
+            let shares = stake_pool.get_position_shares(&token_account.mint, &user_data)?;
+            let pending = get_pending(
+                shares,
+                stake_pool.accrued_token_per_share,
+                stake_pool.precision_factor_rank,
+                user_data.reward_debt,
+                stake_pool.rounds_up_rewards(),
+            )?;
+
+            positions.push(UserPosition {
+                token_account: *token_account_info.key,
+                amount: user_data.amount,
+                pending: stake_pool.get_warmed_up(pending, user_data.deposit_slot, clock.slot)?,
+            });
+        }
+
+        emit(&UserPositions {
+            pool_index: stake_pool.pool_index,
+            slot: clock.slot,
+            positions,
+        })
+    }
+
+    pub fn process_set_tags(
+        accounts: &[AccountInfo],
+        tags: u32,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let pool_owner_info = next_account_info(account_info_iter)?; // 0
+        if !pool_owner_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let mint_info = next_account_info(account_info_iter)?; // 1
+        let pda_stake_pool_info = next_account_info(account_info_iter)?; // 2
+        let mut stake_pool = StakePool::unpack(&pda_stake_pool_info.data.borrow_mut())
+            .expect("Failed to deserialie StakePool");
+
+        validate_stake_pool(
+            &stake_pool,
+            pool_owner_info.key,
+            mint_info.key,
+        )?;
+
+        if tags & !KNOWN_TAGS != 0 {
+            StakingError::InvalidTags.print::<StakingError>();
+            return Err(StakingError::InvalidTags.into());
+        }
+
+        emit_config_changed(
+            stake_pool.pool_index,
+            ConfigField::Tags,
+            stake_pool.tags as u64,
+            tags as u64,
+        )?;
+
+        stake_pool.tags = tags;
+
+        StakePool::pack(stake_pool, &mut pda_stake_pool_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    pub fn process_set_user_frozen(
+        accounts: &[AccountInfo],
+        frozen: bool,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let pool_owner_info = next_account_info(account_info_iter)?; // 0
+        if !pool_owner_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let mint_info = next_account_info(account_info_iter)?; // 1
+        let pda_stake_pool_info = next_account_info(account_info_iter)?; // 2
+        let token_account_info = next_account_info(account_info_iter)?; // 3
+        let pda_user_state_info = next_account_info(account_info_iter)?; // 4
+
+        let instructions_info = next_account_info(account_info_iter)?; // 5
+        validate_not_cpi(instructions_info)?;
+
+        let stake_pool = StakePool::unpack(&pda_stake_pool_info.data.borrow())?;
+
+        validate_stake_pool(
+            &stake_pool,
+            pool_owner_info.key,
+            mint_info.key,
+        )?;
+        validate_user_state(
+            pda_user_state_info,
+            pda_stake_pool_info,
+            token_account_info,
+        )?;
+
+        let mut user_data = UserInfo::from_account_info(pda_user_state_info)?;
+        user_data.frozen = frozen as u8;
+        user_data.serialize(&mut &mut pda_user_state_info.data.borrow_mut()[..])?;
+
+        emit(&UserFrozenChanged {
+            pool_index: stake_pool.pool_index,
+            token_account: *token_account_info.key,
+            frozen,
+        })?;
+
+
+        Ok(())
+    }
+
+    pub fn process_log_pool_summary(
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let pda_stake_pool_info = next_account_info(account_info_iter)?; // 0
+        let stake_pool = StakePool::unpack(&pda_stake_pool_info.data.borrow())?;
+
+        let pda_pool_token_account_staked_info = next_account_info(account_info_iter)?; // 1
+
+        let clock_info = next_account_info(account_info_iter)?; // 2
+        validate_clock_sysvar(clock_info)?;
+        let clock = &Clock::from_account_info(clock_info)?;
+
+        validate_pool_token_account(
+            pda_pool_token_account_staked_info,
+        )?;
+        let pda_pool_token_account_staked = TokenAccount::unpack(
+            &pda_pool_token_account_staked_info.data.borrow(),
+        )?;
+
+        emit(&Self::get_pool_summary(
+            &stake_pool,
+            pda_pool_token_account_staked.amount,
+            clock.slot,
+        )?)
+    }
+
+    /// Shared by LogPoolSummary and LogPoolSummaries
+    fn get_pool_summary(
+        stake_pool: &StakePool,
+        staked_amount: u64,
+        slot: u64,
+    ) -> Result<PoolSummary, ProgramError> {
+        let reward_per_day = reward_per_day(
+            stake_pool.reward_per_block[0],
+            stake_pool.emission_period,
+        )?;
+
+        Ok(PoolSummary {
+            pool_index: stake_pool.pool_index,
+            slot,
+            pool_name: stake_pool.pool_name,
+            project_link: stake_pool.project_link,
+            theme_id: stake_pool.theme_id,
+            tags: stake_pool.tags,
+            start_block: stake_pool.start_block,
+            end_block: stake_pool.end_block,
+            remaining_blocks: stake_pool.end_block.saturating_sub(slot),
+            bonus_active: stake_pool.bonus_active(slot),
+            bonus_multiplier: stake_pool.bonus_multiplier.unwrap_or(1),
+            reward_per_day,
+            apr_bps: apr_bps(reward_per_day, stake_pool.total_staked)?,
+            staked_amount,
+            total_staked: stake_pool.total_staked,
+            stakers: stake_pool.stakers,
+        })
+    }
+
+    pub fn process_migrate_reward_mint(
+        accounts: &[AccountInfo],
+        reward_amount: u64,
+        start_block: u64,
+        end_block: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let pool_owner_info = next_account_info(account_info_iter)?; // 0
+        if !pool_owner_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let mint_info = next_account_info(account_info_iter)?; // 1
+        let pda_stake_pool_info = next_account_info(account_info_iter)?; // 2
+        let new_mint_info = next_account_info(account_info_iter)?; // 3
+        let new_mint = TokenMint::unpack(&new_mint_info.data.borrow())?;
+        let pda_pool_token_account_authority_info = next_account_info(account_info_iter)?; // 4
+        let pda_pool_token_account_staked_info = next_account_info(account_info_iter)?; // 5
+        let pda_pool_token_account_reward_info = next_account_info(account_info_iter)?; // 6
+        let owner_token_account_info = next_account_info(account_info_iter)?; // 7
+        let pda_new_token_account_staked_info = next_account_info(account_info_iter)?; // 8
+        let pda_new_token_account_reward_info = next_account_info(account_info_iter)?; // 9
+
+        let rent_info = next_account_info(account_info_iter)?; // 10
+        validate_rent_sysvar(rent_info)?;
+        let rent = &Rent::from_account_info(rent_info)?;
+
+        let clock_info = next_account_info(account_info_iter)?; // 11
+        validate_clock_sysvar(clock_info)?;
+        let clock = &Clock::from_account_info(clock_info)?;
+
+        let system_program_info = next_account_info(account_info_iter)?; // 12
+        let token_program_info = next_account_info(account_info_iter)?; // 13
+
+        let instructions_info = next_account_info(account_info_iter)?; // 14
+        validate_not_cpi(instructions_info)?;
+
+        let mut stake_pool = StakePool::unpack(&pda_stake_pool_info.data.borrow_mut())
+            .expect("Failed to deserialie StakePool");
+
+        validate_stake_pool(
+            &stake_pool,
+            pool_owner_info.key,
+            mint_info.key,
+        )?;
+
+        // Obligations in the old mint are paid out first: the pool has ended and every position
+        // has withdrawn, so no UserInfo is owed anything and the old vaults hold only leftovers.
+        // Unclaimed boosts aren't tied to stake, so pools with a boost vault don't migrate
+        if clock.slot < stake_pool.end_block
+            || stake_pool.total_staked != 0
+            || stake_pool.receipt_supply != 0
+            || stake_pool.has_stake_mints()
+            || stake_pool.has_boost_vault()
+        {
+            StakingError::MigrationNotAllowed.print::<StakingError>();
+            return Err(StakingError::MigrationNotAllowed.into());
+        }
+
+        // Old vaults are the initial ones or the ones of a previous migration
+        let pool_index = stake_pool.pool_index;
+        if *pda_pool_token_account_staked_info.key != staked_token_account_address(pool_index).0
+            && *pda_pool_token_account_staked_info.key != stake_mint_token_account_address(pool_index, mint_info.key).0
+        {
+            StakingError::PoolTokenAccountMissmatch.print::<StakingError>();
+            return Err(StakingError::PoolTokenAccountMissmatch.into());
+        }
+        if *pda_pool_token_account_reward_info.key != reward_token_account_address(pool_index).0
+            && *pda_pool_token_account_reward_info.key != migrated_reward_token_account_address(pool_index, mint_info.key).0
+        {
+            StakingError::PoolTokenAccountMissmatch.print::<StakingError>();
+            return Err(StakingError::PoolTokenAccountMissmatch.into());
+        }
+
+        if new_mint.decimals >= 21 {
+            StakingError::InvalidMintDecimals.print::<StakingError>();
+            return Err(StakingError::InvalidMintDecimals.into());
+        }
+
+        let precision_factor_rank = 21_u8
+            .checked_sub(new_mint.decimals)
+            .ok_or(StakingError::Overflow)?;
+
+        let duration = end_block
+            .checked_sub(start_block)
+            .ok_or(StakingError::Overflow)?;
+        let reward_per_block = reward_amount
+            .checked_mul(stake_pool.emission_period)
+            .ok_or(StakingError::Overflow)?
+            .checked_div(duration)
+            .ok_or(StakingError::Overflow)?;
+
+        let sign_seeds_pda_pool_token_account_authority = seeds::token_account_authority();
+
+        // Leftovers (dust, donations, unfunded rewards) go to the owner, then the rent of the old vaults
+        let mut swept_amount = 0_u64;
+        for &old_vault_info in [pda_pool_token_account_staked_info, pda_pool_token_account_reward_info].iter() {
+            let old_vault = TokenAccount::unpack(&old_vault_info.data.borrow())?;
+            if old_vault.amount > 0 {
+                invoke_signed(
+                    &spl_token::instruction::transfer(
+                        &spl_token::id(),
+                        old_vault_info.key,
+                        owner_token_account_info.key,
+                        pda_pool_token_account_authority_info.key,
+                        &[pda_pool_token_account_authority_info.key],
+                        old_vault.amount,
+                    )?,
+                    &[
+                    old_vault_info.clone(),
+                    owner_token_account_info.clone(),
+                    pda_pool_token_account_authority_info.clone(),
+                    token_program_info.clone(),
+                    ],
+                    &[&sign_seeds_pda_pool_token_account_authority],
+                )?;
+                swept_amount = swept_amount
+                    .checked_add(old_vault.amount)
+                    .ok_or(StakingError::Overflow)?;
+            }
+
+            invoke_signed(
+                &spl_token::instruction::close_account(
+                    &spl_token::id(),
+                    old_vault_info.key,
+                    pool_owner_info.key,
+                    pda_pool_token_account_authority_info.key,
+                    &[pda_pool_token_account_authority_info.key],
+                )?,
+                &[
+                old_vault_info.clone(),
+                pool_owner_info.clone(),
+                pda_pool_token_account_authority_info.clone(),
+                token_program_info.clone(),
+                ],
+                &[&sign_seeds_pda_pool_token_account_authority],
+            )?;
+        }
+
+        let (pda_new_token_account_staked_pubkey, bump_seed_new_token_account_staked) = stake_mint_token_account_address(
+            pool_index,
+            new_mint_info.key,
+        );
+        let (pda_new_token_account_reward_pubkey, bump_seed_new_token_account_reward) = migrated_reward_token_account_address(
+            pool_index,
+            new_mint_info.key,
+        );
+        if pda_new_token_account_staked_pubkey != *pda_new_token_account_staked_info.key
+            || pda_new_token_account_reward_pubkey != *pda_new_token_account_reward_info.key
+        {
+            StakingError::PoolTokenAccountMissmatch.print::<StakingError>();
+            return Err(StakingError::PoolTokenAccountMissmatch.into());
+        }
+
+        let sign_seeds_pda_new_token_account_staked: &[&[_]] =
+            &[
+            &pool_index.to_le_bytes(),
+            new_mint_info.key.as_ref(),
+            ADD_SEED_STAKED.as_bytes(),
+            &[bump_seed_new_token_account_staked],
+            ];
+        let sign_seeds_pda_new_token_account_reward: &[&[_]] =
+            &[
+            &pool_index.to_le_bytes(),
+            new_mint_info.key.as_ref(),
+            &[bump_seed_new_token_account_reward],
+            ];
+
+        for &(new_vault_info, sign_seeds) in [
+            (pda_new_token_account_staked_info, sign_seeds_pda_new_token_account_staked),
+            (pda_new_token_account_reward_info, sign_seeds_pda_new_token_account_reward),
+        ].iter() {
+            invoke_signed(
+                &system_instruction::create_account(
+                    pool_owner_info.key,
+                    new_vault_info.key,
+                    rent.minimum_balance(TokenAccount::LEN),
+                    TokenAccount::LEN as u64,
+                    &spl_token::id(),
+                ),
+                &[pool_owner_info.clone(), new_vault_info.clone(), system_program_info.clone()],
+                &[sign_seeds],
+            )?;
+
+            invoke_signed(
+                &spl_token::instruction::initialize_account(
+                    &spl_token::id(),
+                    new_vault_info.key,
+                    new_mint_info.key,
+                    pda_pool_token_account_authority_info.key,
+                )?,
+                &[
+                new_vault_info.clone(),
+                new_mint_info.clone(),
+                pda_pool_token_account_authority_info.clone(),
+                rent_info.clone(),
+                token_program_info.clone(),
+                ],
+                &[sign_seeds],
+            )?;
+
+            validate_pool_token_account(
+                new_vault_info,
+            )?;
+        }
+
+        stake_pool.reset_reward_mint(
+            *new_mint_info.key,
+            precision_factor_rank,
+            reward_per_block,
+            start_block,
+            end_block,
+            reward_amount,
+        );
+        // Same bound as Initialize
+        if stake_pool.get_scheduled_reward()? > reward_amount {
+            StakingError::OverCommittedRewards.print::<StakingError>();
+            return Err(StakingError::OverCommittedRewards.into());
+        }
+
+        emit(&RewardMintMigrated {
+            pool_index,
+            old_mint: *mint_info.key,
+            new_mint: *new_mint_info.key,
+            swept_amount,
+        })?;
+
+        StakePool::pack(stake_pool, &mut pda_stake_pool_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    pub fn process_retire_reward_token(
+        accounts: &[AccountInfo],
+        index: u8,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let pool_owner_info = next_account_info(account_info_iter)?; // 0
+        if !pool_owner_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let mint_info = next_account_info(account_info_iter)?; // 1
+        let pda_stake_pool_info = next_account_info(account_info_iter)?; // 2
+        let pda_pool_token_account_staked_info = next_account_info(account_info_iter)?; // 3
+        let pda_pool_token_account_reward_info = next_account_info(account_info_iter)?; // 4
+
+        let clock_info = next_account_info(account_info_iter)?; // 5
+        validate_clock_sysvar(clock_info)?;
+        let clock = &Clock::from_account_info(clock_info)?;
+
+        let mut stake_pool = StakePool::unpack(&pda_stake_pool_info.data.borrow_mut())
+            .expect("Failed to deserialie StakePool");
+
+        validate_stake_pool(
+            &stake_pool,
+            pool_owner_info.key,
+            mint_info.key,
+        )?;
+        validate_staked_token_account(
+            &stake_pool,
+            pda_pool_token_account_staked_info,
+            &stake_pool.mint,
+        )?;
+        if !is_reward_vault(&stake_pool, pda_pool_token_account_reward_info.key) {
+            StakingError::PoolTokenAccountMissmatch.print::<StakingError>();
+            return Err(StakingError::PoolTokenAccountMissmatch.into());
+        }
+
+        // Blocks till now accrue at the rate of the retired token before it is zeroed
+        stake_pool.update_pool(
+            &TokenAccount::unpack(&pda_pool_token_account_staked_info.data.borrow())?,
+            &TokenAccount::unpack(&pda_pool_token_account_reward_info.data.borrow())?,
+            clock,
+        )?;
+
+        let old_n_reward_tokens = stake_pool.n_reward_tokens;
+        stake_pool.retire_reward_token(index as usize)?;
+
+        emit_config_changed(
+            stake_pool.pool_index,
+            ConfigField::NRewardTokens,
+            old_n_reward_tokens as u64,
+            stake_pool.n_reward_tokens as u64,
+        )?;
+
+        StakePool::pack(stake_pool, &mut pda_stake_pool_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    pub fn process_fund_boost_vault(
+        accounts: &[AccountInfo],
+        amount: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let pool_owner_info = next_account_info(account_info_iter)?; // 0
+        if !pool_owner_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let mint_info = next_account_info(account_info_iter)?; // 1
+        let pda_stake_pool_info = next_account_info(account_info_iter)?; // 2
+        let reward_token_account_info = next_account_info(account_info_iter)?; // 3
+        let pda_boost_token_account_info = next_account_info(account_info_iter)?; // 4
+        let token_program_info = next_account_info(account_info_iter)?; // 5
+
+        let stake_pool = StakePool::unpack(&pda_stake_pool_info.data.borrow())?;
+
+        validate_stake_pool(
+            &stake_pool,
+            pool_owner_info.key,
+            mint_info.key,
+        )?;
+        Self::validate_boost_token_account(
+            &stake_pool,
+            pda_boost_token_account_info,
+        )?;
+
+        invoke(
+            &spl_token::instruction::transfer(
+                &spl_token::id(),
+                reward_token_account_info.key,
+                pda_boost_token_account_info.key,
+                pool_owner_info.key,
+                &[pool_owner_info.key],
+                amount,
+            )?,
+            &[
+            reward_token_account_info.clone(),
+            pda_boost_token_account_info.clone(),
+            pool_owner_info.clone(),
+            token_program_info.clone(),
+            ],
+        )?;
+
+        let pda_boost_token_account = TokenAccount::unpack(
+            &pda_boost_token_account_info.data.borrow(),
+        )?;
+        msg!(
+            "Boost vault holds {}, {} is owed",
+            pda_boost_token_account.amount,
+            stake_pool.boost_owed,
+        );
+
+        Ok(())
+    }
+
+    pub fn process_claim_boost(
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let owner_info = next_account_info(account_info_iter)?; // 0
+        if !owner_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let token_account_info = next_account_info(account_info_iter)?; // 1
+        let token_account = unpack_destination_token_account(token_account_info)?;
+        if token_account.owner != *owner_info.key {
+            return Err(TokenError::OwnerMismatch.into());
+        }
+
+        let pda_stake_pool_info = next_account_info(account_info_iter)?; // 2
+        let pda_pool_token_account_authority_info = next_account_info(account_info_iter)?; // 3
+        let pda_pool_token_account_staked_info = next_account_info(account_info_iter)?; // 4
+        let pda_pool_token_account_reward_info = next_account_info(account_info_iter)?; // 5
+        let pda_boost_token_account_info = next_account_info(account_info_iter)?; // 6
+        let pda_user_state_info = next_account_info(account_info_iter)?; // 7
+        let reward_destination_info = next_account_info(account_info_iter)?; // 8
+
+        let clock_info = next_account_info(account_info_iter)?; // 9
+        validate_clock_sysvar(clock_info)?;
+        let clock = &Clock::from_account_info(clock_info)?;
+
+        let token_program_info = next_account_info(account_info_iter)?; // 10
+
+        validate_user_state(
+            pda_user_state_info,
+            pda_stake_pool_info,
+            token_account_info,
+        )?;
+        validate_pool_token_account(
+            pda_pool_token_account_staked_info,
+        )?;
+        validate_pool_token_account(
+            pda_pool_token_account_reward_info,
+        )?;
+
+        let mut stake_pool = StakePool::unpack(&pda_stake_pool_info.data.borrow())?;
+        Self::validate_boost_token_account(
+            &stake_pool,
+            pda_boost_token_account_info,
+        )?;
+        validate_staked_token_account(
+            &stake_pool,
+            pda_pool_token_account_staked_info,
+            &token_account.mint,
+        )?;
+
+        let reward_destination = TokenAccount::unpack(
+            &reward_destination_info.data.borrow(),
+        )?;
+        if reward_destination.owner != *owner_info.key {
+            return Err(TokenError::OwnerMismatch.into());
+        }
+
+        let mut user_data = UserInfo::from_account_info(pda_user_state_info)?;
+        user_data.check_not_frozen()?;
+
+        let pda_pool_token_account_staked = TokenAccount::unpack(
+            &pda_pool_token_account_staked_info.data.borrow(),
+        )?;
+        let pda_pool_token_account_reward = TokenAccount::unpack(
+            &pda_pool_token_account_reward_info.data.borrow(),
+        )?;
+        let pda_boost_token_account = TokenAccount::unpack(
+            &pda_boost_token_account_info.data.borrow(),
+        )?;
+
+        stake_pool.update_pool(
+            &pda_pool_token_account_staked,
+            &pda_pool_token_account_reward,
+            clock,
+        )?;
+
+        let shares = stake_pool.get_position_shares(&token_account.mint, &user_data)?;
+        user_data.settle_boost(&stake_pool, shares, shares)?;
+
+        // An empty boost vault pays nothing, the rest stays unclaimed. Rewards of the reward vault aren't affected
+        let amount = user_data.boost_unclaimed.min(pda_boost_token_account.amount);
+        if amount > 0 {
+            let sign_seeds_pda_pool_token_account_authority = seeds::token_account_authority();
+
+            invoke_signed(
+                &spl_token::instruction::transfer(
+                    &spl_token::id(),
+                    pda_boost_token_account_info.key,
+                    reward_destination_info.key,
+                    pda_pool_token_account_authority_info.key,
+                    &[pda_pool_token_account_authority_info.key],
+                    amount,
+                )?,
+                &[
+                pda_boost_token_account_info.clone(),
+                reward_destination_info.clone(),
+                pda_pool_token_account_authority_info.clone(),
+                token_program_info.clone(),
+                ],
+                &[&sign_seeds_pda_pool_token_account_authority]
+            )?;
+
+            user_data.boost_unclaimed -= amount;
+            stake_pool.decrease_boost_owed(amount);
+        }
+
+        emit(&BoostClaimed {
+            pool_index: stake_pool.pool_index,
+            token_account: *token_account_info.key,
+            amount,
+            unclaimed: user_data.boost_unclaimed,
+        })?;
+
+        user_data.serialize(&mut &mut pda_user_state_info.data.borrow_mut()[..])?;
+
+        StakePool::pack(stake_pool, &mut pda_stake_pool_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    pub fn process_set_accrual_mode(
+        accounts: &[AccountInfo],
+        accrual_mode: u8,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let pool_owner_info = next_account_info(account_info_iter)?; // 0
+        if !pool_owner_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let mint_info = next_account_info(account_info_iter)?; // 1
+        let pda_stake_pool_info = next_account_info(account_info_iter)?; // 2
+        let mut stake_pool = StakePool::unpack(&pda_stake_pool_info.data.borrow_mut())
+            .expect("Failed to deserialie StakePool");
+
+        validate_stake_pool(
+            &stake_pool,
+            pool_owner_info.key,
+            mint_info.key,
+        )?;
+
+        let instructions_info = next_account_info(account_info_iter)?; // 3
+        validate_not_cpi(instructions_info)?;
+
+        if accrual_mode != ACCRUAL_MODE_CLASSIC && accrual_mode != ACCRUAL_MODE_TIME_WEIGHTED {
+            StakingError::InvalidAccrualMode.print::<StakingError>();
+            return Err(StakingError::InvalidAccrualMode.into());
+        }
+        // Shares of existing positions were computed in the old mode
+        if stake_pool.total_staked != 0 {
+            StakingError::PoolNotEmpty.print::<StakingError>();
+            return Err(StakingError::PoolNotEmpty.into());
+        }
+
+        emit_config_changed(
+            stake_pool.pool_index,
+            ConfigField::AccrualMode,
+            stake_pool.accrual_mode as u64,
+            accrual_mode as u64,
+        )?;
+
+        stake_pool.accrual_mode = accrual_mode;
+
+        StakePool::pack(stake_pool, &mut pda_stake_pool_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    pub fn process_close_pool(
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let pool_owner_info = next_account_info(account_info_iter)?; // 0
+        if !pool_owner_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let mint_info = next_account_info(account_info_iter)?; // 1
+        let pda_stake_pool_info = next_account_info(account_info_iter)?; // 2
+        let pda_pool_token_account_authority_info = next_account_info(account_info_iter)?; // 3
+        let pda_pool_token_account_staked_info = next_account_info(account_info_iter)?; // 4
+        let pda_pool_token_account_reward_info = next_account_info(account_info_iter)?; // 5
+        let owner_token_account_info = next_account_info(account_info_iter)?; // 6
+        let pda_wallet_pool_info = next_account_info(account_info_iter)?; // 7
+
+        let clock_info = next_account_info(account_info_iter)?; // 8
+        validate_clock_sysvar(clock_info)?;
+        let clock = &Clock::from_account_info(clock_info)?;
+
+        let system_program_info = next_account_info(account_info_iter)?; // 9
+        let token_program_info = next_account_info(account_info_iter)?; // 10
+
+        let instructions_info = next_account_info(account_info_iter)?; // 11
+        validate_not_cpi(instructions_info)?;
+
+        let stake_pool = StakePool::unpack(&pda_stake_pool_info.data.borrow())?;
+
+        validate_stake_pool(
+            &stake_pool,
+            pool_owner_info.key,
+            mint_info.key,
+        )?;
+
+        // Every position has withdrawn, so nobody is owed anything. Vaults of other stake mints
+        // and unclaimed boosts aren't tracked by total_staked, so those pools don't close
+        if clock.slot < stake_pool.end_block
+            || stake_pool.total_staked != 0
+            || stake_pool.receipt_supply != 0
+            || stake_pool.has_stake_mints()
+            || stake_pool.has_boost_vault()
+        {
+            StakingError::CloseNotAllowed.print::<StakingError>();
+            return Err(StakingError::CloseNotAllowed.into());
+        }
+
+        let pool_index = stake_pool.pool_index;
+        if *pda_pool_token_account_staked_info.key != staked_token_account_address(pool_index).0
+            && *pda_pool_token_account_staked_info.key != stake_mint_token_account_address(pool_index, mint_info.key).0
+        {
+            StakingError::PoolTokenAccountMissmatch.print::<StakingError>();
+            return Err(StakingError::PoolTokenAccountMissmatch.into());
+        }
+        if *pda_pool_token_account_reward_info.key != reward_token_account_address(pool_index).0
+            && *pda_pool_token_account_reward_info.key != migrated_reward_token_account_address(pool_index, mint_info.key).0
+        {
+            StakingError::PoolTokenAccountMissmatch.print::<StakingError>();
+            return Err(StakingError::PoolTokenAccountMissmatch.into());
+        }
+        let (pda_wallet_pool_pubkey, bump_seed_wallet_pool) = wallet_pool_address(pool_index);
+        if pda_wallet_pool_pubkey != *pda_wallet_pool_info.key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+
+        let owner_lamports_before = pool_owner_info.lamports();
+
+        let sign_seeds_pda_pool_token_account_authority = seeds::token_account_authority();
+
+        let mut swept_amount = 0_u64;
+        for &vault_info in [pda_pool_token_account_staked_info, pda_pool_token_account_reward_info].iter() {
+            let vault = TokenAccount::unpack(&vault_info.data.borrow())?;
+            if vault.amount > 0 {
+                invoke_signed(
+                    &spl_token::instruction::transfer(
+                        &spl_token::id(),
+                        vault_info.key,
+                        owner_token_account_info.key,
+                        pda_pool_token_account_authority_info.key,
+                        &[pda_pool_token_account_authority_info.key],
+                        vault.amount,
+                    )?,
+                    &[
+                    vault_info.clone(),
+                    owner_token_account_info.clone(),
+                    pda_pool_token_account_authority_info.clone(),
+                    token_program_info.clone(),
+                    ],
+                    &[&sign_seeds_pda_pool_token_account_authority],
+                )?;
+                swept_amount = swept_amount
+                    .checked_add(vault.amount)
+                    .ok_or(StakingError::Overflow)?;
+            }
+
+            invoke_signed(
+                &spl_token::instruction::close_account(
+                    &spl_token::id(),
+                    vault_info.key,
+                    pool_owner_info.key,
+                    pda_pool_token_account_authority_info.key,
+                    &[pda_pool_token_account_authority_info.key],
+                )?,
+                &[
+                vault_info.clone(),
+                pool_owner_info.clone(),
+                pda_pool_token_account_authority_info.clone(),
+                token_program_info.clone(),
+                ],
+                &[&sign_seeds_pda_pool_token_account_authority],
+            )?;
+        }
+
+        // Rent of closed UserInfo went back to the wallet
+        let pool_index_seed = pool_index.to_le_bytes();
+        let bump_seed_wallet_pool = [bump_seed_wallet_pool];
+        let sign_seeds_pda_wallet_pool = seeds::with_bump(
+            &seeds::wallet_pool(&pool_index_seed),
+            &bump_seed_wallet_pool,
+        );
+
+        invoke_signed(
+            &system_instruction::transfer(
+                pda_wallet_pool_info.key,
+                pool_owner_info.key,
+                pda_wallet_pool_info.lamports(),
+            ),
+            &[pda_wallet_pool_info.clone(), pool_owner_info.clone(), system_program_info.clone()],
+            &[&sign_seeds_pda_wallet_pool],
+        )?;
+
+        // Last, the state itself. Drained program account is removed by the runtime after the transaction
+        let stake_pool_lamports = pda_stake_pool_info.lamports();
+        **pool_owner_info.lamports.borrow_mut() = pool_owner_info
+            .lamports()
+            .checked_add(stake_pool_lamports)
+            .ok_or(StakingError::Overflow)?;
+        **pda_stake_pool_info.lamports.borrow_mut() = 0;
+
+        pda_stake_pool_info.data.borrow_mut().fill(0);
+
+        emit(&PoolClosed {
+            pool_index,
+            swept_amount,
+            reclaimed_lamports: pool_owner_info.lamports().saturating_sub(owner_lamports_before),
+        })?;
+
+        Ok(())
+    }
+
+    fn validate_boost_token_account(
+        stake_pool: &StakePool,
+        pda_boost_token_account_info: &AccountInfo,
+    ) -> ProgramResult {
+        if !stake_pool.has_boost_vault() {
+            StakingError::NoBoostVault.print::<StakingError>();
+            return Err(StakingError::NoBoostVault.into());
+        }
+        if boost_token_account_address(stake_pool.pool_index, &stake_pool.mint).0 != *pda_boost_token_account_info.key {
+            StakingError::PoolTokenAccountMissmatch.print::<StakingError>();
+            return Err(StakingError::PoolTokenAccountMissmatch.into());
+        }
+
+        Ok(())
+    }
+
+    pub fn process_log_bonus_preview(
+        accounts: &[AccountInfo],
+        bonus_multiplier: u8,
+        bonus_start_block: u64,
+        bonus_end_block: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let pda_stake_pool_info = next_account_info(account_info_iter)?; // 0
+        let stake_pool = StakePool::unpack(&pda_stake_pool_info.data.borrow())?;
+
+        let pda_pool_token_account_staked_info = next_account_info(account_info_iter)?; // 1
+        validate_pool_token_account(
+            pda_pool_token_account_staked_info,
+        )?;
+        let pda_pool_token_account_staked = TokenAccount::unpack(
+            &pda_pool_token_account_staked_info.data.borrow(),
+        )?;
+
+        let clock_info = next_account_info(account_info_iter)?; // 2
+        validate_clock_sysvar(clock_info)?;
+        let clock = &Clock::from_account_info(clock_info)?;
+
+        let pda_pool_token_account_reward_info = next_account_info(account_info_iter)?; // 3
+        validate_pool_token_account(
+            pda_pool_token_account_reward_info,
+        )?;
+        let pda_pool_token_account_reward = TokenAccount::unpack(
+            &pda_pool_token_account_reward_info.data.borrow(),
+        )?;
+
+        // Same steps as SetBonusTime on a copy, StakePool isn't written
+        let mut preview = stake_pool;
+        preview.update_pool(
+            &pda_pool_token_account_staked,
+            &pda_pool_token_account_reward,
+            clock,
+        )?;
+
+        let feasible = Self::apply_bonus_time(
+            &mut preview,
+            pda_pool_token_account_reward.amount,
+            clock.slot,
+            bonus_multiplier,
+            bonus_start_block,
+            bonus_end_block,
+        ).is_ok();
+
+        emit(&BonusPreview {
+            pool_index: stake_pool.pool_index,
+            slot: clock.slot,
+            bonus_multiplier,
+            bonus_start_block,
+            bonus_end_block: if feasible { preview.bonus_end_block.unwrap_or(0) } else { bonus_end_block },
+            end_block: if feasible { preview.end_block } else { stake_pool.end_block },
+            feasible,
+        })
+    }
+
+    pub fn process_migrate_user_info(
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let payer_info = next_account_info(account_info_iter)?; // 0
+        if !payer_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let pda_stake_pool_info = next_account_info(account_info_iter)?; // 1
+        if *pda_stake_pool_info.owner != this_program_id() {
+            StakingError::NotInitialized.print::<StakingError>();
+            return Err(StakingError::NotInitialized.into());
+        }
+        let mut stake_pool = StakePool::unpack(&pda_stake_pool_info.data.borrow())?;
+
+        let pda_user_state_info = next_account_info(account_info_iter)?; // 2
+
+        let rent_info = next_account_info(account_info_iter)?; // 3
+        validate_rent_sysvar(rent_info)?;
+        let rent = &Rent::from_account_info(rent_info)?;
+
+        let system_program_info = next_account_info(account_info_iter)?; // 4
+
+        let data_len = pda_user_state_info.data_len();
+        if *pda_user_state_info.owner != this_program_id() || data_len < 32 {
+            StakingError::InvalidUserInfo.print::<StakingError>();
+            return Err(StakingError::InvalidUserInfo.into());
+        }
+
+        // Every layout starts with the token-account, one of the seeds of PDA UserInfo
+        let token_account_id = {
+            let data = pda_user_state_info.data.borrow();
+            Pubkey::new_from_array(*array_ref![data, 0, 32])
+        };
+        let (pda_user_state_pubkey, _) = user_info_address(
+            pda_stake_pool_info.key,
+            &token_account_id,
+        );
+        if pda_user_state_pubkey != *pda_user_state_info.key {
+            StakingError::UserInfoMissmatch.print::<StakingError>();
+            return Err(StakingError::UserInfoMissmatch.into());
+        }
+
+        if data_len == USER_INFO_LEN {
+            msg!("UserInfo {} is up to date", pda_user_state_info.key);
+            return Ok(());
+        }
+
+        let user_data = UserInfo::from_legacy_slice(
+            &pda_user_state_info.data.borrow(),
+            stake_pool.precision_factor_rank,
+        )?;
+
+        let lamports = rent
+            .minimum_balance(USER_INFO_LEN)
+            .saturating_sub(pda_user_state_info.lamports());
+        if lamports > 0 {
+            invoke(
+                &system_instruction::transfer(
+                    payer_info.key,
+                    pda_user_state_info.key,
+                    lamports,
+                ),
+                &[payer_info.clone(), pda_user_state_info.clone(), system_program_info.clone()],
+            )?;
+        }
+
+        pda_user_state_info.realloc(USER_INFO_LEN, true)?;
+        user_data.serialize(&mut &mut pda_user_state_info.data.borrow_mut()[..])?;
+
+        // Layouts after the first were written with stakers counting them
+        if data_len == LEGACY_USER_INFO_LEN {
+            stake_pool.update_stakers(0, user_data.amount)?;
+            StakePool::pack(stake_pool, &mut pda_stake_pool_info.data.borrow_mut())?;
+        }
+
+        msg!("UserInfo {} migrated from {} bytes", pda_user_state_info.key, data_len);
+
+        Ok(())
+    }
+
+    pub fn process_set_emergency_withdraw_enabled(
+        accounts: &[AccountInfo],
+        enabled: bool,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let pool_owner_info = next_account_info(account_info_iter)?; // 0
+        if !pool_owner_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let mint_info = next_account_info(account_info_iter)?; // 1
+        let pda_stake_pool_info = next_account_info(account_info_iter)?; // 2
+        let mut stake_pool = StakePool::unpack(&pda_stake_pool_info.data.borrow_mut())
+            .expect("Failed to deserialie StakePool");
+
+        validate_stake_pool(
+            &stake_pool,
+            pool_owner_info.key,
+            mint_info.key,
+        )?;
+
+        let clock_info = next_account_info(account_info_iter)?; // 3
+        validate_clock_sysvar(clock_info)?;
+        let clock = &Clock::from_account_info(clock_info)?;
+
+        let instructions_info = next_account_info(account_info_iter)?; // 4
+        validate_not_cpi(instructions_info)?;
+
+        let old_enabled = stake_pool.emergency_withdraw_enabled;
+        if enabled {
+            stake_pool.emergency_withdraw_enabled = 1;
+            stake_pool.emergency_withdraw_disable_slot = 0;
+        }
+        else if old_enabled == 1 {
+            // Disabling again doesn't restart the timelock
+            stake_pool.emergency_withdraw_enabled = 0;
+            stake_pool.emergency_withdraw_disable_slot = clock.slot
+                .checked_add(EMERGENCY_WITHDRAW_TIMELOCK_SLOTS)
+                .ok_or(StakingError::Overflow)?;
+            msg!("EmergencyWithdraw is disabled from slot {}", stake_pool.emergency_withdraw_disable_slot);
+        }
+
+        emit_config_changed(
+            stake_pool.pool_index,
+            ConfigField::EmergencyWithdrawEnabled,
+            old_enabled as u64,
+            stake_pool.emergency_withdraw_enabled as u64,
+        )?;
+
+        StakePool::pack(stake_pool, &mut pda_stake_pool_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    pub fn process_cancel_bonus(
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let pool_owner_info = next_account_info(account_info_iter)?; // 0
+        if !pool_owner_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let mint_info = next_account_info(account_info_iter)?; // 1
+        let pda_stake_pool_info = next_account_info(account_info_iter)?; // 2
+        let mut stake_pool = StakePool::unpack(&pda_stake_pool_info.data.borrow_mut())
+            .expect("Failed to deserialie StakePool");
+
+        validate_stake_pool(
+            &stake_pool,
+            pool_owner_info.key,
+            mint_info.key,
+        )?;
+
+        let clock_info = next_account_info(account_info_iter)?; // 3
+        validate_clock_sysvar(clock_info)?;
+        let clock = &Clock::from_account_info(clock_info)?;
+
+        let instructions_info = next_account_info(account_info_iter)?; // 4
+        validate_not_cpi(instructions_info)?;
+
+        // Blocks accrued with the bonus can't be given back to end_block
+        if !stake_pool.is_bonus_cancellable(clock.slot) {
+            StakingError::BonusNotCancellable.print::<StakingError>();
+            return Err(StakingError::BonusNotCancellable.into());
+        }
+
+        let old_bonus_multiplier = stake_pool.bonus_multiplier.unwrap_or(1) as u64;
+        let old_bonus_start_block = stake_pool.bonus_start_block.unwrap_or(0);
+        let old_bonus_end_block = stake_pool.bonus_end_block.unwrap_or(0);
+        let old_end_block = stake_pool.end_block;
+
+        stake_pool.cancel_bonus();
+        stake_pool.check_funding()?;
+
+        emit_config_changed(stake_pool.pool_index, ConfigField::BonusMultiplier, old_bonus_multiplier, 1)?;
+        emit_config_changed(stake_pool.pool_index, ConfigField::BonusStartBlock, old_bonus_start_block, 0)?;
+        emit_config_changed(stake_pool.pool_index, ConfigField::BonusEndBlock, old_bonus_end_block, 0)?;
+        emit_config_changed(stake_pool.pool_index, ConfigField::EndBlock, old_end_block, stake_pool.end_block)?;
+
+        StakePool::pack(stake_pool, &mut pda_stake_pool_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    pub fn process_set_min_reward_duration(
+        accounts: &[AccountInfo],
+        min_reward_duration: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let pool_owner_info = next_account_info(account_info_iter)?; // 0
+        if !pool_owner_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let mint_info = next_account_info(account_info_iter)?; // 1
+        let pda_stake_pool_info = next_account_info(account_info_iter)?; // 2
+        let mut stake_pool = StakePool::unpack(&pda_stake_pool_info.data.borrow_mut())
+            .expect("Failed to deserialie StakePool");
+
+        validate_stake_pool(
+            &stake_pool,
+            pool_owner_info.key,
+            mint_info.key,
+        )?;
+
+        emit_config_changed(
+            stake_pool.pool_index,
+            ConfigField::MinRewardDuration,
+            stake_pool.min_reward_duration,
+            min_reward_duration,
+        )?;
+
+        stake_pool.min_reward_duration = min_reward_duration;
+
+        StakePool::pack(stake_pool, &mut pda_stake_pool_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    pub fn process_log_pool_summaries(
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let n_pools = accounts.len().saturating_sub(1) / 2;
+        if n_pools == 0 || n_pools > MAX_POOLS_PER_LOG || accounts.len() != 1 + 2 * n_pools {
+            StakingError::InvalidPoolCount.print::<StakingError>();
+            return Err(StakingError::InvalidPoolCount.into());
+        }
+
+        let account_info_iter = &mut accounts.iter();
+
+        let clock_info = next_account_info(account_info_iter)?; // 0
+        validate_clock_sysvar(clock_info)?;
+        let clock = &Clock::from_account_info(clock_info)?;
+
+        let mut summaries = Vec::with_capacity(n_pools);
+        for _ in 0..n_pools {
+            let pda_stake_pool_info = next_account_info(account_info_iter)?; // 1 + 2 * i
+            let pda_pool_token_account_staked_info = next_account_info(account_info_iter)?; // 2 + 2 * i
+
+            if *pda_stake_pool_info.owner != this_program_id()
+                || pda_stake_pool_info.data_len() != StakePool::LEN
+            {
+                StakingError::NotInitialized.print::<StakingError>();
+                return Err(StakingError::NotInitialized.into());
+            }
+            let stake_pool = StakePool::unpack(&pda_stake_pool_info.data.borrow())?;
+
+            let pool_index = stake_pool.pool_index;
+            if *pda_pool_token_account_staked_info.key != staked_token_account_address(pool_index).0
+                && *pda_pool_token_account_staked_info.key != stake_mint_token_account_address(pool_index, &stake_pool.mint).0
+            {
+                StakingError::PoolTokenAccountMissmatch.print::<StakingError>();
+                return Err(StakingError::PoolTokenAccountMissmatch.into());
+            }
+            let pda_pool_token_account_staked = TokenAccount::unpack(
+                &pda_pool_token_account_staked_info.data.borrow(),
+            )?;
+
+            summaries.push(Self::get_pool_summary(
+                &stake_pool,
+                pda_pool_token_account_staked.amount,
+                clock.slot,
+            )?);
+        }
+
+        emit(&PoolSummaries {
+            summaries,
+        })
+    }
+
+    pub fn process_log_user_projected_rewards(
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let pda_stake_pool_info = next_account_info(account_info_iter)?; // 0
+        let pda_user_state_info = next_account_info(account_info_iter)?; // 1
+        let token_account_info = next_account_info(account_info_iter)?; // 2
+        let pda_pool_token_account_staked_info = next_account_info(account_info_iter)?; // 3
+
+        let clock_info = next_account_info(account_info_iter)?; // 4
+        validate_clock_sysvar(clock_info)?;
+        let clock = &Clock::from_account_info(clock_info)?;
+
+        validate_user_state(
+            pda_user_state_info,
+            pda_stake_pool_info,
+            token_account_info,
+        )?;
+        validate_pool_token_account(
+            pda_pool_token_account_staked_info,
+        )?;
+
+        let stake_pool = StakePool::unpack(&pda_stake_pool_info.data.borrow())?;
+        let user_data = UserInfo::from_account_info(pda_user_state_info)?;
+        let token_account = TokenAccount::unpack(
+            &token_account_info.data.borrow(),
+        )?;
+        let pda_pool_token_account_staked = TokenAccount::unpack(
+            &pda_pool_token_account_staked_info.data.borrow(),
+        )?;
+
+        let shares = stake_pool.get_position_shares(&token_account.mint, &user_data)?;
+
+        emit(&UserProjectedRewards {
+            pool_index: stake_pool.pool_index,
+            token_account: *token_account_info.key,
+            slot: clock.slot,
+            shares,
+            staked_supply: stake_pool.get_staked_supply(pda_pool_token_account_staked.amount),
+            remaining_blocks: stake_pool.end_block.saturating_sub(clock.slot),
+            projected: stake_pool.get_projected_reward(
+                shares,
+                pda_pool_token_account_staked.amount,
+                clock.slot,
+            )?,
+        })
+    }
+
+    pub fn process_reclaim_rewards(
+        accounts: &[AccountInfo],
+        amount: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let pool_owner_info = next_account_info(account_info_iter)?; // 0
+        if !pool_owner_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let mint_info = next_account_info(account_info_iter)?; // 1
+        let pda_stake_pool_info = next_account_info(account_info_iter)?; // 2
+        let pda_pool_token_account_authority_info = next_account_info(account_info_iter)?; // 3
+        let pda_pool_token_account_staked_info = next_account_info(account_info_iter)?; // 4
+        let pda_pool_token_account_reward_info = next_account_info(account_info_iter)?; // 5
+        let owner_token_account_info = next_account_info(account_info_iter)?; // 6
+
+        let clock_info = next_account_info(account_info_iter)?; // 7
+        validate_clock_sysvar(clock_info)?;
+        let clock = &Clock::from_account_info(clock_info)?;
+
+        let token_program_info = next_account_info(account_info_iter)?; // 8
+
+        let mut stake_pool = StakePool::unpack(&pda_stake_pool_info.data.borrow_mut())
+            .expect("Failed to deserialie StakePool");
+
+        validate_stake_pool(
+            &stake_pool,
+            pool_owner_info.key,
+            mint_info.key,
+        )?;
+
+        let pool_index = stake_pool.pool_index;
+        if *pda_pool_token_account_staked_info.key != staked_token_account_address(pool_index).0
+            && *pda_pool_token_account_staked_info.key != stake_mint_token_account_address(pool_index, mint_info.key).0
+        {
+            StakingError::PoolTokenAccountMissmatch.print::<StakingError>();
+            return Err(StakingError::PoolTokenAccountMissmatch.into());
+        }
+        if *pda_pool_token_account_reward_info.key != reward_token_account_address(pool_index).0
+            && *pda_pool_token_account_reward_info.key != migrated_reward_token_account_address(pool_index, mint_info.key).0
+        {
+            StakingError::PoolTokenAccountMissmatch.print::<StakingError>();
+            return Err(StakingError::PoolTokenAccountMissmatch.into());
+        }
+
+        let pda_pool_token_account_staked = TokenAccount::unpack(
+            &pda_pool_token_account_staked_info.data.borrow(),
+        )?;
+        let pda_pool_token_account_reward = TokenAccount::unpack(
+            &pda_pool_token_account_reward_info.data.borrow(),
+        )?;
+
+        // total_owed must include every block till now
+        stake_pool.update_pool(
+            &pda_pool_token_account_staked,
+            &pda_pool_token_account_reward,
+            clock,
+        )?;
+
+        let surplus = stake_pool.get_reward_surplus(pda_pool_token_account_reward.amount)?;
+        if amount > surplus {
+            msg!("Reward vault has {} not owed to stakers, {} requested", surplus, amount);
+            StakingError::RewardsStillOwed.print::<StakingError>();
+            return Err(StakingError::RewardsStillOwed.into());
+        }
+
+        let sign_seeds_pda_pool_token_account_authority = seeds::token_account_authority();
+
+        invoke_signed(
+            &spl_token::instruction::transfer(
+                &spl_token::id(),
+                pda_pool_token_account_reward_info.key,
+                owner_token_account_info.key,
+                pda_pool_token_account_authority_info.key,
+                &[pda_pool_token_account_authority_info.key],
+                amount,
+            )?,
+            &[
+            pda_pool_token_account_reward_info.clone(),
+            owner_token_account_info.clone(),
+            pda_pool_token_account_authority_info.clone(),
+            token_program_info.clone(),
+            ],
+            &[&sign_seeds_pda_pool_token_account_authority],
+        )?;
+
+        emit(&RewardsReclaimed {
+            pool_index,
+            amount,
+            total_owed: stake_pool.total_owed,
+        })?;
+
+        StakePool::pack(stake_pool, &mut pda_stake_pool_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    pub fn process_deposit_with_ata(
+        accounts: &[AccountInfo],
+        amount: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let owner_info = next_account_info(account_info_iter)?; // 0
+        if !owner_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let ata_info = next_account_info(account_info_iter)?; // 1
+        let mint_info = next_account_info(account_info_iter)?; // 2
+        let source_token_account_info = next_account_info(account_info_iter)?; // 3
+        let ata_program_info = next_account_info(account_info_iter)?; // 4
+
+        // Accounts of Deposit from 3 on
+        if accounts.len() < 15 {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        }
+        let rent_info = &accounts[11];
+        let system_program_info = &accounts[13];
+        let token_program_info = &accounts[14];
+
+        if associated_token_account_address(owner_info.key, mint_info.key)? != *ata_info.key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        if associated_token_program_id()? != *ata_program_info.key {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        if ata_info.data_is_empty() {
+            msg!("Creating associated token-account {}", ata_info.key);
+
+            invoke(
+                &Instruction {
+                    program_id: *ata_program_info.key,
+                    accounts: vec![
+                        AccountMeta::new(*owner_info.key, true),
+                        AccountMeta::new(*ata_info.key, false),
+                        AccountMeta::new_readonly(*owner_info.key, false),
+                        AccountMeta::new_readonly(*mint_info.key, false),
+                        AccountMeta::new_readonly(*system_program_info.key, false),
+                        AccountMeta::new_readonly(*token_program_info.key, false),
+                        AccountMeta::new_readonly(*rent_info.key, false),
+                    ],
+                    data: vec![],
+                },
+                &[
+                owner_info.clone(),
+                ata_info.clone(),
+                mint_info.clone(),
+                system_program_info.clone(),
+                token_program_info.clone(),
+                rent_info.clone(),
+                ata_program_info.clone(),
+                ],
+            )?;
+        }
+
+        // The position is keyed by the ATA, tokens can come from any token-account of the owner
+        if source_token_account_info.key != ata_info.key {
+            invoke(
+                &spl_token::instruction::transfer(
+                    &spl_token::id(),
+                    source_token_account_info.key,
+                    ata_info.key,
+                    owner_info.key,
+                    &[owner_info.key],
+                    amount,
+                )?,
+                &[
+                source_token_account_info.clone(),
+                ata_info.clone(),
+                owner_info.clone(),
+                token_program_info.clone(),
+                ],
+            )?;
+        }
+
+        let deposit_accounts: Vec<AccountInfo> = [owner_info, ata_info, mint_info]
+            .iter()
+            .map(|&account_info| account_info.clone())
+            .chain(accounts[5..].iter().cloned())
+            .collect();
+
+        Self::process_deposit(
+            &deposit_accounts,
+            amount,
+        )
+    }
+
+    pub fn process_set_max_claim_per_tx(
+        accounts: &[AccountInfo],
+        max_claim_per_tx: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let pool_owner_info = next_account_info(account_info_iter)?; // 0
+        if !pool_owner_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let mint_info = next_account_info(account_info_iter)?; // 1
+        let pda_stake_pool_info = next_account_info(account_info_iter)?; // 2
+        let mut stake_pool = StakePool::unpack(&pda_stake_pool_info.data.borrow_mut())
+            .expect("Failed to deserialie StakePool");
+
+        validate_stake_pool(
+            &stake_pool,
+            pool_owner_info.key,
+            mint_info.key,
+        )?;
+
+        emit_config_changed(
+            stake_pool.pool_index,
+            ConfigField::MaxClaimPerTx,
+            stake_pool.max_claim_per_tx,
+            max_claim_per_tx,
+        )?;
+
+        stake_pool.max_claim_per_tx = max_claim_per_tx;
+
+        StakePool::pack(stake_pool, &mut pda_stake_pool_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    pub fn process_reinit_pool_state(
+        accounts: &[AccountInfo],
+        params: ReinitPoolStateParams,
+    ) -> ProgramResult {
+        let ReinitPoolStateParams {
+            pool_index,
+            owner,
+            start_block,
+            end_block,
+            reward_per_block,
+            emission_period,
+            min_compound_interval,
+            round_up_rewards,
+            max_stake_per_user,
+            accrued_token_per_share,
+            last_reward_block,
+            total_staked,
+            total_shares,
+            total_owed,
+            pool_name,
+            project_link,
+            theme_id,
+            tags,
+            stakers,
+        } = params;
+
+        let account_info_iter = &mut accounts.iter();
+
+        let upgrade_authority_info = next_account_info(account_info_iter)?; // 0
+        let program_data_info = next_account_info(account_info_iter)?; // 1
+        validate_upgrade_authority(program_data_info, upgrade_authority_info)?;
+
+        let pda_master_staking_info = next_account_info(account_info_iter)?; // 2
+        if master_staking_address()? != *pda_master_staking_info.key {
+            StakingError::MasterStakingMissmatch.print::<StakingError>();
+            return Err(StakingError::MasterStakingMissmatch.into());
+        }
+        let master_staking = MasterStaking::from_account_info(pda_master_staking_info)?;
+        if pool_index >= master_staking.pool_counter {
+            msg!("Pool {} was never initialized", pool_index);
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let pda_stake_pool_info = next_account_info(account_info_iter)?; // 3
+        let (pda_stake_pool_pubkey, bump_seed_stake_pool) = stake_pool_address(pool_index);
+        if pda_stake_pool_pubkey != *pda_stake_pool_info.key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+
+        // Only a lost or corrupt state is rebuilt, an intact StakePool is changed by its owner
+        let recreate = pda_stake_pool_info.data_is_empty();
+        if !recreate && (
+            *pda_stake_pool_info.owner != this_program_id()
+                || pda_stake_pool_info.data_len() != StakePool::LEN
+                || StakePool::unpack(&pda_stake_pool_info.data.borrow()).is_ok()
+        ) {
+            StakingError::ReinitNotAllowed.print::<StakingError>();
+            return Err(StakingError::ReinitNotAllowed.into());
+        }
+
+        let mint_info = next_account_info(account_info_iter)?; // 4
+        let mint = TokenMint::unpack(&mint_info.data.borrow())?;
+
+        let pda_pool_token_account_authority_info = next_account_info(account_info_iter)?; // 5
+        if token_account_authority_address()? != *pda_pool_token_account_authority_info.key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+
+        let pda_pool_token_account_staked_info = next_account_info(account_info_iter)?; // 6
+        let pda_pool_token_account_reward_info = next_account_info(account_info_iter)?; // 7
+
+        // Vaults are the initial ones or the ones of a MigrateRewardMint
+        if *pda_pool_token_account_staked_info.key != staked_token_account_address(pool_index).0
+            && *pda_pool_token_account_staked_info.key != stake_mint_token_account_address(pool_index, mint_info.key).0
+        {
+            StakingError::PoolTokenAccountMissmatch.print::<StakingError>();
+            return Err(StakingError::PoolTokenAccountMissmatch.into());
+        }
+        if *pda_pool_token_account_reward_info.key != reward_token_account_address(pool_index).0
+            && *pda_pool_token_account_reward_info.key != migrated_reward_token_account_address(pool_index, mint_info.key).0
+        {
+            StakingError::PoolTokenAccountMissmatch.print::<StakingError>();
+            return Err(StakingError::PoolTokenAccountMissmatch.into());
+        }
+
+        let pda_pool_token_account_staked = TokenAccount::unpack(
+            &pda_pool_token_account_staked_info.data.borrow(),
+        )?;
+        let pda_pool_token_account_reward = TokenAccount::unpack(
+            &pda_pool_token_account_reward_info.data.borrow(),
+        )?;
+        for vault in [&pda_pool_token_account_staked, &pda_pool_token_account_reward].iter() {
+            if vault.owner != *pda_pool_token_account_authority_info.key || vault.mint != *mint_info.key {
+                StakingError::PoolTokenAccountMissmatch.print::<StakingError>();
+                return Err(StakingError::PoolTokenAccountMissmatch.into());
+            }
+        }
+
+        let pda_wallet_pool_info = next_account_info(account_info_iter)?; // 8
+        if wallet_pool_address(pool_index).0 != *pda_wallet_pool_info.key || pda_wallet_pool_info.lamports() == 0 {
+            return Err(ProgramError::InvalidSeeds);
+        }
+
+        let rent_info = next_account_info(account_info_iter)?; // 9
+        validate_rent_sysvar(rent_info)?;
+        let rent = &Rent::from_account_info(rent_info)?;
+
+        let system_program_info = next_account_info(account_info_iter)?; // 10
+
+        // Optional, pools created without it pay the bonus from the reward vault
+        let has_boost_vault = if let Ok(pda_boost_token_account_info) = next_account_info(account_info_iter) { // 11
+            if boost_token_account_address(pool_index, mint_info.key).0 != *pda_boost_token_account_info.key {
+                StakingError::PoolTokenAccountMissmatch.print::<StakingError>();
+                return Err(StakingError::PoolTokenAccountMissmatch.into());
+            }
+            validate_pool_token_account(pda_boost_token_account_info)?;
+
+            true
+        } else {
+            false
+        };
+
+        if emission_period == 0 {
+            StakingError::InvalidEmissionPeriod.print::<StakingError>();
+            return Err(StakingError::InvalidEmissionPeriod.into());
+        }
+        let duration = end_block
+            .checked_sub(start_block)
+            .ok_or(StakingError::Overflow)?;
+        if last_reward_block > end_block {
+            StakingError::InvalidBlockRange.print::<StakingError>();
+            return Err(StakingError::InvalidBlockRange.into());
+        }
+
+        if tags & !KNOWN_TAGS != 0 {
+            StakingError::InvalidTags.print::<StakingError>();
+            return Err(StakingError::InvalidTags.into());
+        }
+
+        // The vaults hold what the rebuilt state says is staked and owed
+        if total_staked > pda_pool_token_account_staked.amount {
+            msg!(
+                "total_staked {} exceeds the staked vault balance {}",
+                total_staked,
+                pda_pool_token_account_staked.amount,
+            );
+            return Err(ProgramError::InvalidArgument);
+        }
+        if total_owed > pda_pool_token_account_reward.amount {
+            StakingError::InsufficientRewardFunds.print::<StakingError>();
+            return Err(StakingError::InsufficientRewardFunds.into());
+        }
+
+        if mint.decimals >= 21 {
+            StakingError::InvalidMintDecimals.print::<StakingError>();
+            return Err(StakingError::InvalidMintDecimals.into());
+        }
+
+        let precision_factor_rank = 21_u8
+            .checked_sub(mint.decimals as u8)
+            .ok_or(StakingError::Overflow)?;
+
+        let total_reward = reward_per_block
+            .checked_mul(duration)
+            .ok_or(StakingError::Overflow)?
+            .checked_div(emission_period)
+            .ok_or(StakingError::Overflow)?;
+
+        if recreate {
+            msg!("Creating account for StakePool");
+
+            let pool_index_seed = pool_index.to_le_bytes();
+            let bump_seed_stake_pool = [bump_seed_stake_pool];
+            let sign_seeds_pda_state_pool = seeds::with_bump(
+                &seeds::stake_pool(&pool_index_seed),
+                &bump_seed_stake_pool,
+            );
+
+            invoke_signed(
+                &system_instruction::create_account(
+                    upgrade_authority_info.key,
+                    pda_stake_pool_info.key,
+                    rent.minimum_balance(StakePool::LEN),
+                    StakePool::LEN as u64,
+                    &this_program_id(),
+                ),
+                &[upgrade_authority_info.clone(), pda_stake_pool_info.clone(), system_program_info.clone()],
+                &[&sign_seeds_pda_state_pool],
+            )?;
+        }
+
+        let mut reward_per_block_by_token = [0; MAX_REWARD_TOKENS];
+        reward_per_block_by_token[0] = reward_per_block;
+
+        let stake_pool = StakePool {
+            n_reward_tokens: 1,
+            pool_index,
+            owner,
+            mint: *mint_info.key,
+            is_initialized: 1,
+            precision_factor_rank,
+            bonus_multiplier: COption::Some(1),
+            bonus_start_block: COption::None,
+            bonus_end_block: COption::None,
+            last_reward_block,
+            start_block,
+            end_block,
+            total_reward,
+            reward_per_block: reward_per_block_by_token,
+            accrued_token_per_share,
+            pool_name,
+            project_link,
+            theme_id,
+            total_owed,
+            is_solvent: 1,
+            emission_period,
+            min_compound_interval,
+            total_staked,
+            reward_swap_program: COption::None,
+            round_up_rewards,
+            max_total_staked: 0,
+            stake_mints: [Pubkey::default(); MAX_STAKE_MINTS],
+            stake_mint_weights: [0; MAX_STAKE_MINTS],
+            total_shares,
+            in_progress: 0,
+            is_funded: 1,
+            max_stake_per_user,
+            total_funded: total_reward,
+            receipt_supply: 0,
+            warmup_slots: 0,
+            pending_accrual: 0,
+            tags,
+            has_boost_vault: has_boost_vault as u8,
+            accrued_boost_per_share: 0,
+            pending_boost_accrual: 0,
+            boost_owed: 0,
+            accrual_mode: ACCRUAL_MODE_CLASSIC,
+            emergency_withdraw_enabled: 1,
+            emergency_withdraw_disable_slot: 0,
+            pre_bonus_end_block: 0,
+            min_reward_duration: 0,
+            max_claim_per_tx: 0,
+            deposit_count: 0,
+            withdraw_count: 0,
+            lifetime_deposited: 0,
+            lifetime_withdrawn: 0,
+            extra_accrued_token_per_share: [0; MAX_REWARD_TOKENS],
+            extra_pending_accrual: [0; MAX_REWARD_TOKENS],
+            extra_owed: [0; MAX_REWARD_TOKENS],
+            stakers,
+            is_paused: 0,
+            min_stake_amount: 0,
+        };
+
+        StakePool::pack(stake_pool, &mut pda_stake_pool_info.data.borrow_mut())?;
+
+        emit(&PoolStateReinitialized {
+            pool_index,
+            upgrade_authority: *upgrade_authority_info.key,
+            total_staked,
+            total_owed,
+            recreated: recreate,
+        })?;
+
+        Ok(())
+    }
+
+    pub fn process_donate_rewards(
+        accounts: &[AccountInfo],
+        amount: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let donor_info = next_account_info(account_info_iter)?; // 0
+        if !donor_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let donor_token_account_info = next_account_info(account_info_iter)?; // 1
+        let pda_stake_pool_info = next_account_info(account_info_iter)?; // 2
+        let pda_pool_token_account_reward_info = next_account_info(account_info_iter)?; // 3
+
+        let clock_info = next_account_info(account_info_iter)?; // 4
+        validate_clock_sysvar(clock_info)?;
+        let clock = &Clock::from_account_info(clock_info)?;
+
+        let token_program_info = next_account_info(account_info_iter)?; // 5
+
+        if *pda_stake_pool_info.owner != this_program_id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let mut stake_pool = StakePool::unpack(&pda_stake_pool_info.data.borrow())?;
+
+        // Permissionless, so the vault is checked by address and not only by its authority
+        let pool_index = stake_pool.pool_index;
+        if *pda_pool_token_account_reward_info.key != reward_token_account_address(pool_index).0
+            && *pda_pool_token_account_reward_info.key != migrated_reward_token_account_address(pool_index, &stake_pool.mint).0
+        {
+            StakingError::PoolTokenAccountMissmatch.print::<StakingError>();
+            return Err(StakingError::PoolTokenAccountMissmatch.into());
+        }
+
+        if clock.slot >= stake_pool.end_block {
+            StakingError::PoolEnded.print::<StakingError>();
+            return Err(StakingError::PoolEnded.into());
+        }
+
+        // Reward tokens after the first are funded only up to end_block, a longer pool would run
+        // their vaults dry. Their pools, and pools without a reward_per_block, only get a deeper vault
+        let blocks_added = if stake_pool.n_reward_tokens > 1 {
+            0
+        } else {
+            amount
+                .checked_mul(stake_pool.emission_period)
+                .ok_or(StakingError::Overflow)?
+                .checked_div(stake_pool.reward_per_block[0])
+                .unwrap_or(0)
+        };
+
+        invoke(
+            &spl_token::instruction::transfer(
+                &spl_token::id(),
+                donor_token_account_info.key,
+                pda_pool_token_account_reward_info.key,
+                donor_info.key,
+                &[donor_info.key],
+                amount,
+            )?,
+            &[
+            donor_token_account_info.clone(),
+            pda_pool_token_account_reward_info.clone(),
+            donor_info.clone(),
+            token_program_info.clone(),
+            ],
+        )?;
+
+        stake_pool.add_funded(amount)?;
+
+        let old_end_block = stake_pool.end_block;
+        let end_block = old_end_block
+            .checked_add(blocks_added)
+            .ok_or(StakingError::Overflow)?;
+        stake_pool.set_end_block(end_block);
+        // CancelBonus keeps the added blocks
+        if stake_pool.pre_bonus_end_block != 0 {
+            stake_pool.pre_bonus_end_block = stake_pool.pre_bonus_end_block
+                .checked_add(blocks_added)
+                .ok_or(StakingError::Overflow)?;
+        }
+        stake_pool.check_funding()?;
+
+        emit_config_changed(
+            pool_index,
+            ConfigField::EndBlock,
+            old_end_block,
+            end_block,
+        )?;
+        emit(&DonationEvent {
+            pool_index,
+            amount,
+            blocks_added,
+            end_block,
+        })?;
+
+        StakePool::pack(stake_pool, &mut pda_stake_pool_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    pub fn process_harvest(
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let (accounts, protocol_fee_infos) = split_protocol_fee_accounts(accounts)?;
+        let account_info_iter = &mut accounts.iter();
+
+        let owner_info = next_account_info(account_info_iter)?; // 0
+        if !owner_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let token_account_info = next_account_info(account_info_iter)?; // 1
+        let pda_stake_pool_info = next_account_info(account_info_iter)?; // 2
+        let pda_pool_token_account_authority_info = next_account_info(account_info_iter)?; // 3
+        validate_initialized_pool(
+            pda_stake_pool_info,
+            pda_pool_token_account_authority_info,
+        )?;
+
+        let pda_pool_token_account_staked_info = next_account_info(account_info_iter)?; // 4
+        let pda_pool_token_account_reward_info = next_account_info(account_info_iter)?; // 5
+        let pda_user_state_info = next_account_info(account_info_iter)?; // 6
+
+        let clock_info = next_account_info(account_info_iter)?; // 7
+        validate_clock_sysvar(clock_info)?;
+        let clock = &Clock::from_account_info(clock_info)?;
+
+        let token_program_info = next_account_info(account_info_iter)?; // 8
+
+        let token_account = unpack_destination_token_account(token_account_info)?;
+        if token_account.owner != *owner_info.key {
+            return Err(TokenError::OwnerMismatch.into());
+        }
+        validate_user_token_account(
+            token_account_info,
+            &[pda_pool_token_account_staked_info, pda_pool_token_account_reward_info],
+        )?;
+        validate_user_state(
+            pda_user_state_info,
+            pda_stake_pool_info,
+            token_account_info,
+        )?;
+
+        let mut stake_pool = StakePool::unpack(&pda_stake_pool_info.data.borrow())?;
+
+        let pool_index = stake_pool.pool_index;
+        if *pda_pool_token_account_reward_info.key != reward_token_account_address(pool_index).0
+            && *pda_pool_token_account_reward_info.key != migrated_reward_token_account_address(pool_index, &stake_pool.mint).0
+        {
+            StakingError::PoolTokenAccountMissmatch.print::<StakingError>();
+            return Err(StakingError::PoolTokenAccountMissmatch.into());
+        }
+        validate_staked_token_account(
+            &stake_pool,
+            pda_pool_token_account_staked_info,
+            &token_account.mint,
+        )?;
+
+        let reward_destination_info = if token_account.mint == stake_pool.mint {
+            token_account_info
+        } else {
+            next_account_info(account_info_iter)? // 9
+        };
+        let reward_destination = TokenAccount::unpack(
+            &reward_destination_info.data.borrow(),
+        )?;
+        if reward_destination.owner != *owner_info.key {
+            return Err(TokenError::OwnerMismatch.into());
+        }
+
+        let mut user_data = UserInfo::from_account_info(pda_user_state_info)?;
+        user_data.check_not_frozen()?;
+
+        let pda_pool_token_account_staked = TokenAccount::unpack(
+            &pda_pool_token_account_staked_info.data.borrow(),
+        )?;
+        let pda_pool_token_account_reward = TokenAccount::unpack(
+            &pda_pool_token_account_reward_info.data.borrow(),
+        )?;
+
+        stake_pool.update_pool(
+            &pda_pool_token_account_staked,
+            &pda_pool_token_account_reward,
+            clock,
+        )?;
+
+        let current_shares = stake_pool.get_position_shares(&token_account.mint, &user_data)?;
+        let pending = get_pending(
+            current_shares,
+            stake_pool.accrued_token_per_share,
+            stake_pool.precision_factor_rank,
+            user_data.reward_debt,
+            stake_pool.rounds_up_rewards(),
+        )?;
+        let pending = stake_pool.cap_rounded_up(pending, pda_pool_token_account_reward.amount);
+        if pending == 0 && user_data.pending_owed == 0 {
+            msg!("Nothing to harvest");
+            return Ok(());
+        }
+
+        let earned = stake_pool.get_warmed_up(pending, user_data.deposit_slot, clock.slot)?;
+        let unpaid = get_unpaid(
+            current_shares,
+            stake_pool.accrued_token_per_share,
+            stake_pool.precision_factor_rank,
+            user_data.reward_debt,
+            pending,
+        )?;
+        let mut reward_paid = 0;
+        let (claimed, settled) = user_data.take_claim(&stake_pool, pending, earned)?;
+
+        if pending.max(claimed) > pda_pool_token_account_reward.amount {
+            StakingError::InsufficientRewardFunds.print::<StakingError>();
+            return Err(StakingError::InsufficientRewardFunds.into());
+        }
+
+        if claimed > 0 {
+            let claimed = Self::pay_protocol_fee(
+                protocol_fee_infos,
+                &stake_pool,
+                pda_pool_token_account_reward_info,
+                pda_pool_token_account_authority_info,
+                token_program_info,
+                claimed,
+            )?;
+            reward_paid = claimed;
+
+            let sign_seeds_pda_pool_token_account_authority = seeds::token_account_authority();
+
+            invoke_signed(
+                &spl_token::instruction::transfer(
+                    &spl_token::id(),
+                    pda_pool_token_account_reward_info.key,
+                    reward_destination_info.key,
+                    pda_pool_token_account_authority_info.key,
+                    &[pda_pool_token_account_authority_info.key],
+                    claimed,
+                )?,
+                &[
+                pda_pool_token_account_reward_info.clone(),
+                reward_destination_info.clone(),
+                pda_pool_token_account_authority_info.clone(),
+                token_program_info.clone(),
+                ],
+                &[&sign_seeds_pda_pool_token_account_authority]
+            )?;
+        }
+
+        // The part lost to the warmup stays in the reward vault
+        stake_pool.decrease_owed(settled)?;
+
+        // Loyalty weight may have grown since the last change of the position
+        let shares = stake_pool.get_weighted_shares(
+            &token_account.mint,
+            user_data.amount,
+            user_data.stake_start_slot,
+            clock.slot,
+        )?;
+        stake_pool.update_total_shares(current_shares, shares)?;
+        user_data.shares = shares;
+
+        user_data.settle_boost(&stake_pool, current_shares, shares)?;
+        user_data.settle_extra_rewards(&stake_pool, current_shares, shares)?;
+        user_data.set_reward_debt(
+            get_reward_debt(
+                shares,
+                stake_pool.accrued_token_per_share,
+                unpaid,
+            )?
+        );
+
+        emit(&PositionUpdatedEvent {
+            pool_index: stake_pool.pool_index,
+            token_account: *token_account_info.key,
+            new_amount: user_data.amount,
+            reward_paid,
+            slot: clock.slot,
+        })?;
+
+        user_data.serialize(&mut &mut pda_user_state_info.data.borrow_mut()[..])?;
+
+        StakePool::pack(stake_pool, &mut pda_stake_pool_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    pub fn process_set_protocol_fee(
+        accounts: &[AccountInfo],
+        protocol_fee_bps: u16,
+        protocol_fee_recipient: Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let upgrade_authority_info = next_account_info(account_info_iter)?; // 0
+        let program_data_info = next_account_info(account_info_iter)?; // 1
+        validate_upgrade_authority(program_data_info, upgrade_authority_info)?;
+
+        let pda_master_staking_info = next_account_info(account_info_iter)?; // 2
+        if master_staking_address()? != *pda_master_staking_info.key {
+            StakingError::MasterStakingMissmatch.print::<StakingError>();
+            return Err(StakingError::MasterStakingMissmatch.into());
+        }
+
+        let rent_info = next_account_info(account_info_iter)?; // 3
+        validate_rent_sysvar(rent_info)?;
+        let rent = &Rent::from_account_info(rent_info)?;
+
+        let system_program_info = next_account_info(account_info_iter)?; // 4
+
+        if protocol_fee_bps > MAX_PROTOCOL_FEE_BPS {
+            StakingError::ProtocolFeeTooHigh.print::<StakingError>();
+            return Err(StakingError::ProtocolFeeTooHigh.into());
+        }
+
+        let mut master_staking = MasterStaking::from_account_info(pda_master_staking_info)?;
+
+        if pda_master_staking_info.data_len() == LEGACY_MASTER_STAKING_LEN {
+            msg!("Resizing MasterStaking to {} bytes", MASTER_STAKING_LEN);
+
+            let lamports = rent
+                .minimum_balance(MASTER_STAKING_LEN)
+                .saturating_sub(pda_master_staking_info.lamports());
+            if lamports > 0 {
+                invoke(
+                    &system_instruction::transfer(
+                        upgrade_authority_info.key,
+                        pda_master_staking_info.key,
+                        lamports,
+                    ),
+                    &[upgrade_authority_info.clone(), pda_master_staking_info.clone(), system_program_info.clone()],
+                )?;
+            }
+
+            pda_master_staking_info.realloc(MASTER_STAKING_LEN, true)?;
+        }
+
+        emit(&ProtocolFeeChanged {
+            old_bps: master_staking.protocol_fee_bps,
+            new_bps: protocol_fee_bps,
+            recipient: protocol_fee_recipient,
+        })?;
+
+        master_staking.protocol_fee_bps = protocol_fee_bps;
+        master_staking.protocol_fee_recipient = protocol_fee_recipient;
+        master_staking.serialize_into(&mut pda_master_staking_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    pub fn process_claim_reward_token(
+        accounts: &[AccountInfo],
+        reward_index: u8,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let owner_info = next_account_info(account_info_iter)?; // 0
+        if !owner_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let token_account_info = next_account_info(account_info_iter)?; // 1
+        let token_account = unpack_destination_token_account(token_account_info)?;
+        if token_account.owner != *owner_info.key {
+            return Err(TokenError::OwnerMismatch.into());
+        }
+
+        let pda_stake_pool_info = next_account_info(account_info_iter)?; // 2
+        let pda_pool_token_account_authority_info = next_account_info(account_info_iter)?; // 3
+        let pda_pool_token_account_staked_info = next_account_info(account_info_iter)?; // 4
+        let pda_pool_token_account_reward_info = next_account_info(account_info_iter)?; // 5
+        let pda_user_state_info = next_account_info(account_info_iter)?; // 6
+        let pda_reward_token_info = next_account_info(account_info_iter)?; // 7
+        let pda_reward_vault_info = next_account_info(account_info_iter)?; // 8
+        let reward_destination_info = next_account_info(account_info_iter)?; // 9
+
+        let clock_info = next_account_info(account_info_iter)?; // 10
+        validate_clock_sysvar(clock_info)?;
+        let clock = &Clock::from_account_info(clock_info)?;
+
+        let token_program_info = next_account_info(account_info_iter)?; // 11
+
+        validate_user_state(
+            pda_user_state_info,
+            pda_stake_pool_info,
+            token_account_info,
+        )?;
+        validate_pool_token_account(
+            pda_pool_token_account_staked_info,
+        )?;
+        validate_pool_token_account(
+            pda_pool_token_account_reward_info,
+        )?;
+
+        let mut stake_pool = StakePool::unpack(&pda_stake_pool_info.data.borrow())?;
+        validate_staked_token_account(
+            &stake_pool,
+            pda_pool_token_account_staked_info,
+            &token_account.mint,
+        )?;
+
+        // Index 0 has no RewardTokenInfo, so it can't pass this check
+        let reward_token_info = RewardTokenInfo::from_account_info(pda_reward_token_info)?;
+        if reward_token_info_address(stake_pool.pool_index, reward_index).0 != *pda_reward_token_info.key ||
+           reward_token_info.stake_pool != *pda_stake_pool_info.key ||
+           reward_token_info.reward_index != reward_index {
+            StakingError::InvalidRewardTokenInfo.print::<StakingError>();
+            return Err(StakingError::InvalidRewardTokenInfo.into());
+        }
+        if reward_token_info.vault != *pda_reward_vault_info.key {
+            StakingError::PoolTokenAccountMissmatch.print::<StakingError>();
+            return Err(StakingError::PoolTokenAccountMissmatch.into());
+        }
+
+        let reward_destination = TokenAccount::unpack(
+            &reward_destination_info.data.borrow(),
+        )?;
+        if reward_destination.owner != *owner_info.key {
+            return Err(TokenError::OwnerMismatch.into());
+        }
+
+        let mut user_data = UserInfo::from_account_info(pda_user_state_info)?;
+        user_data.check_not_frozen()?;
+
+        let pda_pool_token_account_staked = TokenAccount::unpack(
+            &pda_pool_token_account_staked_info.data.borrow(),
+        )?;
+        let pda_pool_token_account_reward = TokenAccount::unpack(
+            &pda_pool_token_account_reward_info.data.borrow(),
+        )?;
+        let pda_reward_vault = TokenAccount::unpack(
+            &pda_reward_vault_info.data.borrow(),
+        )?;
+
+        stake_pool.update_pool(
+            &pda_pool_token_account_staked,
+            &pda_pool_token_account_reward,
+            clock,
+        )?;
+
+        let shares = stake_pool.get_position_shares(&token_account.mint, &user_data)?;
+        user_data.settle_extra_rewards(&stake_pool, shares, shares)?;
+
+        // An empty vault pays nothing, the rest stays unclaimed. Other reward tokens aren't affected
+        let reward_index = reward_index as usize;
+        let amount = user_data.extra_unclaimed[reward_index].min(pda_reward_vault.amount);
+        if amount > 0 {
+            let sign_seeds_pda_pool_token_account_authority = seeds::token_account_authority();
+
+            invoke_signed(
+                &spl_token::instruction::transfer(
+                    &spl_token::id(),
+                    pda_reward_vault_info.key,
+                    reward_destination_info.key,
+                    pda_pool_token_account_authority_info.key,
+                    &[pda_pool_token_account_authority_info.key],
+                    amount,
+                )?,
+                &[
+                pda_reward_vault_info.clone(),
+                reward_destination_info.clone(),
+                pda_pool_token_account_authority_info.clone(),
+                token_program_info.clone(),
+                ],
+                &[&sign_seeds_pda_pool_token_account_authority],
+            )?;
+
+            user_data.extra_unclaimed[reward_index] -= amount;
+            stake_pool.decrease_extra_owed(reward_index, amount);
+        }
+
+        emit(&RewardTokenClaimed {
+            pool_index: stake_pool.pool_index,
+            reward_index: reward_index as u8,
+            token_account: *token_account_info.key,
+            amount,
+            unclaimed: user_data.extra_unclaimed[reward_index],
+        })?;
+
+        user_data.serialize(&mut &mut pda_user_state_info.data.borrow_mut()[..])?;
+
+        StakePool::pack(stake_pool, &mut pda_stake_pool_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    pub fn process_close_user_info(
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let owner_info = next_account_info(account_info_iter)?; // 0
+        if !owner_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let token_account_info = next_account_info(account_info_iter)?; // 1
+        let token_account = TokenAccount::unpack(
+            &token_account_info.data.borrow(),
+        )?;
+        if token_account.owner != *owner_info.key {
+            return Err(TokenError::OwnerMismatch.into());
+        }
+
+        let pda_stake_pool_info = next_account_info(account_info_iter)?; // 2
+        let pda_user_state_info = next_account_info(account_info_iter)?; // 3
+        let pda_wallet_pool_info = next_account_info(account_info_iter)?; // 4
+
+        validate_user_state(
+            pda_user_state_info,
+            pda_stake_pool_info,
+            token_account_info,
+        )?;
+
+        let stake_pool = StakePool::unpack(&pda_stake_pool_info.data.borrow())?;
+        if wallet_pool_address(stake_pool.pool_index).0 != *pda_wallet_pool_info.key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+
+        let user_data = UserInfo::from_account_info(pda_user_state_info)?;
+        user_data.check_not_frozen()?;
+
+        // Without shares nothing is pending, so only the settled amounts are left to check
+        if user_data.amount != 0 || user_data.shares != 0 {
+            StakingError::PositionNotEmpty.print::<StakingError>();
+            return Err(StakingError::PositionNotEmpty.into());
+        }
+        if !user_data.is_closable() {
+            StakingError::PositionHasRewards.print::<StakingError>();
+            return Err(StakingError::PositionHasRewards.into());
+        }
+
+        Self::close_user_info(
+            pda_user_state_info,
+            pda_wallet_pool_info,
+        )?;
+
+        Ok(())
+    }
+
+    pub fn process_set_paused(
+        accounts: &[AccountInfo],
+        paused: bool,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let pool_owner_info = next_account_info(account_info_iter)?; // 0
+        if !pool_owner_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let mint_info = next_account_info(account_info_iter)?; // 1
+        let pda_stake_pool_info = next_account_info(account_info_iter)?; // 2
+        let mut stake_pool = StakePool::unpack(&pda_stake_pool_info.data.borrow_mut())
+            .expect("Failed to deserialie StakePool");
+
+        validate_stake_pool(
+            &stake_pool,
+            pool_owner_info.key,
+            mint_info.key,
+        )?;
+
+        emit_config_changed(
+            stake_pool.pool_index,
+            ConfigField::Paused,
+            stake_pool.is_paused as u64,
+            paused as u64,
+        )?;
+
+        stake_pool.is_paused = paused as u8;
+
+        StakePool::pack(stake_pool, &mut pda_stake_pool_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    pub fn process_migrate_stake_pool(
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let payer_info = next_account_info(account_info_iter)?; // 0
+        if !payer_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let pda_stake_pool_info = next_account_info(account_info_iter)?; // 1
+        if *pda_stake_pool_info.owner != this_program_id() {
+            StakingError::NotInitialized.print::<StakingError>();
+            return Err(StakingError::NotInitialized.into());
+        }
+
+        let pda_pool_token_account_staked_info = next_account_info(account_info_iter)?; // 2
+        let pda_pool_token_account_reward_info = next_account_info(account_info_iter)?; // 3
+
+        let rent_info = next_account_info(account_info_iter)?; // 4
+        validate_rent_sysvar(rent_info)?;
+        let rent = &Rent::from_account_info(rent_info)?;
+
+        let system_program_info = next_account_info(account_info_iter)?; // 5
+
+        let data_len = pda_stake_pool_info.data_len();
+        if data_len == StakePool::LEN {
+            msg!("StakePool {} is up to date", pda_stake_pool_info.key);
+            return Ok(());
+        }
+
+        let mut stake_pool = StakePool::from_legacy_slice(&pda_stake_pool_info.data.borrow())?;
+        if stake_pool_address(stake_pool.pool_index).0 != *pda_stake_pool_info.key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+
+        // The legacy pool didn't track its totals, the vaults of its pool_index hold them
+        if staked_token_account_address(stake_pool.pool_index).0 != *pda_pool_token_account_staked_info.key
+            || reward_token_account_address(stake_pool.pool_index).0 != *pda_pool_token_account_reward_info.key
+        {
+            StakingError::PoolTokenAccountMissmatch.print::<StakingError>();
+            return Err(StakingError::PoolTokenAccountMissmatch.into());
+        }
+        let pda_pool_token_account_staked = TokenAccount::unpack(
+            &pda_pool_token_account_staked_info.data.borrow(),
+        )?;
+        let pda_pool_token_account_reward = TokenAccount::unpack(
+            &pda_pool_token_account_reward_info.data.borrow(),
+        )?;
+
+        stake_pool.total_staked = pda_pool_token_account_staked.amount;
+        stake_pool.total_shares = pda_pool_token_account_staked.amount;
+        stake_pool.total_owed = stake_pool.get_legacy_owed(&pda_pool_token_account_reward)?;
+
+        let lamports = rent
+            .minimum_balance(StakePool::LEN)
+            .saturating_sub(pda_stake_pool_info.lamports());
+        if lamports > 0 {
+            invoke(
+                &system_instruction::transfer(
+                    payer_info.key,
+                    pda_stake_pool_info.key,
+                    lamports,
+                ),
+                &[payer_info.clone(), pda_stake_pool_info.clone(), system_program_info.clone()],
+            )?;
+        }
+
+        pda_stake_pool_info.realloc(StakePool::LEN, true)?;
+        StakePool::pack(stake_pool, &mut pda_stake_pool_info.data.borrow_mut())?;
+
+        msg!("StakePool {} migrated from {} bytes", pda_stake_pool_info.key, LEGACY_STAKE_POOL_LEN);
+
         Ok(())
     }
 }
\ No newline at end of file
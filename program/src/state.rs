@@ -28,24 +28,85 @@ use borsh::{
    BorshSerialize,
    BorshSchema,
 };
+use std::convert::TryFrom;
 use crate::error::StakingError;
-use crate::utils::get_precision_factor;
+use crate::utils::{
+   get_precision_factor,
+   get_pending,
+   get_unpaid,
+   get_reward_debt,
+};
+use crate::math::{
+   reward_per_day,
+   base_to_ui_fixed,
+};
+
+pub const MASTER_STAKING_LEN: usize = 42;
+pub const LEGACY_MASTER_STAKING_LEN: usize = 8; // before the protocol fee, upgraded by SetProtocolFee
+pub const MAX_PROTOCOL_FEE_BPS: u16 = 1_000;
+pub const LEGACY_STAKE_POOL_LEN: usize = 321; // first released StakePool, upgraded by MigrateStakePool
+
+// Bits of StakePool::get_health. A set bit means the invariant holds
+pub const HEALTH_STAKED_COVERED: u8 = 1 << 0; // staked vault holds total_staked
+pub const HEALTH_REWARD_COVERED: u8 = 1 << 1; // reward vault holds owed and remaining rewards
+pub const HEALTH_BONUS_CONSISTENT: u8 = 1 << 2; // bonus blocks are both set or both unset, within the pool
+pub const HEALTH_LAST_REWARD_BLOCK: u8 = 1 << 3; // last_reward_block <= end_block
+pub const HEALTH_SOLVENT: u8 = 1 << 4; // is_solvent
+
+pub const MAX_STAKE_MINTS: usize = 4; // Stake mints accepted besides StakePool.mint
+pub const STAKE_WEIGHT_PRECISION: u64 = 10_000; // Weight of StakePool.mint. Weights are in basis points
+
+pub const MAX_REWARD_TOKENS: usize = 4; // Index 0 is the reward of StakePool.mint paid from PDA token-account for reward tokens
+
+pub const ACCRUAL_MODE_CLASSIC: u8 = 0; // shares are the staked amount
+pub const ACCRUAL_MODE_TIME_WEIGHTED: u8 = 1; // shares grow with the time staked, see StakePool::get_loyalty_weight
+
+// Curve of ACCRUAL_MODE_TIME_WEIGHTED: +LOYALTY_STEP_BPS per LOYALTY_STEP_SLOTS staked, up to LOYALTY_MAX_STEPS
+pub const LOYALTY_STEP_SLOTS: u64 = 1_512_000; // about a week
+pub const LOYALTY_STEP_BPS: u64 = 1_000;
+pub const LOYALTY_MAX_STEPS: u64 = 10; // weight is capped at 2x after about 10 weeks
 
-pub const MASTER_STAKING_LEN: usize = 8;
+// Disabling EmergencyWithdraw takes effect after this delay, so stakers can leave before the lockup is enforced
+pub const EMERGENCY_WITHDRAW_TIMELOCK_SLOTS: u64 = 432_000; // about 2 days
+
+pub const MIN_POOL_DURATION_BLOCKS: u64 = 100; // Initialize rejects shorter pools
+
+// Bits of StakePool.tags. Categories for front-end filtering, they don't change the pool behaviour
+pub const TAG_STABLE: u32 = 1 << 0;
+pub const TAG_LP: u32 = 1 << 1;
+pub const TAG_GOVERNANCE: u32 = 1 << 2;
+pub const TAG_MEME: u32 = 1 << 3;
+pub const TAG_NFT: u32 = 1 << 4;
+pub const KNOWN_TAGS: u32 = TAG_STABLE | TAG_LP | TAG_GOVERNANCE | TAG_MEME | TAG_NFT;
 
 #[repr(C)]
 #[derive(Debug, Clone, Copy, BorshSchema, BorshSerialize, BorshDeserialize)]
 pub struct MasterStaking {
    pub pool_counter: u64,
+   pub protocol_fee_bps: u16, // share of every reward payout sent to the protocol, see SetProtocolFee
+   pub protocol_fee_recipient: Pubkey, // owner of the protocol fee token-accounts
 }
 
 impl MasterStaking {
    pub fn from_account_info(
       a: &AccountInfo
    ) -> Result<MasterStaking, ProgramError> {
-      let master = MasterStaking::try_from_slice(
-         &a.data.borrow_mut(),
-      );
+      MasterStaking::from_slice(&a.data.borrow())
+   }
+
+   /// MasterStaking of LEGACY_MASTER_STAKING_LEN reads without the protocol fee
+   pub fn from_slice(
+      src: &[u8],
+   ) -> Result<MasterStaking, ProgramError> {
+      if src.len() != MASTER_STAKING_LEN && src.len() != LEGACY_MASTER_STAKING_LEN {
+         msg!("MasterStaking length is {}, expected {}", src.len(), MASTER_STAKING_LEN);
+         StakingError::InvalidMasterStakingLength.print::<StakingError>();
+         return Err(StakingError::InvalidMasterStakingLength.into());
+      }
+      let mut data = [0_u8; MASTER_STAKING_LEN];
+      data[..src.len()].copy_from_slice(src);
+
+      let master = MasterStaking::try_from_slice(&data);
       let master = match master {
          Ok(v) => v,
          Err(_) => {
@@ -57,6 +118,40 @@ impl MasterStaking {
       Ok(master)
    }
 
+   /// Writes into an account of MASTER_STAKING_LEN or LEGACY_MASTER_STAKING_LEN. A legacy account
+   /// holds no protocol fee, SetProtocolFee resizes it first
+   pub fn serialize_into(
+      &self,
+      dst: &mut [u8],
+   ) -> ProgramResult {
+      let data = self.try_to_vec()?;
+      if dst.len() != MASTER_STAKING_LEN && dst.len() != LEGACY_MASTER_STAKING_LEN {
+         StakingError::InvalidMasterStakingLength.print::<StakingError>();
+         return Err(StakingError::InvalidMasterStakingLength.into());
+      }
+      if data[dst.len()..].iter().any(|&b| b != 0) {
+         StakingError::InvalidMasterStakingLength.print::<StakingError>();
+         return Err(StakingError::InvalidMasterStakingLength.into());
+      }
+
+      dst.copy_from_slice(&data[..dst.len()]);
+
+      Ok(())
+   }
+
+   /// Part of a reward payout sent to the protocol, see SetProtocolFee
+   pub fn get_protocol_fee(
+      &self,
+      payout: u64,
+   ) -> Result<u64, StakingError> {
+      let fee = (payout as u128)
+         .checked_mul(self.protocol_fee_bps as u128)
+         .ok_or(StakingError::Overflow)?
+         / 10_000;
+
+      Ok(u64::try_from(fee)?)
+   }
+
    pub fn increase_counter(
       &mut self,
    ) -> Result<(), ProgramError> {
@@ -84,8 +179,8 @@ pub struct StakePool {
    pub last_reward_block: u64, 
    pub start_block: u64,
    pub end_block: u64,
-   pub reward_amount: u64,
-   pub reward_per_block: u64,
+   pub total_reward: u64, // reward_amount of Initialize or MigrateRewardMint. Later funding is in total_funded
+   pub reward_per_block: [u64; MAX_REWARD_TOKENS], // per reward token, see MAX_REWARD_TOKENS
    pub accrued_token_per_share: u128, 
    #[derivative(Debug="ignore")]
    pub pool_name: [u8; 32],
@@ -93,6 +188,45 @@ pub struct StakePool {
    pub project_link: [u8; 128],
    #[derivative(Debug="ignore")]
    pub theme_id: u8,
+   pub total_owed: u64,
+   pub is_solvent: u8,
+   pub emission_period: u64,
+   pub min_compound_interval: u64,
+   pub total_staked: u64,
+   pub reward_swap_program: COption<Pubkey>,
+   pub round_up_rewards: u8,
+   pub max_total_staked: u64,
+   pub stake_mints: [Pubkey; MAX_STAKE_MINTS],
+   pub stake_mint_weights: [u16; MAX_STAKE_MINTS],
+   pub total_shares: u64,
+   pub in_progress: u8,
+   pub is_funded: u8,
+   pub max_stake_per_user: u64,
+   pub total_funded: u64, // reward tokens sent to the vault by Initialize, FundRewards and UpdateEndBlock
+   pub receipt_supply: u64, // staked tokens represented by receipt tokens. They stay in the staked vault without rewards
+   pub warmup_slots: u64, // rewards ramp up linearly over this many slots after a Deposit. 0 disables the warmup
+   pub pending_accrual: u128, // remainder of reward * precision_factor / staked supply, added to the next accrual. Always < staked supply
+   pub tags: u32, // TAG_* bits for discovery
+   pub has_boost_vault: u8, // 1 pays the bonus from the boost vault. 0 pays it from the reward vault
+   pub accrued_boost_per_share: u128, // as accrued_token_per_share, for the bonus part of the emission
+   pub pending_boost_accrual: u128, // as pending_accrual, for accrued_boost_per_share
+   pub boost_owed: u64, // accrued and not claimed rewards of the boost vault
+   pub accrual_mode: u8, // ACCRUAL_MODE_*, see SetAccrualMode
+   pub emergency_withdraw_enabled: u8, // see StakePool::is_emergency_withdraw_enabled
+   pub emergency_withdraw_disable_slot: u64, // EmergencyWithdraw stays open till this slot after it was disabled
+   pub pre_bonus_end_block: u64, // end_block before SetBonusTime shortened it, 0 without a bonus. See CancelBonus
+   pub min_reward_duration: u64, // slots since UserInfo.deposit_slot before any reward is paid, see SetMinRewardDuration
+   pub deposit_count: u64, // deposits with a non-zero amount, see StakePool::record_deposit
+   pub withdraw_count: u64, // withdrawals with a non-zero amount, emergency ones included
+   pub lifetime_deposited: u64, // staked tokens, saturates
+   pub lifetime_withdrawn: u64, // staked tokens, saturates
+   pub max_claim_per_tx: u64, // max reward paid by one Deposit, Withdraw or Compound, 0 means no limit. See UserInfo.pending_owed
+   pub extra_accrued_token_per_share: [u128; MAX_REWARD_TOKENS], // per reward token, index 0 is accrued_token_per_share
+   pub extra_pending_accrual: [u128; MAX_REWARD_TOKENS], // per reward token, index 0 is pending_accrual
+   pub extra_owed: [u64; MAX_REWARD_TOKENS], // accrued and not claimed, per reward token. Index 0 is total_owed
+   pub stakers: u64, // positions with stake. MigrateUserInfo counts the positions of a migrated pool
+   pub is_paused: u8, // 1 rejects Deposit, see SetPaused. Withdrawals stay open
+   pub min_stake_amount: u64, // min staked amount of a UserInfo holding stake, 0 means no minimum. See Deposit
 }
  
 impl Sealed for StakePool {}
@@ -102,9 +236,9 @@ impl IsInitialized for StakePool {
    }
 }
 impl Pack for StakePool {
-   const LEN: usize = 321;
+   const LEN: usize = 905;
    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
-      let src = array_ref![src, 0, 321];
+      let src = array_ref![src, 0, 905];
       let (
          n_reward_tokens,
          pool_index,
@@ -118,13 +252,52 @@ impl Pack for StakePool {
          last_reward_block,
          start_block,
          end_block,
-         reward_amount,
+         total_reward,
          reward_per_block,
          accrued_token_per_share,
          pool_name,
          project_link,
          theme_id,
-      ) = array_refs![src, 1, 8, 32, 32, 1, 1, 5, 12, 12, 8, 8, 8, 8, 8, 16, 32, 128, 1];
+         total_owed,
+         is_solvent,
+         emission_period,
+         min_compound_interval,
+         total_staked,
+         reward_swap_program,
+         round_up_rewards,
+         max_total_staked,
+         stake_mints,
+         stake_mint_weights,
+         total_shares,
+         in_progress,
+         is_funded,
+         max_stake_per_user,
+         total_funded,
+         receipt_supply,
+         warmup_slots,
+         pending_accrual,
+         tags,
+         has_boost_vault,
+         accrued_boost_per_share,
+         pending_boost_accrual,
+         boost_owed,
+         accrual_mode,
+         emergency_withdraw_enabled,
+         emergency_withdraw_disable_slot,
+         pre_bonus_end_block,
+         min_reward_duration,
+         deposit_count,
+         withdraw_count,
+         lifetime_deposited,
+         lifetime_withdrawn,
+         max_claim_per_tx,
+         extra_accrued_token_per_share,
+         extra_pending_accrual,
+         extra_owed,
+         stakers,
+         is_paused,
+         min_stake_amount,
+      ) = array_refs![src, 1, 8, 32, 32, 1, 1, 5, 12, 12, 8, 8, 8, 8, 32, 16, 32, 128, 1, 8, 1, 8, 8, 8, 36, 1, 8, 128, 8, 8, 1, 1, 8, 8, 8, 8, 16, 4, 1, 16, 16, 8, 1, 1, 8, 8, 8, 8, 8, 8, 8, 8, 64, 64, 32, 8, 1, 8];
       Ok(StakePool {
          n_reward_tokens: u8::from_le_bytes(*n_reward_tokens),
          pool_index: u64::from_le_bytes(*pool_index),
@@ -138,16 +311,55 @@ impl Pack for StakePool {
          last_reward_block: u64::from_le_bytes(*last_reward_block),
          start_block: u64::from_le_bytes(*start_block),
          end_block: u64::from_le_bytes(*end_block),
-         reward_amount: u64::from_le_bytes(*reward_amount),
-         reward_per_block: u64::from_le_bytes(*reward_per_block),
+         total_reward: u64::from_le_bytes(*total_reward),
+         reward_per_block: unpack_reward_per_block(reward_per_block),
          accrued_token_per_share: u128::from_le_bytes(*accrued_token_per_share), 
          pool_name: *pool_name,
          project_link: *project_link,
          theme_id: u8::from_le_bytes(*theme_id),
+         total_owed: u64::from_le_bytes(*total_owed),
+         is_solvent: u8::from_le_bytes(*is_solvent),
+         emission_period: u64::from_le_bytes(*emission_period),
+         min_compound_interval: u64::from_le_bytes(*min_compound_interval),
+         total_staked: u64::from_le_bytes(*total_staked),
+         reward_swap_program: unpack_coption_key(reward_swap_program)?,
+         round_up_rewards: u8::from_le_bytes(*round_up_rewards),
+         max_total_staked: u64::from_le_bytes(*max_total_staked),
+         stake_mints: unpack_stake_mints(stake_mints),
+         stake_mint_weights: unpack_stake_mint_weights(stake_mint_weights),
+         total_shares: u64::from_le_bytes(*total_shares),
+         in_progress: u8::from_le_bytes(*in_progress),
+         is_funded: u8::from_le_bytes(*is_funded),
+         max_stake_per_user: u64::from_le_bytes(*max_stake_per_user),
+         total_funded: u64::from_le_bytes(*total_funded),
+         receipt_supply: u64::from_le_bytes(*receipt_supply),
+         warmup_slots: u64::from_le_bytes(*warmup_slots),
+         pending_accrual: u128::from_le_bytes(*pending_accrual),
+         tags: u32::from_le_bytes(*tags),
+         has_boost_vault: u8::from_le_bytes(*has_boost_vault),
+         accrued_boost_per_share: u128::from_le_bytes(*accrued_boost_per_share),
+         pending_boost_accrual: u128::from_le_bytes(*pending_boost_accrual),
+         boost_owed: u64::from_le_bytes(*boost_owed),
+         accrual_mode: u8::from_le_bytes(*accrual_mode),
+         emergency_withdraw_enabled: u8::from_le_bytes(*emergency_withdraw_enabled),
+         emergency_withdraw_disable_slot: u64::from_le_bytes(*emergency_withdraw_disable_slot),
+         pre_bonus_end_block: u64::from_le_bytes(*pre_bonus_end_block),
+         min_reward_duration: u64::from_le_bytes(*min_reward_duration),
+         deposit_count: u64::from_le_bytes(*deposit_count),
+         withdraw_count: u64::from_le_bytes(*withdraw_count),
+         lifetime_deposited: u64::from_le_bytes(*lifetime_deposited),
+         lifetime_withdrawn: u64::from_le_bytes(*lifetime_withdrawn),
+         max_claim_per_tx: u64::from_le_bytes(*max_claim_per_tx),
+         extra_accrued_token_per_share: unpack_reward_u128s(extra_accrued_token_per_share),
+         extra_pending_accrual: unpack_reward_u128s(extra_pending_accrual),
+         extra_owed: unpack_reward_per_block(extra_owed),
+         stakers: u64::from_le_bytes(*stakers),
+         is_paused: u8::from_le_bytes(*is_paused),
+         min_stake_amount: u64::from_le_bytes(*min_stake_amount),
       })
    }
    fn pack_into_slice(&self, dst: &mut [u8]) {
-       let dst = array_mut_ref![dst, 0, 321];
+       let dst = array_mut_ref![dst, 0, 905];
        let (
          n_reward_tokens_dst,
          pool_index_dst,
@@ -161,13 +373,52 @@ impl Pack for StakePool {
          last_reward_block_dst,
          start_block_dst,
          end_block_dst,
-         reward_amount_dst,
+         total_reward_dst,
          reward_per_block_dst,
          accrued_token_per_share_dst,
          pool_name_dst,
          project_link_dst,
          theme_id_dst,
-      ) = mut_array_refs![dst, 1, 8, 32, 32, 1, 1, 5, 12, 12, 8, 8, 8, 8, 8, 16, 32, 128, 1];
+         total_owed_dst,
+         is_solvent_dst,
+         emission_period_dst,
+         min_compound_interval_dst,
+         total_staked_dst,
+         reward_swap_program_dst,
+         round_up_rewards_dst,
+         max_total_staked_dst,
+         stake_mints_dst,
+         stake_mint_weights_dst,
+         total_shares_dst,
+         in_progress_dst,
+         is_funded_dst,
+         max_stake_per_user_dst,
+         total_funded_dst,
+         receipt_supply_dst,
+         warmup_slots_dst,
+         pending_accrual_dst,
+         tags_dst,
+         has_boost_vault_dst,
+         accrued_boost_per_share_dst,
+         pending_boost_accrual_dst,
+         boost_owed_dst,
+         accrual_mode_dst,
+         emergency_withdraw_enabled_dst,
+         emergency_withdraw_disable_slot_dst,
+         pre_bonus_end_block_dst,
+         min_reward_duration_dst,
+         deposit_count_dst,
+         withdraw_count_dst,
+         lifetime_deposited_dst,
+         lifetime_withdrawn_dst,
+         max_claim_per_tx_dst,
+         extra_accrued_token_per_share_dst,
+         extra_pending_accrual_dst,
+         extra_owed_dst,
+         stakers_dst,
+         is_paused_dst,
+         min_stake_amount_dst,
+      ) = mut_array_refs![dst, 1, 8, 32, 32, 1, 1, 5, 12, 12, 8, 8, 8, 8, 32, 16, 32, 128, 1, 8, 1, 8, 8, 8, 36, 1, 8, 128, 8, 8, 1, 1, 8, 8, 8, 8, 16, 4, 1, 16, 16, 8, 1, 1, 8, 8, 8, 8, 8, 8, 8, 8, 64, 64, 32, 8, 1, 8];
       let &StakePool {
          n_reward_tokens,
          pool_index,
@@ -181,12 +432,51 @@ impl Pack for StakePool {
          last_reward_block,
          start_block,
          end_block,
-         reward_amount,
+         total_reward,
          reward_per_block,
          accrued_token_per_share,
          pool_name,
          project_link,
          theme_id,
+         total_owed,
+         is_solvent,
+         emission_period,
+         min_compound_interval,
+         total_staked,
+         ref reward_swap_program,
+         round_up_rewards,
+         max_total_staked,
+         stake_mints,
+         stake_mint_weights,
+         total_shares,
+         in_progress,
+         is_funded,
+         max_stake_per_user,
+         total_funded,
+         receipt_supply,
+         warmup_slots,
+         pending_accrual,
+         tags,
+         has_boost_vault,
+         accrued_boost_per_share,
+         pending_boost_accrual,
+         boost_owed,
+         accrual_mode,
+         emergency_withdraw_enabled,
+         emergency_withdraw_disable_slot,
+         pre_bonus_end_block,
+         min_reward_duration,
+         deposit_count,
+         withdraw_count,
+         lifetime_deposited,
+         lifetime_withdrawn,
+         max_claim_per_tx,
+         extra_accrued_token_per_share,
+         extra_pending_accrual,
+         extra_owed,
+         stakers,
+         is_paused,
+         min_stake_amount,
       } = self;
       *n_reward_tokens_dst = n_reward_tokens.to_le_bytes();
       *pool_index_dst = pool_index.to_le_bytes();
@@ -200,53 +490,286 @@ impl Pack for StakePool {
       *last_reward_block_dst = last_reward_block.to_le_bytes();
       *start_block_dst = start_block.to_le_bytes();
       *end_block_dst = end_block.to_le_bytes();
-      *reward_amount_dst = reward_amount.to_le_bytes();
-      *reward_per_block_dst = reward_per_block.to_le_bytes();
+      *total_reward_dst = total_reward.to_le_bytes();
+      pack_reward_per_block(&reward_per_block, reward_per_block_dst);
       *accrued_token_per_share_dst = accrued_token_per_share.to_le_bytes();
       pool_name_dst.copy_from_slice(&pool_name);
       project_link_dst.copy_from_slice(&project_link);
       *theme_id_dst = theme_id.to_le_bytes();
+      *total_owed_dst = total_owed.to_le_bytes();
+      *is_solvent_dst = is_solvent.to_le_bytes();
+      *emission_period_dst = emission_period.to_le_bytes();
+      *min_compound_interval_dst = min_compound_interval.to_le_bytes();
+      *total_staked_dst = total_staked.to_le_bytes();
+      pack_coption_key(reward_swap_program, reward_swap_program_dst);
+      *round_up_rewards_dst = round_up_rewards.to_le_bytes();
+      *max_total_staked_dst = max_total_staked.to_le_bytes();
+      pack_stake_mints(&stake_mints, stake_mints_dst);
+      pack_stake_mint_weights(&stake_mint_weights, stake_mint_weights_dst);
+      *total_shares_dst = total_shares.to_le_bytes();
+      *in_progress_dst = in_progress.to_le_bytes();
+      *is_funded_dst = is_funded.to_le_bytes();
+      *max_stake_per_user_dst = max_stake_per_user.to_le_bytes();
+      *total_funded_dst = total_funded.to_le_bytes();
+      *receipt_supply_dst = receipt_supply.to_le_bytes();
+      *warmup_slots_dst = warmup_slots.to_le_bytes();
+      *pending_accrual_dst = pending_accrual.to_le_bytes();
+      *tags_dst = tags.to_le_bytes();
+      *has_boost_vault_dst = has_boost_vault.to_le_bytes();
+      *accrued_boost_per_share_dst = accrued_boost_per_share.to_le_bytes();
+      *pending_boost_accrual_dst = pending_boost_accrual.to_le_bytes();
+      *boost_owed_dst = boost_owed.to_le_bytes();
+      *accrual_mode_dst = accrual_mode.to_le_bytes();
+      *emergency_withdraw_enabled_dst = emergency_withdraw_enabled.to_le_bytes();
+      *emergency_withdraw_disable_slot_dst = emergency_withdraw_disable_slot.to_le_bytes();
+      *pre_bonus_end_block_dst = pre_bonus_end_block.to_le_bytes();
+      *min_reward_duration_dst = min_reward_duration.to_le_bytes();
+      *deposit_count_dst = deposit_count.to_le_bytes();
+      *withdraw_count_dst = withdraw_count.to_le_bytes();
+      *lifetime_deposited_dst = lifetime_deposited.to_le_bytes();
+      *lifetime_withdrawn_dst = lifetime_withdrawn.to_le_bytes();
+      *max_claim_per_tx_dst = max_claim_per_tx.to_le_bytes();
+      pack_reward_u128s(&extra_accrued_token_per_share, extra_accrued_token_per_share_dst);
+      pack_reward_u128s(&extra_pending_accrual, extra_pending_accrual_dst);
+      pack_reward_per_block(&extra_owed, extra_owed_dst);
+      *stakers_dst = stakers.to_le_bytes();
+      *is_paused_dst = is_paused.to_le_bytes();
+      *min_stake_amount_dst = min_stake_amount.to_le_bytes();
    }
 }
 
+/// Data of an account is an initialized StakePool with a sane schedule. For clients checking a pool
+/// before building a Deposit, the program itself validates with StakePool::unpack
+pub fn is_valid_pool(
+   data: &[u8],
+) -> bool {
+   let stake_pool = match StakePool::unpack(data) {
+      Ok(v) => v,
+      Err(_) => return false,
+   };
+
+   stake_pool.is_initialized == 1 &&
+      stake_pool.start_block < stake_pool.end_block &&
+      stake_pool.emission_period != 0 &&
+      stake_pool.n_reward_tokens >= 1 &&
+      stake_pool.n_reward_tokens as usize <= MAX_REWARD_TOKENS &&
+      stake_pool.bonus_multiplier.is_some()
+}
+
 impl StakePool {
+   /// Reads StakePool of LEGACY_STAKE_POOL_LEN, see MigrateStakePool. It had one reward token, reward_amount at
+   /// total_reward and a single reward_per_block. Fields added later take the values of Initialize, total_staked,
+   /// total_shares and total_owed it didn't track are zero until MigrateStakePool sets them from the vaults
+   pub fn from_legacy_slice(src: &[u8]) -> Result<StakePool, ProgramError> {
+      if src.len() != LEGACY_STAKE_POOL_LEN {
+         msg!("StakePool length is {}, expected {}", src.len(), LEGACY_STAKE_POOL_LEN);
+         return Err(ProgramError::InvalidAccountData);
+      }
+      let src = array_ref![src, 0, LEGACY_STAKE_POOL_LEN];
+      let (
+         n_reward_tokens,
+         pool_index,
+         owner,
+         mint,
+         is_initialized,
+         precision_factor_rank,
+         bonus_multiplier,
+         bonus_start_block,
+         bonus_end_block,
+         last_reward_block,
+         start_block,
+         end_block,
+         reward_amount,
+         reward_per_block,
+         accrued_token_per_share,
+         pool_name,
+         project_link,
+         theme_id,
+      ) = array_refs![src, 1, 8, 32, 32, 1, 1, 5, 12, 12, 8, 8, 8, 8, 8, 16, 32, 128, 1];
+
+      let reward_amount = u64::from_le_bytes(*reward_amount);
+      let mut reward_per_block_by_token = [0; MAX_REWARD_TOKENS];
+      reward_per_block_by_token[0] = u64::from_le_bytes(*reward_per_block);
+
+      Ok(StakePool {
+         n_reward_tokens: u8::from_le_bytes(*n_reward_tokens),
+         pool_index: u64::from_le_bytes(*pool_index),
+         owner: Pubkey::new_from_array(*owner),
+         mint: Pubkey::new_from_array(*mint),
+         is_initialized: u8::from_le_bytes(*is_initialized),
+         precision_factor_rank: u8::from_le_bytes(*precision_factor_rank),
+         bonus_multiplier: unpack_coption_u8(bonus_multiplier)?,
+         bonus_start_block: unpack_coption_u64(bonus_start_block)?,
+         bonus_end_block: unpack_coption_u64(bonus_end_block)?,
+         last_reward_block: u64::from_le_bytes(*last_reward_block),
+         start_block: u64::from_le_bytes(*start_block),
+         end_block: u64::from_le_bytes(*end_block),
+         total_reward: reward_amount,
+         reward_per_block: reward_per_block_by_token,
+         accrued_token_per_share: u128::from_le_bytes(*accrued_token_per_share),
+         pool_name: *pool_name,
+         project_link: *project_link,
+         theme_id: u8::from_le_bytes(*theme_id),
+         total_owed: 0,
+         is_solvent: 1,
+         emission_period: 1, // reward_per_block was paid every block
+         min_compound_interval: 0,
+         total_staked: 0,
+         reward_swap_program: COption::None,
+         round_up_rewards: 0,
+         max_total_staked: 0,
+         stake_mints: [Pubkey::default(); MAX_STAKE_MINTS],
+         stake_mint_weights: [0; MAX_STAKE_MINTS],
+         total_shares: 0,
+         in_progress: 0,
+         is_funded: 1, // Initialize sent reward_amount to the vault
+         max_stake_per_user: 0,
+         total_funded: reward_amount,
+         receipt_supply: 0,
+         warmup_slots: 0,
+         pending_accrual: 0,
+         tags: 0,
+         has_boost_vault: 0,
+         accrued_boost_per_share: 0,
+         pending_boost_accrual: 0,
+         boost_owed: 0,
+         accrual_mode: ACCRUAL_MODE_CLASSIC,
+         emergency_withdraw_enabled: 1,
+         emergency_withdraw_disable_slot: 0,
+         pre_bonus_end_block: 0,
+         min_reward_duration: 0,
+         max_claim_per_tx: 0,
+         deposit_count: 0,
+         withdraw_count: 0,
+         lifetime_deposited: 0,
+         lifetime_withdrawn: 0,
+         extra_accrued_token_per_share: [0; MAX_REWARD_TOKENS],
+         extra_pending_accrual: [0; MAX_REWARD_TOKENS],
+         extra_owed: [0; MAX_REWARD_TOKENS],
+         stakers: 0,
+         is_paused: 0,
+         min_stake_amount: 0,
+      })
+   }
+
+   /// Rewards of a legacy pool accrued and not paid, the reward vault less the blocks left to emit.
+   /// Not less than what is owed, blocks without stake and payouts rounded down stay counted
+   pub fn get_legacy_owed(
+      &self,
+      pda_pool_token_account_reward: &TokenAccount,
+   ) -> Result<u64, ProgramError> {
+      let blocks_left = self.end_block
+         .saturating_sub(self.last_reward_block.max(self.start_block));
+      let unemitted = self.reward_per_block[0]
+         .checked_mul(blocks_left)
+         .ok_or(StakingError::Overflow)?;
+
+      Ok(pda_pool_token_account_reward.amount.saturating_sub(unemitted))
+   }
+
+   /// Accrues the blocks [last_reward_block, min(slot, end_block)). last_reward_block stops at end_block,
+   /// so the last block of the pool is accrued once and a pool of end_block - start_block blocks pays
+   /// exactly that many blocks of reward_per_block
    pub fn update_pool(
       &mut self,
       pda_pool_token_account_staked: &TokenAccount,
-      clock: &Clock, 
+      pda_pool_token_account_reward: &TokenAccount,
+      clock: &Clock,
    ) -> ProgramResult {
       let current_block = clock.slot;
-      if current_block <= self.last_reward_block {
+      if current_block <= self.last_reward_block || self.last_reward_block >= self.end_block {
+         // Already updated in this slot or every block till end_block is accrued,
+         // accrued_token_per_share must stay the same
+         self.expire_bonus(current_block);
+         self.update_solvency(pda_pool_token_account_reward);
+
          return Ok(());
       }
 
-      let staked_token_supply = pda_pool_token_account_staked.amount;
+      let staked_token_supply = self.get_staked_supply(pda_pool_token_account_staked.amount);
 
-      if staked_token_supply == 0 { 
+      if staked_token_supply == 0 {
          self.set_last_reward_block(current_block);
-   
+         self.expire_bonus(current_block);
+         self.update_solvency(pda_pool_token_account_reward);
+
+         return Ok(());
+      }
+
+      // Emissions are paused while the reward vault is empty, so no obligations grow
+      // that can't be paid. Blocks till the vault is refunded are skipped
+      if pda_pool_token_account_reward.amount == 0 {
+         msg!("Reward vault is empty, accrual paused");
+         self.set_last_reward_block(current_block.min(self.end_block));
+         self.expire_bonus(current_block);
+         self.update_solvency(pda_pool_token_account_reward);
+
          return Ok(());
       }
 
       let multiplier = self.get_multiplier(self.last_reward_block, current_block);
+      let reward_multiplier = self.get_reward_vault_multiplier(self.last_reward_block, current_block);
 
-      let reward = multiplier
-         .checked_mul(self.reward_per_block)
-         .ok_or(StakingError::RewardOverflow)?;
+      let reward = self.get_reward(reward_multiplier)?;
 
       let precision_factor = get_precision_factor(
          self.precision_factor_rank,
       )?;
 
-      self.accrued_token_per_share = self
-         .accrued_token_per_share
-         .checked_add(
-            (reward as u128)
-            .checked_mul(precision_factor as u128)
-            .ok_or(StakingError::RewardMulPrecisionOverflow)?
-            .checked_div(staked_token_supply as u128)
-            .ok_or(StakingError::RewardMulPrecisionDivSupplyOverflow)?)
-         .ok_or(StakingError::AccuredTokenPerShareOverflow)?;
+      let (accrued_token_per_share, pending_accrual) = accrue(
+         reward,
+         precision_factor,
+         staked_token_supply,
+         self.accrued_token_per_share,
+         self.pending_accrual,
+      )?;
+      self.accrued_token_per_share = accrued_token_per_share;
+      self.pending_accrual = pending_accrual;
+
+      self.total_owed = self.total_owed
+         .checked_add(reward)
+         .ok_or(StakingError::Overflow)?;
+
+      // Bonus blocks beyond the base rate, only with a boost vault
+      let boost = self.get_reward(multiplier.saturating_sub(reward_multiplier))?;
+      if boost > 0 {
+         let (accrued_boost_per_share, pending_boost_accrual) = accrue(
+            boost,
+            precision_factor,
+            staked_token_supply,
+            self.accrued_boost_per_share,
+            self.pending_boost_accrual,
+         )?;
+         self.accrued_boost_per_share = accrued_boost_per_share;
+         self.pending_boost_accrual = pending_boost_accrual;
+
+         self.boost_owed = self.boost_owed
+            .checked_add(boost)
+            .ok_or(StakingError::Overflow)?;
+      }
+
+      // Other reward tokens accrue at their own rate over the same blocks, without the bonus
+      let base_multiplier = self.get_base_multiplier(self.last_reward_block, current_block);
+      for reward_index in 1..self.n_reward_tokens.min(MAX_REWARD_TOKENS as u8) as usize {
+         let reward = self.get_token_reward(reward_index, base_multiplier)?;
+         if reward == 0 {
+            continue;
+         }
+
+         let (accrued_token_per_share, pending_accrual) = accrue(
+            reward,
+            precision_factor,
+            staked_token_supply,
+            self.extra_accrued_token_per_share[reward_index],
+            self.extra_pending_accrual[reward_index],
+         )?;
+         self.extra_accrued_token_per_share[reward_index] = accrued_token_per_share;
+         self.extra_pending_accrual[reward_index] = pending_accrual;
+
+         self.extra_owed[reward_index] = self.extra_owed[reward_index]
+            .checked_add(reward)
+            .ok_or(StakingError::Overflow)?;
+      }
 
       //debug
       msg!(
@@ -261,119 +784,939 @@ impl StakePool {
       );
       //
 
-      if self.end_block > current_block {
-         self.set_last_reward_block(current_block);
-      } 
-      else {
-         self.set_last_reward_block(self.end_block);
-      }
+      self.set_last_reward_block(current_block.min(self.end_block));
+
+      self.expire_bonus(current_block);
+      self.update_solvency(pda_pool_token_account_reward);
 
+      Ok(())
+   }
+
+   /// Drop the bonus once it is over. Blocks of the bonus are accrued by then on every path of update_pool
+   fn expire_bonus(
+      &mut self,
+      current_block: u64,
+   ) {
       if let COption::Some(v) = self.bonus_end_block {
          if v != 0 && current_block > v {
             self.bonus_start_block = COption::None;
             self.bonus_end_block = COption::None;
             self.set_bonus_multiplier(1);
+            self.pre_bonus_end_block = 0;
          }
       }
+   }
 
-      Ok(())
+   /// Reward of StakePool.mint for `multiplier` blocks
+   pub fn get_reward(
+      &self,
+      multiplier: u64,
+   ) -> Result<u64, StakingError> {
+      self.get_token_reward(0, multiplier)
    }
 
-   fn get_multiplier(
+   /// Reward of the token `reward_index` for `multiplier` blocks. `reward_per_block` is paid once per `emission_period` blocks
+   pub fn get_token_reward(
       &self,
-      mut from: u64,
-      mut to: u64,
-   ) -> u64 {
-      if from < self.start_block {
-         from = self.start_block;
+      reward_index: usize,
+      multiplier: u64,
+   ) -> Result<u64, StakingError> {
+      let reward_per_block = *self.reward_per_block
+         .get(reward_index)
+         .ok_or(StakingError::InvalidRewardIndex)?;
+      let reward = multiplier
+         .checked_mul(reward_per_block)
+         .ok_or(StakingError::RewardOverflow)?
+         .checked_div(self.emission_period)
+         .ok_or(StakingError::Overflow)?;
+
+      Ok(reward)
+   }
+
+   /// Bitmask of HEALTH_* invariants holding for the pool
+   pub fn get_health(
+      &self,
+      pda_pool_token_account_staked: &TokenAccount,
+      pda_pool_token_account_reward: &TokenAccount,
+   ) -> Result<u8, StakingError> {
+      let mut health = 0;
+
+      // with several stake mints the vault holds only the tokens of StakePool.mint
+      if self.has_stake_mints() || pda_pool_token_account_staked.amount >= self.total_staked {
+         health |= HEALTH_STAKED_COVERED;
       }
-      if self.end_block < to {
-         to = self.end_block;
+
+      let required = self
+         .get_remaining_reward()?
+         .checked_add(self.total_owed)
+         .ok_or(StakingError::Overflow)?;
+      if pda_pool_token_account_reward.amount >= required {
+         health |= HEALTH_REWARD_COVERED;
       }
 
-      let multiplier: u64 = self.bonus_multiplier.unwrap().into();
-      let start = match self.bonus_start_block {
-         COption::Some(v) => v,
-         COption::None => 0,
+      let bonus_consistent = match (self.bonus_start_block, self.bonus_end_block) {
+         (COption::None, COption::None) => true,
+         (COption::Some(start), COption::Some(end)) => start < end && end <= self.end_block,
+         _ => false,
       };
-      let end = match self.bonus_end_block {
-         COption::Some(v) => v,
-         COption::None => 0,
+      if bonus_consistent {
+         health |= HEALTH_BONUS_CONSISTENT;
+      }
+
+      if self.last_reward_block <= self.end_block {
+         health |= HEALTH_LAST_REWARD_BLOCK;
+      }
+
+      if self.is_solvent() {
+         health |= HEALTH_SOLVENT;
+      }
+
+      Ok(health)
+   }
+
+   /// Reward to be emitted from last_reward_block till end_block by the reward vault.
+   /// Bonus included unless the pool has a boost vault
+   pub fn get_remaining_reward(
+      &self,
+   ) -> Result<u64, StakingError> {
+      let multiplier = self.get_reward_vault_multiplier(self.last_reward_block, self.end_block);
+
+      self.get_reward(multiplier)
+   }
+
+   /// Shares the rewards are split by. With several stake mints the staked vault holds only one of them,
+   /// so shares are used. Time weighted shares aren't the staked amount either. Tokens behind receipts are not accruing
+   pub fn get_staked_supply(
+      &self,
+      staked_vault_amount: u64,
+   ) -> u64 {
+      if self.has_stake_mints() || self.is_time_weighted() {
+         self.total_shares
+      } else {
+         staked_vault_amount.saturating_sub(self.receipt_supply)
+      }
+   }
+
+   /// Reward `shares` earn from `slot` till end_block, bonus included, if the staked supply stays the same.
+   /// An estimate, unlike get_pending
+   pub fn get_projected_reward(
+      &self,
+      shares: u64,
+      staked_vault_amount: u64,
+      slot: u64,
+   ) -> Result<u64, StakingError> {
+      let staked_supply = self.get_staked_supply(staked_vault_amount);
+      if staked_supply == 0 {
+         return Ok(0);
+      }
+
+      let remaining = self.get_reward(
+         self.get_multiplier(slot.max(self.last_reward_block), self.end_block),
+      )?;
+      let projected = (remaining as u128)
+         .checked_mul(shares as u128)
+         .ok_or(StakingError::Overflow)?
+         .checked_div(staked_supply as u128)
+         .ok_or(StakingError::Overflow)?;
+
+      u64::try_from(projected).map_err(StakingError::from)
+   }
+
+   /// Part of the reward vault not owed to stakers: accrued and not paid yet, or scheduled till end_block
+   pub fn get_reward_surplus(
+      &self,
+      reward_vault_amount: u64,
+   ) -> Result<u64, StakingError> {
+      let obligations = self.get_remaining_reward()?
+         .checked_add(self.total_owed)
+         .ok_or(StakingError::Overflow)?;
+
+      Ok(reward_vault_amount.saturating_sub(obligations))
+   }
+
+   /// Reward scheduled from start_block till end_block for the reward vault.
+   /// Bonus included unless the pool has a boost vault
+   pub fn get_scheduled_reward(
+      &self,
+   ) -> Result<u64, StakingError> {
+      let multiplier = self.get_reward_vault_multiplier(self.start_block, self.end_block);
+
+      self.get_reward(multiplier)
+   }
+
+   /// Reward of the token `reward_index` scheduled from start_block till end_block. Never with the bonus
+   pub fn get_scheduled_token_reward(
+      &self,
+      reward_index: usize,
+   ) -> Result<u64, StakingError> {
+      let multiplier = self.get_base_multiplier(self.start_block, self.end_block);
+
+      self.get_token_reward(reward_index, multiplier)
+   }
+
+   /// reward_per_block of StakePool.mint with the bonus multiplier applied at `block`
+   pub fn effective_reward_per_block(
+      &self,
+      block: u64,
+   ) -> Result<u64, StakingError> {
+      let in_bonus = match (self.bonus_start_block, self.bonus_end_block) {
+         (COption::Some(start), COption::Some(end)) => block >= start && block < end,
+         _ => false,
       };
+      if !in_bonus {
+         return Ok(self.reward_per_block[0]);
+      }
 
-      if from < start && to > end {
-         return start - from + to - end + (end - start) * multiplier; 
+      self.reward_per_block[0]
+         .checked_mul(self.bonus_multiplier.unwrap_or(1) as u64)
+         .ok_or(StakingError::RewardOverflow)
+   }
+
+   /// Last slot for which `available` reward tokens cover the emission from last_reward_block.
+   /// end_block if they outlast the schedule
+   pub fn get_depletion_slot(
+      &self,
+      available: u64,
+   ) -> Result<u64, StakingError> {
+      if self.get_remaining_reward()? <= available {
+         return Ok(self.end_block);
       }
-      else if from < start && to > start {
-         return start - from + (to - start) * multiplier;
+
+      // Emission only grows with the slot, so the bound is searched
+      let mut low = self.last_reward_block.max(self.start_block);
+      let mut high = self.end_block;
+      while low < high {
+         let mid = high - (high - low) / 2;
+         let emitted = self.get_reward(self.get_reward_vault_multiplier(self.last_reward_block, mid))?;
+         if emitted <= available {
+            low = mid;
+         } else {
+            high = mid - 1;
+         }
       }
-      else if from < end && to > end {
-         return to - end + (end - from) * multiplier;
+
+      Ok(low)
+   }
+
+   /// Part of `pending` earned by a position deposited at `deposit_slot`:
+   /// pending * min(current_slot - deposit_slot, warmup_slots) / warmup_slots,
+   /// nothing before min_reward_duration
+   pub fn get_warmed_up(
+      &self,
+      pending: u64,
+      deposit_slot: u64,
+      current_slot: u64,
+   ) -> Result<u64, StakingError> {
+      let elapsed = current_slot.saturating_sub(deposit_slot);
+      if elapsed < self.min_reward_duration {
+         return Ok(0);
       }
-      else if from >= start && to <= end {
-         return (to - from) * multiplier;
+      if self.warmup_slots == 0 || elapsed >= self.warmup_slots {
+         return Ok(pending);
       }
-      else {
-         return to - from;
+
+      let earned = (pending as u128)
+         .checked_mul(elapsed as u128)
+         .ok_or(StakingError::Overflow)?
+         .checked_div(self.warmup_slots as u128)
+         .ok_or(StakingError::Overflow)?;
+
+      u64::try_from(earned).map_err(StakingError::from)
+   }
+
+   pub fn add_funded(
+      &mut self,
+      amount: u64,
+   ) -> ProgramResult {
+      self.total_funded = self.total_funded
+         .checked_add(amount)
+         .ok_or(StakingError::Overflow)?;
+
+      Ok(())
+   }
+
+   /// Daily emission of the primary reward token without the bonus, fixed-point with
+   /// math::UI_AMOUNT_DECIMALS. "X tokens/day" of the front ends
+   pub fn reward_per_day_ui(
+      &self,
+      reward_decimals: u8,
+   ) -> Result<u128, StakingError> {
+      base_to_ui_fixed(
+         reward_per_day(self.reward_per_block[0], self.emission_period)?,
+         reward_decimals,
+      )
+   }
+
+   /// Scheduled reward must not exceed total_funded. Unfunded pools are checked by FundRewards
+   pub fn check_funding(
+      &self,
+   ) -> ProgramResult {
+      if !self.is_funded() {
+         return Ok(());
+      }
+
+      let scheduled = self.get_scheduled_reward()?;
+      if scheduled > self.total_funded {
+         msg!(
+            "Schedule requires {} but {} was funded",
+            scheduled,
+            self.total_funded,
+         );
+         StakingError::OverCommittedRewards.print::<StakingError>();
+         return Err(StakingError::OverCommittedRewards.into());
       }
+
+      Ok(())
    }
 
-   fn set_last_reward_block(
+   fn update_solvency(
       &mut self,
-      block: u64,
+      pda_pool_token_account_reward: &TokenAccount,
    ) {
-      self.last_reward_block = block;
+      if pda_pool_token_account_reward.amount >= self.total_owed {
+         self.is_solvent = 1;
+      }
+      else {
+         self.is_solvent = 0;
+      }
    }
 
-   pub fn set_end_block(
+   pub fn is_solvent(&self) -> bool {
+      self.is_solvent != 0
+   }
+
+   pub fn has_boost_vault(&self) -> bool {
+      self.has_boost_vault != 0
+   }
+
+   pub fn decrease_boost_owed(
       &mut self,
-      block: u64,
+      paid: u64,
    ) {
-      self.end_block = block;
+      // accrual floors per position, so claims never exceed boost_owed by more than the dust
+      self.boost_owed = self.boost_owed.saturating_sub(paid);
    }
 
-   pub fn set_bonus_multiplier(
+   /// decrease_boost_owed for the reward token `reward_index`
+   pub fn decrease_extra_owed(
       &mut self,
-      multiplier: u8,
+      reward_index: usize,
+      paid: u64,
    ) {
-      self.bonus_multiplier = COption::Some(multiplier);
+      if let Some(owed) = self.extra_owed.get_mut(reward_index) {
+         *owed = owed.saturating_sub(paid);
+      }
    }
 
-   pub fn set_bonus_start_block(
+   /// Analytics only, so the counters saturate instead of failing a deposit
+   pub fn record_deposit(
       &mut self,
-      block: u64,
+      amount: u64,
    ) {
-      self.bonus_start_block = COption::Some(block);
+      if amount == 0 {
+         return;
+      }
+
+      self.deposit_count = self.deposit_count.saturating_add(1);
+      self.lifetime_deposited = self.lifetime_deposited.saturating_add(amount);
    }
 
-   pub fn set_bonus_end_block(
+   /// As record_deposit, for withdrawals
+   pub fn record_withdraw(
       &mut self,
-      block: u64,
+      amount: u64,
    ) {
-      self.bonus_end_block = COption::Some(block);
+      if amount == 0 {
+         return;
+      }
+
+      self.withdraw_count = self.withdraw_count.saturating_add(1);
+      self.lifetime_withdrawn = self.lifetime_withdrawn.saturating_add(amount);
    }
 
-   pub fn update_project_info(
+   /// A position counts in stakers while it has stake
+   pub fn update_stakers(
       &mut self,
-      pool_name: [u8; 32],
-      project_link: [u8; 128],
-      theme_id: u8,
-   ) {
-      self.pool_name = pool_name;
-      self.project_link = project_link;
-      self.theme_id = theme_id;
+      amount_before: u64,
+      amount_after: u64,
+   ) -> ProgramResult {
+      if amount_before == 0 && amount_after > 0 {
+         self.stakers = self.stakers
+            .checked_add(1)
+            .ok_or(StakingError::Overflow)?;
+      } else if amount_before > 0 && amount_after == 0 {
+         self.stakers = self.stakers
+            .checked_sub(1)
+            .ok_or(StakingError::Overflow)?;
+      }
+
+      Ok(())
    }
-}
 
-pub const USER_INFO_LEN: usize = 48;
+   pub fn decrease_owed(
+      &mut self,
+      paid: u64,
+   ) -> ProgramResult {
+      if self.rounds_up_rewards() {
+         // rounded up payouts may exceed accrued rewards by the dust
+         self.total_owed = self.total_owed.saturating_sub(paid);
+         return Ok(());
+      }
 
-#[repr(C)]
-#[derive(Debug, Copy, Clone, BorshSerialize, BorshDeserialize)]
-pub struct UserInfo {
-   pub token_account_id: Pubkey,
-   pub amount: u64,
-   pub reward_debt: u64,
-}
+      self.total_owed = self.total_owed
+         .checked_sub(paid)
+         .ok_or(StakingError::Overflow)?;
+
+      Ok(())
+   }
+
+   pub fn set_max_total_staked(
+      &mut self,
+      max_total_staked: u64,
+   ) {
+      self.max_total_staked = max_total_staked;
+   }
+
+   pub fn has_stake_mints(&self) -> bool {
+      self.stake_mints.iter().any(|mint| *mint != Pubkey::default())
+   }
+
+   pub fn add_stake_mint(
+      &mut self,
+      mint: &Pubkey,
+      weight: u16,
+   ) -> ProgramResult {
+      if *mint == self.mint ||
+         *mint == Pubkey::default() ||
+         weight == 0 ||
+         self.stake_mints.contains(mint) {
+            StakingError::InvalidStakeMint.print::<StakingError>();
+            return Err(StakingError::InvalidStakeMint.into());
+      }
+
+      let slot = self.stake_mints
+         .iter()
+         .position(|mint| *mint == Pubkey::default())
+         .ok_or(StakingError::StakeMintsFull)?;
+
+      self.stake_mints[slot] = *mint;
+      self.stake_mint_weights[slot] = weight;
+
+      Ok(())
+   }
+
+   /// Stake of `amount` tokens of `mint` in shares. Rewards are shared between stakers by shares.
+   /// StakePool.mint has weight STAKE_WEIGHT_PRECISION, so one token is one share
+   pub fn get_shares(
+      &self,
+      mint: &Pubkey,
+      amount: u64,
+   ) -> Result<u64, ProgramError> {
+      if *mint == self.mint {
+         return Ok(amount);
+      }
+
+      let slot = self.stake_mints
+         .iter()
+         .position(|stake_mint| stake_mint == mint && *mint != Pubkey::default())
+         .ok_or(StakingError::InvalidStakeMint)?;
+
+      let shares = (amount as u128)
+         .checked_mul(self.stake_mint_weights[slot] as u128)
+         .ok_or(StakingError::Overflow)?
+         .checked_div(STAKE_WEIGHT_PRECISION as u128)
+         .ok_or(StakingError::Overflow)?;
+
+      Ok(u64::try_from(shares).map_err(StakingError::from)?)
+   }
+
+   /// Enabling is immediate, disabling waits for EMERGENCY_WITHDRAW_TIMELOCK_SLOTS, see SetEmergencyWithdrawEnabled
+   pub fn is_emergency_withdraw_enabled(
+      &self,
+      slot: u64,
+   ) -> bool {
+      self.emergency_withdraw_enabled == 1 || slot < self.emergency_withdraw_disable_slot
+   }
+
+   pub fn is_time_weighted(&self) -> bool {
+      self.accrual_mode == ACCRUAL_MODE_TIME_WEIGHTED
+   }
+
+   /// Weight in basis points of STAKE_WEIGHT_PRECISION of a position staked continuously since `stake_start_slot`:
+   /// 10_000 + min((slot - stake_start_slot) / LOYALTY_STEP_SLOTS, LOYALTY_MAX_STEPS) * LOYALTY_STEP_BPS.
+   /// 10_000 in ACCRUAL_MODE_CLASSIC
+   pub fn get_loyalty_weight(
+      &self,
+      stake_start_slot: u64,
+      slot: u64,
+   ) -> u64 {
+      if !self.is_time_weighted() {
+         return STAKE_WEIGHT_PRECISION;
+      }
+
+      let steps = (slot.saturating_sub(stake_start_slot) / LOYALTY_STEP_SLOTS).min(LOYALTY_MAX_STEPS);
+
+      STAKE_WEIGHT_PRECISION + steps * LOYALTY_STEP_BPS
+   }
+
+   /// Shares the position accrues with since its last change
+   pub fn get_position_shares(
+      &self,
+      mint: &Pubkey,
+      user_info: &UserInfo,
+   ) -> Result<u64, ProgramError> {
+      if self.is_time_weighted() {
+         return Ok(user_info.shares);
+      }
+
+      self.get_shares(mint, user_info.amount)
+   }
+
+   /// Shares of `amount` staked since `stake_start_slot`. The loyalty weight is fixed till the next change
+   /// of the position, so the weight grows for accrual on Deposit, Withdraw (0 included) and Compound
+   pub fn get_weighted_shares(
+      &self,
+      mint: &Pubkey,
+      amount: u64,
+      stake_start_slot: u64,
+      slot: u64,
+   ) -> Result<u64, ProgramError> {
+      let shares = self.get_shares(mint, amount)?;
+
+      let weighted_shares = (shares as u128)
+         .checked_mul(self.get_loyalty_weight(stake_start_slot, slot) as u128)
+         .ok_or(StakingError::Overflow)?
+         .checked_div(STAKE_WEIGHT_PRECISION as u128)
+         .ok_or(StakingError::Overflow)?;
+
+      Ok(u64::try_from(weighted_shares).map_err(StakingError::from)?)
+   }
+
+   /// Replace `old_shares` of a user with `new_shares` in total_shares
+   pub fn update_total_shares(
+      &mut self,
+      old_shares: u64,
+      new_shares: u64,
+   ) -> ProgramResult {
+      self.total_shares = self.total_shares
+         .checked_sub(old_shares)
+         .ok_or(StakingError::Overflow)?
+         .checked_add(new_shares)
+         .ok_or(StakingError::Overflow)?;
+
+      Ok(())
+   }
+
+   /// Mark the pool busy for the time of Deposit or Withdraw
+   pub fn lock(
+      &mut self,
+   ) -> ProgramResult {
+      if self.in_progress != 0 {
+         StakingError::PoolBusy.print::<StakingError>();
+         return Err(StakingError::PoolBusy.into());
+      }
+      self.in_progress = 1;
+
+      Ok(())
+   }
+
+   pub fn unlock(
+      &mut self,
+   ) {
+      self.in_progress = 0;
+   }
+
+   pub fn is_funded(&self) -> bool {
+      self.is_funded != 0
+   }
+
+   pub fn is_paused(&self) -> bool {
+      self.is_paused != 0
+   }
+
+   pub fn set_funded(
+      &mut self,
+   ) {
+      self.is_funded = 1;
+   }
+
+   pub fn rounds_up_rewards(&self) -> bool {
+      self.round_up_rewards != 0
+   }
+
+   /// Rounded up payouts take the dust of every division from the reward vault, so the last claim of
+   /// the schedule can find less than its pending. It gets what is left instead of failing
+   pub fn cap_rounded_up(
+      &self,
+      pending: u64,
+      reward_vault_amount: u64,
+   ) -> u64 {
+      if self.rounds_up_rewards() {
+         pending.min(reward_vault_amount)
+      } else {
+         pending
+      }
+   }
+
+   /// Blocks in (from, to] within the pool, with the bonus. update_pool of a Deposit at start_block sets
+   /// last_reward_block to start_block, so the position earns from the next block, one block per slot
+   pub fn get_multiplier(
+      &self,
+      mut from: u64,
+      mut to: u64,
+   ) -> u64 {
+      if from < self.start_block {
+         from = self.start_block;
+      }
+      if self.end_block < to {
+         to = self.end_block;
+      }
+      if from >= to {
+         return 0;
+      }
+
+      let multiplier: u64 = self.bonus_multiplier.unwrap().into();
+      // a bonus with one block missing is no bonus, see HEALTH_BONUS_CONSISTENT
+      let (start, end) = match (self.bonus_start_block, self.bonus_end_block) {
+         (COption::Some(start), COption::Some(end)) if start < end => (start, end),
+         _ => (0, 0),
+      };
+
+      if from < start && to > end {
+         return start - from + to - end + (end - start) * multiplier; 
+      }
+      else if from < start && to > start {
+         return start - from + (to - start) * multiplier;
+      }
+      else if from < end && to > end {
+         return to - end + (end - from) * multiplier;
+      }
+      else if from >= start && to <= end {
+         return (to - from) * multiplier;
+      }
+      else {
+         return to - from;
+      }
+   }
+
+   /// Pool starts over with `mint`, see MigrateRewardMint. Only valid without stake, so no UserInfo
+   /// holds a reward_debt of the old accrued_token_per_share
+   pub fn reset_reward_mint(
+      &mut self,
+      mint: Pubkey,
+      precision_factor_rank: u8,
+      reward_per_block: u64,
+      start_block: u64,
+      end_block: u64,
+      total_reward: u64,
+   ) {
+      self.mint = mint;
+      self.precision_factor_rank = precision_factor_rank;
+      self.reward_per_block = [0; MAX_REWARD_TOKENS];
+      self.reward_per_block[0] = reward_per_block;
+      self.start_block = start_block;
+      self.end_block = end_block;
+      self.total_reward = total_reward;
+      self.last_reward_block = 0;
+      self.accrued_token_per_share = 0;
+      self.pending_accrual = 0;
+      self.total_owed = 0;
+      self.is_solvent = 1;
+      self.bonus_multiplier = COption::Some(1);
+      self.bonus_start_block = COption::None;
+      self.bonus_end_block = COption::None;
+      self.pre_bonus_end_block = 0;
+      self.is_funded = 0;
+      self.total_funded = 0;
+   }
+
+   /// Only the last reward token retires, so the indexes of the others stay the same.
+   /// Index 0 is StakePool.mint and never retires
+   pub fn retire_reward_token(
+      &mut self,
+      reward_index: usize,
+   ) -> ProgramResult {
+      if reward_index == 0 || reward_index + 1 != self.n_reward_tokens as usize || reward_index >= MAX_REWARD_TOKENS {
+         StakingError::InvalidRewardIndex.print::<StakingError>();
+         return Err(StakingError::InvalidRewardIndex.into());
+      }
+
+      // Accrued amounts stay claimable with ClaimRewardToken. RewardTokenInfo of the index isn't
+      // replaced, so the index isn't reused for another mint
+      self.reward_per_block[reward_index] = 0;
+      self.n_reward_tokens -= 1;
+
+      Ok(())
+   }
+
+   /// Bonus is scheduled and `slot` is before its start, so no block accrued with it yet
+   pub fn is_bonus_cancellable(
+      &self,
+      slot: u64,
+   ) -> bool {
+      match self.bonus_start_block {
+         COption::Some(start) => self.pre_bonus_end_block != 0 && slot < start,
+         COption::None => false,
+      }
+   }
+
+   /// Drops a bonus checked with is_bonus_cancellable, end_block gets back the blocks paid by the bonus
+   pub fn cancel_bonus(
+      &mut self,
+   ) {
+      self.end_block = self.pre_bonus_end_block;
+      self.bonus_start_block = COption::None;
+      self.bonus_end_block = COption::None;
+      self.set_bonus_multiplier(1);
+      self.pre_bonus_end_block = 0;
+   }
+
+   /// Block `slot` accrues with the bonus. The window is [bonus_start_block, bonus_end_block) as in
+   /// get_multiplier: bonus_end_block is the first block without the bonus. False without a bonus window
+   pub fn bonus_active(
+      &self,
+      slot: u64,
+   ) -> bool {
+      match (self.bonus_start_block, self.bonus_end_block) {
+         (COption::Some(start), COption::Some(end)) => {
+            self.bonus_multiplier.unwrap_or(1) > 1 && start <= slot && slot < end
+         },
+         _ => false,
+      }
+   }
+
+   /// Blocks between `from` and `to` within the pool, without the bonus
+   pub fn get_base_multiplier(
+      &self,
+      from: u64,
+      to: u64,
+   ) -> u64 {
+      let from = from.max(self.start_block);
+      let to = to.min(self.end_block);
+
+      to.saturating_sub(from)
+   }
+
+   /// Multiplier paid by the reward vault. With a boost vault the extra blocks of the bonus are paid by the boost vault
+   pub fn get_reward_vault_multiplier(
+      &self,
+      from: u64,
+      to: u64,
+   ) -> u64 {
+      if self.has_boost_vault() {
+         self.get_base_multiplier(from, to)
+      } else {
+         self.get_multiplier(from, to)
+      }
+   }
+
+   /// Never moves backwards, blocks before last_reward_block are accrued already.
+   /// reset_reward_mint starts a new schedule and sets it directly
+   fn set_last_reward_block(
+      &mut self,
+      block: u64,
+   ) {
+      if block < self.last_reward_block {
+         msg!("last_reward_block {} is kept over {}", self.last_reward_block, block);
+         return;
+      }
+
+      self.last_reward_block = block;
+   }
+
+   pub fn set_end_block(
+      &mut self,
+      block: u64,
+   ) {
+      self.end_block = block;
+   }
+
+   pub fn set_bonus_multiplier(
+      &mut self,
+      multiplier: u8,
+   ) {
+      self.bonus_multiplier = COption::Some(multiplier);
+   }
+
+   pub fn set_bonus_start_block(
+      &mut self,
+      block: u64,
+   ) {
+      self.bonus_start_block = COption::Some(block);
+   }
+
+   pub fn set_bonus_end_block(
+      &mut self,
+      block: u64,
+   ) {
+      self.bonus_end_block = COption::Some(block);
+   }
+
+   pub fn update_project_info(
+      &mut self,
+      pool_name: [u8; 32],
+      project_link: [u8; 128],
+      theme_id: u8,
+   ) {
+      self.pool_name = pool_name;
+      self.project_link = project_link;
+      self.theme_id = theme_id;
+   }
+
+   pub fn set_project_link(
+      &mut self,
+      project_link: [u8; 128],
+   ) {
+      self.project_link = project_link;
+   }
+
+   pub fn set_reward_swap_program(
+      &mut self,
+      reward_swap_program: COption<Pubkey>,
+   ) {
+      self.reward_swap_program = reward_swap_program;
+   }
+}
+
+pub const MAX_LONG_LINK_LEN: usize = 512;
+pub const LONG_LINK_LEN: usize = 4 + MAX_LONG_LINK_LEN;
+
+#[repr(C)]
+#[derive(Debug, Clone, BorshSerialize, BorshDeserialize)]
+pub struct LongLink {
+   pub uri: Vec<u8>,
+}
+
+pub const REFERRAL_CODE_LEN: usize = 16 + 32;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, BorshSerialize, BorshDeserialize)]
+pub struct ReferralCode {
+   pub code: [u8; 16],
+   pub referrer: Pubkey, // token-account of the referrer
+}
+
+impl ReferralCode {
+   pub fn from_account_info(
+      a: &AccountInfo
+   ) -> Result<ReferralCode, ProgramError> {
+      if a.owner != &crate::id() {
+         StakingError::InvalidReferralCode.print::<StakingError>();
+         return Err(StakingError::InvalidReferralCode.into());
+      }
+      let referral_code = ReferralCode::try_from_slice(
+         &a.data.borrow(),
+      );
+      let referral_code = match referral_code {
+         Ok(v) => v,
+         Err(_) => {
+            StakingError::InvalidReferralCode.print::<StakingError>();
+            return Err(StakingError::InvalidReferralCode.into());
+         },
+      };
+
+      Ok(referral_code)
+   }
+}
+
+pub const REWARD_TOKEN_INFO_LEN: usize = 32 + 1 + 32 + 32;
+
+/// Reward token after the first, created by Initialize. Rates and accrual are in StakePool
+#[repr(C)]
+#[derive(Debug, Copy, Clone, BorshSerialize, BorshDeserialize)]
+pub struct RewardTokenInfo {
+   pub stake_pool: Pubkey,
+   pub reward_index: u8, // index in StakePool.reward_per_block, from 1
+   pub mint: Pubkey,
+   pub vault: Pubkey, // PDA token-account, see pda::extra_reward_token_account_address
+}
+
+impl RewardTokenInfo {
+   pub fn from_account_info(
+      a: &AccountInfo
+   ) -> Result<RewardTokenInfo, ProgramError> {
+      if a.owner != &crate::id() {
+         StakingError::InvalidRewardTokenInfo.print::<StakingError>();
+         return Err(StakingError::InvalidRewardTokenInfo.into());
+      }
+      let reward_token_info = RewardTokenInfo::try_from_slice(
+         &a.data.borrow(),
+      );
+      let reward_token_info = match reward_token_info {
+         Ok(v) => v,
+         Err(_) => {
+            StakingError::InvalidRewardTokenInfo.print::<StakingError>();
+            return Err(StakingError::InvalidRewardTokenInfo.into());
+         },
+      };
+
+      Ok(reward_token_info)
+   }
+}
+
+pub const MAX_VOTE_CHOICES: usize = 4;
+pub const PROPOSAL_TALLY_LEN: usize = 32 + 8 + 8 + 8 * MAX_VOTE_CHOICES;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, BorshSerialize, BorshDeserialize)]
+pub struct ProposalTally {
+   pub stake_pool: Pubkey,
+   pub proposal_id: u64,
+   pub start_slot: u64, // slot of the first vote, positions changed since can't vote. See UserInfo.amount_change_slot
+   pub weights: [u64; MAX_VOTE_CHOICES], // sum of UserInfo.amount of the voters per choice
+}
+
+impl ProposalTally {
+   pub fn add_vote(
+      &mut self,
+      choice: u8,
+      weight: u64,
+   ) -> ProgramResult {
+      let choice = choice as usize;
+      if choice >= MAX_VOTE_CHOICES {
+         StakingError::InvalidVoteChoice.print::<StakingError>();
+         return Err(StakingError::InvalidVoteChoice.into());
+      }
+      self.weights[choice] = self.weights[choice]
+         .checked_add(weight)
+         .ok_or(StakingError::Overflow)?;
+
+      Ok(())
+   }
+}
+
+pub const VOTE_RECEIPT_LEN: usize = 1 + 8;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, BorshSerialize, BorshDeserialize)]
+pub struct VoteReceipt {
+   pub choice: u8,
+   pub weight: u64,
+}
+
+pub const USER_INFO_LEN: usize = 265;
+pub const LEGACY_USER_INFO_LEN: usize = 48; // first released UserInfo, its positions aren't in StakePool.stakers
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, BorshSerialize, BorshDeserialize)]
+pub struct UserInfo {
+   pub token_account_id: Pubkey,
+   pub amount: u64,
+   pub reward_debt: u128, // multiplied by the precision factor, see utils::get_pending
+   pub last_compound_slot: u64,
+   pub delegate: Pubkey, // Pubkey::default() if there is no delegate
+   pub deposit_slot: u64, // slot of the last Deposit, start of the warmup
+   pub frozen: u8, // 1 blocks every way out of the position, see SetUserFrozen
+   pub boost_debt: u128, // as reward_debt, for StakePool.accrued_boost_per_share
+   pub boost_unclaimed: u64, // boost settled on changes of the position, paid by ClaimBoost
+   pub stake_start_slot: u64, // start of the continuous stake, reset by Withdraw. See StakePool::get_loyalty_weight
+   pub shares: u64, // shares of the position in StakePool.total_shares
+   pub avg_entry_price: u64, // stake weighted price of Deposits with a price oracle, 0 if unknown. See lib::PRICE_ORACLES
+   pub pending_owed: u64, // settled rewards above StakePool.max_claim_per_tx, paid first by the next payout
+   pub extra_reward_debt: [u128; MAX_REWARD_TOKENS], // as reward_debt, per reward token. Index 0 is reward_debt
+   pub extra_unclaimed: [u64; MAX_REWARD_TOKENS], // settled on changes of the position, paid by ClaimRewardToken
+   pub amount_change_slot: u64, // slot of the last change of amount or of the token-account, see CastVote
+}
 
 impl UserInfo {
    pub fn from_account_info(
@@ -393,12 +1736,242 @@ impl UserInfo {
       Ok(user_info)
    }
 
+   /// Reads UserInfo written with an earlier USER_INFO_LEN, see MigrateUserInfo. Fields added later are zero.
+   /// Before 104 bytes reward_debt was u64 and not multiplied by the precision factor
+   pub fn from_legacy_slice(
+      src: &[u8],
+      precision_factor_rank: u8,
+   ) -> Result<UserInfo, ProgramError> {
+      let mut data = [0_u8; USER_INFO_LEN];
+      match src.len() {
+         // reward_debt: u64
+         48 | 56 | 88 | 96 => {
+            let reward_debt = (u64::from_le_bytes(*array_ref![src, 40, 8]) as u128)
+               .checked_mul(get_precision_factor(precision_factor_rank)? as u128)
+               .ok_or(StakingError::Overflow)?;
+
+            data[..40].copy_from_slice(&src[..40]);
+            data[40..56].copy_from_slice(&reward_debt.to_le_bytes());
+            data[56..src.len() + 8].copy_from_slice(&src[48..]);
+         },
+         // reward_debt: u128, new fields were appended
+         104 | 105 | 129 | 145 | 153 | 161 | 257 => {
+            data[..src.len()].copy_from_slice(src);
+         },
+         _ => {
+            StakingError::InvalidUserInfo.print::<StakingError>();
+            return Err(StakingError::InvalidUserInfo.into());
+         },
+      }
+
+      UserInfo::try_from_slice(&data).map_err(|_| StakingError::InvalidUserInfo.into())
+   }
+
    pub fn set_reward_debt(
       &mut self,
-      value: u64,
+      value: u128,
    ) {
       self.reward_debt = value;
    }
+
+   pub fn set_delegate(
+      &mut self,
+      delegate: Pubkey,
+   ) {
+      self.delegate = delegate;
+   }
+
+   pub fn check_delegate(
+      &self,
+      delegate: &Pubkey,
+   ) -> ProgramResult {
+      if self.delegate == Pubkey::default() || self.delegate != *delegate {
+         StakingError::DelegateMissmatch.print::<StakingError>();
+         return Err(StakingError::DelegateMissmatch.into());
+      }
+
+      Ok(())
+   }
+
+   /// Payout of `earned` rewards settled from `pending` together with pending_owed, within
+   /// StakePool.max_claim_per_tx. The rest is carried in pending_owed.
+   /// Returns the payout and the amount leaving StakePool.total_owed, see StakePool::decrease_owed
+   pub fn take_claim(
+      &mut self,
+      stake_pool: &StakePool,
+      pending: u64,
+      earned: u64,
+   ) -> Result<(u64, u64), ProgramError> {
+      let due = self.pending_owed
+         .checked_add(earned)
+         .ok_or(StakingError::Overflow)?;
+      let payout = if stake_pool.max_claim_per_tx == 0 {
+         due
+      } else {
+         due.min(stake_pool.max_claim_per_tx)
+      };
+      let carried_before = self.pending_owed;
+      self.pending_owed = due - payout;
+
+      // The part lost to the warmup leaves total_owed too, the carried part stays owed
+      let settled = pending
+         .checked_add(carried_before)
+         .ok_or(StakingError::Overflow)?
+         .checked_sub(self.pending_owed)
+         .ok_or(StakingError::Overflow)?;
+
+      Ok((payout, settled))
+   }
+
+   /// Moves the boost accrued by `current_shares` to boost_unclaimed and restarts the accrual with `new_shares`.
+   /// Called whenever the shares of the position change
+   pub fn settle_boost(
+      &mut self,
+      stake_pool: &StakePool,
+      current_shares: u64,
+      new_shares: u64,
+   ) -> Result<(), StakingError> {
+      let mut unpaid = 0;
+      if current_shares > 0 {
+         let pending = get_pending(
+            current_shares,
+            stake_pool.accrued_boost_per_share,
+            stake_pool.precision_factor_rank,
+            self.boost_debt,
+            false,
+         )?;
+         unpaid = get_unpaid(
+            current_shares,
+            stake_pool.accrued_boost_per_share,
+            stake_pool.precision_factor_rank,
+            self.boost_debt,
+            pending,
+         )?;
+
+         self.boost_unclaimed = self.boost_unclaimed
+            .checked_add(pending)
+            .ok_or(StakingError::Overflow)?;
+      }
+
+      self.boost_debt = get_reward_debt(
+         new_shares,
+         stake_pool.accrued_boost_per_share,
+         unpaid,
+      )?;
+
+      Ok(())
+   }
+
+   /// settle_boost for the reward tokens after index 0, into extra_unclaimed. Retired tokens are
+   /// settled as well, what they accrued before RetireRewardToken stays claimable
+   pub fn settle_extra_rewards(
+      &mut self,
+      stake_pool: &StakePool,
+      current_shares: u64,
+      new_shares: u64,
+   ) -> Result<(), StakingError> {
+      for reward_index in 1..MAX_REWARD_TOKENS {
+         let accrued_token_per_share = stake_pool.extra_accrued_token_per_share[reward_index];
+
+         let mut unpaid = 0;
+         if current_shares > 0 {
+            let pending = get_pending(
+               current_shares,
+               accrued_token_per_share,
+               stake_pool.precision_factor_rank,
+               self.extra_reward_debt[reward_index],
+               false,
+            )?;
+            unpaid = get_unpaid(
+               current_shares,
+               accrued_token_per_share,
+               stake_pool.precision_factor_rank,
+               self.extra_reward_debt[reward_index],
+               pending,
+            )?;
+
+            self.extra_unclaimed[reward_index] = self.extra_unclaimed[reward_index]
+               .checked_add(pending)
+               .ok_or(StakingError::Overflow)?;
+         }
+
+         self.extra_reward_debt[reward_index] = get_reward_debt(
+            new_shares,
+            accrued_token_per_share,
+            unpaid,
+         )?;
+      }
+
+      Ok(())
+   }
+
+   /// Nothing is staked or left to claim, so closing UserInfo loses nothing
+   pub fn is_closable(
+      &self,
+   ) -> bool {
+      self.amount == 0 &&
+         self.shares == 0 &&
+         self.pending_owed == 0 &&
+         self.boost_unclaimed == 0 &&
+         self.extra_unclaimed.iter().all(|unclaimed| *unclaimed == 0)
+   }
+
+   pub fn check_not_frozen(
+      &self,
+   ) -> ProgramResult {
+      if self.frozen != 0 {
+         StakingError::UserFrozen.print::<StakingError>();
+         return Err(StakingError::UserFrozen.into());
+      }
+
+      Ok(())
+   }
+
+   pub fn check_compound_interval(
+      &self,
+      min_compound_interval: u64,
+      current_slot: u64,
+   ) -> ProgramResult {
+      if self.last_compound_slot == 0 {
+         return Ok(());
+      }
+
+      let next_compound_slot = self.last_compound_slot
+         .checked_add(min_compound_interval)
+         .ok_or(StakingError::Overflow)?;
+
+      if current_slot < next_compound_slot {
+         StakingError::CooldownActive.print::<StakingError>();
+         return Err(StakingError::CooldownActive.into());
+      }
+
+      Ok(())
+   }
+}
+
+/// accrued_per_share and the remainder after `reward` is shared by `supply`. With a large supply and
+/// a small reward the delta floors to zero each block, so the remainder is carried forward instead of being lost
+fn accrue(
+   reward: u64,
+   precision_factor: u64,
+   supply: u64,
+   accrued_per_share: u128,
+   remainder: u128,
+) -> Result<(u128, u128), StakingError> {
+   let accrual = (reward as u128)
+      .checked_mul(precision_factor as u128)
+      .ok_or(StakingError::RewardMulPrecisionOverflow)?
+      .checked_add(remainder)
+      .ok_or(StakingError::RewardMulPrecisionOverflow)?;
+   let accrued_delta = accrual
+      .checked_div(supply as u128)
+      .ok_or(StakingError::RewardMulPrecisionDivSupplyOverflow)?;
+
+   let accrued_per_share = accrued_per_share
+      .checked_add(accrued_delta)
+      .ok_or(StakingError::AccuredTokenPerShareOverflow)?;
+
+   Ok((accrued_per_share, accrual % supply as u128))
 }
 
 fn unpack_coption_u8(src: &[u8; 5]) -> Result<COption<u8>, ProgramError> {
@@ -441,4 +2014,325 @@ fn pack_coption_u64(src: &COption<u64>, dst: &mut [u8; 12]) {
          *tag = [0; 4];
       }
    }
-}
\ No newline at end of file
+}
+
+fn unpack_coption_key(src: &[u8; 36]) -> Result<COption<Pubkey>, ProgramError> {
+   let (tag, body) = array_refs![src, 4, 32];
+   match *tag {
+      [0, 0, 0, 0] => Ok(COption::None),
+      [1, 0, 0, 0] => Ok(COption::Some(Pubkey::new_from_array(*body))),
+      _ => Err(ProgramError::InvalidAccountData),
+   }
+}
+fn pack_coption_key(src: &COption<Pubkey>, dst: &mut [u8; 36]) {
+   let (tag, body) = mut_array_refs![dst, 4, 32];
+   match src {
+      COption::Some(key) => {
+         *tag = [1, 0, 0, 0];
+         body.copy_from_slice(key.as_ref());
+      }
+      COption::None => {
+         *tag = [0; 4];
+      }
+   }
+}
+
+fn unpack_stake_mints(src: &[u8; 32 * MAX_STAKE_MINTS]) -> [Pubkey; MAX_STAKE_MINTS] {
+   let mut stake_mints = [Pubkey::default(); MAX_STAKE_MINTS];
+   for (i, stake_mint) in stake_mints.iter_mut().enumerate() {
+      *stake_mint = Pubkey::new_from_array(*array_ref![src, i * 32, 32]);
+   }
+   stake_mints
+}
+fn pack_stake_mints(src: &[Pubkey; MAX_STAKE_MINTS], dst: &mut [u8; 32 * MAX_STAKE_MINTS]) {
+   for (i, stake_mint) in src.iter().enumerate() {
+      dst[i * 32..(i + 1) * 32].copy_from_slice(stake_mint.as_ref());
+   }
+}
+
+fn unpack_reward_per_block(src: &[u8; 8 * MAX_REWARD_TOKENS]) -> [u64; MAX_REWARD_TOKENS] {
+   let mut reward_per_block = [0; MAX_REWARD_TOKENS];
+   for (i, value) in reward_per_block.iter_mut().enumerate() {
+      *value = u64::from_le_bytes(*array_ref![src, i * 8, 8]);
+   }
+   reward_per_block
+}
+fn pack_reward_per_block(src: &[u64; MAX_REWARD_TOKENS], dst: &mut [u8; 8 * MAX_REWARD_TOKENS]) {
+   for (i, value) in src.iter().enumerate() {
+      dst[i * 8..(i + 1) * 8].copy_from_slice(&value.to_le_bytes());
+   }
+}
+fn unpack_reward_u128s(src: &[u8; 16 * MAX_REWARD_TOKENS]) -> [u128; MAX_REWARD_TOKENS] {
+   let mut values = [0; MAX_REWARD_TOKENS];
+   for (i, value) in values.iter_mut().enumerate() {
+      *value = u128::from_le_bytes(*array_ref![src, i * 16, 16]);
+   }
+   values
+}
+fn pack_reward_u128s(src: &[u128; MAX_REWARD_TOKENS], dst: &mut [u8; 16 * MAX_REWARD_TOKENS]) {
+   for (i, value) in src.iter().enumerate() {
+      dst[i * 16..(i + 1) * 16].copy_from_slice(&value.to_le_bytes());
+   }
+}
+fn unpack_stake_mint_weights(src: &[u8; 2 * MAX_STAKE_MINTS]) -> [u16; MAX_STAKE_MINTS] {
+   let mut weights = [0; MAX_STAKE_MINTS];
+   for (i, weight) in weights.iter_mut().enumerate() {
+      *weight = u16::from_le_bytes([src[i * 2], src[i * 2 + 1]]);
+   }
+   weights
+}
+fn pack_stake_mint_weights(src: &[u16; MAX_STAKE_MINTS], dst: &mut [u8; 2 * MAX_STAKE_MINTS]) {
+   for (i, weight) in src.iter().enumerate() {
+      dst[i * 2..(i + 1) * 2].copy_from_slice(&weight.to_le_bytes());
+   }
+}
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   /// 1_000 per block over 100..900, x3 over 200..300
+   fn pool_with_bonus() -> StakePool {
+      let mut stake_pool = StakePool::unpack_from_slice(&[0; StakePool::LEN]).unwrap();
+      stake_pool.is_initialized = 1;
+      stake_pool.precision_factor_rank = 12;
+      stake_pool.start_block = 100;
+      stake_pool.end_block = 900;
+      stake_pool.last_reward_block = 100;
+      stake_pool.emission_period = 1;
+      stake_pool.reward_per_block[0] = 1_000;
+      stake_pool.set_bonus_multiplier(3);
+      stake_pool.set_bonus_start_block(200);
+      stake_pool.set_bonus_end_block(300);
+      stake_pool
+   }
+
+   fn token_account(amount: u64) -> TokenAccount {
+      TokenAccount {
+         amount,
+         ..TokenAccount::default()
+      }
+   }
+
+   fn clock(slot: u64) -> Clock {
+      Clock {
+         slot,
+         ..Clock::default()
+      }
+   }
+
+   fn packed(stake_pool: &StakePool) -> Vec<u8> {
+      let mut data = vec![0; StakePool::LEN];
+      stake_pool.pack_into_slice(&mut data);
+      data
+   }
+
+   #[test]
+   fn test_update_pool_twice_in_slot_keeps_state() {
+      let mut stake_pool = pool_with_bonus();
+      let staked = token_account(1_000);
+      let reward = token_account(1_000_000);
+
+      stake_pool.update_pool(&staked, &reward, &clock(250)).unwrap();
+      let updated = packed(&stake_pool);
+      // 100 blocks before the bonus and 50 blocks at x3
+      assert_eq!(stake_pool.total_owed, 250_000);
+
+      stake_pool.update_pool(&staked, &reward, &clock(250)).unwrap();
+      assert_eq!(packed(&stake_pool), updated);
+   }
+
+   #[test]
+   fn test_update_pool_in_updated_slot_expires_bonus() {
+      // accrued past the bonus by an update that left the bonus set
+      let mut stake_pool = pool_with_bonus();
+      stake_pool.last_reward_block = 350;
+      let accrued_token_per_share = stake_pool.accrued_token_per_share;
+
+      stake_pool.update_pool(&token_account(1_000), &token_account(1_000_000), &clock(350)).unwrap();
+      assert!(stake_pool.bonus_start_block.is_none());
+      assert!(stake_pool.bonus_end_block.is_none());
+      assert_eq!(stake_pool.bonus_multiplier, COption::Some(1));
+      assert_eq!(stake_pool.accrued_token_per_share, accrued_token_per_share);
+      assert_eq!(stake_pool.last_reward_block, 350);
+   }
+
+   #[test]
+   fn test_last_reward_block_never_moves_backwards() {
+      let mut stake_pool = pool_with_bonus();
+      let staked = token_account(1_000);
+      let reward = token_account(1_000_000);
+
+      stake_pool.update_pool(&staked, &reward, &clock(500)).unwrap();
+      assert_eq!(stake_pool.last_reward_block, 500);
+      let updated = packed(&stake_pool);
+
+      stake_pool.set_last_reward_block(300);
+      assert_eq!(stake_pool.last_reward_block, 500);
+
+      // a rewound clock, with and without stake
+      stake_pool.update_pool(&staked, &reward, &clock(300)).unwrap();
+      assert_eq!(packed(&stake_pool), updated);
+      stake_pool.update_pool(&token_account(0), &reward, &clock(300)).unwrap();
+      assert_eq!(packed(&stake_pool), updated);
+      stake_pool.update_pool(&staked, &token_account(0), &clock(300)).unwrap();
+      assert_eq!(stake_pool.last_reward_block, 500);
+   }
+
+   #[test]
+   fn test_health_of_consistent_and_inconsistent_pools() {
+      let all = HEALTH_STAKED_COVERED
+         | HEALTH_REWARD_COVERED
+         | HEALTH_BONUS_CONSISTENT
+         | HEALTH_LAST_REWARD_BLOCK
+         | HEALTH_SOLVENT;
+      let mut healthy = pool_with_bonus();
+      healthy.total_staked = 1_000;
+      healthy.is_solvent = 1;
+      let staked = token_account(1_000);
+      // 800 blocks and 100 bonus blocks paying x3
+      let reward = token_account(1_000_000);
+      assert_eq!(healthy.get_health(&staked, &reward).unwrap(), all);
+
+      assert_eq!(
+         healthy.get_health(&token_account(999), &reward).unwrap(),
+         all & !HEALTH_STAKED_COVERED,
+      );
+      assert_eq!(
+         healthy.get_health(&staked, &token_account(999_999)).unwrap(),
+         all & !HEALTH_REWARD_COVERED,
+      );
+
+      let mut stake_pool = healthy;
+      stake_pool.bonus_end_block = COption::None;
+      assert_eq!(stake_pool.get_health(&staked, &reward).unwrap(), all & !HEALTH_BONUS_CONSISTENT);
+      let mut stake_pool = healthy;
+      // the bonus past end_block pays x3 till the end too
+      stake_pool.set_bonus_end_block(1_000);
+      assert_eq!(
+         stake_pool.get_health(&staked, &reward).unwrap(),
+         all & !HEALTH_BONUS_CONSISTENT & !HEALTH_REWARD_COVERED,
+      );
+
+      let mut stake_pool = healthy;
+      stake_pool.last_reward_block = 901;
+      stake_pool.bonus_start_block = COption::None;
+      stake_pool.bonus_end_block = COption::None;
+      assert_eq!(stake_pool.get_health(&staked, &reward).unwrap(), all & !HEALTH_LAST_REWARD_BLOCK);
+
+      let mut stake_pool = healthy;
+      stake_pool.is_solvent = 0;
+      assert_eq!(stake_pool.get_health(&staked, &reward).unwrap(), all & !HEALTH_SOLVENT);
+   }
+
+   #[test]
+   fn test_warmed_up_ramps_over_warmup_slots() {
+      let mut stake_pool = pool_with_bonus();
+      stake_pool.warmup_slots = 100;
+
+      assert_eq!(stake_pool.get_warmed_up(10_000, 200, 200).unwrap(), 0);
+      assert_eq!(stake_pool.get_warmed_up(10_000, 200, 250).unwrap(), 5_000);
+      assert_eq!(stake_pool.get_warmed_up(10_000, 200, 300).unwrap(), 10_000);
+      assert_eq!(stake_pool.get_warmed_up(10_000, 200, 400).unwrap(), 10_000);
+
+      stake_pool.warmup_slots = 0;
+      assert_eq!(stake_pool.get_warmed_up(10_000, 200, 200).unwrap(), 10_000);
+   }
+
+   #[test]
+   fn test_update_pool_carries_accrual_below_one_per_share() {
+      // 1 token of 0 decimals per block over 1_000_000 base units of 18 decimals
+      let mut stake_pool = pool_with_bonus();
+      stake_pool.precision_factor_rank = 3;
+      stake_pool.reward_per_block[0] = 1;
+      stake_pool.last_reward_block = 1_000;
+      stake_pool.end_block = 2_000;
+      let staked = token_account(1_000_000);
+      let reward = token_account(1_000);
+
+      for slot in 1_001..=1_500 {
+         stake_pool.update_pool(&staked, &reward, &clock(slot)).unwrap();
+      }
+      assert_eq!(stake_pool.accrued_token_per_share, 0);
+      assert_eq!(stake_pool.pending_accrual, 500_000);
+
+      for slot in 1_501..=2_000 {
+         stake_pool.update_pool(&staked, &reward, &clock(slot)).unwrap();
+      }
+      assert_eq!(stake_pool.accrued_token_per_share, 1);
+      assert_eq!(stake_pool.pending_accrual, 0);
+      assert_eq!(get_pending(1_000_000, stake_pool.accrued_token_per_share, 3, 0, false).unwrap(), 1_000);
+   }
+
+   #[test]
+   fn test_reward_per_day_ui_ignores_bonus() {
+      let mut stake_pool = pool_with_bonus();
+      // 1 token per block with 3 decimals
+      assert_eq!(stake_pool.reward_per_day_ui(3).unwrap(), 216_000 * 1_000_000);
+
+      stake_pool.emission_period = 100;
+      assert_eq!(stake_pool.reward_per_day_ui(3).unwrap(), 2_160 * 1_000_000);
+   }
+
+   #[test]
+   fn test_bonus_active_around_the_window() {
+      let mut stake_pool = pool_with_bonus();
+      assert!(!stake_pool.bonus_active(100));
+      assert!(!stake_pool.bonus_active(199));
+      assert!(stake_pool.bonus_active(200));
+      assert!(stake_pool.bonus_active(299));
+      // bonus_end_block is the first block without the bonus, as in get_multiplier
+      assert!(!stake_pool.bonus_active(300));
+      assert!(!stake_pool.bonus_active(800));
+      assert_eq!(stake_pool.get_multiplier(299, 300), 3);
+      assert_eq!(stake_pool.get_multiplier(300, 301), 1);
+
+      stake_pool.bonus_start_block = COption::None;
+      stake_pool.bonus_end_block = COption::None;
+      assert!(!stake_pool.bonus_active(250));
+
+      // no bonus without a multiplier above 1
+      let mut stake_pool = pool_with_bonus();
+      stake_pool.set_bonus_multiplier(1);
+      assert!(!stake_pool.bonus_active(250));
+   }
+   #[test]
+   fn test_is_valid_pool_of_valid_uninitialized_and_garbage_data() {
+      let mut stake_pool = pool_with_bonus();
+      stake_pool.n_reward_tokens = 1;
+      assert!(is_valid_pool(&packed(&stake_pool)));
+
+      assert!(!is_valid_pool(&[0; StakePool::LEN]));
+      assert!(!is_valid_pool(&[0xab; StakePool::LEN]));
+      assert!(!is_valid_pool(&packed(&stake_pool)[..StakePool::LEN - 1]));
+      assert!(!is_valid_pool(&[]));
+
+      // initialized, with an implausible schedule
+      stake_pool.end_block = stake_pool.start_block;
+      assert!(!is_valid_pool(&packed(&stake_pool)));
+   }
+
+   #[test]
+   fn test_accrual_remainder_carry_converges_over_many_blocks() {
+      // 1_000 per block over 100..900 for 7 staked, 10 * 1_000 / 7 leaves a remainder every block
+      let mut carried = pool_with_bonus();
+      carried.bonus_start_block = COption::None;
+      carried.bonus_end_block = COption::None;
+      carried.set_bonus_multiplier(1);
+      carried.precision_factor_rank = 1;
+      let mut dropped = carried;
+      let staked = token_account(7);
+      let reward = token_account(1_000_000);
+
+      for slot in 101..=900 {
+         carried.update_pool(&staked, &reward, &clock(slot)).unwrap();
+         dropped.update_pool(&staked, &reward, &clock(slot)).unwrap();
+         dropped.pending_accrual = 0;
+      }
+
+      let distributed = |stake_pool: &StakePool| stake_pool.accrued_token_per_share * 7 / 10;
+      assert_eq!(carried.total_owed, 800_000);
+      // short of the emission by less than a token, instead of 2 / 7 of a token each block
+      assert_eq!(distributed(&carried), 799_999);
+      assert_eq!(distributed(&dropped), 799_680);
+   }
+}
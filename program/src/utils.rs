@@ -1,4 +1,5 @@
 use std::convert::TryFrom;
+use std::str::FromStr;
 use solana_program::{
     account_info::{
         AccountInfo
@@ -7,18 +8,41 @@ use solana_program::{
         PrintProgramError,
     },
     entrypoint::ProgramResult, 
-    program_pack::Pack, 
+    program_pack::{
+        Pack,
+        IsInitialized,
+    },
+    msg,
     pubkey::Pubkey, 
+    program_error::ProgramError,
+    system_program,
+    sysvar,
+    bpf_loader_upgradeable,
 };
 use spl_token::{
     state::Account as TokenAccount,
 };
 use crate::{
-    state::StakePool,
+    state::{
+        StakePool,
+        MAX_REWARD_TOKENS,
+    },
     error::StakingError, 
+    pda::{
+        seeds,
+        user_info_address,
+        user_info_address_with_bump,
+        stake_mint_token_account_address,
+        token_account_authority_address,
+        staked_token_account_address,
+        reward_token_account_address,
+        migrated_reward_token_account_address,
+        wallet_pool_address,
+        master_staking_address,
+    },
     id as this_program_id,
-    ADD_SEED_TOKEN_ACCOUNT_AUTHORITY,
-    BUMP_SEED_TOKEN_ACCOUNT_AUTHORITY,
+    REWARD_SWAP_PROGRAMS,
+    PRICE_ORACLES,
 };
 
 pub fn validate_stake_pool(
@@ -35,6 +59,30 @@ pub fn validate_stake_pool(
     Ok(())
 }
 
+/// Setup of the program and the pool happened. The authority PDA is created without lamports and
+/// doesn't persist, so it is recognized by its address
+pub fn validate_initialized_pool(
+    stake_pool_info: &AccountInfo,
+    pool_token_account_authority_info: &AccountInfo,
+) -> ProgramResult {
+    if *stake_pool_info.owner != this_program_id()
+        || stake_pool_info.data_len() != StakePool::LEN
+        || !StakePool::unpack_unchecked(&stake_pool_info.data.borrow())?.is_initialized()
+    {
+        msg!("StakePool {} is not initialized", stake_pool_info.key);
+        StakingError::NotInitialized.print::<StakingError>();
+        return Err(StakingError::NotInitialized.into());
+    }
+
+    if token_account_authority_address()? != *pool_token_account_authority_info.key {
+        msg!("{} is not the PDA authority for token-accounts", pool_token_account_authority_info.key);
+        StakingError::NotInitialized.print::<StakingError>();
+        return Err(StakingError::NotInitialized.into());
+    }
+
+    Ok(())
+}
+
 pub fn validate_pool_token_account(
     pool_token_account_info: &AccountInfo,
 ) -> ProgramResult {
@@ -42,7 +90,7 @@ pub fn validate_pool_token_account(
         &pool_token_account_info.data.borrow(),
     )?;
     let pool_token_account_authority_pubkey = Pubkey::create_program_address(
-        &[ADD_SEED_TOKEN_ACCOUNT_AUTHORITY.as_bytes(), &[BUMP_SEED_TOKEN_ACCOUNT_AUTHORITY]],
+        &seeds::token_account_authority(),
         &this_program_id(),
     )?;
 
@@ -54,14 +102,69 @@ pub fn validate_pool_token_account(
     Ok(())
 }
 
+/// Token-account of a position receiving tokens. UserInfo is keyed by the token-account address,
+/// so a closed token-account recreated at the same address (the same ATA) gets the position back
+pub fn unpack_destination_token_account(
+    token_account_info: &AccountInfo,
+) -> Result<TokenAccount, ProgramError> {
+    match TokenAccount::unpack(&token_account_info.data.borrow()) {
+        Ok(token_account) => Ok(token_account),
+        Err(_) => {
+            msg!(
+                "Token-account {} is closed. Recreate it at the same address to access the position",
+                token_account_info.key,
+            );
+            StakingError::DestinationClosed.print::<StakingError>();
+            Err(StakingError::DestinationClosed.into())
+        },
+    }
+}
+
+/// User token-account must not be a vault of any pool. All vaults are owned by the PDA authority
+pub fn validate_user_token_account(
+    token_account_info: &AccountInfo,
+    vaults_infos: &[&AccountInfo],
+) -> ProgramResult {
+    let token_account = TokenAccount::unpack(
+        &token_account_info.data.borrow(),
+    )?;
+    let pool_token_account_authority_pubkey = Pubkey::create_program_address(
+        &seeds::token_account_authority(),
+        &this_program_id(),
+    )?;
+
+    if token_account.owner == pool_token_account_authority_pubkey ||
+       vaults_infos.iter().any(|vault_info| vault_info.key == token_account_info.key) {
+            StakingError::InvalidDestination.print::<StakingError>();
+            return Err(StakingError::InvalidDestination.into());
+    }
+
+    Ok(())
+}
+
+/// Staked tokens of StakePool.mint are kept in the pool vault, other stake mints have own vaults.
+/// Every vault has the same authority, so the address is checked for StakePool.mint too
+pub fn validate_staked_token_account(
+    stake_pool: &StakePool,
+    pool_token_account_staked_info: &AccountInfo,
+    stake_mint_key: &Pubkey,
+) -> ProgramResult {
+    if !is_staked_vault(stake_pool, pool_token_account_staked_info.key, stake_mint_key) {
+        StakingError::PoolTokenAccountMissmatch.print::<StakingError>();
+        return Err(StakingError::PoolTokenAccountMissmatch.into());
+    }
+
+    Ok(())
+}
+
 pub fn validate_user_state(
     user_state_info: &AccountInfo,
     stake_pool_info: &AccountInfo,
     token_account_info: &AccountInfo,
 ) -> ProgramResult {
-    let (user_state_pubkey, _) = Pubkey::find_program_address(
-        &[stake_pool_info.key.as_ref(), token_account_info.key.as_ref()],
-        &this_program_id(),
+    let (user_state_pubkey, _) = user_info_address(
+        stake_pool_info.key,
+        token_account_info.key,
     );
 
     if user_state_pubkey != *user_state_info.key {
@@ -72,20 +175,379 @@ pub fn validate_user_state(
     Ok(())
 }
 
+/// Same check as validate_user_state with the bump passed by the caller
+pub fn validate_user_state_with_bump(
+    user_state_info: &AccountInfo,
+    stake_pool_info: &AccountInfo,
+    token_account_info: &AccountInfo,
+    bump_seed: u8,
+) -> ProgramResult {
+    let user_state_pubkey = user_info_address_with_bump(
+        stake_pool_info.key,
+        token_account_info.key,
+        bump_seed,
+    );
+
+    match user_state_pubkey {
+        Ok(v) if v == *user_state_info.key => Ok(()),
+        _ => {
+            StakingError::UserInfoMissmatch.print::<StakingError>();
+            Err(StakingError::UserInfoMissmatch.into())
+        },
+    }
+}
+
+fn check_account(
+    accounts: &[AccountInfo],
+    index: usize,
+    valid: bool,
+    expected: &str,
+) -> ProgramResult {
+    if !valid {
+        msg!("Account {} ({}) is not {}", index, accounts[index].key, expected);
+        StakingError::InvalidAccountList.print::<StakingError>();
+        return Err(StakingError::InvalidAccountList.into());
+    }
+
+    Ok(())
+}
+
+fn check_account_count(
+    accounts: &[AccountInfo],
+    expected: usize,
+) -> ProgramResult {
+    if accounts.len() < expected {
+        msg!("Expected at least {} accounts, got {}", expected, accounts.len());
+        StakingError::InvalidAccountList.print::<StakingError>();
+        return Err(StakingError::InvalidAccountList.into());
+    }
+
+    Ok(())
+}
+
+/// StakePool of the account list, read without trusting any other account
+fn unpack_listed_stake_pool(
+    accounts: &[AccountInfo],
+    index: usize,
+) -> Result<StakePool, ProgramError> {
+    let stake_pool_info = &accounts[index];
+    check_account(
+        accounts,
+        index,
+        *stake_pool_info.owner == this_program_id() && stake_pool_info.data_len() == StakePool::LEN,
+        "StakePool",
+    )?;
+
+    let stake_pool = StakePool::unpack(&stake_pool_info.data.borrow());
+    check_account(accounts, index, stake_pool.is_ok(), "an initialized StakePool")?;
+
+    stake_pool
+}
+
+/// Staked vault of `stake_mint`, the pool vault after a MigrateRewardMint included
+pub fn is_staked_vault(
+    stake_pool: &StakePool,
+    vault_key: &Pubkey,
+    stake_mint_key: &Pubkey,
+) -> bool {
+    let pool_index = stake_pool.pool_index;
+
+    *vault_key == stake_mint_token_account_address(pool_index, stake_mint_key).0
+        || (*stake_mint_key == stake_pool.mint && *vault_key == staked_token_account_address(pool_index).0)
+}
+
+/// Reward vault of the pool, the vault after a MigrateRewardMint included
+pub fn is_reward_vault(
+    stake_pool: &StakePool,
+    vault_key: &Pubkey,
+) -> bool {
+    let pool_index = stake_pool.pool_index;
+
+    *vault_key == reward_token_account_address(pool_index).0
+        || *vault_key == migrated_reward_token_account_address(pool_index, &stake_pool.mint).0
+}
+
+/// Checks the fixed accounts of Deposit before anything is processed, so a misordered list fails
+/// naming the index of the wrong account instead of deep inside a CPI
+pub fn validate_deposit_accounts(
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    check_account_count(accounts, 13)?;
+
+    check_account(accounts, 0, accounts[0].is_signer, "a signer")?;
+    for &index in [1, 3, 5, 6, 7, 8].iter() {
+        check_account(accounts, index, accounts[index].is_writable, "writable")?;
+    }
+
+    // before the listed pool is unpacked, so a missing setup isn't reported as a wrong account
+    validate_initialized_pool(&accounts[3], &accounts[4])?;
+
+    let stake_pool = unpack_listed_stake_pool(accounts, 3)?;
+    let mint_key = accounts[2].key;
+    if *mint_key != stake_pool.mint {
+        // token-account for reward tokens
+        check_account_count(accounts, 14)?;
+        check_account(accounts, 13, accounts[13].is_writable, "writable")?;
+    }
+
+    check_account(accounts, 4, token_account_authority_address()? == *accounts[4].key, "the PDA authority")?;
+    check_account(accounts, 5, is_staked_vault(&stake_pool, accounts[5].key, mint_key), "the staked vault of the mint")?;
+    check_account(accounts, 6, is_reward_vault(&stake_pool, accounts[6].key), "the reward vault")?;
+    check_account(
+        accounts,
+        7,
+        wallet_pool_address(stake_pool.pool_index).0 == *accounts[7].key,
+        "the PDA wallet stake pool",
+    )?;
+    check_account(
+        accounts,
+        8,
+        user_info_address(accounts[3].key, accounts[1].key).0 == *accounts[8].key,
+        "the UserInfo of the token-account",
+    )?;
+    check_account(accounts, 9, *accounts[9].key == sysvar::rent::id(), "the rent sysvar")?;
+    check_account(accounts, 10, *accounts[10].key == sysvar::clock::id(), "the clock sysvar")?;
+    check_account(accounts, 11, *accounts[11].key == system_program::id(), "the system program")?;
+    check_account(accounts, 12, *accounts[12].key == spl_token::id(), "the token program")?;
+
+    Ok(())
+}
+
+/// As validate_deposit_accounts, for Withdraw and WithdrawDelegated
+pub fn validate_withdraw_accounts(
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    check_withdraw_accounts(accounts, 0)
+}
+
+/// As validate_withdraw_accounts, for MintReceipt. Its receipt mint and receipt token-account are
+/// accounts 2 and 3, the accounts of Withdraw from 2 on follow them
+pub fn validate_mint_receipt_accounts(
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    check_withdraw_accounts(accounts, 2)?;
+
+    check_account(accounts, 2, accounts[2].is_writable, "writable")?;
+    check_account(accounts, 3, accounts[3].is_writable, "writable")?;
+
+    Ok(())
+}
+
+/// Accounts of Withdraw with `shift` more accounts inserted after the token-account
+fn check_withdraw_accounts(
+    accounts: &[AccountInfo],
+    shift: usize,
+) -> ProgramResult {
+    let at = |index: usize| if index < 2 { index } else { index + shift };
+
+    check_account_count(accounts, at(9))?;
+
+    check_account(accounts, 0, accounts[0].is_signer, "a signer")?;
+    for &index in [1, 2, 4, 5, 6].iter() {
+        check_account(accounts, at(index), accounts[at(index)].is_writable, "writable")?;
+    }
+
+    let stake_pool = unpack_listed_stake_pool(accounts, at(2))?;
+
+    check_account(accounts, at(3), token_account_authority_address()? == *accounts[at(3)].key, "the PDA authority")?;
+    check_account(accounts, at(5), is_reward_vault(&stake_pool, accounts[at(5)].key), "the reward vault")?;
+    check_account(
+        accounts,
+        at(6),
+        user_info_address(accounts[at(2)].key, accounts[1].key).0 == *accounts[at(6)].key,
+        "the UserInfo of the token-account",
+    )?;
+    check_account(accounts, at(7), *accounts[at(7)].key == sysvar::clock::id(), "the clock sysvar")?;
+    check_account(accounts, at(8), *accounts[at(8)].key == spl_token::id(), "the token program")?;
+
+    // A closed token-account is reported by unpack_destination_token_account
+    let mint_key = match TokenAccount::unpack(&accounts[1].data.borrow()) {
+        Ok(token_account) => token_account.mint,
+        Err(_) => return Ok(()),
+    };
+    check_account(
+        accounts,
+        at(4),
+        is_staked_vault(&stake_pool, accounts[at(4)].key, &mint_key),
+        "the staked vault of the mint",
+    )?;
+    if mint_key != stake_pool.mint {
+        // token-account for reward tokens
+        check_account_count(accounts, at(10))?;
+        check_account(accounts, at(9), accounts[at(9)].is_writable, "writable")?;
+    }
+
+    Ok(())
+}
+
+/// Disaster-recovery instructions are signed by the upgrade authority of this program, see ReinitPoolState
+pub fn validate_upgrade_authority(
+    program_data_info: &AccountInfo,
+    authority_info: &AccountInfo,
+) -> ProgramResult {
+    let (program_data_pubkey, _) = Pubkey::find_program_address(
+        &[this_program_id().as_ref()],
+        &bpf_loader_upgradeable::id(),
+    );
+    if program_data_pubkey != *program_data_info.key || *program_data_info.owner != bpf_loader_upgradeable::id() {
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    // UpgradeableLoaderState::ProgramData is u32 tag 3, u64 slot, Option<Pubkey> upgrade authority
+    let data = program_data_info.data.borrow();
+    let is_upgrade_authority = data.len() >= 45
+        && data[..4] == 3_u32.to_le_bytes()
+        && data[12] == 1
+        && data[13..45] == authority_info.key.as_ref()[..];
+    if !authority_info.is_signer || !is_upgrade_authority {
+        StakingError::NotUpgradeAuthority.print::<StakingError>();
+        return Err(StakingError::NotUpgradeAuthority.into());
+    }
+
+    Ok(())
+}
+
+/// Instructions paying rewards may end with PDA master-staking and the protocol fee token-account,
+/// see SetProtocolFee. Returns the other accounts and these two, none if the second to last account
+/// isn't PDA master-staking. Clients of pools without a protocol fee don't pass them
+pub fn split_protocol_fee_accounts<'b, 'a>(
+    accounts: &'b [AccountInfo<'a>],
+) -> Result<(&'b [AccountInfo<'a>], &'b [AccountInfo<'a>]), ProgramError> {
+    if accounts.len() >= 2 && master_staking_address()? == *accounts[accounts.len() - 2].key {
+        return Ok(accounts.split_at(accounts.len() - 2));
+    }
+
+    Ok((accounts, &[]))
+}
+
+/// Initialize ends with 4 accounts per reward token after the first, see StakingInstruction::Initialize.
+/// Returns the other accounts and these. A pool without reward tokens would lock stake for nothing
+pub fn split_reward_token_accounts<'b, 'a>(
+    accounts: &'b [AccountInfo<'a>],
+    n_reward_tokens: u8,
+) -> Result<(&'b [AccountInfo<'a>], &'b [AccountInfo<'a>]), ProgramError> {
+    if n_reward_tokens == 0 || n_reward_tokens as usize > MAX_REWARD_TOKENS {
+        StakingError::InvalidRewardTokenCount.print::<StakingError>();
+        return Err(StakingError::InvalidRewardTokenCount.into());
+    }
+    let n_extra_reward_tokens = n_reward_tokens as usize - 1;
+    let n_accounts = 4 * n_extra_reward_tokens;
+    if accounts.len() < n_accounts {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
+
+    Ok(accounts.split_at(accounts.len() - n_accounts))
+}
+
+/// A crafted account deserializing as Rent could lower the minimum balance of created accounts
+pub fn validate_rent_sysvar(
+    rent_info: &AccountInfo,
+) -> ProgramResult {
+    if *rent_info.key != sysvar::rent::id() {
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    Ok(())
+}
+
+/// Reward math depends on the slot, a spoofed clock could accrue rewards of future blocks
+pub fn validate_clock_sysvar(
+    clock_info: &AccountInfo,
+) -> ProgramResult {
+    if *clock_info.key != sysvar::clock::id() {
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    Ok(())
+}
+
+/// Guarded owner instructions must be top-level instructions of this program, not a CPI of another
+/// program. Guarded are SetUserFrozen, MigrateRewardMint, SetAccrualMode, ClosePool,
+/// SetEmergencyWithdrawEnabled and CancelBonus. They take the instructions sysvar as the last account
+pub fn validate_not_cpi(
+    instructions_info: &AccountInfo,
+) -> ProgramResult {
+    if *instructions_info.key != sysvar::instructions::id() {
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let current_index = sysvar::instructions::load_current_index_checked(instructions_info)?;
+    let current_instruction = sysvar::instructions::load_instruction_at_checked(
+        current_index as usize,
+        instructions_info,
+    )?;
+    if current_instruction.program_id != this_program_id() {
+        StakingError::CpiNotAllowed.print::<StakingError>();
+        return Err(StakingError::CpiNotAllowed.into());
+    }
+
+    Ok(())
+}
+
+pub fn validate_reward_swap_program(
+    swap_program_key: &Pubkey,
+) -> ProgramResult {
+    let allowed = REWARD_SWAP_PROGRAMS
+        .iter()
+        .filter_map(|key| Pubkey::from_str(key).ok())
+        .any(|key| key == *swap_program_key);
+
+    if !allowed {
+        StakingError::RewardSwapProgramNotAllowed.print::<StakingError>();
+        return Err(StakingError::RewardSwapProgramNotAllowed.into());
+    }
+
+    Ok(())
+}
+
+pub fn is_price_oracle(
+    oracle_key: &Pubkey,
+) -> bool {
+    PRICE_ORACLES
+        .iter()
+        .filter_map(|key| Pubkey::from_str(key).ok())
+        .any(|key| key == *oracle_key)
+}
+
+/// Price of one staked token read from an account of PRICE_ORACLES
+pub fn get_oracle_price(
+    oracle_info: &AccountInfo,
+) -> Result<u64, ProgramError> {
+    if !is_price_oracle(oracle_info.key) {
+        StakingError::PriceOracleNotAllowed.print::<StakingError>();
+        return Err(StakingError::PriceOracleNotAllowed.into());
+    }
+
+    let data = oracle_info.data.borrow();
+    if data.len() < 8 {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    let mut price = [0_u8; 8];
+    price.copy_from_slice(&data[..8]);
+
+    Ok(u64::from_le_bytes(price))
+}
+
+/// reward_debt is kept multiplied by the precision factor, so the only truncation is the payout itself
 pub fn get_pending(
     current_amount: u64,
     accrued_token_per_share: u128,
     precision_factor_rank: u8,
-    reward_debt: u64,
+    reward_debt: u128,
+    round_up: bool, // true gives the dust of the division to the user
 ) -> Result<u64, StakingError> {
     let precision_factor = get_precision_factor(precision_factor_rank)?;
+    let rounding = if round_up { precision_factor as u128 - 1 } else { 0 };
 
     let pending = (current_amount as u128) 
         .checked_mul(accrued_token_per_share)
         .ok_or(StakingError::Overflow)?
-        .checked_div(precision_factor as u128)
+        .checked_sub(reward_debt)
+        .ok_or(StakingError::Overflow)?
+        .checked_add(rounding)
         .ok_or(StakingError::Overflow)?
-        .checked_sub(reward_debt as u128)
+        .checked_div(precision_factor as u128)
         .ok_or(StakingError::Overflow)?;
     
     match u64::try_from(pending) {
@@ -94,18 +556,40 @@ pub fn get_pending(
     }
 }
 
-pub fn get_reward_debt(
-    user_amount: u64,
+/// Accrued reward of the position left after paying `pending`, multiplied by the precision factor.
+/// It is less than one token, the dust of the division in get_pending
+pub fn get_unpaid(
+    current_amount: u64,
     accrued_token_per_share: u128,
     precision_factor_rank: u8,
-) -> Result<u64, StakingError> {
+    reward_debt: u128,
+    pending: u64,
+) -> Result<u128, StakingError> {
     let precision_factor = get_precision_factor(precision_factor_rank)?;
 
+    let accrued = (current_amount as u128)
+        .checked_mul(accrued_token_per_share)
+        .ok_or(StakingError::Overflow)?
+        .checked_sub(reward_debt)
+        .ok_or(StakingError::Overflow)?;
+    let paid = (pending as u128)
+        .checked_mul(precision_factor as u128)
+        .ok_or(StakingError::Overflow)?;
+
+    // Rounding up pays more than accrued
+    Ok(accrued.saturating_sub(paid))
+}
+
+/// `unpaid` is carried to the next payout, see get_unpaid
+pub fn get_reward_debt(
+    user_amount: u64,
+    accrued_token_per_share: u128,
+    unpaid: u128,
+) -> Result<u128, StakingError> {
     let reward_debt = (user_amount as u128)
         .checked_mul(accrued_token_per_share)
         .ok_or(StakingError::Overflow)?
-        .checked_div(precision_factor as u128)
-        .ok_or(StakingError::Overflow)? as u64;
+        .saturating_sub(unpaid);
 
     Ok(reward_debt)
 }
@@ -118,4 +602,93 @@ pub fn get_precision_factor(
         .ok_or(StakingError::Overflow)?;
 
     Ok(precision_factor)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_user_state_accepts_deposit_address() {
+        let stake_pool_key = Pubkey::new_from_array([1; 32]);
+        let token_account_key = Pubkey::new_from_array([2; 32]);
+
+        // process_deposit creates UserInfo signing with these seeds
+        let (_, bump_seed_user_state) = user_info_address(&stake_pool_key, &token_account_key);
+        let user_state_key = Pubkey::create_program_address(
+            &[stake_pool_key.as_ref(), token_account_key.as_ref(), &[bump_seed_user_state]],
+            &crate::id(),
+        )
+        .unwrap();
+
+        let owner = crate::id();
+        let (mut user_state_lamports, mut stake_pool_lamports, mut token_account_lamports) = (0, 0, 0);
+        let (mut user_state_data, mut stake_pool_data, mut token_account_data) = ([], [], []);
+        let user_state_info = AccountInfo::new(&user_state_key, false, false, &mut user_state_lamports, &mut user_state_data, &owner, false, 0);
+        let stake_pool_info = AccountInfo::new(&stake_pool_key, false, false, &mut stake_pool_lamports, &mut stake_pool_data, &owner, false, 0);
+        let token_account_info = AccountInfo::new(&token_account_key, false, false, &mut token_account_lamports, &mut token_account_data, &owner, false, 0);
+
+        assert_eq!(validate_user_state(&user_state_info, &stake_pool_info, &token_account_info), Ok(()));
+        assert_eq!(
+            validate_user_state(&user_state_info, &token_account_info, &stake_pool_info),
+            Err(StakingError::UserInfoMissmatch.into()),
+        );
+    }
+
+    #[test]
+    fn test_validate_user_state_with_bump_matches_derivation() {
+        let stake_pool_key = Pubkey::new_from_array([1; 32]);
+        let token_account_key = Pubkey::new_from_array([2; 32]);
+        let (user_state_key, bump_seed) = user_info_address(&stake_pool_key, &token_account_key);
+        assert_eq!(user_info_address_with_bump(&stake_pool_key, &token_account_key, bump_seed), Ok(user_state_key));
+
+        let owner = crate::id();
+        let (mut user_state_lamports, mut stake_pool_lamports, mut token_account_lamports) = (0, 0, 0);
+        let (mut user_state_data, mut stake_pool_data, mut token_account_data) = ([], [], []);
+        let user_state_info = AccountInfo::new(&user_state_key, false, false, &mut user_state_lamports, &mut user_state_data, &owner, false, 0);
+        let stake_pool_info = AccountInfo::new(&stake_pool_key, false, false, &mut stake_pool_lamports, &mut stake_pool_data, &owner, false, 0);
+        let token_account_info = AccountInfo::new(&token_account_key, false, false, &mut token_account_lamports, &mut token_account_data, &owner, false, 0);
+
+        assert_eq!(validate_user_state(&user_state_info, &stake_pool_info, &token_account_info), Ok(()));
+        assert_eq!(
+            validate_user_state_with_bump(&user_state_info, &stake_pool_info, &token_account_info, bump_seed),
+            Ok(()),
+        );
+        // another bump gives another address or none
+        assert_eq!(
+            validate_user_state_with_bump(&user_state_info, &stake_pool_info, &token_account_info, bump_seed.wrapping_sub(1)),
+            Err(StakingError::UserInfoMissmatch.into()),
+        );
+    }
+
+    #[test]
+    fn test_reward_debt_keeps_dust_of_changing_position() {
+        let precision_factor = get_precision_factor(12).unwrap() as u128;
+        let accrued_per_step = 123_456_789_123;
+        let mut accrued_token_per_share = 0;
+        let mut amount = 1_000_000_000_000;
+        let (mut reward_debt, mut paid) = (0, 0);
+        // reward_debt truncated to tokens, as a u64 reward_debt was
+        let (mut truncated_reward_debt, mut truncated_paid) = (0, 0);
+        let mut accrued = 0;
+
+        // a large position harvested and resized every step
+        for step in 0..1_000_u64 {
+            accrued_token_per_share += accrued_per_step;
+            accrued += amount as u128 * accrued_per_step;
+
+            let pending = get_pending(amount, accrued_token_per_share, 12, reward_debt, false).unwrap();
+            let unpaid = get_unpaid(amount, accrued_token_per_share, 12, reward_debt, pending).unwrap();
+            paid += pending as u128;
+            truncated_paid += amount as u128 * accrued_token_per_share / precision_factor - truncated_reward_debt;
+
+            amount = 1_000_000_000_000 + step * 7_919 % 10_007;
+            reward_debt = get_reward_debt(amount, accrued_token_per_share, unpaid).unwrap();
+            truncated_reward_debt = amount as u128 * accrued_token_per_share / precision_factor;
+        }
+
+        assert_eq!(paid, accrued / precision_factor);
+        // the dust of every resize is lost or paid twice
+        assert_eq!(truncated_paid, accrued / precision_factor + 19);
+    }
+}
@@ -0,0 +1,163 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use solana_program::{
+    instruction::{
+        AccountMeta,
+        Instruction,
+    },
+    sysvar,
+};
+use solana_program_test::*;
+use staking_program::{
+    error::StakingError,
+    events::{
+        BonusPreview,
+        ConfigChangedEvent,
+    },
+    instruction::StakingInstruction,
+};
+
+#[tokio::test]
+async fn test_bonus_overspending_vault_is_rejected() {
+    let mut env = TestEnv::new().await;
+    let pool = env.create_pool(&PoolConfig::default()).await; // 1_000 per block over 100..1_100
+    // x3 over 200..300 moves end_block to 900, the schedule still emits 1_000_000
+    env.set_balance(&pool.reward_vault(), 900_000).await;
+
+    let result = env.process(&[set_bonus_time(&pool, 3, 200, 300)], &[&pool.owner]).await;
+    assert_eq!(result.unwrap_err(), staking_error(StakingError::InsufficientRewardFunds));
+    let stake_pool = env.stake_pool(&pool).await;
+    assert_eq!(stake_pool.end_block, 1_100);
+    assert!(stake_pool.bonus_end_block.is_none());
+
+    env.set_balance(&pool.reward_vault(), 1_000_000).await;
+    env.process(&[set_bonus_time(&pool, 3, 200, 300)], &[&pool.owner]).await.unwrap();
+    assert_eq!(env.stake_pool(&pool).await.end_block, 900);
+}
+
+#[tokio::test]
+async fn test_set_bonus_time_emits_config_changes() {
+    let mut env = TestEnv::new().await;
+    let pool = env.create_pool(&PoolConfig::default()).await; // 1_000 per block over 100..1_100
+
+    let (result, logs) = env.process_with_logs(&[set_bonus_time(&pool, 3, 200, 300)], &[&pool.owner]).await;
+    result.unwrap();
+    let changes: Vec<_> = events::<ConfigChangedEvent>(&logs)
+        .into_iter()
+        .map(|event| {
+            assert_eq!(event.pool_index, pool.index);
+            (format!("{:?}", event.field), event.old_value, event.new_value)
+        })
+        .collect();
+    assert_eq!(
+        changes,
+        vec![
+            ("BonusMultiplier".to_string(), 1, 3),
+            ("BonusStartBlock".to_string(), 0, 200),
+            ("BonusEndBlock".to_string(), 0, 300),
+            ("EndBlock".to_string(), 1_100, 900),
+        ],
+    );
+}
+
+#[tokio::test]
+async fn test_bonus_overflowing_extra_blocks_is_clean_error() {
+    let mut env = TestEnv::new().await;
+    let pool = env.create_pool(&PoolConfig::default()).await;
+
+    // (u64::MAX - 200) * 254 extra blocks
+    let result = env.process(&[set_bonus_time(&pool, 255, 200, u64::MAX)], &[&pool.owner]).await;
+    assert_eq!(result.unwrap_err(), staking_error(StakingError::Overflow));
+    // the extra blocks of multiplier 0 underflow
+    let result = env.process(&[set_bonus_time(&pool, 0, 200, 300)], &[&pool.owner]).await;
+    assert_eq!(result.unwrap_err(), staking_error(StakingError::Overflow));
+    assert_eq!(env.stake_pool(&pool).await.end_block, 1_100);
+}
+
+fn log_bonus_preview(pool: &Pool, bonus_multiplier: u8, bonus_start_block: u64, bonus_end_block: u64) -> Instruction {
+    instruction(
+        StakingInstruction::LogBonusPreview {
+            bonus_multiplier,
+            bonus_start_block,
+            bonus_end_block,
+        },
+        vec![
+            AccountMeta::new_readonly(pool.key(), false),
+            AccountMeta::new_readonly(pool.staked_vault(), false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(pool.reward_vault(), false),
+        ],
+    )
+}
+
+async fn bonus_preview(env: &mut TestEnv, pool: &Pool, bonus_multiplier: u8, bonus_start_block: u64, bonus_end_block: u64) -> BonusPreview {
+    let (result, logs) = env.process_with_logs(
+        &[log_bonus_preview(pool, bonus_multiplier, bonus_start_block, bonus_end_block)],
+        &[],
+    ).await;
+    result.unwrap();
+
+    events::<BonusPreview>(&logs).remove(0)
+}
+
+#[tokio::test]
+async fn test_bonus_preview_matches_set_bonus_time() {
+    let mut env = TestEnv::new().await;
+    let pool = env.create_pool(&PoolConfig::default()).await; // 1_000 per block over 100..1_100
+
+    // x3 over 200..300 needs the whole vault
+    env.set_balance(&pool.reward_vault(), 900_000).await;
+    let preview = bonus_preview(&mut env, &pool, 3, 200, 300).await;
+    assert!(!preview.feasible);
+    assert_eq!(preview.end_block, 1_100);
+    assert!(env.stake_pool(&pool).await.bonus_end_block.is_none());
+
+    env.set_balance(&pool.reward_vault(), 1_000_000).await;
+    // x2 over 200..900 is cut at the shortened end_block 400
+    for (bonus_multiplier, bonus_start_block, bonus_end_block) in [(3, 200, 300), (2, 200, 900)] {
+        let preview = bonus_preview(&mut env, &pool, bonus_multiplier, bonus_start_block, bonus_end_block).await;
+        assert!(preview.feasible);
+        assert_eq!(preview.pool_index, pool.index);
+        assert_eq!(preview.bonus_multiplier, bonus_multiplier);
+        assert_eq!(preview.bonus_start_block, bonus_start_block);
+        assert_eq!(env.stake_pool(&pool).await.end_block, 1_100);
+
+        let stake_pool = env.stake_pool(&pool).await;
+        env.process(&[set_bonus_time(&pool, bonus_multiplier, bonus_start_block, bonus_end_block)], &[&pool.owner]).await.unwrap();
+        let applied = env.stake_pool(&pool).await;
+        assert_eq!(preview.end_block, applied.end_block);
+        assert_eq!(Some(preview.bonus_end_block), Option::from(applied.bonus_end_block));
+
+        // the next bonus is previewed on the same pool
+        env.set_stake_pool(&pool, stake_pool).await;
+    }
+}
+
+#[tokio::test]
+async fn test_cancel_future_bonus_restores_end_block() {
+    let mut env = TestEnv::new().await;
+    let pool = env.create_pool(&PoolConfig::default()).await; // 1_000 per block over 100..1_100
+
+    env.warp_to(150).await;
+    env.process(&[set_bonus_time(&pool, 3, 200, 300)], &[&pool.owner]).await.unwrap();
+    assert_eq!(env.stake_pool(&pool).await.end_block, 900);
+
+    env.process(&[cancel_bonus(&pool)], &[&pool.owner]).await.unwrap();
+    let stake_pool = env.stake_pool(&pool).await;
+    assert_eq!(stake_pool.end_block, 1_100);
+    assert!(stake_pool.bonus_start_block.is_none());
+    assert!(stake_pool.bonus_end_block.is_none());
+    assert_eq!(stake_pool.bonus_multiplier.unwrap_or(1), 1);
+
+    // another bonus can be set, and is not cancellable once started
+    env.process(&[set_bonus_time(&pool, 2, 200, 300)], &[&pool.owner]).await.unwrap();
+    env.warp_to(200).await;
+    assert_eq!(
+        env.process(&[cancel_bonus(&pool)], &[&pool.owner]).await.unwrap_err(),
+        staking_error(StakingError::BonusNotCancellable),
+    );
+    assert_eq!(env.stake_pool(&pool).await.end_block, 1_000);
+}
@@ -0,0 +1,129 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use solana_program::{
+    instruction::{
+        AccountMeta,
+        Instruction,
+    },
+    pubkey::Pubkey,
+    sysvar,
+};
+use solana_program_test::*;
+use solana_sdk::signature::Signer;
+use staking_program::{
+    instruction::StakingInstruction,
+    pda::{
+        boost_token_account_address,
+        token_account_authority_address,
+    },
+};
+
+fn boost_vault(pool: &Pool) -> Pubkey {
+    boost_token_account_address(pool.index, &pool.mint).0
+}
+
+fn fund_boost_vault(pool: &Pool, amount: u64) -> Instruction {
+    instruction(
+        StakingInstruction::FundBoostVault { amount },
+        vec![
+            AccountMeta::new_readonly(pool.owner.pubkey(), true),
+            AccountMeta::new_readonly(pool.mint, false),
+            AccountMeta::new_readonly(pool.key(), false),
+            AccountMeta::new(pool.reward_source, false),
+            AccountMeta::new(boost_vault(pool), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+    )
+}
+
+/// Boost paid to the token-account of the position
+fn claim_boost(pool: &Pool, staker: &Staker) -> Instruction {
+    instruction(
+        StakingInstruction::ClaimBoost,
+        vec![
+            AccountMeta::new_readonly(staker.owner.pubkey(), true),
+            AccountMeta::new_readonly(staker.token_account, false),
+            AccountMeta::new(pool.key(), false),
+            AccountMeta::new_readonly(token_account_authority_address().unwrap(), false),
+            AccountMeta::new_readonly(pool.staked_vault(), false),
+            AccountMeta::new_readonly(pool.reward_vault(), false),
+            AccountMeta::new(boost_vault(pool), false),
+            AccountMeta::new(staker.user_info(pool), false),
+            AccountMeta::new(staker.token_account, false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+    )
+}
+
+fn boosted_pool_config() -> PoolConfig {
+    PoolConfig {
+        with_receipt_mint: true,
+        with_boost_vault: true,
+        ..PoolConfig::default()
+    }
+}
+
+#[tokio::test]
+async fn test_base_and_boost_are_paid_from_their_vaults() {
+    let mut env = TestEnv::new().await;
+    let pool = env.create_pool(&boosted_pool_config()).await; // 1_000 per block over 100..1_100
+    let staker = env.create_staker(&pool.mint, 1_000).await;
+
+    // x2 over 200..300, the extra 100_000 are owed by the boost vault
+    env.process(&[set_bonus_time(&pool, 2, 200, 300)], &[&pool.owner]).await.unwrap();
+    env.mint_to(&pool.mint, &pool.reward_source, 100_000).await;
+    env.process(&[fund_boost_vault(&pool, 100_000)], &[&pool.owner]).await.unwrap();
+    assert_eq!(env.stake_pool(&pool).await.end_block, 1_100);
+
+    env.warp_to(100).await;
+    env.deposit(&pool, &staker, 1_000).await.unwrap();
+    env.warp_to(400).await;
+
+    env.harvest(&pool, &staker).await.unwrap();
+    assert_eq!(env.balance(&staker.token_account).await, 300_000);
+    assert_eq!(env.balance(&pool.reward_vault()).await, 700_000);
+    assert_eq!(env.balance(&boost_vault(&pool)).await, 100_000);
+
+    env.process(&[claim_boost(&pool, &staker)], &[&staker.owner]).await.unwrap();
+    assert_eq!(env.balance(&staker.token_account).await, 400_000);
+    assert_eq!(env.balance(&pool.reward_vault()).await, 700_000);
+    assert_eq!(env.balance(&boost_vault(&pool)).await, 0);
+    assert_eq!(env.user_info(&pool, &staker).await.boost_unclaimed, 0);
+    assert_eq!(env.stake_pool(&pool).await.boost_owed, 0);
+}
+
+#[tokio::test]
+async fn test_empty_boost_vault_does_not_block_base_rewards() {
+    let mut env = TestEnv::new().await;
+    let pool = env.create_pool(&boosted_pool_config()).await; // 1_000 per block over 100..1_100
+    let staker = env.create_staker(&pool.mint, 1_000).await;
+
+    // Bonus never funded
+    env.process(&[set_bonus_time(&pool, 2, 200, 300)], &[&pool.owner]).await.unwrap();
+
+    env.warp_to(100).await;
+    env.deposit(&pool, &staker, 1_000).await.unwrap();
+    env.warp_to(400).await;
+
+    env.harvest(&pool, &staker).await.unwrap();
+    assert_eq!(env.balance(&staker.token_account).await, 300_000);
+
+    // Pays nothing and keeps the boost owed
+    env.process(&[claim_boost(&pool, &staker)], &[&staker.owner]).await.unwrap();
+    assert_eq!(env.balance(&staker.token_account).await, 300_000);
+    assert_eq!(env.user_info(&pool, &staker).await.boost_unclaimed, 100_000);
+
+    env.warp_to(500).await;
+    env.withdraw(&pool, &staker, 1_000).await.unwrap();
+    assert_eq!(env.balance(&staker.token_account).await, 1_000 + 400_000);
+
+    // Paid once the vault is funded
+    env.mint_to(&pool.mint, &pool.reward_source, 100_000).await;
+    env.process(&[fund_boost_vault(&pool, 100_000)], &[&pool.owner]).await.unwrap();
+    env.process(&[claim_boost(&pool, &staker)], &[&staker.owner]).await.unwrap();
+    assert_eq!(env.balance(&staker.token_account).await, 1_000 + 500_000);
+}
@@ -0,0 +1,71 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use solana_program::{
+    instruction::{
+        AccountMeta,
+        Instruction,
+    },
+    sysvar,
+};
+use solana_program_test::*;
+use solana_sdk::signature::Signer;
+use staking_program::{
+    events::PoolClosed,
+    instruction::StakingInstruction,
+    pda::token_account_authority_address,
+};
+
+fn close_pool(pool: &Pool) -> Instruction {
+    instruction(
+        StakingInstruction::ClosePool,
+        vec![
+            AccountMeta::new(pool.owner.pubkey(), true),
+            AccountMeta::new_readonly(pool.mint, false),
+            AccountMeta::new(pool.key(), false),
+            AccountMeta::new_readonly(token_account_authority_address().unwrap(), false),
+            AccountMeta::new(pool.staked_vault(), false),
+            AccountMeta::new(pool.reward_vault(), false),
+            AccountMeta::new(pool.reward_source, false),
+            AccountMeta::new(pool.wallet(), false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(solana_program::system_program::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(sysvar::instructions::id(), false),
+        ],
+    )
+}
+
+#[tokio::test]
+async fn test_close_pool_reclaims_rent_of_the_state() {
+    let mut env = TestEnv::new().await;
+    let pool = env.create_pool(&PoolConfig::default()).await; // 1_000 per block over 100..1_100
+    let staker = env.create_staker(&pool.mint, 1_000).await;
+
+    env.warp_to(100).await;
+    env.deposit(&pool, &staker, 1_000).await.unwrap();
+    env.warp_to(600).await;
+    env.withdraw(&pool, &staker, 1_000).await.unwrap();
+    env.warp_to(1_100).await;
+
+    let owner_lamports = env.lamports(&pool.owner.pubkey()).await;
+    let stake_pool_lamports = env.lamports(&pool.key()).await;
+    assert!(stake_pool_lamports > 0);
+    let other_lamports = env.lamports(&pool.staked_vault()).await
+        + env.lamports(&pool.reward_vault()).await
+        + env.lamports(&pool.wallet()).await;
+
+    let (result, logs) = env.process_with_logs(&[close_pool(&pool)], &[&pool.owner]).await;
+    result.unwrap();
+
+    assert_eq!(env.lamports(&pool.owner.pubkey()).await, owner_lamports + other_lamports + stake_pool_lamports);
+    assert!(env.get_account(&pool.key()).await.is_none());
+    assert_eq!(env.balance(&pool.reward_source).await, 500_000);
+
+    let closed = &events::<PoolClosed>(&logs)[0];
+    assert_eq!(closed.pool_index, pool.index);
+    assert_eq!(closed.swept_amount, 500_000);
+    assert_eq!(closed.reclaimed_lamports, other_lamports + stake_pool_lamports);
+}
@@ -0,0 +1,832 @@
+//! Pool setup shared by the integration tests. Every test binary uses a part of it
+#![allow(dead_code)]
+
+use borsh::{
+    BorshDeserialize,
+    BorshSerialize,
+};
+use solana_program::{
+    account_info::AccountInfo,
+    bpf_loader_upgradeable,
+    clock::Clock,
+    entrypoint::ProgramResult,
+    instruction::{
+        AccountMeta,
+        Instruction,
+        InstructionError,
+    },
+    program_error::ProgramError,
+    program_pack::Pack,
+    program_stubs::{
+        set_syscall_stubs,
+        SyscallStubs,
+    },
+    pubkey::Pubkey,
+    rent::Rent,
+    system_instruction,
+    system_program,
+    sysvar,
+};
+use solana_program_test::*;
+use solana_sdk::{
+    account::Account,
+    signature::{
+        Keypair,
+        Signer,
+    },
+    transaction::{
+        Transaction,
+        TransactionError,
+    },
+};
+use spl_token::state::{
+    Account as TokenAccount,
+    Mint as TokenMint,
+};
+use staking_program::{
+    error::StakingError,
+    instruction::{
+        initialize_for_next_index,
+        InitializeParams,
+        StakingInstruction,
+    },
+    pda::{
+        master_staking_address,
+        token_account_authority_address,
+        stake_pool_address,
+        wallet_pool_address,
+        staked_token_account_address,
+        reward_token_account_address,
+        extra_reward_token_account_address,
+        user_info_address,
+    },
+    processor::Processor,
+    state::{
+        MasterStaking,
+        StakePool,
+        UserInfo,
+        MAX_REWARD_TOKENS,
+    },
+    id,
+};
+use std::sync::Once;
+
+pub const DECIMALS: u8 = 6;
+
+/// Prefix of the events in the logs, see LoggingStubs
+const EVENT_LOG_PREFIX: &str = "Program log: data: ";
+
+pub fn program_test() -> ProgramTest {
+    ProgramTest::new(
+        "staking_program",
+        id(),
+        processor!(Processor::process),
+    )
+}
+
+pub fn program_data_address() -> Pubkey {
+    Pubkey::find_program_address(&[id().as_ref()], &bpf_loader_upgradeable::id()).0
+}
+
+/// Funded upgrade authority of a ProgramData of this program, which builtin programs don't have
+pub fn add_upgrade_authority(program_test: &mut ProgramTest) -> Keypair {
+    let upgrade_authority = Keypair::new();
+    let mut data = vec![0; 45];
+    data[..4].copy_from_slice(&3_u32.to_le_bytes());
+    data[12] = 1;
+    data[13..45].copy_from_slice(upgrade_authority.pubkey().as_ref());
+
+    program_test.add_account(
+        program_data_address(),
+        Account {
+            lamports: 1_000_000_000,
+            data,
+            owner: bpf_loader_upgradeable::id(),
+            ..Account::default()
+        },
+    );
+    program_test.add_account(
+        upgrade_authority.pubkey(),
+        Account {
+            lamports: 1_000_000_000,
+            owner: system_program::id(),
+            ..Account::default()
+        },
+    );
+
+    upgrade_authority
+}
+
+/// program-test drops sol_log_data of a builtin program, events::emit is logged as a message instead
+struct LoggingStubs(Box<dyn SyscallStubs>);
+
+impl SyscallStubs for LoggingStubs {
+    fn sol_log(&self, message: &str) {
+        self.0.sol_log(message);
+    }
+    fn sol_log_compute_units(&self) {
+        self.0.sol_log_compute_units();
+    }
+    fn sol_remaining_compute_units(&self) -> u64 {
+        self.0.sol_remaining_compute_units()
+    }
+    fn sol_invoke_signed(
+        &self,
+        instruction: &Instruction,
+        account_infos: &[AccountInfo],
+        signers_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        self.0.sol_invoke_signed(instruction, account_infos, signers_seeds)
+    }
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_clock_sysvar(var_addr)
+    }
+    fn sol_get_epoch_schedule_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_epoch_schedule_sysvar(var_addr)
+    }
+    fn sol_get_fees_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_fees_sysvar(var_addr)
+    }
+    fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_rent_sysvar(var_addr)
+    }
+    fn sol_get_return_data(&self) -> Option<(Pubkey, Vec<u8>)> {
+        self.0.sol_get_return_data()
+    }
+    fn sol_set_return_data(&self, data: &[u8]) {
+        self.0.sol_set_return_data(data);
+    }
+    fn sol_log_data(&self, fields: &[&[u8]]) {
+        for field in fields {
+            self.0.sol_log(&format!("data: {}", base64::encode(field)));
+        }
+    }
+    fn sol_get_stack_height(&self) -> u64 {
+        self.0.sol_get_stack_height()
+    }
+}
+
+/// Events of type T in the logs of a transaction
+pub fn events<T: BorshDeserialize>(
+    logs: &[String],
+) -> Vec<T> {
+    logs
+        .iter()
+        .filter_map(|log| log.strip_prefix(EVENT_LOG_PREFIX))
+        .filter_map(|data| base64::decode(data).ok())
+        .filter_map(|data| T::try_from_slice(&data).ok())
+        .collect()
+}
+
+/// Error of the first instruction of a transaction failing with `error`
+pub fn staking_error(
+    error: StakingError,
+) -> TransactionError {
+    TransactionError::InstructionError(0, InstructionError::Custom(error as u32))
+}
+
+/// Error of the first instruction of a transaction failing with `error`
+pub fn program_error(
+    error: ProgramError,
+) -> TransactionError {
+    let error = match error {
+        ProgramError::Custom(code) => InstructionError::Custom(code),
+        ProgramError::InvalidArgument => InstructionError::InvalidArgument,
+        ProgramError::InvalidInstructionData => InstructionError::InvalidInstructionData,
+        ProgramError::InvalidAccountData => InstructionError::InvalidAccountData,
+        ProgramError::IllegalOwner => InstructionError::IllegalOwner,
+        ProgramError::InvalidSeeds => InstructionError::InvalidSeeds,
+        ProgramError::MissingRequiredSignature => InstructionError::MissingRequiredSignature,
+        ProgramError::NotEnoughAccountKeys => InstructionError::NotEnoughAccountKeys,
+        error => panic!("no InstructionError for {:?}", error),
+    };
+
+    TransactionError::InstructionError(0, error)
+}
+
+pub fn name(name: &str) -> [u8; 32] {
+    let mut bytes = [0; 32];
+    bytes[..name.len()].copy_from_slice(name.as_bytes());
+    bytes
+}
+
+/// Arguments of Initialize. Blocks are slots, the test warps to them
+pub struct PoolConfig {
+    pub reward_amount: u64,
+    pub reward_source_amount: Option<u64>, // minted to the owner instead of reward_amount
+    pub start_block: u64,
+    pub end_block: u64,
+    pub emission_period: u64,
+    pub min_compound_interval: u64,
+    pub round_up_rewards: u8,
+    pub prefund: bool,
+    pub max_stake_per_user: u64,
+    pub min_stake_amount: u64,
+    pub tags: u32,
+    pub decimals: u8,
+    pub extra_reward_amounts: Vec<u64>, // reward tokens after the first, each with its own mint
+    pub with_receipt_mint: bool,
+    pub with_boost_vault: bool, // requires with_receipt_mint
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        PoolConfig {
+            reward_amount: 1_000_000,
+            reward_source_amount: None,
+            start_block: 100,
+            end_block: 1_100,
+            emission_period: 1,
+            min_compound_interval: 0,
+            round_up_rewards: 0,
+            prefund: true,
+            max_stake_per_user: 0,
+            min_stake_amount: 0,
+            tags: 0,
+            decimals: DECIMALS,
+            extra_reward_amounts: vec![],
+            with_receipt_mint: false,
+            with_boost_vault: false,
+        }
+    }
+}
+
+/// Pool created by TestEnv::create_pool. Staked and reward tokens share `mint`
+pub struct Pool {
+    pub index: u64,
+    pub owner: Keypair,
+    pub mint: Pubkey,
+    pub reward_source: Pubkey, // token-account of the owner with reward tokens
+    pub extra_mints: Vec<Pubkey>, // by reward index from 1
+    pub extra_sources: Vec<Pubkey>, // token-accounts of the owner with the rewards of extra_mints
+}
+
+impl Pool {
+    pub fn key(&self) -> Pubkey {
+        stake_pool_address(self.index).0
+    }
+
+    pub fn wallet(&self) -> Pubkey {
+        wallet_pool_address(self.index).0
+    }
+
+    pub fn staked_vault(&self) -> Pubkey {
+        staked_token_account_address(self.index).0
+    }
+
+    pub fn reward_vault(&self) -> Pubkey {
+        reward_token_account_address(self.index).0
+    }
+
+    /// Vault of the reward token `reward_index` after the first
+    pub fn extra_vault(&self, reward_index: u8) -> Pubkey {
+        extra_reward_token_account_address(self.index, reward_index).0
+    }
+}
+
+/// Owner of a token-account of the pool mint, the position is keyed by the token-account
+pub struct Staker {
+    pub owner: Keypair,
+    pub token_account: Pubkey,
+}
+
+impl Staker {
+    pub fn user_info(&self, pool: &Pool) -> Pubkey {
+        user_info_address(&pool.key(), &self.token_account).0
+    }
+}
+
+pub struct TestEnv {
+    pub context: ProgramTestContext,
+}
+
+impl TestEnv {
+    /// Started program with PDA master-staking and the PDA authority created
+    pub async fn new() -> Self {
+        Self::with_program_test(program_test()).await
+    }
+
+    pub async fn with_program_test(program_test: ProgramTest) -> Self {
+        let mut env = Self::without_setup(program_test).await;
+        let payer = env.payer();
+        env.process(
+            &[create_master_and_authority(&payer)],
+            &[],
+        )
+        .await
+        .unwrap();
+
+        env
+    }
+
+    /// Started program without CreateMasterAndAuthority
+    pub async fn without_setup(program_test: ProgramTest) -> Self {
+        let context = program_test.start_with_context().await;
+
+        static LOGGING_STUBS: Once = Once::new();
+        LOGGING_STUBS.call_once(|| {
+            // the stubs of program-test are set by start_with_context
+            let stubs = set_syscall_stubs(Box::new(LoggingStubs(Box::new(NoStubs))));
+            set_syscall_stubs(Box::new(LoggingStubs(stubs)));
+        });
+
+        TestEnv { context }
+    }
+
+    pub fn payer(&self) -> Pubkey {
+        self.context.payer.pubkey()
+    }
+
+    /// Runs the instructions in one transaction paid by the payer of the context
+    pub async fn process(
+        &mut self,
+        instructions: &[Instruction],
+        signers: &[&Keypair],
+    ) -> Result<(), TransactionError> {
+        self.process_with_logs(instructions, signers).await.0
+    }
+
+    /// As process, with the log messages of the transaction
+    pub async fn process_with_logs(
+        &mut self,
+        instructions: &[Instruction],
+        signers: &[&Keypair],
+    ) -> (Result<(), TransactionError>, Vec<String>) {
+        loop {
+            let mut all_signers = vec![&self.context.payer];
+            all_signers.extend_from_slice(signers);
+
+            let transaction = Transaction::new_signed_with_payer(
+                instructions,
+                Some(&self.context.payer.pubkey()),
+                &all_signers,
+                self.context.last_blockhash,
+            );
+
+            let result = self.context
+                .banks_client
+                .process_transaction_with_metadata(transaction)
+                .await
+                .unwrap();
+
+            // The same instructions in the same blockhash are the same transaction
+            if result.result == Err(TransactionError::AlreadyProcessed) {
+                self.context.get_new_latest_blockhash().await.unwrap();
+                continue;
+            }
+
+            let logs = result.metadata.map(|metadata| metadata.log_messages).unwrap_or_default();
+
+            return (result.result, logs);
+        }
+    }
+
+    pub async fn slot(&mut self) -> u64 {
+        self.context.banks_client.get_sysvar::<Clock>().await.unwrap().slot
+    }
+
+    pub async fn warp_to(&mut self, slot: u64) {
+        self.context.warp_to_slot(slot).unwrap();
+    }
+
+    pub async fn rent(&mut self) -> Rent {
+        self.context.banks_client.get_rent().await.unwrap()
+    }
+
+    pub async fn get_account(&mut self, key: &Pubkey) -> Option<Account> {
+        self.context.banks_client.get_account(*key).await.unwrap()
+    }
+
+    pub async fn lamports(&mut self, key: &Pubkey) -> u64 {
+        self.get_account(key).await.map(|account| account.lamports).unwrap_or(0)
+    }
+
+    pub async fn balance(&mut self, token_account: &Pubkey) -> u64 {
+        let account = self.get_account(token_account).await.unwrap();
+
+        TokenAccount::unpack(&account.data).unwrap().amount
+    }
+
+    /// Overwrites the amount of a token-account, as if it was drained or refunded outside of the program
+    pub async fn set_balance(&mut self, token_account: &Pubkey, amount: u64) {
+        let mut account = self.get_account(token_account).await.unwrap();
+        let mut state = TokenAccount::unpack(&account.data).unwrap();
+        state.amount = amount;
+        TokenAccount::pack(state, &mut account.data).unwrap();
+
+        self.context.set_account(token_account, &account.into());
+    }
+
+    pub async fn stake_pool(&mut self, pool: &Pool) -> StakePool {
+        let account = self.get_account(&pool.key()).await.unwrap();
+
+        StakePool::unpack(&account.data).unwrap()
+    }
+
+    /// Overwrites the state of a pool, as if an instruction left it so
+    pub async fn set_stake_pool(&mut self, pool: &Pool, stake_pool: StakePool) {
+        let mut account = self.get_account(&pool.key()).await.unwrap();
+        StakePool::pack(stake_pool, &mut account.data).unwrap();
+
+        self.context.set_account(&pool.key(), &account.into());
+    }
+
+    pub async fn user_info(&mut self, pool: &Pool, staker: &Staker) -> UserInfo {
+        let account = self.get_account(&staker.user_info(pool)).await.unwrap();
+
+        UserInfo::try_from_slice(&account.data).unwrap()
+    }
+
+    pub async fn master_staking(&mut self) -> MasterStaking {
+        let account = self.get_account(&master_staking_address().unwrap()).await.unwrap();
+
+        MasterStaking::from_slice(&account.data).unwrap()
+    }
+
+    /// Funded system account
+    pub async fn create_wallet(&mut self) -> Keypair {
+        let wallet = Keypair::new();
+        let payer = self.payer();
+        self.process(
+            &[system_instruction::transfer(&payer, &wallet.pubkey(), 1_000_000_000)],
+            &[],
+        )
+        .await
+        .unwrap();
+
+        wallet
+    }
+
+    /// Mint with the payer as the mint authority
+    pub async fn create_mint(&mut self, decimals: u8) -> Pubkey {
+        let mint = Keypair::new();
+        let payer = self.payer();
+        let lamports = self.rent().await.minimum_balance(TokenMint::LEN);
+        self.process(
+            &[
+                system_instruction::create_account(
+                    &payer,
+                    &mint.pubkey(),
+                    lamports,
+                    TokenMint::LEN as u64,
+                    &spl_token::id(),
+                ),
+                spl_token::instruction::initialize_mint(
+                    &spl_token::id(),
+                    &mint.pubkey(),
+                    &payer,
+                    None,
+                    decimals,
+                )
+                .unwrap(),
+            ],
+            &[&mint],
+        )
+        .await
+        .unwrap();
+
+        mint.pubkey()
+    }
+
+    pub async fn create_token_account(&mut self, mint: &Pubkey, owner: &Pubkey) -> Pubkey {
+        self.create_token_account_at(&Keypair::new(), mint, owner).await
+    }
+
+    /// Token-account at the address of `token_account`, e.g. again after it was closed
+    pub async fn create_token_account_at(&mut self, token_account: &Keypair, mint: &Pubkey, owner: &Pubkey) -> Pubkey {
+        let payer = self.payer();
+        let lamports = self.rent().await.minimum_balance(TokenAccount::LEN);
+        self.process(
+            &[
+                system_instruction::create_account(
+                    &payer,
+                    &token_account.pubkey(),
+                    lamports,
+                    TokenAccount::LEN as u64,
+                    &spl_token::id(),
+                ),
+                spl_token::instruction::initialize_account(
+                    &spl_token::id(),
+                    &token_account.pubkey(),
+                    mint,
+                    owner,
+                )
+                .unwrap(),
+            ],
+            &[token_account],
+        )
+        .await
+        .unwrap();
+
+        token_account.pubkey()
+    }
+
+    pub async fn mint_to(&mut self, mint: &Pubkey, token_account: &Pubkey, amount: u64) {
+        let payer = self.payer();
+        self.process(
+            &[spl_token::instruction::mint_to(
+                &spl_token::id(),
+                mint,
+                token_account,
+                &payer,
+                &[],
+                amount,
+            )
+            .unwrap()],
+            &[],
+        )
+        .await
+        .unwrap();
+    }
+
+    /// Token-account of a new wallet holding `amount` of `mint`
+    pub async fn create_staker(&mut self, mint: &Pubkey, amount: u64) -> Staker {
+        let owner = self.create_wallet().await;
+        let token_account = self.create_token_account(mint, &owner.pubkey()).await;
+        if amount > 0 {
+            self.mint_to(mint, &token_account, amount).await;
+        }
+
+        Staker {
+            owner,
+            token_account,
+        }
+    }
+
+    /// Initialize of the next pool index. The owner holds exactly the reward amounts
+    pub async fn try_create_pool(&mut self, config: &PoolConfig) -> (Pool, Result<(), TransactionError>) {
+        let mint = self.create_mint(config.decimals).await;
+
+        self.try_create_pool_of_mint(&mint, config).await
+    }
+
+    /// As try_create_pool, over an existing mint with the payer as the mint authority
+    pub async fn try_create_pool_of_mint(&mut self, mint: &Pubkey, config: &PoolConfig) -> (Pool, Result<(), TransactionError>) {
+        let owner = self.create_wallet().await;
+
+        self.try_create_pool_for(&owner, mint, config).await
+    }
+
+    /// As try_create_pool_of_mint, with an existing owner, e.g. for several pools of the same owner
+    pub async fn try_create_pool_for(&mut self, owner: &Keypair, mint: &Pubkey, config: &PoolConfig) -> (Pool, Result<(), TransactionError>) {
+        let (pool, instruction) = self.initialize_instruction(owner, mint, config).await;
+        let result = self.process(&[instruction], &[&pool.owner]).await;
+
+        (pool, result)
+    }
+
+    /// Funds the owner and builds the Initialize of try_create_pool_for without sending it
+    pub async fn initialize_instruction(&mut self, owner: &Keypair, mint: &Pubkey, config: &PoolConfig) -> (Pool, Instruction) {
+        let owner = Keypair::from_bytes(&owner.to_bytes()).unwrap();
+        let mint = *mint;
+        let reward_source = self.create_token_account(&mint, &owner.pubkey()).await;
+        self.mint_to(&mint, &reward_source, config.reward_source_amount.unwrap_or(config.reward_amount)).await;
+
+        let mut extra_mints = vec![];
+        let mut extra_sources = vec![];
+        let mut extra_reward_tokens = vec![];
+        let mut extra_reward_amounts = [0; MAX_REWARD_TOKENS - 1];
+        for (i, &amount) in config.extra_reward_amounts.iter().enumerate() {
+            let extra_mint = self.create_mint(config.decimals).await;
+            let extra_source = self.create_token_account(&extra_mint, &owner.pubkey()).await;
+            self.mint_to(&extra_mint, &extra_source, amount).await;
+            extra_mints.push(extra_mint);
+            extra_sources.push(extra_source);
+            extra_reward_tokens.push((extra_mint, extra_source));
+            extra_reward_amounts[i] = amount;
+        }
+
+        let index = self.master_staking().await.pool_counter;
+        let mut instruction = initialize_for_next_index(
+            index,
+            &owner.pubkey(),
+            &mint,
+            &reward_source,
+            &extra_reward_tokens,
+            InitializeParams {
+                n_reward_tokens: 1 + extra_reward_tokens.len() as u8,
+                reward_amount: config.reward_amount,
+                start_block: config.start_block,
+                end_block: config.end_block,
+                emission_period: config.emission_period,
+                min_compound_interval: config.min_compound_interval,
+                round_up_rewards: config.round_up_rewards,
+                prefund: config.prefund,
+                max_stake_per_user: config.max_stake_per_user,
+                min_stake_amount: config.min_stake_amount,
+                pool_name: name("test pool"),
+                project_link: [0; 128],
+                theme_id: 0,
+                tags: config.tags,
+                extra_reward_amounts,
+            },
+        )
+        .unwrap();
+        // The receipt mint (13) and the boost vault (14) are optional
+        let n_optional = if config.with_boost_vault { 0 } else if config.with_receipt_mint { 1 } else { 2 };
+        for _ in 0..n_optional {
+            instruction.accounts.remove(15 - n_optional);
+        }
+
+        let pool = Pool {
+            index,
+            owner,
+            mint,
+            reward_source,
+            extra_mints,
+            extra_sources,
+        };
+
+        (pool, instruction)
+    }
+
+    pub async fn create_pool(&mut self, config: &PoolConfig) -> Pool {
+        let (pool, result) = self.try_create_pool(config).await;
+        result.unwrap();
+
+        pool
+    }
+
+    pub async fn deposit(&mut self, pool: &Pool, staker: &Staker, amount: u64) -> Result<(), TransactionError> {
+        self.process(&[deposit(pool, staker, amount)], &[&staker.owner]).await
+    }
+
+    pub async fn withdraw(&mut self, pool: &Pool, staker: &Staker, amount: u64) -> Result<(), TransactionError> {
+        self.process(&[withdraw(pool, staker, amount)], &[&staker.owner]).await
+    }
+
+    pub async fn harvest(&mut self, pool: &Pool, staker: &Staker) -> Result<(), TransactionError> {
+        self.process(&[harvest(pool, staker)], &[&staker.owner]).await
+    }
+}
+
+/// Placeholder while the stubs of program-test are swapped, never called
+struct NoStubs;
+
+impl SyscallStubs for NoStubs {}
+
+pub fn instruction(
+    data: StakingInstruction,
+    accounts: Vec<AccountMeta>,
+) -> Instruction {
+    Instruction {
+        program_id: id(),
+        accounts,
+        data: data.try_to_vec().unwrap(),
+    }
+}
+
+pub fn create_master_and_authority(payer: &Pubkey) -> Instruction {
+    instruction(
+        StakingInstruction::CreateMasterAndAuthority,
+        vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new(token_account_authority_address().unwrap(), false),
+            AccountMeta::new(master_staking_address().unwrap(), false),
+            AccountMeta::new_readonly(id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+pub fn deposit(pool: &Pool, staker: &Staker, amount: u64) -> Instruction {
+    instruction(
+        StakingInstruction::Deposit { amount },
+        vec![
+            AccountMeta::new_readonly(staker.owner.pubkey(), true),
+            AccountMeta::new(staker.token_account, false),
+            AccountMeta::new_readonly(pool.mint, false),
+            AccountMeta::new(pool.key(), false),
+            AccountMeta::new_readonly(token_account_authority_address().unwrap(), false),
+            AccountMeta::new(pool.staked_vault(), false),
+            AccountMeta::new(pool.reward_vault(), false),
+            AccountMeta::new(pool.wallet(), false),
+            AccountMeta::new(staker.user_info(pool), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+    )
+}
+
+pub fn withdraw(pool: &Pool, staker: &Staker, amount: u64) -> Instruction {
+    instruction(
+        StakingInstruction::Withdraw {
+            amount,
+            close_on_empty: false,
+        },
+        vec![
+            AccountMeta::new_readonly(staker.owner.pubkey(), true),
+            AccountMeta::new(staker.token_account, false),
+            AccountMeta::new(pool.key(), false),
+            AccountMeta::new_readonly(token_account_authority_address().unwrap(), false),
+            AccountMeta::new(pool.staked_vault(), false),
+            AccountMeta::new(pool.reward_vault(), false),
+            AccountMeta::new(staker.user_info(pool), false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+    )
+}
+
+pub fn harvest(pool: &Pool, staker: &Staker) -> Instruction {
+    instruction(
+        StakingInstruction::Harvest,
+        vec![
+            AccountMeta::new_readonly(staker.owner.pubkey(), true),
+            AccountMeta::new(staker.token_account, false),
+            AccountMeta::new(pool.key(), false),
+            AccountMeta::new_readonly(token_account_authority_address().unwrap(), false),
+            AccountMeta::new_readonly(pool.staked_vault(), false),
+            AccountMeta::new(pool.reward_vault(), false),
+            AccountMeta::new(staker.user_info(pool), false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+    )
+}
+
+pub fn update_end_block(pool: &Pool, end_block: u64) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(pool.owner.pubkey(), true),
+        AccountMeta::new_readonly(pool.mint, false),
+        AccountMeta::new(pool.key(), false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new(pool.reward_source, false),
+        AccountMeta::new(pool.reward_vault(), false),
+    ];
+    for (i, extra_source) in pool.extra_sources.iter().enumerate() {
+        accounts.push(AccountMeta::new(*extra_source, false));
+        accounts.push(AccountMeta::new(pool.extra_vault(1 + i as u8), false));
+    }
+
+    instruction(StakingInstruction::UpdateEndBlock { end_block }, accounts)
+}
+
+pub fn set_bonus_time(pool: &Pool, bonus_multiplier: u8, bonus_start_block: u64, bonus_end_block: u64) -> Instruction {
+    instruction(
+        StakingInstruction::SetBonusTime {
+            bonus_multiplier,
+            bonus_start_block,
+            bonus_end_block,
+        },
+        vec![
+            AccountMeta::new_readonly(pool.owner.pubkey(), true),
+            AccountMeta::new_readonly(pool.mint, false),
+            AccountMeta::new(pool.key(), false),
+            AccountMeta::new_readonly(pool.staked_vault(), false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(pool.reward_vault(), false),
+        ],
+    )
+}
+
+pub fn cancel_bonus(pool: &Pool) -> Instruction {
+    instruction(
+        StakingInstruction::CancelBonus,
+        vec![
+            AccountMeta::new_readonly(pool.owner.pubkey(), true),
+            AccountMeta::new_readonly(pool.mint, false),
+            AccountMeta::new(pool.key(), false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(sysvar::instructions::id(), false),
+        ],
+    )
+}
+
+/// Reward tokens from the reward source of the owner
+pub fn fund_rewards(pool: &Pool, amount: u64) -> Instruction {
+    instruction(
+        StakingInstruction::FundRewards { amount },
+        vec![
+            AccountMeta::new_readonly(pool.owner.pubkey(), true),
+            AccountMeta::new_readonly(pool.mint, false),
+            AccountMeta::new(pool.key(), false),
+            AccountMeta::new(pool.reward_source, false),
+            AccountMeta::new(pool.reward_vault(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(pool.staked_vault(), false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+        ],
+    )
+}
+
+pub fn compound(pool: &Pool, staker: &Staker) -> Instruction {
+    instruction(
+        StakingInstruction::Compound,
+        vec![
+            AccountMeta::new_readonly(staker.owner.pubkey(), true),
+            AccountMeta::new_readonly(staker.token_account, false),
+            AccountMeta::new(pool.key(), false),
+            AccountMeta::new_readonly(token_account_authority_address().unwrap(), false),
+            AccountMeta::new(pool.staked_vault(), false),
+            AccountMeta::new(pool.reward_vault(), false),
+            AccountMeta::new(staker.user_info(pool), false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+    )
+}
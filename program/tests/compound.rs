@@ -0,0 +1,33 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use solana_program_test::*;
+use staking_program::error::StakingError;
+
+#[tokio::test]
+async fn test_compound_twice_within_interval_is_rejected() {
+    let mut env = TestEnv::new().await;
+    let pool = env.create_pool(&PoolConfig {
+        min_compound_interval: 50,
+        ..PoolConfig::default()
+    }).await;
+    let staker = env.create_staker(&pool.mint, 1_000).await;
+
+    env.warp_to(100).await;
+    env.deposit(&pool, &staker, 1_000).await.unwrap();
+
+    env.warp_to(200).await;
+    env.process(&[compound(&pool, &staker)], &[&staker.owner]).await.unwrap();
+    assert_eq!(env.user_info(&pool, &staker).await.amount, 101_000);
+
+    env.warp_to(210).await;
+    assert_eq!(
+        env.process(&[compound(&pool, &staker)], &[&staker.owner]).await.unwrap_err(),
+        staking_error(StakingError::CooldownActive),
+    );
+
+    env.warp_to(250).await;
+    env.process(&[compound(&pool, &staker)], &[&staker.owner]).await.unwrap();
+}
@@ -0,0 +1,75 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    instruction::{
+        AccountMeta,
+        Instruction,
+    },
+    program::invoke,
+    pubkey::Pubkey,
+};
+use solana_program_test::*;
+use staking_program::error::StakingError;
+
+fn relay_program_id() -> Pubkey {
+    Pubkey::new_from_array([7; 32])
+}
+
+/// Forwards its data to the program of account 0 with the other accounts, as a program
+/// spoofing an owner instruction by CPI would
+fn process_relay(
+    _program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    input: &[u8],
+) -> ProgramResult {
+    let instruction = Instruction {
+        program_id: *accounts[0].key,
+        accounts: accounts[1..]
+            .iter()
+            .map(|account| AccountMeta {
+                pubkey: *account.key,
+                is_signer: account.is_signer,
+                is_writable: account.is_writable,
+            })
+            .collect(),
+        data: input.to_vec(),
+    };
+
+    invoke(&instruction, accounts)
+}
+
+fn relay(instruction: Instruction) -> Instruction {
+    let mut accounts = vec![AccountMeta::new_readonly(instruction.program_id, false)];
+    accounts.extend(instruction.accounts);
+
+    Instruction {
+        program_id: relay_program_id(),
+        accounts,
+        data: instruction.data,
+    }
+}
+
+#[tokio::test]
+async fn test_guarded_instruction_by_cpi_is_rejected() {
+    let mut program_test = program_test();
+    program_test.add_program("relay", relay_program_id(), processor!(process_relay));
+    let mut env = TestEnv::with_program_test(program_test).await;
+    let pool = env.create_pool(&PoolConfig::default()).await; // 1_000 per block over 100..1_100
+
+    env.process(&[set_bonus_time(&pool, 3, 200, 300)], &[&pool.owner]).await.unwrap();
+
+    // signed by the owner, yet not a top-level instruction of this program
+    assert_eq!(
+        env.process(&[relay(cancel_bonus(&pool))], &[&pool.owner]).await.unwrap_err(),
+        staking_error(StakingError::CpiNotAllowed),
+    );
+    assert_eq!(env.stake_pool(&pool).await.end_block, 900);
+
+    env.process(&[cancel_bonus(&pool)], &[&pool.owner]).await.unwrap();
+    assert_eq!(env.stake_pool(&pool).await.end_block, 1_100);
+}
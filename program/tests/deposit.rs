@@ -0,0 +1,236 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use solana_program::{
+    instruction::{
+        AccountMeta,
+        Instruction,
+    },
+    sysvar,
+};
+use solana_program_test::*;
+use solana_sdk::signature::{
+    Keypair,
+    Signer,
+};
+use staking_program::{
+    error::StakingError,
+    instruction::StakingInstruction,
+    pda::{
+        associated_token_account_address,
+        associated_token_program_id,
+    },
+};
+
+#[tokio::test]
+async fn test_busy_pool_rejects_reentrant_deposit_and_withdraw() {
+    let mut env = TestEnv::new().await;
+    let pool = env.create_pool(&PoolConfig::default()).await;
+    let staker = env.create_staker(&pool.mint, 2_000).await;
+
+    env.warp_to(100).await;
+    env.deposit(&pool, &staker, 1_000).await.unwrap();
+    // the guard is released at the end of the instruction
+    assert_eq!(env.stake_pool(&pool).await.in_progress, 0);
+
+    // the pool as a callback from the token transfer of a Deposit or Withdraw sees it
+    let mut stake_pool = env.stake_pool(&pool).await;
+    stake_pool.lock().unwrap();
+    env.set_stake_pool(&pool, stake_pool).await;
+
+    assert_eq!(
+        env.deposit(&pool, &staker, 1_000).await.unwrap_err(),
+        staking_error(StakingError::PoolBusy),
+    );
+    assert_eq!(
+        env.withdraw(&pool, &staker, 1_000).await.unwrap_err(),
+        staking_error(StakingError::PoolBusy),
+    );
+    assert_eq!(env.balance(&staker.token_account).await, 1_000);
+    assert_eq!(env.user_info(&pool, &staker).await.amount, 1_000);
+
+    let mut stake_pool = env.stake_pool(&pool).await;
+    stake_pool.unlock();
+    env.set_stake_pool(&pool, stake_pool).await;
+    env.withdraw(&pool, &staker, 1_000).await.unwrap();
+    assert_eq!(env.balance(&staker.token_account).await, 2_000);
+}
+
+#[tokio::test]
+async fn test_deposit_up_to_user_cap() {
+    let mut env = TestEnv::new().await;
+    let pool = env.create_pool(&PoolConfig {
+        max_stake_per_user: 2_000,
+        ..PoolConfig::default()
+    }).await;
+    let staker = env.create_staker(&pool.mint, 3_000).await;
+    let other = env.create_staker(&pool.mint, 2_000).await;
+
+    env.warp_to(100).await;
+    env.deposit(&pool, &staker, 1_500).await.unwrap();
+    env.deposit(&pool, &staker, 500).await.unwrap();
+    assert_eq!(
+        env.deposit(&pool, &staker, 1).await.unwrap_err(),
+        staking_error(StakingError::UserCapExceeded),
+    );
+    assert_eq!(env.user_info(&pool, &staker).await.amount, 2_000);
+
+    // the cap is per position, not for the pool
+    env.deposit(&pool, &other, 2_000).await.unwrap();
+    assert_eq!(env.balance(&pool.staked_vault()).await, 4_000);
+}
+
+#[tokio::test]
+async fn test_deposit_from_minimum_stake() {
+    let mut env = TestEnv::new().await;
+    let pool = env.create_pool(&PoolConfig {
+        min_stake_amount: 500,
+        ..PoolConfig::default()
+    }).await;
+    let staker = env.create_staker(&pool.mint, 1_000).await;
+
+    env.warp_to(100).await;
+    assert_eq!(
+        env.deposit(&pool, &staker, 499).await.unwrap_err(),
+        staking_error(StakingError::BelowMinimumStake),
+    );
+    env.deposit(&pool, &staker, 500).await.unwrap();
+    // a top-up below the minimum keeps the position above it
+    env.deposit(&pool, &staker, 100).await.unwrap();
+    env.deposit(&pool, &staker, 400).await.unwrap();
+    assert_eq!(env.user_info(&pool, &staker).await.amount, 1_000);
+
+    // harvests with a zero deposit
+    env.warp_to(200).await;
+    env.deposit(&pool, &staker, 0).await.unwrap();
+    assert_eq!(env.balance(&staker.token_account).await, 100_000);
+}
+
+#[tokio::test]
+async fn test_deposit_before_setup_is_not_initialized() {
+    let mut env = TestEnv::without_setup(program_test()).await;
+    let mint = env.create_mint(DECIMALS).await;
+    let staker = env.create_staker(&mint, 1_000).await;
+    let pool = Pool {
+        index: 0,
+        owner: env.create_wallet().await,
+        mint,
+        reward_source: mint,
+        extra_mints: vec![],
+        extra_sources: vec![],
+    };
+
+    assert_eq!(
+        env.deposit(&pool, &staker, 1_000).await.unwrap_err(),
+        staking_error(StakingError::NotInitialized),
+    );
+}
+
+#[tokio::test]
+async fn test_deposit_with_misordered_accounts_names_the_index() {
+    let mut env = TestEnv::new().await;
+    let pool = env.create_pool(&PoolConfig::default()).await;
+    let staker = env.create_staker(&pool.mint, 1_000).await;
+    env.warp_to(100).await;
+
+    let mut vaults_swapped = deposit(&pool, &staker, 1_000);
+    vaults_swapped.accounts.swap(5, 6);
+    let mut sysvars_swapped = deposit(&pool, &staker, 1_000);
+    sysvars_swapped.accounts.swap(9, 10);
+    let mut truncated = deposit(&pool, &staker, 1_000);
+    truncated.accounts.pop();
+
+    for (instruction, expected) in [
+        (vaults_swapped, format!("Account 5 ({}) is not the staked vault of the mint", pool.reward_vault())),
+        (sysvars_swapped, format!("Account 9 ({}) is not the rent sysvar", sysvar::clock::id())),
+        (truncated, "Expected at least 13 accounts, got 12".to_string()),
+    ] {
+        let (result, logs) = env.process_with_logs(&[instruction], &[&staker.owner]).await;
+        assert_eq!(result.unwrap_err(), staking_error(StakingError::InvalidAccountList));
+        assert!(logs.contains(&format!("Program log: {}", expected)), "{:?}", logs);
+    }
+
+    env.deposit(&pool, &staker, 1_000).await.unwrap();
+}
+
+/// Deposit into the position of the associated token-account of the owner, tokens from `staker.token_account`
+fn deposit_with_ata(pool: &Pool, staker: &Staker, amount: u64) -> Instruction {
+    let ata = associated_token_account_address(&staker.owner.pubkey(), &pool.mint).unwrap();
+    let mut accounts = vec![
+        AccountMeta::new(staker.owner.pubkey(), true),
+        AccountMeta::new(ata, false),
+        AccountMeta::new_readonly(pool.mint, false),
+        AccountMeta::new(staker.token_account, false),
+        AccountMeta::new_readonly(associated_token_program_id().unwrap(), false),
+    ];
+    let position = Staker {
+        owner: Keypair::from_bytes(&staker.owner.to_bytes()).unwrap(),
+        token_account: ata,
+    };
+    accounts.extend(deposit(pool, &position, amount).accounts.into_iter().skip(3));
+
+    instruction(StakingInstruction::DepositWithAta { amount }, accounts)
+}
+
+#[tokio::test]
+async fn test_deposit_with_ata_creates_token_account_and_position() {
+    let mut env = TestEnv::new().await;
+    let pool = env.create_pool(&PoolConfig::default()).await;
+    let staker = env.create_staker(&pool.mint, 1_000).await;
+    let ata = associated_token_account_address(&staker.owner.pubkey(), &pool.mint).unwrap();
+    assert!(env.get_account(&ata).await.is_none());
+
+    env.warp_to(100).await;
+    env.process(&[deposit_with_ata(&pool, &staker, 600)], &[&staker.owner]).await.unwrap();
+
+    assert_eq!(env.get_account(&ata).await.unwrap().owner, spl_token::id());
+    assert_eq!(env.balance(&ata).await, 0);
+    assert_eq!(env.balance(&staker.token_account).await, 400);
+    assert_eq!(env.balance(&pool.staked_vault()).await, 600);
+
+    let position = Staker {
+        owner: staker.owner,
+        token_account: ata,
+    };
+    let user_info = env.user_info(&pool, &position).await;
+    assert_eq!(user_info.token_account_id, ata);
+    assert_eq!(user_info.amount, 600);
+}
+
+fn set_paused(pool: &Pool, paused: bool) -> Instruction {
+    instruction(
+        StakingInstruction::SetPaused { paused },
+        vec![
+            AccountMeta::new_readonly(pool.owner.pubkey(), true),
+            AccountMeta::new_readonly(pool.mint, false),
+            AccountMeta::new(pool.key(), false),
+        ],
+    )
+}
+
+#[tokio::test]
+async fn test_paused_pool_rejects_deposits_but_allows_withdrawals() {
+    let mut env = TestEnv::new().await;
+    let pool = env.create_pool(&PoolConfig::default()).await;
+    let staker = env.create_staker(&pool.mint, 2_000).await;
+
+    env.warp_to(100).await;
+    env.deposit(&pool, &staker, 1_000).await.unwrap();
+    env.process(&[set_paused(&pool, true)], &[&pool.owner]).await.unwrap();
+    assert!(env.stake_pool(&pool).await.is_paused());
+
+    env.warp_to(200).await;
+    assert_eq!(
+        env.deposit(&pool, &staker, 1_000).await.unwrap_err(),
+        staking_error(StakingError::PoolPaused),
+    );
+    env.withdraw(&pool, &staker, 400).await.unwrap();
+    assert_eq!(env.user_info(&pool, &staker).await.amount, 600);
+    assert_eq!(env.balance(&staker.token_account).await, 1_400 + 100_000);
+
+    env.process(&[set_paused(&pool, false)], &[&pool.owner]).await.unwrap();
+    env.deposit(&pool, &staker, 1_000).await.unwrap();
+    assert_eq!(env.user_info(&pool, &staker).await.amount, 1_600);
+}
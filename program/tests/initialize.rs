@@ -0,0 +1,250 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    program_pack::Pack,
+    pubkey::Pubkey,
+};
+use solana_program_test::*;
+use spl_token::{
+    instruction::TokenInstruction,
+    state::Account as TokenAccount,
+};
+use staking_program::{
+    error::StakingError,
+    pda::master_staking_address,
+    state::{
+        MAX_REWARD_TOKENS,
+        MIN_POOL_DURATION_BLOCKS,
+    },
+};
+use std::sync::Mutex;
+
+/// Mints whose transfers lose 1% on the way, as with a transfer fee
+static FEE_MINTS: Mutex<Vec<Pubkey>> = Mutex::new(Vec::new());
+
+fn process_fee_token(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    input: &[u8],
+) -> ProgramResult {
+    spl_token::processor::Processor::process(program_id, accounts, input)?;
+
+    if let Ok(TokenInstruction::Transfer { amount }) = TokenInstruction::unpack(input) {
+        let destination_info = &accounts[1];
+        let mut destination = TokenAccount::unpack(&destination_info.data.borrow())?;
+        if FEE_MINTS.lock().unwrap().contains(&destination.mint) {
+            destination.amount -= amount / 100;
+            TokenAccount::pack(destination, &mut destination_info.data.borrow_mut())?;
+        }
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_initialize_rejects_fee_mint_underfunding_vault() {
+    let mut program_test = program_test();
+    program_test.add_program("spl_token", spl_token::id(), processor!(process_fee_token));
+    let mut env = TestEnv::with_program_test(program_test).await;
+
+    let pool = env.create_pool(&PoolConfig::default()).await;
+    assert_eq!(env.balance(&pool.reward_vault()).await, 1_000_000);
+
+    let mint = env.create_mint(DECIMALS).await;
+    FEE_MINTS.lock().unwrap().push(mint);
+    let (pool, result) = env.try_create_pool_of_mint(&mint, &PoolConfig::default()).await;
+    assert_eq!(result.unwrap_err(), staking_error(StakingError::RewardScheduleUnfunded));
+    assert!(env.get_account(&pool.key()).await.is_none());
+}
+
+#[tokio::test]
+async fn test_initialize_rejects_transfer_short_of_reward_amount() {
+    let mut program_test = program_test();
+    program_test.add_program("spl_token", spl_token::id(), processor!(process_fee_token));
+    let mut env = TestEnv::with_program_test(program_test).await;
+    let mint = env.create_mint(DECIMALS).await;
+    FEE_MINTS.lock().unwrap().push(mint);
+
+    // 100_000 over 100..1_100 needs all of it, the vault receives 99_000
+    let (pool, result) = env.try_create_pool_of_mint(&mint, &PoolConfig {
+        reward_amount: 100_000,
+        ..PoolConfig::default()
+    }).await;
+    assert_eq!(result.unwrap_err(), staking_error(StakingError::RewardScheduleUnfunded));
+    assert!(env.get_account(&pool.key()).await.is_none());
+
+    // 99_999 pays 99 per block, the 99_000 received still backs it
+    let (pool, result) = env.try_create_pool_of_mint(&mint, &PoolConfig {
+        reward_amount: 99_999,
+        ..PoolConfig::default()
+    }).await;
+    result.unwrap();
+    assert_eq!(env.stake_pool(&pool).await.reward_per_block[0], 99);
+    assert_eq!(env.balance(&pool.reward_vault()).await, 99_000);
+}
+
+#[tokio::test]
+async fn test_initialize_of_underfunded_owner_leaves_no_accounts() {
+    let mut env = TestEnv::new().await;
+    let pool_counter = env.master_staking().await.pool_counter;
+
+    let (pool, result) = env.try_create_pool(&PoolConfig {
+        reward_source_amount: Some(999_999),
+        ..PoolConfig::default()
+    }).await;
+    assert_eq!(result.unwrap_err(), staking_error(StakingError::InsufficientRewardFunds));
+    for account in [pool.key(), pool.wallet(), pool.staked_vault(), pool.reward_vault()] {
+        assert!(env.get_account(&account).await.is_none());
+    }
+    assert_eq!(env.balance(&pool.reward_source).await, 999_999);
+    // the index stays free for the next pool
+    assert_eq!(env.master_staking().await.pool_counter, pool_counter);
+    let pool = env.create_pool(&PoolConfig::default()).await;
+    assert_eq!(pool.index, pool_counter);
+}
+
+#[tokio::test]
+async fn test_initialize_rejects_other_master_staking() {
+    let mut env = TestEnv::new().await;
+    let master_staking = master_staking_address().unwrap();
+    let mut master_account = env.get_account(&master_staking).await.unwrap();
+
+    // a copy of the master outside the PDA, with another counter
+    let fake_master = Pubkey::new_unique();
+    let mut fake_master_account = master_account.clone();
+    fake_master_account.data[..8].copy_from_slice(&42_u64.to_le_bytes());
+    env.context.set_account(&fake_master, &fake_master_account.into());
+    let owner = env.create_wallet().await;
+    let mint = env.create_mint(DECIMALS).await;
+    let (pool, mut instruction) = env.initialize_instruction(&owner, &mint, &PoolConfig::default()).await;
+    instruction.accounts[1].pubkey = fake_master;
+    assert_eq!(
+        env.process(&[instruction], &[&pool.owner]).await.unwrap_err(),
+        staking_error(StakingError::MasterStakingMissmatch),
+    );
+
+    let (pool, instruction) = env.initialize_instruction(&owner, &mint, &PoolConfig::default()).await;
+    master_account.data.pop();
+    env.context.set_account(&master_staking, &master_account.into());
+    assert_eq!(
+        env.process(&[instruction], &[&pool.owner]).await.unwrap_err(),
+        staking_error(StakingError::InvalidMasterStakingLength),
+    );
+}
+
+#[tokio::test]
+async fn test_initialize_rejects_reward_token_count_out_of_range() {
+    let mut env = TestEnv::new().await;
+    let owner = env.create_wallet().await;
+    let mint = env.create_mint(DECIMALS).await;
+
+    for n_reward_tokens in [0, MAX_REWARD_TOKENS as u8 + 1] {
+        let (pool, mut instruction) = env.initialize_instruction(&owner, &mint, &PoolConfig::default()).await;
+        // n_reward_tokens follows the tag of Initialize
+        assert_eq!(instruction.data[1], 1);
+        instruction.data[1] = n_reward_tokens;
+        assert_eq!(
+            env.process(&[instruction], &[&pool.owner]).await.unwrap_err(),
+            staking_error(StakingError::InvalidRewardTokenCount),
+        );
+    }
+
+    env.try_create_pool_for(&owner, &mint, &PoolConfig::default()).await.1.unwrap();
+}
+
+#[tokio::test]
+async fn test_initialize_rejects_mint_of_21_decimals() {
+    let mut env = TestEnv::new().await;
+    let mint = env.create_mint(21).await;
+    let (pool, result) = env.try_create_pool_of_mint(&mint, &PoolConfig::default()).await;
+    assert_eq!(result.unwrap_err(), staking_error(StakingError::InvalidMintDecimals));
+    assert!(env.get_account(&pool.key()).await.is_none());
+
+    let mint = env.create_mint(20).await;
+    env.try_create_pool_of_mint(&mint, &PoolConfig::default()).await.1.unwrap();
+}
+
+#[tokio::test]
+async fn test_initialize_keeps_rounding_remainder_of_schedule() {
+    let mut env = TestEnv::new().await;
+    // 999 short of another token per block over 100..1_100
+    let pool = env.create_pool(&PoolConfig {
+        reward_amount: 1_000_999,
+        ..PoolConfig::default()
+    }).await;
+    assert_eq!(env.stake_pool(&pool).await.reward_per_block[0], 1_000);
+    assert_eq!(env.balance(&pool.reward_vault()).await, 1_000_999);
+    assert_eq!(env.balance(&pool.reward_source).await, 0);
+}
+
+#[tokio::test]
+async fn test_initialize_rejects_unfunded_or_short_schedule() {
+    let mut env = TestEnv::new().await;
+    // no reward, and less than a token per block over 100..1_100
+    for reward_amount in [0, 999] {
+        let (_, result) = env.try_create_pool(&PoolConfig {
+            reward_amount,
+            ..PoolConfig::default()
+        }).await;
+        assert_eq!(result.unwrap_err(), staking_error(StakingError::InsufficientRewardFunding));
+    }
+
+    let (_, result) = env.try_create_pool(&PoolConfig {
+        end_block: 100 + MIN_POOL_DURATION_BLOCKS - 1,
+        ..PoolConfig::default()
+    }).await;
+    assert_eq!(result.unwrap_err(), staking_error(StakingError::InvalidBlockRange));
+    let pool = env.create_pool(&PoolConfig {
+        end_block: 100 + MIN_POOL_DURATION_BLOCKS,
+        ..PoolConfig::default()
+    }).await;
+    assert_eq!(env.stake_pool(&pool).await.reward_per_block[0], 1_000_000 / MIN_POOL_DURATION_BLOCKS);
+}
+
+#[tokio::test]
+async fn test_initialize_stores_total_reward() {
+    let mut env = TestEnv::new().await;
+    for reward_amount in [1_000_000, 1_000_999] {
+        let pool = env.create_pool(&PoolConfig {
+            reward_amount,
+            ..PoolConfig::default()
+        }).await;
+        let stake_pool = env.stake_pool(&pool).await;
+        assert_eq!(stake_pool.total_reward, reward_amount);
+        assert_eq!(stake_pool.total_funded, reward_amount);
+    }
+}
+
+#[tokio::test]
+async fn test_pool_without_prefund_takes_deposits_once_funded() {
+    let mut env = TestEnv::new().await;
+    let pool = env.create_pool(&PoolConfig {
+        prefund: false,
+        ..PoolConfig::default()
+    }).await;
+    assert_eq!(env.balance(&pool.reward_vault()).await, 0);
+    assert_eq!(env.balance(&pool.reward_source).await, 1_000_000);
+    let staker = env.create_staker(&pool.mint, 1_000).await;
+
+    env.warp_to(50).await;
+    assert_eq!(
+        env.deposit(&pool, &staker, 1_000).await.unwrap_err(),
+        staking_error(StakingError::PoolNotFunded),
+    );
+
+    env.process(&[fund_rewards(&pool, 600_000)], &[&pool.owner]).await.unwrap();
+    assert_eq!(
+        env.deposit(&pool, &staker, 1_000).await.unwrap_err(),
+        staking_error(StakingError::PoolNotFunded),
+    );
+
+    env.process(&[fund_rewards(&pool, 400_000)], &[&pool.owner]).await.unwrap();
+    assert_eq!(env.balance(&pool.reward_vault()).await, 1_000_000);
+    env.deposit(&pool, &staker, 1_000).await.unwrap();
+    assert_eq!(env.user_info(&pool, &staker).await.amount, 1_000);
+}
@@ -0,0 +1,30 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use solana_program::instruction::Instruction;
+use solana_program_test::*;
+use staking_program::{
+    error::StakingError,
+    id,
+};
+
+#[tokio::test]
+async fn test_malformed_instruction_data_is_invalid_instruction() {
+    let mut env = TestEnv::new().await;
+
+    let deposit_without_amount = vec![1, 0xe8, 0x03];
+    for data in [vec![], vec![255], deposit_without_amount] {
+        let result = env.process(
+            &[Instruction {
+                program_id: id(),
+                accounts: vec![],
+                data,
+            }],
+            &[],
+        )
+        .await;
+        assert_eq!(result.unwrap_err(), staking_error(StakingError::InvalidInstruction));
+    }
+}
@@ -0,0 +1,380 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use solana_program::{
+    instruction::{
+        AccountMeta,
+        Instruction,
+    },
+    program_option::COption,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    system_program,
+    sysvar,
+};
+use solana_program_test::*;
+use solana_sdk::{
+    account::Account,
+    signature::Signer,
+};
+use staking_program::{
+    error::StakingError,
+    id as this_program_id,
+    instruction::{
+        ReinitPoolStateParams,
+        StakingInstruction,
+    },
+    pda::{
+        master_staking_address,
+        migrated_reward_token_account_address,
+        stake_mint_token_account_address,
+        token_account_authority_address,
+    },
+    state::{
+        StakePool,
+        LEGACY_STAKE_POOL_LEN,
+        LEGACY_USER_INFO_LEN,
+        USER_INFO_LEN,
+    },
+    utils::get_precision_factor,
+};
+
+/// Vaults of `mint` once a pool migrated to it
+fn migrated_vaults(pool: &Pool, mint: &Pubkey) -> (Pubkey, Pubkey) {
+    (
+        stake_mint_token_account_address(pool.index, mint).0,
+        migrated_reward_token_account_address(pool.index, mint).0,
+    )
+}
+
+fn migrate_reward_mint(pool: &Pool, new_mint: &Pubkey, reward_amount: u64, start_block: u64, end_block: u64) -> Instruction {
+    let (staked_vault, reward_vault) = migrated_vaults(pool, new_mint);
+    instruction(
+        StakingInstruction::MigrateRewardMint {
+            reward_amount,
+            start_block,
+            end_block,
+        },
+        vec![
+            AccountMeta::new(pool.owner.pubkey(), true),
+            AccountMeta::new_readonly(pool.mint, false),
+            AccountMeta::new(pool.key(), false),
+            AccountMeta::new_readonly(*new_mint, false),
+            AccountMeta::new_readonly(token_account_authority_address().unwrap(), false),
+            AccountMeta::new(pool.staked_vault(), false),
+            AccountMeta::new(pool.reward_vault(), false),
+            AccountMeta::new(pool.reward_source, false),
+            AccountMeta::new(staked_vault, false),
+            AccountMeta::new(reward_vault, false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(sysvar::instructions::id(), false),
+        ],
+    )
+}
+
+#[tokio::test]
+async fn test_migrated_pool_accrues_new_mint_rewards() {
+    let mut env = TestEnv::new().await;
+    let pool = env.create_pool(&PoolConfig::default()).await; // 1_000 per block over 100..1_100
+    let staker = env.create_staker(&pool.mint, 1_000).await;
+    let new_mint = env.create_mint(DECIMALS).await;
+    let (new_staked_vault, new_reward_vault) = migrated_vaults(&pool, &new_mint);
+
+    env.warp_to(100).await;
+    env.deposit(&pool, &staker, 1_000).await.unwrap();
+
+    // positions are paid out in the old mint before the pool moves
+    env.warp_to(1_100).await;
+    assert_eq!(
+        env.process(&[migrate_reward_mint(&pool, &new_mint, 500_000, 1_200, 1_700)], &[&pool.owner]).await.unwrap_err(),
+        staking_error(StakingError::MigrationNotAllowed),
+    );
+    env.withdraw(&pool, &staker, 1_000).await.unwrap();
+    assert_eq!(env.balance(&staker.token_account).await, 1_000 + 1_000_000);
+    env.process(&[migrate_reward_mint(&pool, &new_mint, 500_000, 1_200, 1_700)], &[&pool.owner]).await.unwrap();
+
+    assert!(env.get_account(&pool.staked_vault()).await.is_none());
+    assert!(env.get_account(&pool.reward_vault()).await.is_none());
+    let stake_pool = env.stake_pool(&pool).await;
+    assert_eq!(stake_pool.mint, new_mint);
+    assert_eq!(stake_pool.reward_per_block[0], 1_000);
+    assert_eq!((stake_pool.start_block, stake_pool.end_block), (1_200, 1_700));
+
+    // the owner funds the new schedule
+    let new_reward_source = env.create_token_account(&new_mint, &pool.owner.pubkey()).await;
+    env.mint_to(&new_mint, &new_reward_source, 500_000).await;
+    let mut fund_rewards = fund_rewards(&pool, 500_000);
+    fund_rewards.accounts[1].pubkey = new_mint;
+    fund_rewards.accounts[3].pubkey = new_reward_source;
+    fund_rewards.accounts[4].pubkey = new_reward_vault;
+    fund_rewards.accounts[6].pubkey = new_staked_vault;
+    env.process(&[fund_rewards], &[&pool.owner]).await.unwrap();
+    assert_eq!(env.balance(&new_reward_vault).await, 500_000);
+
+    let new_staker = env.create_staker(&new_mint, 1_000).await;
+    env.warp_to(1_200).await;
+    let mut deposit = deposit(&pool, &new_staker, 1_000);
+    deposit.accounts[2].pubkey = new_mint;
+    deposit.accounts[5].pubkey = new_staked_vault;
+    deposit.accounts[6].pubkey = new_reward_vault;
+    env.process(&[deposit], &[&new_staker.owner]).await.unwrap();
+
+    env.warp_to(1_300).await;
+    let mut harvest = harvest(&pool, &new_staker);
+    harvest.accounts[4].pubkey = new_staked_vault;
+    harvest.accounts[5].pubkey = new_reward_vault;
+    env.process(&[harvest], &[&new_staker.owner]).await.unwrap();
+    assert_eq!(env.balance(&new_staker.token_account).await, 100_000);
+    assert_eq!(env.balance(&new_staked_vault).await, 1_000);
+    assert_eq!(env.balance(&new_reward_vault).await, 400_000);
+}
+
+fn migrate_user_info(payer: &Pubkey, pool: &Pool, staker: &Staker) -> Instruction {
+    instruction(
+        StakingInstruction::MigrateUserInfo,
+        vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new(pool.key(), false),
+            AccountMeta::new(staker.user_info(pool), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+#[tokio::test]
+async fn test_migrate_legacy_user_info() {
+    let mut env = TestEnv::new().await;
+    let pool = env.create_pool(&PoolConfig::default()).await;
+    let staker = env.create_staker(&pool.mint, 0).await;
+
+    // token_account_id, amount and reward_debt of u64 of the first layout
+    let mut data = staker.token_account.to_bytes().to_vec();
+    data.extend_from_slice(&1_000_u64.to_le_bytes());
+    data.extend_from_slice(&5_u64.to_le_bytes());
+    assert_eq!(data.len(), LEGACY_USER_INFO_LEN);
+    let account = Account {
+        lamports: env.rent().await.minimum_balance(LEGACY_USER_INFO_LEN),
+        data,
+        owner: this_program_id(),
+        executable: false,
+        rent_epoch: 0,
+    };
+    env.context.set_account(&staker.user_info(&pool), &account.into());
+
+    let payer = env.payer();
+    env.process(&[migrate_user_info(&payer, &pool, &staker)], &[]).await.unwrap();
+
+    let account = env.get_account(&staker.user_info(&pool)).await.unwrap();
+    assert_eq!(account.data.len(), USER_INFO_LEN);
+    assert_eq!(account.lamports, env.rent().await.minimum_balance(USER_INFO_LEN));
+
+    let stake_pool = env.stake_pool(&pool).await;
+    let user_info = env.user_info(&pool, &staker).await;
+    assert_eq!(user_info.token_account_id, staker.token_account);
+    assert_eq!(user_info.amount, 1_000);
+    assert_eq!(user_info.reward_debt, 5 * get_precision_factor(stake_pool.precision_factor_rank).unwrap() as u128);
+    assert_eq!(user_info.delegate, Pubkey::default());
+    assert_eq!(user_info.frozen, 0);
+    assert_eq!(user_info.shares, 0);
+    // positions of the first layout weren't counted
+    assert_eq!(stake_pool.stakers, 1);
+
+    // up to date, nothing changes
+    env.process(&[migrate_user_info(&payer, &pool, &staker)], &[]).await.unwrap();
+    assert_eq!(env.stake_pool(&pool).await.stakers, 1);
+}
+
+/// State of `pool` rebuilt from `stake_pool`, the last known one
+fn reinit_pool_state(upgrade_authority: &Pubkey, pool: &Pool, stake_pool: &StakePool) -> Instruction {
+    instruction(
+        StakingInstruction::ReinitPoolState {
+            params: ReinitPoolStateParams {
+                pool_index: pool.index,
+                owner: stake_pool.owner,
+                start_block: stake_pool.start_block,
+                end_block: stake_pool.end_block,
+                reward_per_block: stake_pool.reward_per_block[0],
+                emission_period: stake_pool.emission_period,
+                min_compound_interval: stake_pool.min_compound_interval,
+                round_up_rewards: stake_pool.round_up_rewards,
+                max_stake_per_user: stake_pool.max_stake_per_user,
+                accrued_token_per_share: stake_pool.accrued_token_per_share,
+                last_reward_block: stake_pool.last_reward_block,
+                total_staked: stake_pool.total_staked,
+                total_shares: stake_pool.total_shares,
+                total_owed: stake_pool.total_owed,
+                pool_name: stake_pool.pool_name,
+                project_link: stake_pool.project_link,
+                theme_id: stake_pool.theme_id,
+                tags: stake_pool.tags,
+                stakers: stake_pool.stakers,
+            },
+        },
+        vec![
+            AccountMeta::new(*upgrade_authority, true),
+            AccountMeta::new_readonly(program_data_address(), false),
+            AccountMeta::new_readonly(master_staking_address().unwrap(), false),
+            AccountMeta::new(pool.key(), false),
+            AccountMeta::new_readonly(pool.mint, false),
+            AccountMeta::new_readonly(token_account_authority_address().unwrap(), false),
+            AccountMeta::new_readonly(pool.staked_vault(), false),
+            AccountMeta::new_readonly(pool.reward_vault(), false),
+            AccountMeta::new_readonly(pool.wallet(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+#[tokio::test]
+async fn test_reinit_zeroed_pool_state_accepts_deposits() {
+    let mut program_test = program_test();
+    let upgrade_authority = add_upgrade_authority(&mut program_test);
+    let mut env = TestEnv::with_program_test(program_test).await;
+    let pool = env.create_pool(&PoolConfig::default()).await; // 1_000 per block over 100..1_100
+    let first = env.create_staker(&pool.mint, 1_000).await;
+    let second = env.create_staker(&pool.mint, 500).await;
+
+    env.warp_to(100).await;
+    env.deposit(&pool, &first, 1_000).await.unwrap();
+    env.warp_to(200).await;
+    let last_known = env.stake_pool(&pool).await;
+
+    // An intact state is changed by its owner only
+    assert_eq!(
+        env.process(&[reinit_pool_state(&upgrade_authority.pubkey(), &pool, &last_known)], &[&upgrade_authority]).await.unwrap_err(),
+        staking_error(StakingError::ReinitNotAllowed),
+    );
+
+    let mut account = env.get_account(&pool.key()).await.unwrap();
+    account.data.fill(0);
+    env.context.set_account(&pool.key(), &account.into());
+    assert!(StakePool::unpack(&env.get_account(&pool.key()).await.unwrap().data).is_err());
+
+    env.process(&[reinit_pool_state(&upgrade_authority.pubkey(), &pool, &last_known)], &[&upgrade_authority]).await.unwrap();
+    let stake_pool = env.stake_pool(&pool).await;
+    assert_eq!(stake_pool.owner, pool.owner.pubkey());
+    assert_eq!((stake_pool.start_block, stake_pool.end_block), (100, 1_100));
+    assert_eq!(stake_pool.total_staked, 1_000);
+
+    env.deposit(&pool, &second, 500).await.unwrap();
+    assert_eq!(env.stake_pool(&pool).await.total_staked, 1_500);
+    assert_eq!(env.balance(&pool.staked_vault()).await, 1_500);
+
+    // The 100 blocks accrued before the state was lost are still paid
+    env.harvest(&pool, &first).await.unwrap();
+    assert_eq!(env.balance(&first.token_account).await, 100_000);
+}
+
+/// `stake_pool` in the first released layout of LEGACY_STAKE_POOL_LEN
+fn legacy_stake_pool_data(stake_pool: &StakePool) -> Vec<u8> {
+    let mut data = vec![stake_pool.n_reward_tokens];
+    data.extend_from_slice(&stake_pool.pool_index.to_le_bytes());
+    data.extend_from_slice(stake_pool.owner.as_ref());
+    data.extend_from_slice(stake_pool.mint.as_ref());
+    data.push(stake_pool.is_initialized);
+    data.push(stake_pool.precision_factor_rank);
+    // COption is a u32 tag and the value
+    match stake_pool.bonus_multiplier {
+        COption::Some(multiplier) => data.extend_from_slice(&[1, 0, 0, 0, multiplier]),
+        COption::None => data.extend_from_slice(&[0; 5]),
+    }
+    for block in [stake_pool.bonus_start_block, stake_pool.bonus_end_block] {
+        match block {
+            COption::Some(block) => {
+                data.extend_from_slice(&[1, 0, 0, 0]);
+                data.extend_from_slice(&block.to_le_bytes());
+            },
+            COption::None => data.extend_from_slice(&[0; 12]),
+        }
+    }
+    data.extend_from_slice(&stake_pool.last_reward_block.to_le_bytes());
+    data.extend_from_slice(&stake_pool.start_block.to_le_bytes());
+    data.extend_from_slice(&stake_pool.end_block.to_le_bytes());
+    data.extend_from_slice(&stake_pool.total_reward.to_le_bytes()); // reward_amount
+    data.extend_from_slice(&stake_pool.reward_per_block[0].to_le_bytes());
+    data.extend_from_slice(&stake_pool.accrued_token_per_share.to_le_bytes());
+    data.extend_from_slice(&stake_pool.pool_name);
+    data.extend_from_slice(&stake_pool.project_link);
+    data.push(stake_pool.theme_id);
+
+    data
+}
+
+fn migrate_stake_pool(payer: &Pubkey, pool: &Pool) -> Instruction {
+    instruction(
+        StakingInstruction::MigrateStakePool,
+        vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new(pool.key(), false),
+            AccountMeta::new_readonly(pool.staked_vault(), false),
+            AccountMeta::new_readonly(pool.reward_vault(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+#[tokio::test]
+async fn test_migrate_legacy_stake_pool_takes_deposits_and_withdrawals() {
+    let mut env = TestEnv::new().await;
+    let pool = env.create_pool(&PoolConfig::default()).await; // 1_000 per block over 100..1_100
+    let staker = env.create_staker(&pool.mint, 2_000).await;
+
+    env.warp_to(100).await;
+    env.deposit(&pool, &staker, 1_000).await.unwrap();
+
+    // the pool and the position as the first release wrote them
+    let stake_pool = env.stake_pool(&pool).await;
+    let data = legacy_stake_pool_data(&stake_pool);
+    assert_eq!(data.len(), LEGACY_STAKE_POOL_LEN);
+    // the lamports stay, the test validator checks the capitalization
+    let mut account = env.get_account(&pool.key()).await.unwrap();
+    account.data = data;
+    env.context.set_account(&pool.key(), &account.into());
+    let mut data = staker.token_account.to_bytes().to_vec();
+    data.extend_from_slice(&1_000_u64.to_le_bytes());
+    data.extend_from_slice(&0_u64.to_le_bytes());
+    let mut account = env.get_account(&staker.user_info(&pool)).await.unwrap();
+    account.data = data;
+    env.context.set_account(&staker.user_info(&pool), &account.into());
+
+    env.warp_to(200).await;
+    assert!(env.deposit(&pool, &staker, 1_000).await.is_err());
+
+    let payer = env.payer();
+    env.process(&[migrate_stake_pool(&payer, &pool)], &[]).await.unwrap();
+    let account = env.get_account(&pool.key()).await.unwrap();
+    assert_eq!(account.data.len(), StakePool::LEN);
+    let migrated = env.stake_pool(&pool).await;
+    assert_eq!(migrated.pool_index, pool.index);
+    assert_eq!(migrated.owner, pool.owner.pubkey());
+    assert_eq!((migrated.start_block, migrated.end_block), (100, 1_100));
+    assert_eq!(migrated.reward_per_block, stake_pool.reward_per_block);
+    assert_eq!(migrated.last_reward_block, stake_pool.last_reward_block);
+    assert_eq!(migrated.accrued_token_per_share, stake_pool.accrued_token_per_share);
+    assert_eq!(migrated.total_reward, 1_000_000);
+    // the totals the first layout didn't track come from the vaults
+    assert_eq!((migrated.total_staked, migrated.total_shares), (1_000, 1_000));
+    assert_eq!(migrated.total_owed, 0);
+
+    // up to date, nothing changes
+    env.process(&[migrate_stake_pool(&payer, &pool)], &[]).await.unwrap();
+    assert_eq!(env.stake_pool(&pool).await.total_staked, 1_000);
+
+    env.process(&[migrate_user_info(&payer, &pool, &staker)], &[]).await.unwrap();
+    env.deposit(&pool, &staker, 1_000).await.unwrap();
+    assert_eq!(env.balance(&staker.token_account).await, 100_000);
+    env.warp_to(300).await;
+    env.withdraw(&pool, &staker, 2_000).await.unwrap();
+    assert_eq!(env.balance(&staker.token_account).await, 2_000 + 200_000);
+    let stake_pool = env.stake_pool(&pool).await;
+    assert_eq!((stake_pool.total_staked, stake_pool.total_shares, stake_pool.total_owed), (0, 0, 0));
+    assert_eq!(env.balance(&pool.reward_vault()).await, 800_000);
+}
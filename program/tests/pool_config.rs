@@ -0,0 +1,100 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use borsh::BorshDeserialize;
+use common::*;
+use solana_program::{
+    instruction::{
+        AccountMeta,
+        Instruction,
+    },
+    pubkey::Pubkey,
+    system_program,
+    sysvar,
+};
+use solana_program_test::*;
+use solana_sdk::signature::Signer;
+use staking_program::{
+    error::StakingError,
+    instruction::StakingInstruction,
+    state::{
+        LongLink,
+        TAG_GOVERNANCE,
+        TAG_LP,
+        TAG_STABLE,
+    },
+    id,
+    ADD_SEED_LINK,
+};
+
+fn long_link_address(pool: &Pool) -> Pubkey {
+    Pubkey::find_program_address(
+        &[&pool.index.to_le_bytes(), ADD_SEED_LINK.as_bytes()],
+        &id(),
+    ).0
+}
+
+fn set_long_link(pool: &Pool, uri: Vec<u8>) -> Instruction {
+    instruction(
+        StakingInstruction::SetLongLink { uri },
+        vec![
+            AccountMeta::new(pool.owner.pubkey(), true),
+            AccountMeta::new_readonly(pool.mint, false),
+            AccountMeta::new(pool.key(), false),
+            AccountMeta::new(long_link_address(pool), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+#[tokio::test]
+async fn test_set_long_link_round_trip() {
+    let mut env = TestEnv::new().await;
+    let pool = env.create_pool(&PoolConfig::default()).await;
+
+    let uri: Vec<u8> = (0..300).map(|i| b'a' + (i % 26) as u8).collect();
+    env.process(&[set_long_link(&pool, uri.clone())], &[&pool.owner]).await.unwrap();
+
+    let account = env.get_account(&long_link_address(&pool)).await.unwrap();
+    let long_link = LongLink::deserialize(&mut &account.data[..]).unwrap();
+    assert_eq!(long_link.uri, uri);
+    assert_eq!(env.stake_pool(&pool).await.project_link[..], uri[..128]);
+
+    // A shorter link replaces the longer one
+    let uri = b"https://example.com".to_vec();
+    env.process(&[set_long_link(&pool, uri.clone())], &[&pool.owner]).await.unwrap();
+
+    let account = env.get_account(&long_link_address(&pool)).await.unwrap();
+    assert_eq!(LongLink::deserialize(&mut &account.data[..]).unwrap().uri, uri);
+}
+
+fn set_tags(pool: &Pool, tags: u32) -> Instruction {
+    instruction(
+        StakingInstruction::SetTags { tags },
+        vec![
+            AccountMeta::new_readonly(pool.owner.pubkey(), true),
+            AccountMeta::new_readonly(pool.mint, false),
+            AccountMeta::new(pool.key(), false),
+        ],
+    )
+}
+
+#[tokio::test]
+async fn test_set_tags_of_pool() {
+    let mut env = TestEnv::new().await;
+    let pool = env.create_pool(&PoolConfig {
+        tags: TAG_STABLE,
+        ..PoolConfig::default()
+    }).await;
+    assert_eq!(env.stake_pool(&pool).await.tags, TAG_STABLE);
+
+    env.process(&[set_tags(&pool, TAG_LP | TAG_GOVERNANCE)], &[&pool.owner]).await.unwrap();
+    assert_eq!(env.stake_pool(&pool).await.tags, TAG_LP | TAG_GOVERNANCE);
+
+    // bits without a category are rejected
+    let result = env.process(&[set_tags(&pool, TAG_LP | 1 << 31)], &[&pool.owner]).await;
+    assert_eq!(result.unwrap_err(), staking_error(StakingError::InvalidTags));
+    assert_eq!(env.stake_pool(&pool).await.tags, TAG_LP | TAG_GOVERNANCE);
+}
@@ -0,0 +1,116 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use solana_program::{
+    instruction::{
+        AccountMeta,
+        Instruction,
+    },
+    pubkey::Pubkey,
+    system_program,
+    sysvar,
+};
+use solana_program_test::*;
+use solana_sdk::signature::{
+    Keypair,
+    Signer,
+};
+use staking_program::{
+    error::StakingError,
+    instruction::StakingInstruction,
+    pda::master_staking_address,
+    state::MAX_PROTOCOL_FEE_BPS,
+};
+
+fn set_protocol_fee(upgrade_authority: &Keypair, protocol_fee_bps: u16, protocol_fee_recipient: &Pubkey) -> Instruction {
+    instruction(
+        StakingInstruction::SetProtocolFee {
+            protocol_fee_bps,
+            protocol_fee_recipient: *protocol_fee_recipient,
+        },
+        vec![
+            AccountMeta::new(upgrade_authority.pubkey(), true),
+            AccountMeta::new_readonly(program_data_address(), false),
+            AccountMeta::new(master_staking_address().unwrap(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+/// Harvest ending with the protocol fee accounts
+fn harvest_with_fee(pool: &Pool, staker: &Staker, protocol_fee_token_account: &Pubkey) -> Instruction {
+    let mut instruction = harvest(pool, staker);
+    instruction.accounts.push(AccountMeta::new_readonly(master_staking_address().unwrap(), false));
+    instruction.accounts.push(AccountMeta::new(*protocol_fee_token_account, false));
+
+    instruction
+}
+
+#[tokio::test]
+async fn test_harvest_splits_protocol_fee() {
+    let mut program_test = program_test();
+    let upgrade_authority = add_upgrade_authority(&mut program_test);
+    let mut env = TestEnv::with_program_test(program_test).await;
+    let pool = env.create_pool(&PoolConfig::default()).await; // 1_000 per block over 100..1_100
+    let staker = env.create_staker(&pool.mint, 1_000).await;
+
+    let recipient = env.create_wallet().await;
+    let recipient_token_account = env.create_token_account(&pool.mint, &recipient.pubkey()).await;
+    let other_token_account = env.create_token_account(&pool.mint, &env.payer()).await;
+    env.process(&[set_protocol_fee(&upgrade_authority, 500, &recipient.pubkey())], &[&upgrade_authority]).await.unwrap();
+    assert_eq!(env.master_staking().await.protocol_fee_bps, 500);
+
+    env.warp_to(100).await;
+    env.deposit(&pool, &staker, 1_000).await.unwrap();
+    env.warp_to(200).await;
+
+    assert_eq!(
+        env.process(&[harvest_with_fee(&pool, &staker, &other_token_account)], &[&staker.owner]).await.unwrap_err(),
+        staking_error(StakingError::InvalidProtocolFeeAccount),
+    );
+    env.process(&[harvest_with_fee(&pool, &staker, &recipient_token_account)], &[&staker.owner]).await.unwrap();
+    // 5% of 100_000
+    assert_eq!(env.balance(&staker.token_account).await, 95_000);
+    assert_eq!(env.balance(&recipient_token_account).await, 5_000);
+
+    // Clients without the fee accounts keep working
+    env.warp_to(250).await;
+    env.harvest(&pool, &staker).await.unwrap();
+    assert_eq!(env.balance(&staker.token_account).await, 95_000 + 50_000);
+
+    // bps=0 pays the user everything
+    env.process(&[set_protocol_fee(&upgrade_authority, 0, &recipient.pubkey())], &[&upgrade_authority]).await.unwrap();
+    env.warp_to(300).await;
+    env.process(&[harvest_with_fee(&pool, &staker, &recipient_token_account)], &[&staker.owner]).await.unwrap();
+    assert_eq!(env.balance(&staker.token_account).await, 145_000 + 50_000);
+    assert_eq!(env.balance(&recipient_token_account).await, 5_000);
+}
+
+#[tokio::test]
+async fn test_set_protocol_fee_requires_upgrade_authority_and_caps_bps() {
+    let mut program_test = program_test();
+    let upgrade_authority = add_upgrade_authority(&mut program_test);
+    let mut env = TestEnv::with_program_test(program_test).await;
+    let recipient = Pubkey::new_unique();
+
+    let other = env.create_wallet().await;
+    assert!(env.process(&[set_protocol_fee(&other, 500, &recipient)], &[&other]).await.is_err());
+    assert_eq!(
+        env.process(
+            &[set_protocol_fee(&upgrade_authority, MAX_PROTOCOL_FEE_BPS + 1, &recipient)],
+            &[&upgrade_authority],
+        ).await.unwrap_err(),
+        staking_error(StakingError::ProtocolFeeTooHigh),
+    );
+
+    env.process(
+        &[set_protocol_fee(&upgrade_authority, MAX_PROTOCOL_FEE_BPS, &recipient)],
+        &[&upgrade_authority],
+    ).await.unwrap();
+    let master_staking = env.master_staking().await;
+    assert_eq!(master_staking.protocol_fee_bps, MAX_PROTOCOL_FEE_BPS);
+    assert_eq!(master_staking.protocol_fee_recipient, recipient);
+}
@@ -0,0 +1,464 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use solana_program::{
+    instruction::{
+        AccountMeta,
+        Instruction,
+    },
+    sysvar,
+};
+use solana_program_test::*;
+use solana_sdk::signature::Signer;
+use staking_program::{
+    error::StakingError,
+    events::{
+        PoolSummaries,
+        PoolSummary,
+        TvlSnapshot,
+        UserPositions,
+        UserProjectedRewards,
+    },
+    instruction::StakingInstruction,
+};
+
+fn log_emitted_between(pool: &Pool, from: u64, to: u64) -> Instruction {
+    instruction(
+        StakingInstruction::LogEmittedBetween { from, to },
+        vec![AccountMeta::new_readonly(pool.key(), false)],
+    )
+}
+
+fn set_max_total_staked(pool: &Pool, max_total_staked: u64) -> Instruction {
+    instruction(
+        StakingInstruction::SetMaxTotalStaked { max_total_staked },
+        vec![
+            AccountMeta::new_readonly(pool.owner.pubkey(), true),
+            AccountMeta::new_readonly(pool.mint, false),
+            AccountMeta::new(pool.key(), false),
+        ],
+    )
+}
+
+fn log_pool_capacity(pool: &Pool) -> Instruction {
+    instruction(
+        StakingInstruction::LogPoolCapacity,
+        vec![
+            AccountMeta::new_readonly(pool.key(), false),
+            AccountMeta::new_readonly(pool.staked_vault(), false),
+        ],
+    )
+}
+
+fn log_depletion_slot(pool: &Pool) -> Instruction {
+    instruction(
+        StakingInstruction::LogDepletionSlot,
+        vec![
+            AccountMeta::new_readonly(pool.key(), false),
+            AccountMeta::new_readonly(pool.reward_vault(), false),
+        ],
+    )
+}
+
+fn log_tvl_snapshot(pool: &Pool) -> Instruction {
+    instruction(
+        StakingInstruction::LogTvlSnapshot,
+        vec![
+            AccountMeta::new_readonly(pool.key(), false),
+            AccountMeta::new_readonly(pool.staked_vault(), false),
+            AccountMeta::new_readonly(pool.reward_vault(), false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+        ],
+    )
+}
+
+fn log_user_debug(pool: &Pool, staker: &Staker) -> Instruction {
+    instruction(
+        StakingInstruction::LogUserDebug,
+        vec![
+            AccountMeta::new_readonly(pool.key(), false),
+            AccountMeta::new_readonly(staker.user_info(pool), false),
+            AccountMeta::new_readonly(staker.token_account, false),
+            AccountMeta::new_readonly(pool.staked_vault(), false),
+            AccountMeta::new_readonly(pool.reward_vault(), false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+        ],
+    )
+}
+
+fn log_user_positions(pool: &Pool, stakers: &[&Staker]) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(pool.key(), false),
+        AccountMeta::new_readonly(pool.staked_vault(), false),
+        AccountMeta::new_readonly(pool.reward_vault(), false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+    ];
+    for staker in stakers {
+        accounts.push(AccountMeta::new_readonly(staker.user_info(pool), false));
+        accounts.push(AccountMeta::new_readonly(staker.token_account, false));
+    }
+
+    instruction(StakingInstruction::LogUserPositions, accounts)
+}
+
+fn log_pool_summary(pool: &Pool) -> Instruction {
+    instruction(
+        StakingInstruction::LogPoolSummary,
+        vec![
+            AccountMeta::new_readonly(pool.key(), false),
+            AccountMeta::new_readonly(pool.staked_vault(), false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+        ],
+    )
+}
+
+#[tokio::test]
+async fn test_log_emitted_between_across_bonus_window() {
+    let mut env = TestEnv::new().await;
+    let pool = env.create_pool(&PoolConfig::default()).await; // 1_000 per block over 100..1_100
+    env.process(&[set_bonus_time(&pool, 3, 200, 300)], &[&pool.owner]).await.unwrap();
+
+    let (result, logs) = env.process_with_logs(&[log_emitted_between(&pool, 150, 350)], &[]).await;
+    result.unwrap();
+    // 50 blocks before the bonus, 100 blocks at x3 and 50 blocks after it
+    assert!(logs.contains(&"Program log: Emitted between 150 and 350: 400000".to_string()));
+
+    let (result, logs) = env.process_with_logs(&[log_emitted_between(&pool, 200, 300)], &[]).await;
+    result.unwrap();
+    assert!(logs.contains(&"Program log: Emitted between 200 and 300: 300000".to_string()));
+}
+
+#[tokio::test]
+async fn test_log_tvl_snapshot_matches_accounts() {
+    let mut env = TestEnv::new().await;
+    let pool = env.create_pool(&PoolConfig {
+        tags: 0b101,
+        ..PoolConfig::default()
+    }).await;
+    let first = env.create_staker(&pool.mint, 1_000).await;
+    let second = env.create_staker(&pool.mint, 3_000).await;
+
+    env.warp_to(100).await;
+    env.deposit(&pool, &first, 1_000).await.unwrap();
+    env.deposit(&pool, &second, 3_000).await.unwrap();
+    env.warp_to(150).await;
+    env.harvest(&pool, &first).await.unwrap();
+
+    let (result, logs) = env.process_with_logs(&[log_tvl_snapshot(&pool)], &[]).await;
+    result.unwrap();
+    let snapshots = events::<TvlSnapshot>(&logs);
+    assert_eq!(snapshots.len(), 1);
+    let snapshot = &snapshots[0];
+
+    let stake_pool = env.stake_pool(&pool).await;
+    assert_eq!(snapshot.pool_index, pool.index);
+    assert_eq!(snapshot.slot, 150);
+    assert_eq!(snapshot.staked_amount, env.balance(&pool.staked_vault()).await);
+    assert_eq!(snapshot.staked_amount, 4_000);
+    assert_eq!(snapshot.reward_amount, env.balance(&pool.reward_vault()).await);
+    assert_eq!(snapshot.reward_amount, 1_000_000 - 12_500);
+    assert_eq!(snapshot.total_staked, stake_pool.total_staked);
+    assert_eq!(snapshot.tags, 0b101);
+    assert_eq!(snapshot.stakers, 2);
+}
+
+async fn capacity_log(env: &mut TestEnv, pool: &Pool) -> String {
+    let (result, logs) = env.process_with_logs(&[log_pool_capacity(pool)], &[]).await;
+    result.unwrap();
+    logs.into_iter()
+        .find(|log| log.starts_with("Program log: Capacity"))
+        .expect("No capacity log")
+}
+
+#[tokio::test]
+async fn test_log_pool_capacity_empty_partial_and_full() {
+    let mut env = TestEnv::new().await;
+    let pool = env.create_pool(&PoolConfig::default()).await;
+    assert_eq!(
+        capacity_log(&mut env, &pool).await,
+        "Program log: Capacity: unlimited, staked: 0",
+    );
+
+    env.process(&[set_max_total_staked(&pool, 3_000)], &[&pool.owner]).await.unwrap();
+    let staker = env.create_staker(&pool.mint, 4_000).await;
+    assert_eq!(
+        capacity_log(&mut env, &pool).await,
+        "Program log: Capacity: 3000, staked: 0, headroom: 3000",
+    );
+
+    env.warp_to(100).await;
+    env.deposit(&pool, &staker, 1_000).await.unwrap();
+    assert_eq!(
+        capacity_log(&mut env, &pool).await,
+        "Program log: Capacity: 3000, staked: 1000, headroom: 2000",
+    );
+
+    env.deposit(&pool, &staker, 2_000).await.unwrap();
+    assert_eq!(
+        capacity_log(&mut env, &pool).await,
+        "Program log: Capacity: 3000, staked: 3000, headroom: 0",
+    );
+    assert_eq!(
+        env.deposit(&pool, &staker, 1_000).await.unwrap_err(),
+        staking_error(StakingError::PoolCapacityExceeded),
+    );
+
+    // tokens sent to the vault directly put it over the cap
+    env.set_balance(&pool.staked_vault(), 3_500).await;
+    assert_eq!(
+        capacity_log(&mut env, &pool).await,
+        "Program log: Capacity: 3000, staked: 3500, headroom: 0",
+    );
+}
+
+async fn depletion_log(env: &mut TestEnv, pool: &Pool) -> String {
+    let (result, logs) = env.process_with_logs(&[log_depletion_slot(pool)], &[]).await;
+    result.unwrap();
+    logs.into_iter()
+        .find(|log| log.starts_with("Program log: Depletion slot"))
+        .expect("No depletion slot log")
+}
+
+#[tokio::test]
+async fn test_log_depletion_slot_by_funding() {
+    let mut env = TestEnv::new().await;
+    let pool = env.create_pool(&PoolConfig::default()).await; // 1_000 per block over 100..1_100
+    assert_eq!(
+        depletion_log(&mut env, &pool).await,
+        "Program log: Depletion slot: 1100, end block: 1100",
+    );
+
+    env.set_balance(&pool.reward_vault(), 1_500_000).await;
+    assert_eq!(
+        depletion_log(&mut env, &pool).await,
+        "Program log: Depletion slot: 1100, end block: 1100",
+    );
+
+    env.set_balance(&pool.reward_vault(), 500_000).await;
+    assert_eq!(
+        depletion_log(&mut env, &pool).await,
+        "Program log: Depletion slot: 600, end block: 1100",
+    );
+
+    // 100 blocks before the bonus, then 50 blocks at x3
+    env.set_balance(&pool.reward_vault(), 1_000_000).await;
+    env.process(&[set_bonus_time(&pool, 3, 200, 300)], &[&pool.owner]).await.unwrap();
+    env.set_balance(&pool.reward_vault(), 250_000).await;
+    assert_eq!(
+        depletion_log(&mut env, &pool).await,
+        "Program log: Depletion slot: 250, end block: 900",
+    );
+}
+
+#[tokio::test]
+async fn test_log_user_debug_pending_matches_harvest() {
+    let mut env = TestEnv::new().await;
+    let pool = env.create_pool(&PoolConfig::default()).await;
+    let staker = env.create_staker(&pool.mint, 1_000).await;
+    let other = env.create_staker(&pool.mint, 2_000).await;
+
+    env.warp_to(100).await;
+    env.deposit(&pool, &staker, 1_000).await.unwrap();
+    env.deposit(&pool, &other, 2_000).await.unwrap();
+    env.warp_to(350).await;
+
+    let (result, logs) = env.process_with_logs(&[log_user_debug(&pool, &staker)], &[]).await;
+    result.unwrap();
+    let user_info = env.user_info(&pool, &staker).await;
+    assert!(logs.contains(&format!(
+        "Program log: amount: 1000, reward_debt: {}, shares: {}, pending_owed: 0, deposit_slot: 100",
+        user_info.reward_debt,
+        user_info.shares,
+    )));
+    let debug = logs.iter().find(|log| log.starts_with("Program log: slot: 350,")).unwrap();
+    let pending: u64 = debug
+        .split("pending: ").nth(1).unwrap()
+        .split(',').next().unwrap()
+        .parse().unwrap();
+    // a third of 250_000, rounded down
+    assert_eq!(pending, 83_333);
+    assert!(debug.ends_with(&format!("earned after warmup: {}", pending)));
+
+    env.harvest(&pool, &staker).await.unwrap();
+    assert_eq!(env.balance(&staker.token_account).await, pending);
+}
+
+#[tokio::test]
+async fn test_log_user_positions_of_three_stakers() {
+    let mut env = TestEnv::new().await;
+    let pool = env.create_pool(&PoolConfig::default()).await;
+    let mut stakers = vec![];
+    for amount in [1_000, 2_000, 3_000] {
+        stakers.push(env.create_staker(&pool.mint, amount).await);
+    }
+
+    env.warp_to(100).await;
+    for (staker, amount) in stakers.iter().zip([1_000, 2_000, 3_000]) {
+        env.deposit(&pool, staker, amount).await.unwrap();
+    }
+    env.warp_to(400).await;
+
+    let (result, logs) = env.process_with_logs(&[log_user_positions(&pool, &stakers.iter().collect::<Vec<_>>())], &[]).await;
+    result.unwrap();
+    let pages = events::<UserPositions>(&logs);
+    assert_eq!(pages.len(), 1);
+    assert_eq!(pages[0].pool_index, pool.index);
+    assert_eq!(pages[0].slot, 400);
+    // 300_000 emitted over 6_000 staked
+    let positions: Vec<_> = pages[0]
+        .positions
+        .iter()
+        .map(|position| (position.token_account, position.amount, position.pending))
+        .collect();
+    assert_eq!(positions, vec![
+        (stakers[0].token_account, 1_000, 50_000),
+        (stakers[1].token_account, 2_000, 100_000),
+        (stakers[2].token_account, 3_000, 150_000),
+    ]);
+
+    let result = env.process(&[log_user_positions(&pool, &[])], &[]).await;
+    assert_eq!(result.unwrap_err(), staking_error(StakingError::InvalidPositionCount));
+}
+
+#[tokio::test]
+async fn test_log_pool_summary_matches_state() {
+    let mut env = TestEnv::new().await;
+    let pool = env.create_pool(&PoolConfig {
+        tags: 0b11,
+        ..PoolConfig::default()
+    }).await;
+    env.process(&[set_bonus_time(&pool, 3, 200, 300)], &[&pool.owner]).await.unwrap();
+    let staker = env.create_staker(&pool.mint, 2_000).await;
+
+    env.warp_to(100).await;
+    env.deposit(&pool, &staker, 2_000).await.unwrap();
+    env.warp_to(250).await;
+
+    let (result, logs) = env.process_with_logs(&[log_pool_summary(&pool)], &[]).await;
+    result.unwrap();
+    let summaries = events::<PoolSummary>(&logs);
+    assert_eq!(summaries.len(), 1);
+    let summary = &summaries[0];
+
+    let stake_pool = env.stake_pool(&pool).await;
+    assert_eq!(summary.pool_index, pool.index);
+    assert_eq!(summary.slot, 250);
+    assert_eq!(summary.pool_name, stake_pool.pool_name);
+    assert_eq!(summary.pool_name, name("test pool"));
+    assert_eq!(summary.project_link, stake_pool.project_link);
+    assert_eq!(summary.theme_id, stake_pool.theme_id);
+    assert_eq!(summary.tags, 0b11);
+    assert_eq!((summary.start_block, summary.end_block), (stake_pool.start_block, stake_pool.end_block));
+    assert_eq!(summary.remaining_blocks, stake_pool.end_block - 250);
+    assert!(summary.bonus_active);
+    assert_eq!(summary.bonus_multiplier, 3);
+    // 1_000 per block over 216_000 slots a day, staked 2_000
+    assert_eq!(summary.reward_per_day, 216_000_000);
+    assert_eq!(summary.apr_bps, 216_000_000 * 365 * 10_000 / 2_000);
+    assert_eq!(summary.staked_amount, env.balance(&pool.staked_vault()).await);
+    assert_eq!(summary.total_staked, stake_pool.total_staked);
+    assert_eq!(summary.total_staked, 2_000);
+    assert_eq!(summary.stakers, 1);
+}
+
+fn log_pool_summaries(pools: &[&Pool]) -> Instruction {
+    let mut accounts = vec![AccountMeta::new_readonly(sysvar::clock::id(), false)];
+    for pool in pools {
+        accounts.push(AccountMeta::new_readonly(pool.key(), false));
+        accounts.push(AccountMeta::new_readonly(pool.staked_vault(), false));
+    }
+
+    instruction(StakingInstruction::LogPoolSummaries, accounts)
+}
+
+#[tokio::test]
+async fn test_log_pool_summaries_of_three_pools() {
+    let mut env = TestEnv::new().await;
+    let mut pools = vec![];
+    for end_block in [1_100, 600, 2_100] {
+        pools.push(env.create_pool(&PoolConfig {
+            end_block,
+            ..PoolConfig::default()
+        }).await);
+    }
+    let staker = env.create_staker(&pools[1].mint, 2_000).await;
+
+    env.warp_to(100).await;
+    env.deposit(&pools[1], &staker, 2_000).await.unwrap();
+    env.warp_to(250).await;
+
+    let pools = pools.iter().collect::<Vec<_>>();
+    let (result, logs) = env.process_with_logs(&[log_pool_summaries(&pools)], &[]).await;
+    result.unwrap();
+    let batches = events::<PoolSummaries>(&logs);
+    assert_eq!(batches.len(), 1);
+    let summaries = &batches[0].summaries;
+    assert_eq!(summaries.len(), 3);
+
+    for ((summary, pool), end_block) in summaries.iter().zip(pools.iter()).zip([1_100, 600, 2_100]) {
+        assert_eq!(summary.pool_index, pool.index);
+        assert_eq!(summary.slot, 250);
+        assert_eq!(summary.end_block, end_block);
+        assert_eq!(summary.remaining_blocks, end_block - 250);
+    }
+    assert_eq!(summaries.iter().map(|summary| summary.total_staked).collect::<Vec<_>>(), vec![0, 2_000, 0]);
+    assert_eq!(summaries[1].stakers, 1);
+
+    // an account that is not a StakePool, and no pools at all
+    let mut not_a_pool = log_pool_summaries(&pools);
+    not_a_pool.accounts[3].pubkey = staker.token_account;
+    assert_eq!(
+        env.process(&[not_a_pool], &[]).await.unwrap_err(),
+        staking_error(StakingError::NotInitialized),
+    );
+    assert_eq!(
+        env.process(&[log_pool_summaries(&[])], &[]).await.unwrap_err(),
+        staking_error(StakingError::InvalidPoolCount),
+    );
+}
+
+fn log_user_projected_rewards(pool: &Pool, staker: &Staker) -> Instruction {
+    instruction(
+        StakingInstruction::LogUserProjectedRewards,
+        vec![
+            AccountMeta::new_readonly(pool.key(), false),
+            AccountMeta::new_readonly(staker.user_info(pool), false),
+            AccountMeta::new_readonly(staker.token_account, false),
+            AccountMeta::new_readonly(pool.staked_vault(), false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+        ],
+    )
+}
+
+#[tokio::test]
+async fn test_log_user_projected_rewards_by_stake_proportion() {
+    let mut env = TestEnv::new().await;
+    let pool = env.create_pool(&PoolConfig::default()).await; // 1_000 per block over 100..1_100
+    // x3 over 200..300 moves end_block to 900
+    env.process(&[set_bonus_time(&pool, 3, 200, 300)], &[&pool.owner]).await.unwrap();
+    let first = env.create_staker(&pool.mint, 1_000).await;
+    let second = env.create_staker(&pool.mint, 3_000).await;
+
+    env.warp_to(100).await;
+    env.deposit(&pool, &first, 1_000).await.unwrap();
+    env.deposit(&pool, &second, 3_000).await.unwrap();
+    env.warp_to(250).await;
+
+    // 50 blocks at x3 and 600 blocks after the bonus
+    for (staker, shares, projected) in [(&first, 1_000, 187_500), (&second, 3_000, 562_500)] {
+        let (result, logs) = env.process_with_logs(&[log_user_projected_rewards(&pool, staker)], &[]).await;
+        result.unwrap();
+        let events = events::<UserProjectedRewards>(&logs);
+        assert_eq!(events.len(), 1);
+        let event = &events[0];
+        assert_eq!(event.pool_index, pool.index);
+        assert_eq!(event.token_account, staker.token_account);
+        assert_eq!(event.slot, 250);
+        assert_eq!(event.shares, shares);
+        assert_eq!(event.staked_supply, 4_000);
+        assert_eq!(event.remaining_blocks, 650);
+        assert_eq!(event.projected, projected);
+    }
+}
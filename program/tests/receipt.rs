@@ -0,0 +1,107 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use solana_program::{
+    instruction::{
+        AccountMeta,
+        Instruction,
+    },
+    pubkey::Pubkey,
+    sysvar,
+};
+use solana_program_test::*;
+use solana_sdk::signature::Signer;
+use staking_program::{
+    instruction::StakingInstruction,
+    pda::{
+        receipt_mint_address,
+        token_account_authority_address,
+    },
+};
+
+fn receipt_mint(pool: &Pool) -> Pubkey {
+    receipt_mint_address(pool.index).0
+}
+
+fn mint_receipt(pool: &Pool, staker: &Staker, receipt_account: &Pubkey, amount: u64) -> Instruction {
+    instruction(
+        StakingInstruction::MintReceipt { amount },
+        vec![
+            AccountMeta::new_readonly(staker.owner.pubkey(), true),
+            AccountMeta::new(staker.token_account, false),
+            AccountMeta::new(receipt_mint(pool), false),
+            AccountMeta::new(*receipt_account, false),
+            AccountMeta::new(pool.key(), false),
+            AccountMeta::new_readonly(token_account_authority_address().unwrap(), false),
+            AccountMeta::new(pool.staked_vault(), false),
+            AccountMeta::new(pool.reward_vault(), false),
+            AccountMeta::new(staker.user_info(pool), false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+    )
+}
+
+/// `holder` redeems the receipts of `receipt_account` into its token-account
+fn burn_receipt(pool: &Pool, holder: &Staker, receipt_account: &Pubkey, amount: u64) -> Instruction {
+    instruction(
+        StakingInstruction::BurnReceipt { amount },
+        vec![
+            AccountMeta::new_readonly(holder.owner.pubkey(), true),
+            AccountMeta::new(*receipt_account, false),
+            AccountMeta::new(holder.token_account, false),
+            AccountMeta::new(pool.key(), false),
+            AccountMeta::new_readonly(token_account_authority_address().unwrap(), false),
+            AccountMeta::new(pool.staked_vault(), false),
+            AccountMeta::new(receipt_mint(pool), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+    )
+}
+
+#[tokio::test]
+async fn test_receipt_redeemed_from_another_wallet() {
+    let mut env = TestEnv::new().await;
+    let pool = env.create_pool(&PoolConfig {
+        with_receipt_mint: true,
+        ..PoolConfig::default()
+    }).await;
+    let staker = env.create_staker(&pool.mint, 1_000).await;
+    let buyer = env.create_staker(&pool.mint, 0).await;
+    let staker_receipts = env.create_token_account(&receipt_mint(&pool), &staker.owner.pubkey()).await;
+    let buyer_receipts = env.create_token_account(&receipt_mint(&pool), &buyer.owner.pubkey()).await;
+
+    env.warp_to(100).await;
+    env.deposit(&pool, &staker, 1_000).await.unwrap();
+    env.warp_to(200).await;
+    env.process(&[mint_receipt(&pool, &staker, &staker_receipts, 400)], &[&staker.owner]).await.unwrap();
+    // one receipt for each staked token moved out of the position, pending rewards are paid
+    assert_eq!(env.balance(&staker_receipts).await, 400);
+    assert_eq!(env.user_info(&pool, &staker).await.amount, 600);
+    assert_eq!(env.stake_pool(&pool).await.receipt_supply, 400);
+    assert_eq!(env.balance(&staker.token_account).await, 100_000);
+
+    env.process(
+        &[spl_token::instruction::transfer(
+            &spl_token::id(),
+            &staker_receipts,
+            &buyer_receipts,
+            &staker.owner.pubkey(),
+            &[],
+            400,
+        )
+        .unwrap()],
+        &[&staker.owner],
+    )
+    .await
+    .unwrap();
+
+    env.process(&[burn_receipt(&pool, &buyer, &buyer_receipts, 400)], &[&buyer.owner]).await.unwrap();
+    assert_eq!(env.balance(&buyer_receipts).await, 0);
+    assert_eq!(env.balance(&buyer.token_account).await, 400);
+    assert_eq!(env.stake_pool(&pool).await.receipt_supply, 0);
+    assert_eq!(env.balance(&pool.staked_vault()).await, 600);
+    assert_eq!(env.user_info(&pool, &staker).await.amount, 600);
+}
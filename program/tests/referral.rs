@@ -0,0 +1,81 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use borsh::BorshDeserialize;
+use common::*;
+use solana_program::{
+    instruction::{
+        AccountMeta,
+        Instruction,
+    },
+    system_program,
+    sysvar,
+};
+use solana_program_test::*;
+use solana_sdk::signature::Signer;
+use staking_program::{
+    error::StakingError,
+    events::ReferralDeposit,
+    instruction::StakingInstruction,
+    pda::referral_code_address,
+    state::ReferralCode,
+};
+
+const CODE: [u8; 16] = *b"WELCOME\0\0\0\0\0\0\0\0\0";
+
+fn register_referral_code(referrer: &Staker, code: [u8; 16]) -> Instruction {
+    instruction(
+        StakingInstruction::RegisterReferralCode { code },
+        vec![
+            AccountMeta::new(referrer.owner.pubkey(), true),
+            AccountMeta::new_readonly(referrer.token_account, false),
+            AccountMeta::new(referral_code_address(&code).0, false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+#[tokio::test]
+async fn test_deposit_with_registered_referral_code() {
+    let mut env = TestEnv::new().await;
+    let pool = env.create_pool(&PoolConfig::default()).await;
+    let referrer = env.create_staker(&pool.mint, 0).await;
+    let staker = env.create_staker(&pool.mint, 1_000).await;
+    env.process(&[register_referral_code(&referrer, CODE)], &[&referrer.owner]).await.unwrap();
+
+    let account = env.get_account(&referral_code_address(&CODE).0).await.unwrap();
+    let referral_code = ReferralCode::try_from_slice(&account.data).unwrap();
+    assert_eq!(referral_code.code, CODE);
+    assert_eq!(referral_code.referrer, referrer.token_account);
+
+    env.warp_to(100).await;
+    let mut instruction = deposit(&pool, &staker, 1_000);
+    instruction.accounts.push(AccountMeta::new_readonly(referral_code_address(&CODE).0, false));
+    let (result, logs) = env.process_with_logs(&[instruction], &[&staker.owner]).await;
+    result.unwrap();
+
+    let deposits = events::<ReferralDeposit>(&logs);
+    assert_eq!(deposits.len(), 1);
+    assert_eq!(deposits[0].pool_index, pool.index);
+    assert_eq!(deposits[0].code, CODE);
+    assert_eq!(deposits[0].referrer, referrer.token_account);
+    assert_eq!(deposits[0].token_account, staker.token_account);
+    assert_eq!(deposits[0].amount, 1_000);
+}
+
+#[tokio::test]
+async fn test_register_taken_referral_code_is_rejected() {
+    let mut env = TestEnv::new().await;
+    let pool = env.create_pool(&PoolConfig::default()).await;
+    let first = env.create_staker(&pool.mint, 0).await;
+    let second = env.create_staker(&pool.mint, 0).await;
+    env.process(&[register_referral_code(&first, CODE)], &[&first.owner]).await.unwrap();
+
+    let result = env.process(&[register_referral_code(&second, CODE)], &[&second.owner]).await;
+    assert_eq!(result.unwrap_err(), staking_error(StakingError::CodeTaken));
+
+    let account = env.get_account(&referral_code_address(&CODE).0).await.unwrap();
+    assert_eq!(ReferralCode::try_from_slice(&account.data).unwrap().referrer, first.token_account);
+}
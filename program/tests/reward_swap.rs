@@ -0,0 +1,139 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use borsh::BorshDeserialize;
+use common::*;
+use solana_program::{
+    account_info::{
+        next_account_info,
+        AccountInfo,
+    },
+    entrypoint::ProgramResult,
+    instruction::{
+        AccountMeta,
+        Instruction,
+    },
+    program::{
+        invoke,
+        invoke_signed,
+    },
+    pubkey::Pubkey,
+};
+use solana_program_test::*;
+use solana_sdk::signature::Signer;
+use staking_program::{
+    error::StakingError,
+    instruction::{
+        RewardSwap,
+        StakingInstruction,
+    },
+    REWARD_SWAP_PROGRAMS,
+};
+use std::str::FromStr;
+
+fn mock_swap_program_id() -> Pubkey {
+    Pubkey::from_str(REWARD_SWAP_PROGRAMS[0]).unwrap()
+}
+
+/// Owner of the output vault of the mock swap
+fn mock_swap_authority() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"swap"], &mock_swap_program_id())
+}
+
+/// Pays 2 output tokens per reward token. Accounts after the ones of the CPI contract:
+/// input token-account of the swap, output vault, swap authority
+fn process_mock_swap(
+    _program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    input: &[u8],
+) -> ProgramResult {
+    let RewardSwap { amount_in } = RewardSwap::try_from_slice(input)?;
+    let account_info_iter = &mut accounts.iter();
+    let authority_info = next_account_info(account_info_iter)?;
+    let reward_vault_info = next_account_info(account_info_iter)?;
+    let destination_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+    let swap_input_info = next_account_info(account_info_iter)?;
+    let swap_output_info = next_account_info(account_info_iter)?;
+    let swap_authority_info = next_account_info(account_info_iter)?;
+
+    invoke(
+        &spl_token::instruction::transfer(
+            &spl_token::id(),
+            reward_vault_info.key,
+            swap_input_info.key,
+            authority_info.key,
+            &[],
+            amount_in,
+        )?,
+        &[reward_vault_info.clone(), swap_input_info.clone(), authority_info.clone(), token_program_info.clone()],
+    )?;
+
+    invoke_signed(
+        &spl_token::instruction::transfer(
+            &spl_token::id(),
+            swap_output_info.key,
+            destination_info.key,
+            swap_authority_info.key,
+            &[],
+            2 * amount_in,
+        )?,
+        &[swap_output_info.clone(), destination_info.clone(), swap_authority_info.clone(), token_program_info.clone()],
+        &[&[b"swap", &[mock_swap_authority().1]]],
+    )
+}
+
+fn set_reward_swap_program(pool: &Pool, reward_swap_program: Option<Pubkey>) -> Instruction {
+    instruction(
+        StakingInstruction::SetRewardSwapProgram { reward_swap_program },
+        vec![
+            AccountMeta::new_readonly(pool.owner.pubkey(), true),
+            AccountMeta::new_readonly(pool.mint, false),
+            AccountMeta::new(pool.key(), false),
+        ],
+    )
+}
+
+#[tokio::test]
+async fn test_withdraw_pays_rewards_through_swap_program() {
+    let mut program_test = program_test();
+    program_test.add_program("mock_swap", mock_swap_program_id(), processor!(process_mock_swap));
+    let mut env = TestEnv::with_program_test(program_test).await;
+
+    let pool = env.create_pool(&PoolConfig::default()).await;
+    let staker = env.create_staker(&pool.mint, 1_000).await;
+
+    let output_mint = env.create_mint(DECIMALS).await;
+    let swap_input = env.create_token_account(&pool.mint, &mock_swap_authority().0).await;
+    let swap_output = env.create_token_account(&output_mint, &mock_swap_authority().0).await;
+    env.mint_to(&output_mint, &swap_output, 1_000_000).await;
+    let destination = env.create_token_account(&output_mint, &staker.owner.pubkey()).await;
+
+    // Only allowlisted programs are accepted
+    assert_eq!(
+        env.process(&[set_reward_swap_program(&pool, Some(Pubkey::new_unique()))], &[&pool.owner]).await.unwrap_err(),
+        staking_error(StakingError::RewardSwapProgramNotAllowed),
+    );
+    env.process(&[set_reward_swap_program(&pool, Some(mock_swap_program_id()))], &[&pool.owner]).await.unwrap();
+
+    env.warp_to(100).await;
+    env.deposit(&pool, &staker, 1_000).await.unwrap();
+    env.warp_to(200).await;
+
+    let mut withdraw = withdraw(&pool, &staker, 1_000);
+    withdraw.accounts.extend([
+        AccountMeta::new_readonly(mock_swap_program_id(), false),
+        AccountMeta::new(destination, false),
+        AccountMeta::new(swap_input, false),
+        AccountMeta::new(swap_output, false),
+        AccountMeta::new_readonly(mock_swap_authority().0, false),
+    ]);
+    env.process(&[withdraw], &[&staker.owner]).await.unwrap();
+
+    // 100 blocks of 1_000 go through the swap, the staked tokens come back as they are
+    assert_eq!(env.balance(&swap_input).await, 100_000);
+    assert_eq!(env.balance(&destination).await, 200_000);
+    assert_eq!(env.balance(&staker.token_account).await, 1_000);
+    assert_eq!(env.balance(&pool.reward_vault()).await, 900_000);
+}
@@ -0,0 +1,153 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use solana_program::{
+    instruction::{
+        AccountMeta,
+        Instruction,
+    },
+    pubkey::Pubkey,
+    sysvar,
+};
+use solana_program_test::*;
+use solana_sdk::signature::Signer;
+use staking_program::{
+    error::StakingError,
+    instruction::StakingInstruction,
+    pda::{
+        reward_token_info_address,
+        token_account_authority_address,
+    },
+};
+
+fn claim_reward_token(pool: &Pool, staker: &Staker, reward_index: u8, destination: &Pubkey) -> Instruction {
+    instruction(
+        StakingInstruction::ClaimRewardToken { reward_index },
+        vec![
+            AccountMeta::new_readonly(staker.owner.pubkey(), true),
+            AccountMeta::new_readonly(staker.token_account, false),
+            AccountMeta::new(pool.key(), false),
+            AccountMeta::new_readonly(token_account_authority_address().unwrap(), false),
+            AccountMeta::new_readonly(pool.staked_vault(), false),
+            AccountMeta::new_readonly(pool.reward_vault(), false),
+            AccountMeta::new(staker.user_info(pool), false),
+            AccountMeta::new_readonly(reward_token_info_address(pool.index, reward_index).0, false),
+            AccountMeta::new(pool.extra_vault(reward_index), false),
+            AccountMeta::new(*destination, false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+    )
+}
+
+#[tokio::test]
+async fn test_reward_tokens_accrue_at_own_rates() {
+    let mut env = TestEnv::new().await;
+    // 1_000 and 300 per block over 100..1_100
+    let pool = env.create_pool(&PoolConfig {
+        extra_reward_amounts: vec![300_000],
+        ..PoolConfig::default()
+    }).await;
+    assert_eq!(env.stake_pool(&pool).await.reward_per_block, [1_000, 300, 0, 0]);
+    let staker = env.create_staker(&pool.mint, 1_000).await;
+    let extra_rewards = env.create_token_account(&pool.extra_mints[0], &staker.owner.pubkey()).await;
+
+    env.warp_to(100).await;
+    env.deposit(&pool, &staker, 1_000).await.unwrap();
+    env.warp_to(300).await;
+    env.harvest(&pool, &staker).await.unwrap();
+    env.process(&[claim_reward_token(&pool, &staker, 1, &extra_rewards)], &[&staker.owner]).await.unwrap();
+
+    assert_eq!(env.balance(&staker.token_account).await, 200_000);
+    assert_eq!(env.balance(&extra_rewards).await, 60_000);
+    assert_eq!(env.balance(&pool.extra_vault(1)).await, 240_000);
+}
+
+#[tokio::test]
+async fn test_two_reward_tokens_pay_out_proportionally() {
+    let mut env = TestEnv::new().await;
+    // 1_000 and 300 per block over 100..1_100
+    let pool = env.create_pool(&PoolConfig {
+        extra_reward_amounts: vec![300_000],
+        ..PoolConfig::default()
+    }).await;
+    let first = env.create_staker(&pool.mint, 1_000).await;
+    let second = env.create_staker(&pool.mint, 3_000).await;
+    let first_extra_rewards = env.create_token_account(&pool.extra_mints[0], &first.owner.pubkey()).await;
+    let second_extra_rewards = env.create_token_account(&pool.extra_mints[0], &second.owner.pubkey()).await;
+
+    // 100 more blocks funded for both tokens
+    env.mint_to(&pool.mint, &pool.reward_source, 100_000).await;
+    env.mint_to(&pool.extra_mints[0], &pool.extra_sources[0], 30_000).await;
+    env.process(&[update_end_block(&pool, 1_200)], &[&pool.owner]).await.unwrap();
+    assert_eq!(env.balance(&pool.reward_vault()).await, 1_100_000);
+    assert_eq!(env.balance(&pool.extra_vault(1)).await, 330_000);
+
+    env.warp_to(100).await;
+    env.deposit(&pool, &first, 1_000).await.unwrap();
+    env.deposit(&pool, &second, 3_000).await.unwrap();
+    env.warp_to(300).await;
+
+    // a quarter and three quarters of 200_000 and 60_000
+    for (staker, extra_rewards, reward, extra_reward) in [
+        (&first, &first_extra_rewards, 50_000, 15_000),
+        (&second, &second_extra_rewards, 150_000, 45_000),
+    ] {
+        env.harvest(&pool, staker).await.unwrap();
+        env.process(&[claim_reward_token(&pool, staker, 1, extra_rewards)], &[&staker.owner]).await.unwrap();
+        assert_eq!(env.balance(&staker.token_account).await, reward);
+        assert_eq!(env.balance(extra_rewards).await, extra_reward);
+    }
+}
+
+fn retire_reward_token(pool: &Pool, index: u8) -> Instruction {
+    instruction(
+        StakingInstruction::RetireRewardToken { index },
+        vec![
+            AccountMeta::new_readonly(pool.owner.pubkey(), true),
+            AccountMeta::new_readonly(pool.mint, false),
+            AccountMeta::new(pool.key(), false),
+            AccountMeta::new_readonly(pool.staked_vault(), false),
+            AccountMeta::new_readonly(pool.reward_vault(), false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+        ],
+    )
+}
+
+#[tokio::test]
+async fn test_retire_second_reward_token() {
+    let mut env = TestEnv::new().await;
+    let pool = env.create_pool(&PoolConfig {
+        extra_reward_amounts: vec![300_000],
+        ..PoolConfig::default()
+    }).await;
+    let staker = env.create_staker(&pool.mint, 1_000).await;
+    let extra_rewards = env.create_token_account(&pool.extra_mints[0], &staker.owner.pubkey()).await;
+
+    env.warp_to(100).await;
+    env.deposit(&pool, &staker, 1_000).await.unwrap();
+    env.warp_to(200).await;
+
+    // the primary reward token stays
+    assert_eq!(
+        env.process(&[retire_reward_token(&pool, 0)], &[&pool.owner]).await.unwrap_err(),
+        staking_error(StakingError::InvalidRewardIndex),
+    );
+    env.process(&[retire_reward_token(&pool, 1)], &[&pool.owner]).await.unwrap();
+    let stake_pool = env.stake_pool(&pool).await;
+    assert_eq!(stake_pool.n_reward_tokens, 1);
+    assert_eq!(stake_pool.reward_per_block, [1_000, 0, 0, 0]);
+    assert_eq!(
+        env.process(&[retire_reward_token(&pool, 1)], &[&pool.owner]).await.unwrap_err(),
+        staking_error(StakingError::InvalidRewardIndex),
+    );
+
+    // accrued till the retirement stays claimable, nothing accrues after it
+    env.warp_to(400).await;
+    env.harvest(&pool, &staker).await.unwrap();
+    env.process(&[claim_reward_token(&pool, &staker, 1, &extra_rewards)], &[&staker.owner]).await.unwrap();
+    assert_eq!(env.balance(&staker.token_account).await, 300_000);
+    assert_eq!(env.balance(&extra_rewards).await, 30_000);
+}
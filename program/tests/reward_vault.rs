@@ -0,0 +1,176 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use solana_program::{
+    instruction::{
+        AccountMeta,
+        Instruction,
+    },
+    sysvar,
+};
+use solana_program_test::*;
+use solana_sdk::signature::Signer;
+use staking_program::{
+    error::StakingError,
+    events::{
+        DonationEvent,
+        RewardsReclaimed,
+    },
+    instruction::StakingInstruction,
+    pda::token_account_authority_address,
+};
+
+#[tokio::test]
+async fn test_solvency_follows_reward_vault() {
+    let mut env = TestEnv::new().await;
+    let pool = env.create_pool(&PoolConfig::default()).await; // 1_000 per block from 100
+    let first = env.create_staker(&pool.mint, 1_000).await;
+    let second = env.create_staker(&pool.mint, 1_000).await;
+
+    env.warp_to(100).await;
+    env.deposit(&pool, &first, 1_000).await.unwrap();
+    assert!(env.stake_pool(&pool).await.is_solvent());
+
+    // 100_000 is owed to the first staker at 200
+    env.warp_to(200).await;
+    env.set_balance(&pool.reward_vault(), 50_000).await;
+    assert_eq!(
+        env.deposit(&pool, &second, 500).await.unwrap_err(),
+        staking_error(StakingError::PoolInsolvent),
+    );
+
+    env.set_balance(&pool.reward_vault(), 1_000_000).await;
+    env.deposit(&pool, &second, 500).await.unwrap();
+    let stake_pool = env.stake_pool(&pool).await;
+    assert!(stake_pool.is_solvent());
+    assert_eq!(stake_pool.total_owed, 100_000);
+
+    env.warp_to(300).await;
+    env.set_balance(&pool.reward_vault(), 100_000).await;
+    assert_eq!(
+        env.deposit(&pool, &second, 500).await.unwrap_err(),
+        staking_error(StakingError::PoolInsolvent),
+    );
+}
+
+#[tokio::test]
+async fn test_empty_reward_vault_pauses_accrual_till_funded() {
+    let mut env = TestEnv::new().await;
+    let pool = env.create_pool(&PoolConfig::default()).await; // 1_000 per block from 100
+    let staker = env.create_staker(&pool.mint, 1_000).await;
+
+    env.warp_to(100).await;
+    env.deposit(&pool, &staker, 1_000).await.unwrap();
+    env.warp_to(200).await;
+    env.harvest(&pool, &staker).await.unwrap();
+    assert_eq!(env.balance(&staker.token_account).await, 100_000);
+
+    // drained at 200, refunded at 300
+    env.set_balance(&pool.reward_vault(), 0).await;
+    env.warp_to(300).await;
+    env.mint_to(&pool.mint, &pool.reward_source, 200_000).await;
+    env.process(&[fund_rewards(&pool, 200_000)], &[&pool.owner]).await.unwrap();
+    let stake_pool = env.stake_pool(&pool).await;
+    assert_eq!(stake_pool.total_owed, 0);
+    assert_eq!(stake_pool.last_reward_block, 300);
+
+    // only the blocks after the refund are paid
+    env.warp_to(400).await;
+    env.harvest(&pool, &staker).await.unwrap();
+    assert_eq!(env.balance(&staker.token_account).await, 200_000);
+    assert_eq!(env.balance(&pool.reward_vault()).await, 100_000);
+}
+
+/// Rewards to the reward source of the owner
+fn reclaim_rewards(pool: &Pool, amount: u64) -> Instruction {
+    instruction(
+        StakingInstruction::ReclaimRewards { amount },
+        vec![
+            AccountMeta::new_readonly(pool.owner.pubkey(), true),
+            AccountMeta::new_readonly(pool.mint, false),
+            AccountMeta::new(pool.key(), false),
+            AccountMeta::new_readonly(token_account_authority_address().unwrap(), false),
+            AccountMeta::new_readonly(pool.staked_vault(), false),
+            AccountMeta::new(pool.reward_vault(), false),
+            AccountMeta::new(pool.reward_source, false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+    )
+}
+
+#[tokio::test]
+async fn test_reclaim_rewards_leaves_unclaimed_position_owed() {
+    let mut env = TestEnv::new().await;
+    let pool = env.create_pool(&PoolConfig::default()).await; // 1_000 per block over 100..1_100
+    let staker = env.create_staker(&pool.mint, 1_000).await;
+
+    env.warp_to(100).await;
+    env.deposit(&pool, &staker, 1_000).await.unwrap();
+    env.warp_to(1_200).await;
+    // 200_000 on top of the 1_000_000 the staker hasn't harvested yet
+    env.mint_to(&pool.mint, &pool.reward_vault(), 200_000).await;
+    let source_balance = env.balance(&pool.reward_source).await;
+
+    assert_eq!(
+        env.process(&[reclaim_rewards(&pool, 200_001)], &[&pool.owner]).await.unwrap_err(),
+        staking_error(StakingError::RewardsStillOwed),
+    );
+
+    let (result, logs) = env.process_with_logs(&[reclaim_rewards(&pool, 200_000)], &[&pool.owner]).await;
+    result.unwrap();
+    let reclaimed = &events::<RewardsReclaimed>(&logs)[0];
+    assert_eq!((reclaimed.amount, reclaimed.total_owed), (200_000, 1_000_000));
+    assert_eq!(env.balance(&pool.reward_source).await, source_balance + 200_000);
+    assert_eq!(env.balance(&pool.reward_vault()).await, 1_000_000);
+
+    // Harvesting late still pays everything
+    env.harvest(&pool, &staker).await.unwrap();
+    assert_eq!(env.balance(&staker.token_account).await, 1_000_000);
+}
+
+/// Reward tokens of the token-account of `donor`
+fn donate_rewards(pool: &Pool, donor: &Staker, amount: u64) -> Instruction {
+    instruction(
+        StakingInstruction::DonateRewards { amount },
+        vec![
+            AccountMeta::new_readonly(donor.owner.pubkey(), true),
+            AccountMeta::new(donor.token_account, false),
+            AccountMeta::new(pool.key(), false),
+            AccountMeta::new(pool.reward_vault(), false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+    )
+}
+
+#[tokio::test]
+async fn test_donation_by_non_owner_deepens_vault_and_extends_pool() {
+    let mut env = TestEnv::new().await;
+    let pool = env.create_pool(&PoolConfig::default()).await; // 1_000 per block over 100..1_100
+    let donor = env.create_staker(&pool.mint, 50_000).await;
+    let total_funded = env.stake_pool(&pool).await.total_funded;
+
+    env.warp_to(200).await;
+    let (result, logs) = env.process_with_logs(&[donate_rewards(&pool, &donor, 50_000)], &[&donor.owner]).await;
+    result.unwrap();
+
+    assert_eq!(env.balance(&donor.token_account).await, 0);
+    assert_eq!(env.balance(&pool.reward_vault()).await, 1_050_000);
+    let stake_pool = env.stake_pool(&pool).await;
+    assert_eq!(stake_pool.total_funded, total_funded + 50_000);
+    // 50 blocks at 1_000 per block
+    assert_eq!(stake_pool.end_block, 1_150);
+    let donation = &events::<DonationEvent>(&logs)[0];
+    assert_eq!(donation.pool_index, pool.index);
+    assert_eq!((donation.amount, donation.blocks_added, donation.end_block), (50_000, 50, 1_150));
+
+    env.warp_to(1_150).await;
+    env.mint_to(&pool.mint, &donor.token_account, 1_000).await;
+    assert_eq!(
+        env.process(&[donate_rewards(&pool, &donor, 1_000)], &[&donor.owner]).await.unwrap_err(),
+        staking_error(StakingError::PoolEnded),
+    );
+}
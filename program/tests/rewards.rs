@@ -0,0 +1,334 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use solana_program::{
+    instruction::{
+        AccountMeta,
+        Instruction,
+    },
+    sysvar,
+};
+use solana_program_test::*;
+use solana_sdk::signature::Signer;
+use staking_program::{
+    instruction::StakingInstruction,
+    state::{
+        ACCRUAL_MODE_TIME_WEIGHTED,
+        LOYALTY_STEP_SLOTS,
+    },
+};
+
+/// Reward harvested past end_block by the only staker of a pool, staking from start_block
+async fn harvest_whole_schedule(
+    env: &mut TestEnv,
+    config: &PoolConfig,
+) -> u64 {
+    let pool = env.create_pool(config).await;
+    let staker = env.create_staker(&pool.mint, 1_000).await;
+
+    env.warp_to(config.start_block).await;
+    env.deposit(&pool, &staker, 1_000).await.unwrap();
+    env.warp_to(config.end_block + 10).await;
+    env.harvest(&pool, &staker).await.unwrap();
+
+    env.balance(&staker.token_account).await
+}
+
+#[tokio::test]
+async fn test_emission_period_keeps_total_emission() {
+    let mut env = TestEnv::new().await;
+
+    let every_slot = harvest_whole_schedule(&mut env, &PoolConfig {
+        emission_period: 1,
+        ..PoolConfig::default()
+    }).await;
+    let every_100_slots = harvest_whole_schedule(&mut env, &PoolConfig {
+        start_block: 1_300,
+        end_block: 2_300,
+        emission_period: 100,
+        ..PoolConfig::default()
+    }).await;
+
+    assert_eq!(every_slot, 1_000_000);
+    assert_eq!(every_100_slots, every_slot);
+}
+
+/// Reward paid to three equal stakers of a pool over the whole schedule, and the reward left in the vault
+async fn harvest_three_stakers(
+    env: &mut TestEnv,
+    config: &PoolConfig,
+) -> (u64, u64) {
+    let pool = env.create_pool(config).await;
+    let mut stakers = vec![];
+    for _ in 0..3 {
+        stakers.push(env.create_staker(&pool.mint, 1_000).await);
+    }
+
+    env.warp_to(config.start_block).await;
+    for staker in stakers.iter() {
+        env.deposit(&pool, staker, 1_000).await.unwrap();
+    }
+    env.warp_to(config.end_block + 10).await;
+
+    let mut paid = 0;
+    for staker in stakers.iter() {
+        env.harvest(&pool, staker).await.unwrap();
+        paid += env.balance(&staker.token_account).await;
+    }
+
+    (paid, env.balance(&pool.reward_vault()).await)
+}
+
+#[tokio::test]
+async fn test_rounding_modes_total_paid() {
+    let mut env = TestEnv::new().await;
+
+    let (paid_rounded_down, left_rounded_down) = harvest_three_stakers(&mut env, &PoolConfig {
+        round_up_rewards: 0,
+        ..PoolConfig::default()
+    }).await;
+    let (paid_rounded_up, left_rounded_up) = harvest_three_stakers(&mut env, &PoolConfig {
+        start_block: 1_300,
+        end_block: 2_300,
+        round_up_rewards: 1,
+        ..PoolConfig::default()
+    }).await;
+
+    // rounding down leaves the dust of the division in the vault
+    assert_eq!(paid_rounded_down + left_rounded_down, 1_000_000);
+    assert!(left_rounded_down > 0);
+    // rounding up pays the dust out, the last harvest takes what is left of the vault
+    assert!(paid_rounded_up > paid_rounded_down);
+    assert_eq!(paid_rounded_up, 1_000_000);
+    assert_eq!(left_rounded_up, 0);
+}
+
+fn set_warmup_slots(pool: &Pool, warmup_slots: u64) -> Instruction {
+    instruction(
+        StakingInstruction::SetWarmupSlots { warmup_slots },
+        vec![
+            AccountMeta::new_readonly(pool.owner.pubkey(), true),
+            AccountMeta::new_readonly(pool.mint, false),
+            AccountMeta::new(pool.key(), false),
+        ],
+    )
+}
+
+#[tokio::test]
+async fn test_warmup_keeps_unearned_rewards_in_vault() {
+    let mut env = TestEnv::new().await;
+    let pool = env.create_pool(&PoolConfig::default()).await;
+    let staker = env.create_staker(&pool.mint, 1_000).await;
+    env.process(&[set_warmup_slots(&pool, 200)], &[&pool.owner]).await.unwrap();
+
+    env.warp_to(100).await;
+    env.deposit(&pool, &staker, 1_000).await.unwrap();
+
+    // half way through the warmup, half of 100_000
+    env.warp_to(200).await;
+    env.harvest(&pool, &staker).await.unwrap();
+    assert_eq!(env.balance(&staker.token_account).await, 50_000);
+    assert_eq!(env.balance(&pool.reward_vault()).await, 950_000);
+
+    // a harvest doesn't restart the warmup, all of the next 200_000 is earned
+    env.warp_to(400).await;
+    env.harvest(&pool, &staker).await.unwrap();
+    assert_eq!(env.balance(&staker.token_account).await, 250_000);
+    assert_eq!(env.balance(&pool.reward_vault()).await, 750_000);
+}
+
+fn set_accrual_mode(pool: &Pool, accrual_mode: u8) -> Instruction {
+    instruction(
+        StakingInstruction::SetAccrualMode { accrual_mode },
+        vec![
+            AccountMeta::new_readonly(pool.owner.pubkey(), true),
+            AccountMeta::new_readonly(pool.mint, false),
+            AccountMeta::new(pool.key(), false),
+            AccountMeta::new_readonly(sysvar::instructions::id(), false),
+        ],
+    )
+}
+
+#[tokio::test]
+async fn test_time_weighted_long_term_staker_earns_more() {
+    let mut env = TestEnv::new().await;
+    // 22 per block, split 12 / 10 between a 1.2x and a 1x position of the same amount
+    let pool = env.create_pool(&PoolConfig {
+        reward_amount: 22 * 3 * LOYALTY_STEP_SLOTS,
+        end_block: 100 + 3 * LOYALTY_STEP_SLOTS,
+        ..PoolConfig::default()
+    }).await;
+    let long_term = env.create_staker(&pool.mint, 1_000).await;
+    let short_term = env.create_staker(&pool.mint, 1_000).await;
+    env.process(&[set_accrual_mode(&pool, ACCRUAL_MODE_TIME_WEIGHTED)], &[&pool.owner]).await.unwrap();
+
+    env.warp_to(100).await;
+    env.deposit(&pool, &long_term, 1_000).await.unwrap();
+
+    // two steps later, a Withdraw of 0 applies the weight of the long-term staker
+    let joined = 100 + 2 * LOYALTY_STEP_SLOTS;
+    env.warp_to(joined).await;
+    env.process(
+        &[deposit(&pool, &short_term, 1_000), withdraw(&pool, &long_term, 0)],
+        &[&short_term.owner, &long_term.owner],
+    ).await.unwrap();
+    assert_eq!(env.user_info(&pool, &long_term).await.shares, 1_200);
+    assert_eq!(env.user_info(&pool, &short_term).await.shares, 1_000);
+    let long_term_paid = env.balance(&long_term.token_account).await;
+    assert_eq!(long_term_paid, 22 * 2 * LOYALTY_STEP_SLOTS);
+
+    env.warp_to(joined + 1_000).await;
+    env.harvest(&pool, &long_term).await.unwrap();
+    env.harvest(&pool, &short_term).await.unwrap();
+    assert_eq!(env.balance(&long_term.token_account).await - long_term_paid, 12_000);
+    assert_eq!(env.balance(&short_term.token_account).await, 10_000);
+
+    // Withdraw restarts the stake
+    env.withdraw(&pool, &long_term, 1_000).await.unwrap();
+    env.deposit(&pool, &long_term, 1_000).await.unwrap();
+    let user_info = env.user_info(&pool, &long_term).await;
+    assert_eq!(user_info.shares, 1_000);
+    assert_eq!(user_info.stake_start_slot, joined + 1_000);
+}
+
+#[tokio::test]
+async fn test_harvest_past_end_block_pays_last_block_once() {
+    let mut env = TestEnv::new().await;
+    let pool = env.create_pool(&PoolConfig::default()).await; // 1_000 per block over 100..1_100
+    let staker = env.create_staker(&pool.mint, 1_000).await;
+
+    env.warp_to(300).await;
+    env.deposit(&pool, &staker, 1_000).await.unwrap();
+
+    // one block before the end, then the last block, then past it
+    env.warp_to(1_099).await;
+    env.harvest(&pool, &staker).await.unwrap();
+    assert_eq!(env.balance(&staker.token_account).await, 1_000 * (1_099 - 300));
+    env.warp_to(1_100).await;
+    env.harvest(&pool, &staker).await.unwrap();
+    env.warp_to(1_200).await;
+    env.harvest(&pool, &staker).await.unwrap();
+    env.warp_to(1_300).await;
+    env.harvest(&pool, &staker).await.unwrap();
+
+    assert_eq!(env.balance(&staker.token_account).await, 1_000 * (1_100 - 300));
+    assert_eq!(env.stake_pool(&pool).await.last_reward_block, 1_100);
+    // the blocks before the deposit stay in the vault
+    assert_eq!(env.balance(&pool.reward_vault()).await, 1_000 * (300 - 100));
+}
+
+fn set_min_reward_duration(pool: &Pool, min_reward_duration: u64) -> Instruction {
+    instruction(
+        StakingInstruction::SetMinRewardDuration { min_reward_duration },
+        vec![
+            AccountMeta::new_readonly(pool.owner.pubkey(), true),
+            AccountMeta::new_readonly(pool.mint, false),
+            AccountMeta::new(pool.key(), false),
+        ],
+    )
+}
+
+#[tokio::test]
+async fn test_withdraw_before_min_reward_duration_forfeits_rewards() {
+    let mut env = TestEnv::new().await;
+    let pool = env.create_pool(&PoolConfig::default()).await; // 1_000 per block over 100..1_100
+    let early = env.create_staker(&pool.mint, 1_000).await;
+    let patient = env.create_staker(&pool.mint, 1_000).await;
+    env.process(&[set_min_reward_duration(&pool, 500)], &[&pool.owner]).await.unwrap();
+
+    env.warp_to(100).await;
+    env.deposit(&pool, &early, 1_000).await.unwrap();
+    env.deposit(&pool, &patient, 1_000).await.unwrap();
+
+    // the principal only, the 100_000 earned stay in the vault
+    env.warp_to(300).await;
+    env.withdraw(&pool, &early, 1_000).await.unwrap();
+    assert_eq!(env.balance(&early.token_account).await, 1_000);
+
+    env.warp_to(700).await;
+    env.withdraw(&pool, &patient, 1_000).await.unwrap();
+    assert_eq!(env.balance(&patient.token_account).await, 1_000 + 100_000 + 400_000);
+    assert_eq!(env.balance(&pool.reward_vault()).await, 1_000_000 - 500_000);
+}
+
+fn set_max_claim_per_tx(pool: &Pool, max_claim_per_tx: u64) -> Instruction {
+    instruction(
+        StakingInstruction::SetMaxClaimPerTx { max_claim_per_tx },
+        vec![
+            AccountMeta::new_readonly(pool.owner.pubkey(), true),
+            AccountMeta::new_readonly(pool.mint, false),
+            AccountMeta::new(pool.key(), false),
+        ],
+    )
+}
+
+#[tokio::test]
+async fn test_payout_above_max_claim_is_carried_to_next_claim() {
+    let mut env = TestEnv::new().await;
+    let pool = env.create_pool(&PoolConfig::default()).await; // 1_000 per block over 100..1_100
+    let staker = env.create_staker(&pool.mint, 1_000).await;
+    env.process(&[set_max_claim_per_tx(&pool, 60_000)], &[&pool.owner]).await.unwrap();
+
+    env.warp_to(100).await;
+    env.deposit(&pool, &staker, 1_000).await.unwrap();
+    env.warp_to(200).await;
+
+    env.harvest(&pool, &staker).await.unwrap();
+    assert_eq!(env.balance(&staker.token_account).await, 60_000);
+    assert_eq!(env.user_info(&pool, &staker).await.pending_owed, 40_000);
+    assert_eq!(env.stake_pool(&pool).await.total_owed, 40_000);
+
+    // the carried 40_000 are paid before the 1_000 of the next block
+    env.warp_to(201).await;
+    env.harvest(&pool, &staker).await.unwrap();
+    assert_eq!(env.balance(&staker.token_account).await, 101_000);
+    assert_eq!(env.user_info(&pool, &staker).await.pending_owed, 0);
+    assert_eq!(env.stake_pool(&pool).await.total_owed, 0);
+}
+
+#[tokio::test]
+async fn test_deposit_at_start_block_earns_from_next_slot() {
+    let mut env = TestEnv::new().await;
+    let pool = env.create_pool(&PoolConfig::default()).await; // 1_000 per block over 100..1_100
+    let first = env.create_staker(&pool.mint, 1_000).await;
+    let second = env.create_staker(&pool.mint, 3_000).await;
+
+    env.warp_to(100).await;
+    env.deposit(&pool, &first, 1_000).await.unwrap();
+    env.deposit(&pool, &second, 3_000).await.unwrap();
+    // nothing accrued in start_block itself
+    env.harvest(&pool, &first).await.unwrap();
+    assert_eq!(env.balance(&first.token_account).await, 0);
+
+    env.warp_to(101).await;
+    env.harvest(&pool, &first).await.unwrap();
+    env.harvest(&pool, &second).await.unwrap();
+    assert_eq!(env.balance(&first.token_account).await, 250);
+    assert_eq!(env.balance(&second.token_account).await, 750);
+}
+
+#[tokio::test]
+async fn test_deposit_in_block_of_prior_staker_credits_no_unearned_reward() {
+    let mut env = TestEnv::new().await;
+    let pool = env.create_pool(&PoolConfig::default()).await; // 1_000 per block over 100..1_100
+    let first = env.create_staker(&pool.mint, 2_000).await;
+    let second = env.create_staker(&pool.mint, 1_000).await;
+
+    env.warp_to(100).await;
+    env.deposit(&pool, &first, 1_000).await.unwrap();
+    env.warp_to(200).await;
+    // the pool is brought to block 200 before the second stake counts
+    env.deposit(&pool, &first, 1_000).await.unwrap();
+    env.deposit(&pool, &second, 1_000).await.unwrap();
+    assert_eq!(env.balance(&first.token_account).await, 100_000);
+    env.harvest(&pool, &second).await.unwrap();
+    assert_eq!(env.balance(&second.token_account).await, 0);
+
+    env.warp_to(300).await;
+    env.harvest(&pool, &first).await.unwrap();
+    env.harvest(&pool, &second).await.unwrap();
+    assert_eq!(env.balance(&first.token_account).await, 100_000 + 66_666);
+    assert_eq!(env.balance(&second.token_account).await, 33_333);
+}
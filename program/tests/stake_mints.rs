@@ -0,0 +1,137 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use solana_program::{
+    instruction::{
+        AccountMeta,
+        Instruction,
+    },
+    pubkey::Pubkey,
+    system_program,
+    sysvar,
+};
+use solana_program_test::*;
+use solana_sdk::signature::Signer;
+use staking_program::{
+    error::StakingError,
+    instruction::StakingInstruction,
+    pda::{
+        stake_mint_token_account_address,
+        token_account_authority_address,
+    },
+    state::STAKE_WEIGHT_PRECISION,
+};
+
+fn add_stake_mint(pool: &Pool, stake_mint: &Pubkey, weight: u16) -> Instruction {
+    instruction(
+        StakingInstruction::AddStakeMint { weight },
+        vec![
+            AccountMeta::new(pool.owner.pubkey(), true),
+            AccountMeta::new_readonly(pool.mint, false),
+            AccountMeta::new(pool.key(), false),
+            AccountMeta::new_readonly(*stake_mint, false),
+            AccountMeta::new_readonly(token_account_authority_address().unwrap(), false),
+            AccountMeta::new(stake_mint_vault(pool, stake_mint), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+    )
+}
+
+fn stake_mint_vault(pool: &Pool, stake_mint: &Pubkey) -> Pubkey {
+    stake_mint_token_account_address(pool.index, stake_mint).0
+}
+
+/// Deposit of a stake mint added by AddStakeMint, rewards go to `reward_destination`
+fn deposit_stake_mint(pool: &Pool, staker: &Staker, stake_mint: &Pubkey, reward_destination: &Pubkey, amount: u64) -> Instruction {
+    let mut instruction = deposit(pool, staker, amount);
+    instruction.accounts[2] = AccountMeta::new_readonly(*stake_mint, false);
+    instruction.accounts[5] = AccountMeta::new(stake_mint_vault(pool, stake_mint), false);
+    instruction.accounts.push(AccountMeta::new(*reward_destination, false));
+    instruction
+}
+
+fn harvest_stake_mint(pool: &Pool, staker: &Staker, stake_mint: &Pubkey, reward_destination: &Pubkey) -> Instruction {
+    let mut instruction = harvest(pool, staker);
+    instruction.accounts[4] = AccountMeta::new_readonly(stake_mint_vault(pool, stake_mint), false);
+    instruction.accounts.push(AccountMeta::new(*reward_destination, false));
+    instruction
+}
+
+fn withdraw_stake_mint(pool: &Pool, staker: &Staker, stake_mint: &Pubkey, reward_destination: &Pubkey, amount: u64) -> Instruction {
+    let mut instruction = withdraw(pool, staker, amount);
+    instruction.accounts[4] = AccountMeta::new(stake_mint_vault(pool, stake_mint), false);
+    instruction.accounts.push(AccountMeta::new(*reward_destination, false));
+    instruction
+}
+
+#[tokio::test]
+async fn test_two_stake_mints_share_rewards_by_weight() {
+    let mut env = TestEnv::new().await;
+    let pool = env.create_pool(&PoolConfig::default()).await; // 1_000 per block over 100..1_100
+    let basket_mint = env.create_mint(DECIMALS).await;
+    // a token of the basket mint counts twice a token of the pool mint
+    env.process(
+        &[add_stake_mint(&pool, &basket_mint, 2 * STAKE_WEIGHT_PRECISION as u16)],
+        &[&pool.owner],
+    )
+    .await
+    .unwrap();
+
+    let pool_mint_staker = env.create_staker(&pool.mint, 1_000).await;
+    let basket_staker = env.create_staker(&basket_mint, 1_000).await;
+    let basket_rewards = env.create_token_account(&pool.mint, &basket_staker.owner.pubkey()).await;
+
+    env.warp_to(100).await;
+    env.deposit(&pool, &pool_mint_staker, 1_000).await.unwrap();
+    env.process(
+        &[deposit_stake_mint(&pool, &basket_staker, &basket_mint, &basket_rewards, 1_000)],
+        &[&basket_staker.owner],
+    )
+    .await
+    .unwrap();
+    // each mint stays in its own vault
+    assert_eq!(env.balance(&pool.staked_vault()).await, 1_000);
+    assert_eq!(env.balance(&stake_mint_vault(&pool, &basket_mint)).await, 1_000);
+
+    env.warp_to(400).await;
+    env.harvest(&pool, &pool_mint_staker).await.unwrap();
+    env.process(
+        &[harvest_stake_mint(&pool, &basket_staker, &basket_mint, &basket_rewards)],
+        &[&basket_staker.owner],
+    )
+    .await
+    .unwrap();
+    // 300_000 emitted over 3_000 shares
+    assert_eq!(env.balance(&pool_mint_staker.token_account).await, 100_000);
+    assert_eq!(env.balance(&basket_rewards).await, 200_000);
+
+    env.process(
+        &[withdraw_stake_mint(&pool, &basket_staker, &basket_mint, &basket_rewards, 1_000)],
+        &[&basket_staker.owner],
+    )
+    .await
+    .unwrap();
+    assert_eq!(env.balance(&basket_staker.token_account).await, 1_000);
+    assert_eq!(env.balance(&stake_mint_vault(&pool, &basket_mint)).await, 0);
+}
+
+#[tokio::test]
+async fn test_deposit_of_mint_outside_basket_is_rejected() {
+    let mut env = TestEnv::new().await;
+    let pool = env.create_pool(&PoolConfig::default()).await;
+    let other_mint = env.create_mint(DECIMALS).await;
+    let staker = env.create_staker(&other_mint, 1_000).await;
+    let rewards = env.create_token_account(&pool.mint, &staker.owner.pubkey()).await;
+
+    env.warp_to(100).await;
+    // the vault of the pool mint is the only vault of the pool for a mint outside the basket
+    let mut instruction = deposit_stake_mint(&pool, &staker, &other_mint, &rewards, 1_000);
+    instruction.accounts[5] = AccountMeta::new(pool.staked_vault(), false);
+    let result = env.process(&[instruction], &[&staker.owner]).await;
+    assert_eq!(result.unwrap_err(), staking_error(StakingError::InvalidAccountList));
+    assert_eq!(env.balance(&staker.token_account).await, 1_000);
+}
@@ -0,0 +1,91 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use solana_program::{
+    clock::Clock,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    rent::Rent,
+};
+use solana_program_test::*;
+use solana_sdk::account::create_account_shared_data_for_test;
+use staking_program::error::StakingError;
+
+/// Account of a sysvar at an address of its own. Set after the last warp, the lamports
+/// it is created with would break the capitalization check of a warp
+fn set_fake_sysvar<S: solana_program::sysvar::Sysvar>(env: &mut TestEnv, sysvar: &S) -> Pubkey {
+    let fake_sysvar = Pubkey::new_unique();
+    env.context.set_account(&fake_sysvar, &create_account_shared_data_for_test(sysvar));
+
+    fake_sysvar
+}
+
+#[tokio::test]
+async fn test_fake_rent_sysvar_is_rejected() {
+    let mut env = TestEnv::new().await;
+    let pool = env.create_pool(&PoolConfig::default()).await;
+    let staker = env.create_staker(&pool.mint, 1_000).await;
+    let owner = env.create_wallet().await;
+    let mint = env.create_mint(DECIMALS).await;
+    let (_, mut initialize) = env.initialize_instruction(&owner, &mint, &PoolConfig::default()).await;
+    env.warp_to(100).await;
+
+    // nothing would be rent exempt under this rent
+    let fake_rent = set_fake_sysvar(&mut env, &Rent {
+        lamports_per_byte_year: 0,
+        exemption_threshold: 0.0,
+        ..Rent::default()
+    });
+
+    initialize.accounts[6].pubkey = fake_rent;
+    assert_eq!(
+        env.process(&[initialize], &[&owner]).await.unwrap_err(),
+        program_error(ProgramError::InvalidArgument),
+    );
+
+    // Deposit checks the sysvars together with the rest of its account list
+    let mut deposit = deposit(&pool, &staker, 1_000);
+    deposit.accounts[9].pubkey = fake_rent;
+    assert_eq!(
+        env.process(&[deposit], &[&staker.owner]).await.unwrap_err(),
+        staking_error(StakingError::InvalidAccountList),
+    );
+    assert!(env.get_account(&staker.user_info(&pool)).await.is_none());
+}
+
+#[tokio::test]
+async fn test_spoofed_clock_sysvar_is_rejected() {
+    let mut env = TestEnv::new().await;
+    let pool = env.create_pool(&PoolConfig::default()).await;
+    let staker = env.create_staker(&pool.mint, 1_000).await;
+
+    env.warp_to(100).await;
+    env.deposit(&pool, &staker, 1_000).await.unwrap();
+    env.warp_to(200).await;
+
+    // past end_block, the whole schedule would be accrued
+    let fake_clock = set_fake_sysvar(&mut env, &Clock {
+        slot: 1_000_000,
+        ..Clock::default()
+    });
+
+    // as Deposit, Withdraw checks the sysvars together with the rest of its account list
+    let mut withdraw = withdraw(&pool, &staker, 1_000);
+    withdraw.accounts[7].pubkey = fake_clock;
+    assert_eq!(
+        env.process(&[withdraw], &[&staker.owner]).await.unwrap_err(),
+        staking_error(StakingError::InvalidAccountList),
+    );
+
+    let mut set_bonus_time = set_bonus_time(&pool, 3, 300, 400);
+    set_bonus_time.accounts[4].pubkey = fake_clock;
+    assert_eq!(
+        env.process(&[set_bonus_time], &[&pool.owner]).await.unwrap_err(),
+        program_error(ProgramError::InvalidArgument),
+    );
+
+    env.withdraw(&pool, &staker, 1_000).await.unwrap();
+    assert_eq!(env.balance(&staker.token_account).await, 1_000 + 100_000);
+}
@@ -0,0 +1,70 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use solana_program::{
+    instruction::{
+        AccountMeta,
+        Instruction,
+    },
+    pubkey::Pubkey,
+    system_program,
+    sysvar,
+};
+use solana_program_test::*;
+use solana_sdk::signature::Signer;
+use staking_program::{
+    instruction::StakingInstruction,
+    pda::user_info_address,
+};
+
+fn transfer_position(pool: &Pool, staker: &Staker, new_token_account: &Pubkey) -> Instruction {
+    instruction(
+        StakingInstruction::TransferPosition,
+        vec![
+            AccountMeta::new(staker.owner.pubkey(), true),
+            AccountMeta::new_readonly(staker.token_account, false),
+            AccountMeta::new_readonly(*new_token_account, false),
+            AccountMeta::new_readonly(pool.key(), false),
+            AccountMeta::new(staker.user_info(pool), false),
+            AccountMeta::new(user_info_address(&pool.key(), new_token_account).0, false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+        ],
+    )
+}
+
+#[tokio::test]
+async fn test_transfer_position_conserves_principal_and_pending() {
+    let mut env = TestEnv::new().await;
+    let pool = env.create_pool(&PoolConfig::default()).await;
+    let sender = env.create_staker(&pool.mint, 1_000).await;
+    let control = env.create_staker(&pool.mint, 1_000).await;
+    let receiver = env.create_staker(&pool.mint, 0).await;
+
+    env.warp_to(100).await;
+    env.deposit(&pool, &sender, 1_000).await.unwrap();
+    env.deposit(&pool, &control, 1_000).await.unwrap();
+
+    env.warp_to(300).await;
+    let position = env.user_info(&pool, &sender).await;
+    env.process(&[transfer_position(&pool, &sender, &receiver.token_account)], &[&sender.owner]).await.unwrap();
+
+    assert!(env.get_account(&sender.user_info(&pool)).await.is_none());
+    let transferred = env.user_info(&pool, &receiver).await;
+    assert_eq!(transferred.token_account_id, receiver.token_account);
+    assert_eq!(transferred.amount, position.amount);
+    assert_eq!(transferred.reward_debt, position.reward_debt);
+
+    // The receiver claims exactly what the sender would have, as the control position does
+    env.warp_to(400).await;
+    env.harvest(&pool, &receiver).await.unwrap();
+    env.harvest(&pool, &control).await.unwrap();
+    assert_eq!(env.balance(&receiver.token_account).await, 150_000);
+    assert_eq!(env.balance(&control.token_account).await, 150_000);
+
+    env.withdraw(&pool, &receiver, 1_000).await.unwrap();
+    assert_eq!(env.balance(&receiver.token_account).await, 151_000);
+}
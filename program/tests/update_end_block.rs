@@ -0,0 +1,144 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use solana_program::{
+    instruction::{
+        AccountMeta,
+        Instruction,
+    },
+    sysvar,
+};
+use solana_program_test::*;
+use solana_sdk::signature::Signer;
+use staking_program::{
+    error::StakingError,
+    instruction::StakingInstruction,
+};
+
+fn update_end_block_many(pools: &[&Pool], end_blocks: Vec<u64>) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(pools[0].owner.pubkey(), true),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+    for pool in pools {
+        accounts.push(AccountMeta::new(pool.key(), false));
+        accounts.push(AccountMeta::new_readonly(pool.mint, false));
+        accounts.push(AccountMeta::new(pool.reward_source, false));
+        accounts.push(AccountMeta::new(pool.reward_vault(), false));
+        for (i, extra_source) in pool.extra_sources.iter().enumerate() {
+            accounts.push(AccountMeta::new(*extra_source, false));
+            accounts.push(AccountMeta::new(pool.extra_vault(1 + i as u8), false));
+        }
+    }
+
+    instruction(StakingInstruction::UpdateEndBlockMany { end_blocks }, accounts)
+}
+
+#[tokio::test]
+async fn test_update_end_block_underfunded_second_reward_transfers_nothing() {
+    let mut env = TestEnv::new().await;
+    let pool = env.create_pool(&PoolConfig {
+        reward_amount: 1_000_000,
+        extra_reward_amounts: vec![500_000],
+        ..PoolConfig::default()
+    }).await;
+
+    // Enough of the first reward token for 100 more blocks, none of the second
+    env.mint_to(&pool.mint, &pool.reward_source, 100_000).await;
+    let reward_vault_before = env.balance(&pool.reward_vault()).await;
+    let extra_vault_before = env.balance(&pool.extra_vault(1)).await;
+
+    let result = env.process(&[update_end_block(&pool, 1_200)], &[&pool.owner]).await;
+    assert_eq!(result.unwrap_err(), staking_error(StakingError::InsufficientRewardFunds));
+
+    assert_eq!(env.balance(&pool.reward_source).await, 100_000);
+    assert_eq!(env.balance(&pool.extra_sources[0]).await, 0);
+    assert_eq!(env.balance(&pool.reward_vault()).await, reward_vault_before);
+    assert_eq!(env.balance(&pool.extra_vault(1)).await, extra_vault_before);
+    let stake_pool = env.stake_pool(&pool).await;
+    assert_eq!(stake_pool.end_block, 1_100);
+    assert_eq!(stake_pool.total_funded, 1_000_000);
+
+    // Funded, both reward tokens are transferred for the added blocks
+    env.mint_to(&pool.extra_mints[0], &pool.extra_sources[0], 50_000).await;
+    env.process(&[update_end_block(&pool, 1_200)], &[&pool.owner]).await.unwrap();
+
+    assert_eq!(env.balance(&pool.reward_source).await, 0);
+    assert_eq!(env.balance(&pool.extra_sources[0]).await, 0);
+    assert_eq!(env.balance(&pool.reward_vault()).await, reward_vault_before + 100_000);
+    assert_eq!(env.balance(&pool.extra_vault(1)).await, extra_vault_before + 50_000);
+    assert_eq!(env.stake_pool(&pool).await.end_block, 1_200);
+}
+
+#[tokio::test]
+async fn test_update_end_block_many_extends_three_pools() {
+    let mut env = TestEnv::new().await;
+    let owner = env.create_wallet().await;
+    let mut pools = vec![];
+    for config in [
+        PoolConfig::default(),
+        PoolConfig {
+            end_block: 600,
+            reward_amount: 500_000,
+            ..PoolConfig::default()
+        },
+        PoolConfig {
+            extra_reward_amounts: vec![500_000],
+            ..PoolConfig::default()
+        },
+    ] {
+        let mint = env.create_mint(DECIMALS).await;
+        let (pool, result) = env.try_create_pool_for(&owner, &mint, &config).await;
+        result.unwrap();
+        // 100 more blocks of each reward token
+        env.mint_to(&pool.mint, &pool.reward_source, 100_000).await;
+        for (extra_mint, extra_source) in pool.extra_mints.iter().zip(pool.extra_sources.iter()) {
+            env.mint_to(extra_mint, extra_source, 50_000).await;
+        }
+        pools.push(pool);
+    }
+
+    env.process(
+        &[update_end_block_many(&pools.iter().collect::<Vec<_>>(), vec![1_200, 700, 1_200])],
+        &[&owner],
+    ).await.unwrap();
+
+    for (pool, end_block) in pools.iter().zip([1_200, 700, 1_200]) {
+        assert_eq!(env.stake_pool(pool).await.end_block, end_block);
+        assert_eq!(env.balance(&pool.reward_source).await, 0);
+    }
+    assert_eq!(env.balance(&pools[0].reward_vault()).await, 1_100_000);
+    assert_eq!(env.balance(&pools[1].reward_vault()).await, 600_000);
+    assert_eq!(env.balance(&pools[2].extra_vault(1)).await, 550_000);
+}
+
+#[tokio::test]
+async fn test_extend_without_funding_is_rejected() {
+    let mut env = TestEnv::new().await;
+    let pool = env.create_pool(&PoolConfig::default()).await; // 1_000 per block over 100..1_100
+    assert_eq!(env.balance(&pool.reward_source).await, 0);
+
+    let result = env.process(&[update_end_block(&pool, 1_200)], &[&pool.owner]).await;
+    assert_eq!(result.unwrap_err(), staking_error(StakingError::InsufficientRewardFunds));
+    let stake_pool = env.stake_pool(&pool).await;
+    assert_eq!(stake_pool.end_block, 1_100);
+    assert_eq!(stake_pool.total_funded, 1_000_000);
+
+    // a schedule already over total_funded can't grow, whatever is transferred
+    let mut over_committed = stake_pool;
+    over_committed.total_funded = 900_000;
+    env.set_stake_pool(&pool, over_committed).await;
+    env.mint_to(&pool.mint, &pool.reward_source, 100_000).await;
+    let result = env.process(&[update_end_block(&pool, 1_200)], &[&pool.owner]).await;
+    assert_eq!(result.unwrap_err(), staking_error(StakingError::OverCommittedRewards));
+
+    env.set_stake_pool(&pool, stake_pool).await;
+    env.process(&[update_end_block(&pool, 1_200)], &[&pool.owner]).await.unwrap();
+    let stake_pool = env.stake_pool(&pool).await;
+    assert_eq!(stake_pool.end_block, 1_200);
+    assert_eq!(stake_pool.total_funded, 1_100_000);
+    assert_eq!(env.balance(&pool.reward_vault()).await, 1_100_000);
+}
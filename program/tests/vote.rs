@@ -0,0 +1,108 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use borsh::BorshDeserialize;
+use common::*;
+use solana_program::{
+    instruction::{
+        AccountMeta,
+        Instruction,
+    },
+    pubkey::Pubkey,
+    system_program,
+    sysvar,
+};
+use solana_program_test::*;
+use solana_sdk::signature::Signer;
+use staking_program::{
+    error::StakingError,
+    instruction::StakingInstruction,
+    pda::{
+        proposal_tally_address,
+        vote_receipt_address,
+    },
+    state::ProposalTally,
+};
+
+const PROPOSAL_ID: u64 = 7;
+
+fn proposal_tally(pool: &Pool) -> Pubkey {
+    proposal_tally_address(&pool.key(), PROPOSAL_ID).0
+}
+
+fn cast_vote(pool: &Pool, staker: &Staker, choice: u8) -> Instruction {
+    instruction(
+        StakingInstruction::CastVote {
+            proposal_id: PROPOSAL_ID,
+            choice,
+        },
+        vec![
+            AccountMeta::new(staker.owner.pubkey(), true),
+            AccountMeta::new_readonly(staker.token_account, false),
+            AccountMeta::new_readonly(pool.key(), false),
+            AccountMeta::new_readonly(staker.user_info(pool), false),
+            AccountMeta::new(proposal_tally(pool), false),
+            AccountMeta::new(vote_receipt_address(&proposal_tally(pool), &staker.user_info(pool)).0, false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+        ],
+    )
+}
+
+async fn tally(env: &mut TestEnv, pool: &Pool) -> ProposalTally {
+    let account = env.get_account(&proposal_tally(pool)).await.unwrap();
+
+    ProposalTally::try_from_slice(&account.data).unwrap()
+}
+
+#[tokio::test]
+async fn test_votes_weighted_by_staked_amount() {
+    let mut env = TestEnv::new().await;
+    let pool = env.create_pool(&PoolConfig::default()).await;
+    let mut stakers = vec![];
+    for &amount in [1_000, 3_000, 2_000].iter() {
+        stakers.push((env.create_staker(&pool.mint, amount).await, amount));
+    }
+
+    env.warp_to(100).await;
+    for (staker, amount) in stakers.iter() {
+        env.deposit(&pool, staker, *amount).await.unwrap();
+    }
+
+    env.warp_to(150).await;
+    for ((staker, _), choice) in stakers.iter().zip([1, 1, 2].iter()) {
+        env.process(&[cast_vote(&pool, staker, *choice)], &[&staker.owner]).await.unwrap();
+    }
+
+    let tally = tally(&mut env, &pool).await;
+    assert_eq!(tally.stake_pool, pool.key());
+    assert_eq!(tally.proposal_id, PROPOSAL_ID);
+    assert_eq!(tally.start_slot, 150);
+    assert_eq!(tally.weights, [0, 4_000, 2_000, 0]);
+}
+
+#[tokio::test]
+async fn test_second_vote_of_position_is_rejected() {
+    let mut env = TestEnv::new().await;
+    let pool = env.create_pool(&PoolConfig::default()).await;
+    let staker = env.create_staker(&pool.mint, 1_000).await;
+    let late = env.create_staker(&pool.mint, 1_000).await;
+
+    env.warp_to(100).await;
+    env.deposit(&pool, &staker, 1_000).await.unwrap();
+    env.warp_to(150).await;
+    env.process(&[cast_vote(&pool, &staker, 1)], &[&staker.owner]).await.unwrap();
+
+    env.warp_to(160).await;
+    let result = env.process(&[cast_vote(&pool, &staker, 2)], &[&staker.owner]).await;
+    assert_eq!(result.unwrap_err(), staking_error(StakingError::AlreadyVoted));
+    assert_eq!(tally(&mut env, &pool).await.weights, [0, 1_000, 0, 0]);
+
+    // stake deposited after the first vote doesn't vote
+    env.deposit(&pool, &late, 1_000).await.unwrap();
+    env.warp_to(170).await;
+    let result = env.process(&[cast_vote(&pool, &late, 2)], &[&late.owner]).await;
+    assert_eq!(result.unwrap_err(), staking_error(StakingError::PositionChangedAfterProposal));
+}
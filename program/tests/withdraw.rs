@@ -0,0 +1,453 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use borsh::BorshSerialize;
+use common::*;
+use solana_program::{
+    instruction::{
+        AccountMeta,
+        Instruction,
+    },
+    pubkey::Pubkey,
+    sysvar,
+};
+use solana_program_test::*;
+use solana_sdk::signature::{
+    Keypair,
+    Signer,
+};
+use staking_program::{
+    error::StakingError,
+    instruction::StakingInstruction,
+    pda::{
+        token_account_authority_address,
+        user_info_address,
+    },
+    events::PositionUpdatedEvent,
+    state::EMERGENCY_WITHDRAW_TIMELOCK_SLOTS,
+};
+
+#[tokio::test]
+async fn test_withdraw_from_drained_staked_vault_logs_drift() {
+    let mut env = TestEnv::new().await;
+    let pool = env.create_pool(&PoolConfig::default()).await;
+    let staker = env.create_staker(&pool.mint, 1_000).await;
+
+    env.warp_to(100).await;
+    env.deposit(&pool, &staker, 1_000).await.unwrap();
+    env.set_balance(&pool.staked_vault(), 400).await;
+
+    let (result, logs) = env.process_with_logs(&[withdraw(&pool, &staker, 1_000)], &[&staker.owner]).await;
+    assert_eq!(result.unwrap_err(), staking_error(StakingError::InsufficientStaked));
+    assert!(logs.contains(&"Program log: Staked vault holds 400 but 1000 requested".to_string()));
+
+    env.withdraw(&pool, &staker, 400).await.unwrap();
+    assert_eq!(env.user_info(&pool, &staker).await.amount, 600);
+}
+
+#[tokio::test]
+async fn test_withdraw_into_vault_is_rejected() {
+    let mut env = TestEnv::new().await;
+    let pool = env.create_pool(&PoolConfig::default()).await;
+    let staker = env.create_staker(&pool.mint, 1_000).await;
+
+    env.warp_to(100).await;
+    env.deposit(&pool, &staker, 1_000).await.unwrap();
+
+    for vault in [pool.reward_vault(), pool.staked_vault()] {
+        let mut withdraw = withdraw(&pool, &staker, 1_000);
+        withdraw.accounts[1].pubkey = vault;
+        withdraw.accounts[6].pubkey = user_info_address(&pool.key(), &vault).0;
+        assert_eq!(
+            env.process(&[withdraw], &[&staker.owner]).await.unwrap_err(),
+            staking_error(StakingError::InvalidDestination),
+        );
+
+        let mut deposit = deposit(&pool, &staker, 1_000);
+        deposit.accounts[1].pubkey = vault;
+        deposit.accounts[8].pubkey = user_info_address(&pool.key(), &vault).0;
+        assert_eq!(
+            env.process(&[deposit], &[&staker.owner]).await.unwrap_err(),
+            staking_error(StakingError::InvalidDestination),
+        );
+    }
+}
+
+fn set_delegate(pool: &Pool, staker: &Staker, delegate: Option<Pubkey>) -> Instruction {
+    instruction(
+        StakingInstruction::SetDelegate { delegate },
+        vec![
+            AccountMeta::new_readonly(staker.owner.pubkey(), true),
+            AccountMeta::new_readonly(staker.token_account, false),
+            AccountMeta::new_readonly(pool.key(), false),
+            AccountMeta::new(staker.user_info(pool), false),
+        ],
+    )
+}
+
+fn withdraw_delegated(pool: &Pool, staker: &Staker, delegate: &Pubkey, amount: u64) -> Instruction {
+    let mut withdraw_delegated = withdraw(pool, staker, amount);
+    withdraw_delegated.data = StakingInstruction::WithdrawDelegated { amount }.try_to_vec().unwrap();
+    withdraw_delegated.accounts[0] = AccountMeta::new_readonly(*delegate, true);
+
+    withdraw_delegated
+}
+
+#[tokio::test]
+async fn test_withdraw_delegated_exit() {
+    let mut env = TestEnv::new().await;
+    let pool = env.create_pool(&PoolConfig::default()).await;
+    let staker = env.create_staker(&pool.mint, 1_000).await;
+    let delegate = env.create_wallet().await;
+
+    env.warp_to(100).await;
+    env.deposit(&pool, &staker, 1_000).await.unwrap();
+    env.warp_to(200).await;
+
+    // Not a delegate until the owner sets it
+    assert_eq!(
+        env.process(&[withdraw_delegated(&pool, &staker, &delegate.pubkey(), 1_000)], &[&delegate]).await.unwrap_err(),
+        staking_error(StakingError::DelegateMissmatch),
+    );
+
+    env.process(&[set_delegate(&pool, &staker, Some(delegate.pubkey()))], &[&staker.owner]).await.unwrap();
+    env.process(&[withdraw_delegated(&pool, &staker, &delegate.pubkey(), 1_000)], &[&delegate]).await.unwrap();
+
+    // Principal and rewards go to the token-account of the position only
+    assert_eq!(env.user_info(&pool, &staker).await.amount, 0);
+    assert_eq!(env.balance(&staker.token_account).await, 1_000 + 100_000);
+}
+
+fn withdraw_and_close(pool: &Pool, staker: &Staker, amount: u64) -> Instruction {
+    let mut withdraw_and_close = withdraw(pool, staker, amount);
+    withdraw_and_close.data = StakingInstruction::Withdraw { amount, close_on_empty: true }.try_to_vec().unwrap();
+    withdraw_and_close.accounts.push(AccountMeta::new(pool.wallet(), false));
+
+    withdraw_and_close
+}
+
+#[tokio::test]
+async fn test_withdraw_closes_emptied_position() {
+    let mut env = TestEnv::new().await;
+    let pool = env.create_pool(&PoolConfig::default()).await;
+    let staker = env.create_staker(&pool.mint, 1_000).await;
+
+    env.warp_to(100).await;
+    env.deposit(&pool, &staker, 1_000).await.unwrap();
+    env.warp_to(200).await;
+
+    // Stays open while tokens are staked
+    env.process(&[withdraw_and_close(&pool, &staker, 500)], &[&staker.owner]).await.unwrap();
+    assert_eq!(env.user_info(&pool, &staker).await.amount, 500);
+
+    let user_info_lamports = env.lamports(&staker.user_info(&pool)).await;
+    let wallet_lamports = env.lamports(&pool.wallet()).await;
+    env.warp_to(300).await;
+    env.process(&[withdraw_and_close(&pool, &staker, 500)], &[&staker.owner]).await.unwrap();
+
+    assert!(env.get_account(&staker.user_info(&pool)).await.is_none());
+    assert_eq!(env.lamports(&pool.wallet()).await, wallet_lamports + user_info_lamports);
+    assert_eq!(env.balance(&staker.token_account).await, 1_000 + 200_000);
+}
+
+#[tokio::test]
+async fn test_position_recreated_after_close_starts_clean() {
+    let mut env = TestEnv::new().await;
+    let pool = env.create_pool(&PoolConfig::default()).await; // 1_000 per block over 100..1_100
+    let staker = env.create_staker(&pool.mint, 1_000).await;
+
+    env.warp_to(100).await;
+    env.deposit(&pool, &staker, 1_000).await.unwrap();
+    env.warp_to(300).await;
+    env.process(&[withdraw_and_close(&pool, &staker, 1_000)], &[&staker.owner]).await.unwrap();
+    assert!(env.get_account(&staker.user_info(&pool)).await.is_none());
+
+    // Same PDA, created again by Deposit
+    env.warp_to(400).await;
+    env.deposit(&pool, &staker, 500).await.unwrap();
+    let user_info = env.user_info(&pool, &staker).await;
+    assert_eq!(user_info.token_account_id, staker.token_account);
+    assert_eq!((user_info.amount, user_info.shares), (500, 500));
+    assert_eq!(user_info.deposit_slot, 400);
+    assert_eq!(user_info.pending_owed, 0);
+
+    // Nothing of the closed position is paid again
+    env.warp_to(500).await;
+    env.harvest(&pool, &staker).await.unwrap();
+    assert_eq!(env.balance(&staker.token_account).await, 500 + 200_000 + 100_000);
+}
+
+fn set_user_frozen(pool: &Pool, staker: &Staker, frozen: bool) -> Instruction {
+    instruction(
+        StakingInstruction::SetUserFrozen { frozen },
+        vec![
+            AccountMeta::new_readonly(pool.owner.pubkey(), true),
+            AccountMeta::new_readonly(pool.mint, false),
+            AccountMeta::new_readonly(pool.key(), false),
+            AccountMeta::new_readonly(staker.token_account, false),
+            AccountMeta::new(staker.user_info(pool), false),
+            AccountMeta::new_readonly(sysvar::instructions::id(), false),
+        ],
+    )
+}
+
+#[tokio::test]
+async fn test_withdraw_of_frozen_position_is_rejected() {
+    let mut env = TestEnv::new().await;
+    let pool = env.create_pool(&PoolConfig::default()).await;
+    let staker = env.create_staker(&pool.mint, 2_000).await;
+
+    env.warp_to(100).await;
+    env.deposit(&pool, &staker, 1_000).await.unwrap();
+    env.process(&[set_user_frozen(&pool, &staker, true)], &[&pool.owner]).await.unwrap();
+    assert_eq!(env.user_info(&pool, &staker).await.frozen, 1);
+
+    env.warp_to(200).await;
+    assert_eq!(
+        env.withdraw(&pool, &staker, 1_000).await.unwrap_err(),
+        staking_error(StakingError::UserFrozen),
+    );
+    assert_eq!(
+        env.harvest(&pool, &staker).await.unwrap_err(),
+        staking_error(StakingError::UserFrozen),
+    );
+    // a Deposit, even of nothing, would pay out the pending rewards
+    for amount in [0, 1_000] {
+        assert_eq!(
+            env.deposit(&pool, &staker, amount).await.unwrap_err(),
+            staking_error(StakingError::UserFrozen),
+        );
+    }
+    assert_eq!(env.user_info(&pool, &staker).await.amount, 1_000);
+
+    env.process(&[set_user_frozen(&pool, &staker, false)], &[&pool.owner]).await.unwrap();
+    env.deposit(&pool, &staker, 1_000).await.unwrap();
+    env.withdraw(&pool, &staker, 2_000).await.unwrap();
+    assert_eq!(env.user_info(&pool, &staker).await.amount, 0);
+    assert_eq!(env.balance(&staker.token_account).await, 2_000 + 100_000);
+}
+
+fn emergency_withdraw(pool: &Pool, staker: &Staker) -> Instruction {
+    instruction(
+        StakingInstruction::EmergencyWithdraw,
+        vec![
+            AccountMeta::new_readonly(staker.owner.pubkey(), true),
+            AccountMeta::new(staker.token_account, false),
+            AccountMeta::new_readonly(token_account_authority_address().unwrap(), false),
+            AccountMeta::new(pool.staked_vault(), false),
+            AccountMeta::new(staker.user_info(pool), false),
+            AccountMeta::new(pool.key(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+        ],
+    )
+}
+
+fn set_max_claim_per_tx(pool: &Pool, max_claim_per_tx: u64) -> Instruction {
+    instruction(
+        StakingInstruction::SetMaxClaimPerTx { max_claim_per_tx },
+        vec![
+            AccountMeta::new_readonly(pool.owner.pubkey(), true),
+            AccountMeta::new_readonly(pool.mint, false),
+            AccountMeta::new(pool.key(), false),
+        ],
+    )
+}
+
+#[tokio::test]
+async fn test_emergency_withdraw_forfeits_carried_reward() {
+    let mut env = TestEnv::new().await;
+    let pool = env.create_pool(&PoolConfig::default()).await; // 1_000 per block over 100..1_100
+    let staker = env.create_staker(&pool.mint, 1_000).await;
+    env.process(&[set_max_claim_per_tx(&pool, 60_000)], &[&pool.owner]).await.unwrap();
+
+    env.warp_to(100).await;
+    env.deposit(&pool, &staker, 1_000).await.unwrap();
+    env.warp_to(200).await;
+    env.harvest(&pool, &staker).await.unwrap();
+    assert_eq!(env.user_info(&pool, &staker).await.pending_owed, 40_000);
+
+    env.warp_to(250).await;
+    env.process(&[emergency_withdraw(&pool, &staker)], &[&staker.owner]).await.unwrap();
+    let user_info = env.user_info(&pool, &staker).await;
+    assert_eq!((user_info.amount, user_info.reward_debt, user_info.pending_owed), (0, 0, 0));
+    assert!(user_info.is_closable());
+    assert_eq!(env.stake_pool(&pool).await.total_owed, 0);
+    assert_eq!(env.balance(&staker.token_account).await, 1_000 + 60_000);
+
+    // the position starts over
+    env.warp_to(300).await;
+    env.deposit(&pool, &staker, 1_000).await.unwrap();
+    env.warp_to(310).await;
+    env.harvest(&pool, &staker).await.unwrap();
+    assert_eq!(env.balance(&staker.token_account).await, 60_000 + 10_000);
+    assert_eq!(env.stake_pool(&pool).await.total_owed, 0);
+}
+
+fn set_emergency_withdraw_enabled(pool: &Pool, enabled: bool) -> Instruction {
+    instruction(
+        StakingInstruction::SetEmergencyWithdrawEnabled { enabled },
+        vec![
+            AccountMeta::new_readonly(pool.owner.pubkey(), true),
+            AccountMeta::new_readonly(pool.mint, false),
+            AccountMeta::new(pool.key(), false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(sysvar::instructions::id(), false),
+        ],
+    )
+}
+
+#[tokio::test]
+async fn test_emergency_withdraw_disabled_after_timelock() {
+    let mut env = TestEnv::new().await;
+    let pool = env.create_pool(&PoolConfig::default()).await;
+    let early = env.create_staker(&pool.mint, 1_000).await;
+    let late = env.create_staker(&pool.mint, 1_000).await;
+
+    env.warp_to(100).await;
+    env.deposit(&pool, &early, 1_000).await.unwrap();
+    env.deposit(&pool, &late, 1_000).await.unwrap();
+    env.process(&[set_emergency_withdraw_enabled(&pool, false)], &[&pool.owner]).await.unwrap();
+    assert_eq!(env.stake_pool(&pool).await.emergency_withdraw_disable_slot, 100 + EMERGENCY_WITHDRAW_TIMELOCK_SLOTS);
+
+    // still open during the timelock
+    env.warp_to(200).await;
+    env.process(&[emergency_withdraw(&pool, &early)], &[&early.owner]).await.unwrap();
+    assert_eq!(env.balance(&early.token_account).await, 1_000);
+
+    env.warp_to(100 + EMERGENCY_WITHDRAW_TIMELOCK_SLOTS).await;
+    assert_eq!(
+        env.process(&[emergency_withdraw(&pool, &late)], &[&late.owner]).await.unwrap_err(),
+        staking_error(StakingError::EmergencyDisabled),
+    );
+
+    // enabling is immediate
+    env.process(&[set_emergency_withdraw_enabled(&pool, true)], &[&pool.owner]).await.unwrap();
+    env.process(&[emergency_withdraw(&pool, &late)], &[&late.owner]).await.unwrap();
+    assert_eq!(env.balance(&late.token_account).await, 1_000);
+    assert_eq!(env.user_info(&pool, &late).await.amount, 0);
+}
+
+#[tokio::test]
+async fn test_position_updated_event_of_each_operation() {
+    let mut env = TestEnv::new().await;
+    let pool = env.create_pool(&PoolConfig::default()).await;
+    let staker = env.create_staker(&pool.mint, 1_000).await;
+
+    let position_update = |logs: &[String]| {
+        let updates = events::<PositionUpdatedEvent>(logs);
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].pool_index, pool.index);
+        assert_eq!(updates[0].token_account, staker.token_account);
+        (updates[0].new_amount, updates[0].reward_paid, updates[0].slot)
+    };
+
+    env.warp_to(100).await;
+    let (result, logs) = env.process_with_logs(&[deposit(&pool, &staker, 1_000)], &[&staker.owner]).await;
+    result.unwrap();
+    assert_eq!(position_update(&logs), (1_000, 0, 100));
+
+    env.warp_to(200).await;
+    let (result, logs) = env.process_with_logs(&[harvest(&pool, &staker)], &[&staker.owner]).await;
+    result.unwrap();
+    assert_eq!(position_update(&logs), (1_000, 100_000, 200));
+
+    env.warp_to(300).await;
+    let (result, logs) = env.process_with_logs(&[withdraw(&pool, &staker, 400)], &[&staker.owner]).await;
+    result.unwrap();
+    assert_eq!(position_update(&logs), (600, 100_000, 300));
+
+    // forfeits the rewards
+    env.warp_to(400).await;
+    let (result, logs) = env.process_with_logs(&[emergency_withdraw(&pool, &staker)], &[&staker.owner]).await;
+    result.unwrap();
+    assert_eq!(position_update(&logs), (0, 0, 400));
+}
+
+#[tokio::test]
+async fn test_withdraw_with_misordered_accounts_names_the_index() {
+    let mut env = TestEnv::new().await;
+    let pool = env.create_pool(&PoolConfig::default()).await;
+    let staker = env.create_staker(&pool.mint, 1_000).await;
+
+    env.warp_to(100).await;
+    env.deposit(&pool, &staker, 1_000).await.unwrap();
+    env.warp_to(200).await;
+
+    let mut misordered = withdraw(&pool, &staker, 1_000);
+    misordered.accounts.swap(5, 6);
+    let (result, logs) = env.process_with_logs(&[misordered], &[&staker.owner]).await;
+    assert_eq!(result.unwrap_err(), staking_error(StakingError::InvalidAccountList));
+    let expected = format!("Program log: Account 5 ({}) is not the reward vault", staker.user_info(&pool));
+    assert!(logs.contains(&expected), "{:?}", logs);
+
+    env.withdraw(&pool, &staker, 1_000).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_pool_counters_across_deposits_and_withdrawals() {
+    let mut env = TestEnv::new().await;
+    let pool = env.create_pool(&PoolConfig::default()).await;
+    let first = env.create_staker(&pool.mint, 1_000).await;
+    let second = env.create_staker(&pool.mint, 500).await;
+
+    env.warp_to(100).await;
+    env.deposit(&pool, &first, 1_000).await.unwrap();
+    env.deposit(&pool, &second, 500).await.unwrap();
+    env.warp_to(200).await;
+    env.withdraw(&pool, &first, 300).await.unwrap();
+    // no staked tokens move
+    env.harvest(&pool, &first).await.unwrap();
+    env.deposit(&pool, &first, 0).await.unwrap();
+    env.process(&[emergency_withdraw(&pool, &second)], &[&second.owner]).await.unwrap();
+
+    let stake_pool = env.stake_pool(&pool).await;
+    assert_eq!((stake_pool.deposit_count, stake_pool.lifetime_deposited), (2, 1_500));
+    assert_eq!((stake_pool.withdraw_count, stake_pool.lifetime_withdrawn), (2, 800));
+    assert_eq!(stake_pool.lifetime_deposited - stake_pool.lifetime_withdrawn, stake_pool.total_staked);
+}
+
+#[tokio::test]
+async fn test_position_of_recreated_token_account_is_recoverable() {
+    let mut env = TestEnv::new().await;
+    let pool = env.create_pool(&PoolConfig::default()).await; // 1_000 per block over 100..1_100
+    let owner = env.create_wallet().await;
+    let token_account_keypair = Keypair::new();
+    let token_account = env.create_token_account_at(&token_account_keypair, &pool.mint, &owner.pubkey()).await;
+    env.mint_to(&pool.mint, &token_account, 1_000).await;
+    let staker = Staker {
+        owner,
+        token_account,
+    };
+
+    env.warp_to(100).await;
+    env.deposit(&pool, &staker, 1_000).await.unwrap();
+    env.process(
+        &[spl_token::instruction::close_account(
+            &spl_token::id(),
+            &staker.token_account,
+            &staker.owner.pubkey(),
+            &staker.owner.pubkey(),
+            &[],
+        ).unwrap()],
+        &[&staker.owner],
+    ).await.unwrap();
+    assert!(env.get_account(&staker.token_account).await.is_none());
+
+    env.warp_to(200).await;
+    assert_eq!(
+        env.harvest(&pool, &staker).await.unwrap_err(),
+        staking_error(StakingError::DestinationClosed),
+    );
+    assert_eq!(
+        env.withdraw(&pool, &staker, 1_000).await.unwrap_err(),
+        staking_error(StakingError::DestinationClosed),
+    );
+
+    // the same address gets the position back
+    env.create_token_account_at(&token_account_keypair, &pool.mint, &staker.owner.pubkey()).await;
+    env.withdraw(&pool, &staker, 1_000).await.unwrap();
+    assert_eq!(env.user_info(&pool, &staker).await.amount, 0);
+    assert_eq!(env.balance(&staker.token_account).await, 1_000 + 100_000);
+}